@@ -46,7 +46,6 @@ fn bench_call_function_returning_large_string_no_instrumentation(c: &mut Criteri
             let source = include_str!("../tests/contracts/VeLogo.hex");
             let bytecode = hex::decode(source).unwrap();
             let mut exe = TinyEVM::default();
-            exe.instrument_config_mut().enabled = false;
 
             let resp = {
                 exe.deploy_helper(OWNER, bytecode, UZERO, None, Some(DEPLOY_TO_ADDRESS))
@@ -55,6 +54,7 @@ fn bench_call_function_returning_large_string_no_instrumentation(c: &mut Criteri
 
             assert!(resp.success, "Contract deploy should succeed.");
             let address = Address::from_slice(&resp.data);
+            exe.set_inspector_enabled(false);
 
             let fn_sig = "tokenURI(uint256,uint256,uint256,uint256)";
             b.iter(|| {
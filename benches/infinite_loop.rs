@@ -58,10 +58,109 @@ fn bench_infinite_loop_adderss_call(c: &mut Criterion) {
     });
 }
 
+// Same as `bench_infinite_loop_math` with all the detectors enabled, the
+// baseline of `bench_infinite_loop_math_without_detectors`
+fn bench_infinite_loop_math_with_detectors(c: &mut Criterion) {
+    c.bench_function("infinite_loop_with_simple_math_with_detectors", |b| {
+        let source = include_str!("../tests/contracts/infinite_loop_Test2.hex");
+        let bytecode = hex::decode(source).unwrap();
+        let fn_sig = "test1(int256)";
+        let fn_args_hex = format!("{:0>64x}", U256::from(0));
+        let add_hex = format!("{}{}", fn_sig_to_prefix(fn_sig), fn_args_hex);
+
+        let data = hex::decode(add_hex).unwrap();
+        let mut exe = TinyEVM::default();
+        let config = exe.instrument_config_mut();
+        config.detect_arithmetic = true;
+        config.detect_dependency = true;
+        config.detect_storage = true;
+
+        let resp = {
+            exe.deploy_helper(OWNER, bytecode, UZERO, None, Some(DEPLOY_TO_ADDRESS))
+                .unwrap()
+        };
+
+        assert!(resp.success, "Contract deploy should succeed.");
+        let address = Address::from_slice(&resp.data);
+
+        b.iter(|| {
+            let _ = exe.contract_call_helper(address, OWNER, data.clone(), UZERO, None);
+        })
+    });
+}
+
+// Same as `bench_infinite_loop_math_with_detectors` with the arithmetic and
+// dependency detectors disabled, their opcodes then have no handler in
+// `step_end`
+fn bench_infinite_loop_math_without_detectors(c: &mut Criterion) {
+    c.bench_function("infinite_loop_with_simple_math_without_detectors", |b| {
+        let source = include_str!("../tests/contracts/infinite_loop_Test2.hex");
+        let bytecode = hex::decode(source).unwrap();
+        let fn_sig = "test1(int256)";
+        let fn_args_hex = format!("{:0>64x}", U256::from(0));
+        let add_hex = format!("{}{}", fn_sig_to_prefix(fn_sig), fn_args_hex);
+
+        let data = hex::decode(add_hex).unwrap();
+        let mut exe = TinyEVM::default();
+        let config = exe.instrument_config_mut();
+        config.detect_arithmetic = false;
+        config.detect_dependency = false;
+
+        let resp = {
+            exe.deploy_helper(OWNER, bytecode, UZERO, None, Some(DEPLOY_TO_ADDRESS))
+                .unwrap()
+        };
+
+        assert!(resp.success, "Contract deploy should succeed.");
+        let address = Address::from_slice(&resp.data);
+
+        b.iter(|| {
+            let _ = exe.contract_call_helper(address, OWNER, data.clone(), UZERO, None);
+        })
+    });
+}
+
+// Same as `bench_infinite_loop_math` recording only the coverage: without
+// any detector reading the opcode inputs or the taint, `step` returns early
+fn bench_infinite_loop_math_coverage_only(c: &mut Criterion) {
+    c.bench_function("infinite_loop_with_simple_math_coverage_only", |b| {
+        let source = include_str!("../tests/contracts/infinite_loop_Test2.hex");
+        let bytecode = hex::decode(source).unwrap();
+        let fn_sig = "test1(int256)";
+        let fn_args_hex = format!("{:0>64x}", U256::from(0));
+        let add_hex = format!("{}{}", fn_sig_to_prefix(fn_sig), fn_args_hex);
+
+        let data = hex::decode(add_hex).unwrap();
+        let mut exe = TinyEVM::default();
+        let config = exe.instrument_config_mut();
+        config.detect_arithmetic = false;
+        config.detect_dependency = false;
+        config.detect_storage = false;
+        config.record_sha3_mapping = false;
+        config.calldata_taint = false;
+
+        let resp = {
+            exe.deploy_helper(OWNER, bytecode, UZERO, None, Some(DEPLOY_TO_ADDRESS))
+                .unwrap()
+        };
+
+        assert!(resp.success, "Contract deploy should succeed.");
+        let address = Address::from_slice(&resp.data);
+
+        b.iter(|| {
+            let _ = exe.contract_call_helper(address, OWNER, data.clone(), UZERO, None);
+        })
+    });
+}
+
 criterion_group!(
     name = infinite_loop;
     config = Criterion::default();
-    targets = bench_infinite_loop_math, bench_infinite_loop_adderss_call
+    targets = bench_infinite_loop_math,
+        bench_infinite_loop_adderss_call,
+        bench_infinite_loop_math_with_detectors,
+        bench_infinite_loop_math_without_detectors,
+        bench_infinite_loop_math_coverage_only
 );
 
 criterion_main!(infinite_loop);
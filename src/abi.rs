@@ -0,0 +1,185 @@
+use std::borrow::Cow;
+
+use alloy_dyn_abi::{DynSolType, DynSolValue};
+use eyre::{eyre, ContextCompat, Result};
+use num_bigint::{BigInt, Sign};
+use pyo3::{prelude::*, types::PyTuple, IntoPyObjectExt};
+use revm::primitives::{alloy_primitives::I256, keccak256, B256};
+use ruint::aliases::U256;
+
+use crate::{
+    args::{AddressArg, BytesArg, PyU256, U256Arg},
+    bigint_to_ruint_u256,
+    errors::PyEvmResult,
+    ruint_u256_to_bigint,
+};
+
+/// Tuple type of a comma separated list of types, e.g. `address,uint256`
+fn parse_params(types: &str) -> Result<DynSolType> {
+    Ok(DynSolType::parse(&format!("({})", types))?)
+}
+
+/// Name of `ty` in the function signatures. Unlike `sol_type_name`, the
+/// tuples with a single element have no trailing comma
+fn canonical_name(ty: &DynSolType) -> String {
+    match ty {
+        DynSolType::Tuple(types) => {
+            let names: Vec<_> = types.iter().map(canonical_name).collect();
+            format!("({})", names.join(","))
+        }
+        DynSolType::Array(ty) => format!("{}[]", canonical_name(ty)),
+        DynSolType::FixedArray(ty, len) => format!("{}[{}]", canonical_name(ty), len),
+        ty => ty.sol_type_name().into_owned(),
+    }
+}
+
+/// Items of a Python sequence
+fn sequence<'py>(value: &Bound<'py, PyAny>) -> Result<Vec<Bound<'py, PyAny>>> {
+    Ok(value.try_iter()?.collect::<PyResult<_>>()?)
+}
+
+/// ABI value of type `ty` from a Python value: addresses, bytes and uints
+/// are accepted as in the rest of the API, arrays and tuples as sequences
+fn py_to_sol(ty: &DynSolType, value: &Bound<'_, PyAny>) -> Result<DynSolValue> {
+    let sol = match ty {
+        DynSolType::Address => DynSolValue::Address(value.extract::<AddressArg>()?.address()?),
+        DynSolType::Bool => DynSolValue::Bool(value.extract()?),
+        DynSolType::Uint(bits) => DynSolValue::Uint(value.extract::<U256Arg>()?.value()?, *bits),
+        DynSolType::Int(bits) => {
+            let value: BigInt = value.extract()?;
+            let raw = match value.sign() {
+                Sign::Minus => U256::ZERO.wrapping_sub(bigint_to_ruint_u256(&-value)?),
+                _ => bigint_to_ruint_u256(&value)?,
+            };
+            DynSolValue::Int(I256::from_raw(raw), *bits)
+        }
+        DynSolType::Bytes => DynSolValue::Bytes(value.extract::<BytesArg>()?.into_bytes()?),
+        DynSolType::FixedBytes(size) => {
+            let bytes = value.extract::<BytesArg>()?.into_bytes()?;
+            if bytes.len() != *size {
+                return Err(eyre!("Expecting {} bytes, got {}", size, bytes.len()));
+            }
+            let mut word = B256::ZERO;
+            word[..*size].copy_from_slice(&bytes);
+            DynSolValue::FixedBytes(word, *size)
+        }
+        DynSolType::String => DynSolValue::String(value.extract()?),
+        DynSolType::Array(inner) => DynSolValue::Array(
+            sequence(value)?
+                .iter()
+                .map(|v| py_to_sol(inner, v))
+                .collect::<Result<_>>()?,
+        ),
+        DynSolType::FixedArray(inner, size) => {
+            let values = sequence(value)?;
+            if values.len() != *size {
+                return Err(eyre!("Expecting {} values, got {}", size, values.len()));
+            }
+            DynSolValue::FixedArray(
+                values
+                    .iter()
+                    .map(|v| py_to_sol(inner, v))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        DynSolType::Tuple(types) => {
+            let values = sequence(value)?;
+            if values.len() != types.len() {
+                return Err(eyre!(
+                    "Expecting {} values, got {}",
+                    types.len(),
+                    values.len()
+                ));
+            }
+            DynSolValue::Tuple(
+                types
+                    .iter()
+                    .zip(&values)
+                    .map(|(ty, v)| py_to_sol(ty, v))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        _ => return Err(eyre!("Unsupported type {}", ty)),
+    };
+    Ok(sol)
+}
+
+/// Python value of an ABI value: addresses and bytes are hex encoded,
+/// arrays are lists and tuples are tuples
+fn sol_to_py(py: Python<'_>, value: &DynSolValue) -> PyResult<PyObject> {
+    match value {
+        DynSolValue::Address(address) => format!("{:#x}", address).into_py_any(py),
+        DynSolValue::Bool(b) => b.into_py_any(py),
+        DynSolValue::Uint(v, _) => PyU256(*v).into_py_any(py),
+        DynSolValue::Int(v, _) if v.is_negative() => {
+            (ruint_u256_to_bigint(&v.into_raw()) - (BigInt::from(1) << 256usize)).into_py_any(py)
+        }
+        DynSolValue::Int(v, _) => PyU256(v.into_raw()).into_py_any(py),
+        DynSolValue::FixedBytes(word, size) => {
+            format!("0x{}", hex::encode(&word[..*size])).into_py_any(py)
+        }
+        DynSolValue::Bytes(bytes) => format!("0x{}", hex::encode(bytes)).into_py_any(py),
+        DynSolValue::String(s) => s.into_py_any(py),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) => values
+            .iter()
+            .map(|v| sol_to_py(py, v))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_py_any(py),
+        DynSolValue::Tuple(values) => {
+            let values = values
+                .iter()
+                .map(|v| sol_to_py(py, v))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyTuple::new(py, values)?.into_py_any(py)
+        }
+        // Function pointers
+        value => format!("0x{}", hex::encode(value.abi_encode_packed())).into_py_any(py),
+    }
+}
+
+/// Calldata of the function `signature`, e.g. `transfer(address,uint256)`,
+/// called with `args`
+pub fn encode_call_helper(signature: &str, args: &Bound<'_, PyAny>) -> Result<Vec<u8>> {
+    let (name, params) = signature
+        .split_once('(')
+        .context("Missing parameters in the signature")?;
+    let params = params
+        .strip_suffix(')')
+        .context("Missing ) in the signature")?;
+    let ty = parse_params(params)?;
+    let value = py_to_sol(&ty, args)?;
+
+    // The selector is computed from the canonical types, e.g. `uint` is `uint256`
+    let selector = keccak256(format!("{}{}", name.trim(), canonical_name(&ty)));
+    let mut data = selector[..4].to_vec();
+    data.extend(value.abi_encode_params());
+    Ok(data)
+}
+
+/// ABI encoded calldata of the function `signature`, e.g.
+/// `transfer(address,uint256)`, called with the sequence `args`
+#[pyfunction]
+pub fn encode_call(signature: &str, args: &Bound<'_, PyAny>) -> PyEvmResult<Cow<'static, [u8]>> {
+    Ok(Cow::Owned(encode_call_helper(signature, args)?))
+}
+
+/// Decode the ABI encoded `data` as values of `types`
+pub fn decode_params(types: &[String], data: &[u8]) -> Result<Vec<DynSolValue>> {
+    let ty = parse_params(&types.join(","))?;
+    match ty.abi_decode_params(data)? {
+        DynSolValue::Tuple(values) => Ok(values),
+        value => Ok(vec![value]),
+    }
+}
+
+/// Decode the ABI encoded `data`, e.g. the output of a call, as a list of
+/// values of `types`, e.g. `["uint256", "address"]`
+#[pyfunction]
+pub fn decode_output(py: Python<'_>, types: Vec<String>, data: BytesArg) -> PyEvmResult<PyObject> {
+    let values = decode_params(&types, &data.into_bytes()?)?;
+    Ok(values
+        .iter()
+        .map(|v| sol_to_py(py, v))
+        .collect::<PyResult<Vec<_>>>()?
+        .into_py_any(py)?)
+}
@@ -0,0 +1,182 @@
+use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use revm::interpreter::opcode;
+
+use crate::{errors::PyEvmResult, instrument::PcSet, trim_prefix};
+
+/// Branches and basic blocks of a bytecode, found without executing it
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BytecodeAnalysis {
+    /// PCs of the JUMPDEST instructions
+    pub jumpdests: Vec<usize>,
+    /// JUMPI sites as `(pc, dest)`, the destination is known when it is
+    /// pushed right before the JUMPI
+    pub jumpis: Vec<(usize, Option<usize>)>,
+    /// JUMP sites as `(pc, dest)`, see `jumpis`
+    pub jumps: Vec<(usize, Option<usize>)>,
+    /// Basic blocks as `(pc of the first instruction, pc of the last instruction)`
+    pub basic_blocks: Vec<(usize, usize)>,
+}
+
+/// Control-flow graph of a bytecode, blocks are identified by the pc of
+/// their first instruction
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    /// Basic blocks as `(pc of the first instruction, pc of the last instruction)`
+    pub basic_blocks: Vec<(usize, usize)>,
+    /// Edges `(from block, to block)` of the JUMPs, the JUMPIs and the
+    /// fallthroughs, sorted. A fallthrough edge goes to the block right
+    /// after the last instruction of `from`.
+    pub edges: Vec<(usize, usize)>,
+}
+
+/// Number of bytes of the instruction at `pc`, PUSH data included
+fn instruction_len(code: &[u8], pc: usize) -> usize {
+    match code[pc] {
+        op @ opcode::PUSH1..=opcode::PUSH32 => (op - opcode::PUSH1 + 1) as usize + 1,
+        _ => 1,
+    }
+}
+
+/// Whether the instruction ends a basic block
+fn is_terminator(op: u8) -> bool {
+    matches!(
+        op,
+        opcode::STOP
+            | opcode::JUMP
+            | opcode::JUMPI
+            | opcode::RETURN
+            | opcode::REVERT
+            | opcode::INVALID
+            | opcode::SELFDESTRUCT
+    )
+}
+
+/// Find the JUMPDESTs, JUMPIs and basic blocks of `code`
+pub fn analyze(code: &[u8]) -> BytecodeAnalysis {
+    let mut analysis = BytecodeAnalysis::default();
+    let mut block_start = None;
+    // Value of the PUSH right before the current instruction
+    let mut pushed: Option<usize> = None;
+
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        let push_len = if (opcode::PUSH1..=opcode::PUSH32).contains(&op) {
+            (op - opcode::PUSH1 + 1) as usize
+        } else {
+            0
+        };
+
+        if op == opcode::JUMPDEST {
+            if let Some(start) = block_start {
+                analysis.basic_blocks.push((start, pc - 1));
+            }
+            analysis.jumpdests.push(pc);
+            block_start = Some(pc);
+        } else if block_start.is_none() {
+            block_start = Some(pc);
+        }
+
+        if op == opcode::JUMPI {
+            analysis.jumpis.push((pc, pushed));
+        } else if op == opcode::JUMP {
+            analysis.jumps.push((pc, pushed));
+        }
+
+        let next = pc + 1 + push_len;
+        if is_terminator(op) || next >= code.len() {
+            if let Some(start) = block_start.take() {
+                analysis.basic_blocks.push((start, pc));
+            }
+        }
+
+        pushed = if push_len > 0 {
+            let data = &code[pc + 1..next.min(code.len())];
+            let first = data.iter().position(|b| *b != 0).unwrap_or(data.len());
+            let data = &data[first..];
+            if data.len() <= std::mem::size_of::<usize>() {
+                Some(data.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        pc = next;
+    }
+
+    analysis
+}
+
+impl BytecodeAnalysis {
+    /// Branches `(jumpi pc, target pc)` whose target was never visited,
+    /// including both branches of the JUMPIs never reached. The jump branch
+    /// is only known for the JUMPIs with a pushed destination.
+    pub fn uncovered_branches(&self, seen_pcs: &PcSet) -> Vec<(usize, usize)> {
+        let mut branches = vec![];
+        for (pc, dest) in &self.jumpis {
+            let targets = dest
+                .filter(|dest| self.jumpdests.binary_search(dest).is_ok())
+                .into_iter()
+                .chain([pc + 1]);
+            for target in targets {
+                if !seen_pcs.contains(target) {
+                    branches.push((*pc, target));
+                }
+            }
+        }
+        branches
+    }
+}
+
+/// Build the control-flow graph of `code` from the jump destinations pushed
+/// right before the jumps, and from the jumps `(pc, destination)` observed
+/// during executions
+pub fn control_flow_graph(code: &[u8], jumps: &HashSet<(usize, usize)>) -> ControlFlowGraph {
+    let analysis = analyze(code);
+    let is_jumpdest = |pc: &usize| analysis.jumpdests.binary_search(pc).is_ok();
+    let pushed_dests: HashMap<_, _> = analysis
+        .jumps
+        .iter()
+        .chain(&analysis.jumpis)
+        .filter_map(|(pc, dest)| Some((*pc, (*dest)?)))
+        .collect();
+
+    let mut edges = vec![];
+    for (start, end) in &analysis.basic_blocks {
+        let op = code[*end];
+        let next = end + instruction_len(code, *end);
+        if (!is_terminator(op) || op == opcode::JUMPI) && next < code.len() {
+            edges.push((*start, next));
+        }
+        if let Some(dest) = pushed_dests.get(end).filter(|dest| is_jumpdest(dest)) {
+            edges.push((*start, *dest));
+        }
+        if matches!(op, opcode::JUMP | opcode::JUMPI) {
+            edges.extend(
+                jumps
+                    .iter()
+                    .filter(|(pc, dest)| pc == end && is_jumpdest(dest))
+                    .map(|(_, dest)| (*start, *dest)),
+            );
+        }
+    }
+    edges.sort_unstable();
+    edges.dedup();
+
+    ControlFlowGraph {
+        basic_blocks: analysis.basic_blocks,
+        edges,
+    }
+}
+
+/// Find the JUMPDESTs, JUMPI sites and basic blocks of a hex encoded bytecode
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn analyze_bytecode(code: String) -> PyEvmResult<BytecodeAnalysis> {
+    let code = hex::decode(trim_prefix(&code, "0x"))?;
+    Ok(analyze(&code))
+}
@@ -0,0 +1,200 @@
+use eyre::Result;
+use num_bigint::BigInt;
+use pyo3::{exceptions::PyOverflowError, ffi, prelude::*, types::PyInt};
+use revm::primitives::Address;
+use ruint::aliases::U256;
+use std::convert::Infallible;
+
+use crate::{
+    bigint_to_ruint_u256,
+    errors::{parse_address, Failure},
+    trim_prefix,
+};
+
+/// Address given as a hex string, with or without the `0x` prefix, or as
+/// 20 bytes
+#[derive(FromPyObject, Clone, Debug)]
+pub enum AddressArg {
+    #[pyo3(transparent)]
+    Hex(String),
+    #[pyo3(transparent)]
+    Bytes(Vec<u8>),
+}
+
+impl AddressArg {
+    pub fn address(&self) -> Result<Address> {
+        match self {
+            AddressArg::Hex(address) => parse_address(address),
+            AddressArg::Bytes(bytes) if bytes.len() == Address::len_bytes() => {
+                Ok(Address::from_slice(bytes))
+            }
+            AddressArg::Bytes(bytes) => {
+                Err(Failure::InvalidAddress(format!("0x{}", hex::encode(bytes))).into())
+            }
+        }
+    }
+}
+
+impl From<String> for AddressArg {
+    fn from(address: String) -> Self {
+        AddressArg::Hex(address)
+    }
+}
+
+impl From<&str> for AddressArg {
+    fn from(address: &str) -> Self {
+        AddressArg::Hex(address.into())
+    }
+}
+
+impl From<Address> for AddressArg {
+    fn from(address: Address) -> Self {
+        AddressArg::Bytes(address.to_vec())
+    }
+}
+
+/// Data given as a hex string, with or without the `0x` prefix, or as bytes
+#[derive(FromPyObject, Clone, Debug)]
+pub enum BytesArg {
+    #[pyo3(transparent)]
+    Hex(String),
+    #[pyo3(transparent)]
+    Bytes(Vec<u8>),
+}
+
+impl BytesArg {
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        match self {
+            BytesArg::Hex(data) => Ok(hex::decode(trim_prefix(&data, "0x"))?),
+            BytesArg::Bytes(bytes) => Ok(bytes),
+        }
+    }
+}
+
+impl From<String> for BytesArg {
+    fn from(data: String) -> Self {
+        BytesArg::Hex(data)
+    }
+}
+
+impl From<&str> for BytesArg {
+    fn from(data: &str) -> Self {
+        BytesArg::Hex(data.into())
+    }
+}
+
+impl From<Vec<u8>> for BytesArg {
+    fn from(bytes: Vec<u8>) -> Self {
+        BytesArg::Bytes(bytes)
+    }
+}
+
+/// 256-bit unsigned integer converted from and to a Python int without an
+/// intermediate `BigInt` when it fits in 128 bits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PyU256(pub U256);
+
+impl<'py> FromPyObject<'py> for PyU256 {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(value) = ob.extract::<u128>() {
+            return Ok(Self(U256::from(value)));
+        }
+        let value: BigInt = ob.extract()?;
+        bigint_to_ruint_u256(&value)
+            .map(Self)
+            .map_err(|e| PyOverflowError::new_err(e.to_string()))
+    }
+}
+
+impl<'py> IntoPyObject<'py> for PyU256 {
+    type Target = PyInt;
+    type Output = Bound<'py, PyInt>;
+    type Error = Infallible;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        let limbs = self.0.as_limbs();
+        if limbs[2] == 0 && limbs[3] == 0 {
+            return (((limbs[1] as u128) << 64) | limbs[0] as u128).into_pyobject(py);
+        }
+        let bytes = self.0.as_le_bytes();
+        // SAFETY: `bytes` is valid for its length, the new reference is owned
+        // and points to an int
+        unsafe {
+            Ok(Bound::from_owned_ptr(
+                py,
+                ffi::_PyLong_FromByteArray(bytes.as_ptr(), bytes.len(), 1, 0),
+            )
+            .downcast_into_unchecked())
+        }
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &PyU256 {
+    type Target = PyInt;
+    type Output = Bound<'py, PyInt>;
+    type Error = Infallible;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        (*self).into_pyobject(py)
+    }
+}
+
+impl From<U256> for PyU256 {
+    fn from(value: U256) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&U256> for PyU256 {
+    fn from(value: &U256) -> Self {
+        Self(*value)
+    }
+}
+
+/// 256-bit unsigned integer given as an int, or as a hex string with or
+/// without the `0x` prefix
+#[derive(FromPyObject, Clone, Debug)]
+pub enum U256Arg {
+    /// Int converted directly, see `PyU256`
+    #[pyo3(transparent)]
+    Uint(PyU256),
+    /// Negative or too large int, rejected by `value`
+    #[pyo3(transparent)]
+    Int(BigInt),
+    #[pyo3(transparent)]
+    Hex(String),
+}
+
+impl U256Arg {
+    pub fn value(&self) -> Result<U256> {
+        match self {
+            U256Arg::Uint(value) => Ok(value.0),
+            U256Arg::Int(value) => bigint_to_ruint_u256(value),
+            U256Arg::Hex(value) => Ok(U256::from_str_radix(trim_prefix(value, "0x"), 16)?),
+        }
+    }
+}
+
+impl From<BigInt> for U256Arg {
+    fn from(value: BigInt) -> Self {
+        U256Arg::Int(value)
+    }
+}
+
+impl From<U256> for U256Arg {
+    fn from(value: U256) -> Self {
+        U256Arg::Uint(PyU256(value))
+    }
+}
+
+impl From<String> for U256Arg {
+    fn from(value: String) -> Self {
+        U256Arg::Hex(value)
+    }
+}
+
+impl From<&str> for U256Arg {
+    fn from(value: &str) -> Self {
+        U256Arg::Hex(value.into())
+    }
+}
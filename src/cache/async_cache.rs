@@ -0,0 +1,155 @@
+use super::{cache_key, CacheKey, ProviderCache};
+use eyre::{eyre, ContextCompat, Result};
+use revm::primitives::B256;
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+use tracing::warn;
+
+/// Chain, block, API and request hash of a cache entry
+type EntryId = (String, u64, String, B256);
+
+enum Message {
+    Store(EntryId, Vec<u8>),
+    /// Notify the sender once all previously queued writes are stored
+    Flush(Sender<()>),
+}
+
+/// Background writer shared by all clones of an [`AsyncProviderCache`]. The
+/// queue is drained and the thread joined when the last clone is dropped.
+struct Writer {
+    sender: Option<Sender<Message>>,
+    handle: Option<JoinHandle<()>>,
+    /// Entries queued but not yet written to the inner cache, so that reads
+    /// right after a write do not miss
+    pending: Arc<Mutex<HashMap<CacheKey, Vec<u8>>>>,
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        // Closing the channel makes the writer thread exit after the queue is drained
+        drop(self.sender.take());
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                warn!("Cache writer thread panicked, some entries may not be stored");
+            }
+        }
+    }
+}
+
+/// A provider cache wrapper which performs `store` on a background thread,
+/// so that execution only pays for the RPC request itself.
+#[derive(Clone)]
+pub struct AsyncProviderCache<T: ProviderCache> {
+    inner: T,
+    writer: Arc<Writer>,
+}
+
+impl<T: ProviderCache + Send + 'static> AsyncProviderCache<T> {
+    pub fn new(inner: T) -> Self {
+        let (sender, receiver) = mpsc::channel::<Message>();
+        let pending: Arc<Mutex<HashMap<CacheKey, Vec<u8>>>> = Default::default();
+
+        let handle = {
+            let inner = inner.clone();
+            let pending = pending.clone();
+            thread::spawn(move || {
+                for message in receiver {
+                    match message {
+                        Message::Store(id, response) => {
+                            let (chain, block, api, request_hash) = &id;
+                            if let Err(e) = inner.store(chain, *block, api, request_hash, &response)
+                            {
+                                warn!("Failed to store cache entry {:?}: {}", id, e);
+                            }
+                            let key = cache_key(chain, *block, api, request_hash);
+                            let mut pending = pending.lock().unwrap();
+                            // Keep a newer value queued for the same entry
+                            if pending.get(&key) == Some(&response) {
+                                pending.remove(&key);
+                            }
+                        }
+                        Message::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+        };
+
+        Self {
+            inner,
+            writer: Arc::new(Writer {
+                sender: Some(sender),
+                handle: Some(handle),
+                pending,
+            }),
+        }
+    }
+
+    fn send(&self, message: Message) -> Result<()> {
+        let sender = self
+            .writer
+            .sender
+            .as_ref()
+            .context("Cache writer is closed")?;
+        sender
+            .send(message)
+            .map_err(|_| eyre!("Cache writer thread is not running"))
+    }
+}
+
+impl<T: ProviderCache + Send + 'static> Default for AsyncProviderCache<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ProviderCache + Send + 'static> ProviderCache for AsyncProviderCache<T> {
+    fn store(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+        response: &[u8],
+    ) -> Result<()> {
+        let key = cache_key(chain, block, api, request_hash);
+        self.writer
+            .pending
+            .lock()
+            .unwrap()
+            .insert(key, response.to_vec());
+        let id = (chain.to_string(), block, api.to_string(), *request_hash);
+        let result = self.send(Message::Store(id, response.to_vec()));
+        if result.is_err() {
+            // Never written, it would be served until the cache is dropped
+            self.writer.pending.lock().unwrap().remove(&key);
+        }
+        result
+    }
+
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>> {
+        {
+            let key = cache_key(chain, block, api, request_hash);
+            let pending = self.writer.pending.lock().unwrap();
+            if let Some(response) = pending.get(&key) {
+                return Ok(response.clone());
+            }
+        }
+        self.inner.get(chain, block, api, request_hash)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let (done, wait) = mpsc::channel();
+        self.send(Message::Flush(done))?;
+        wait.recv()
+            .map_err(|_| eyre!("Cache writer thread stopped before flushing"))?;
+        self.inner.flush()
+    }
+}
@@ -1,50 +1,122 @@
 use super::ProviderCache;
 use eyre::Result;
+use revm::primitives::B256;
 use std::{
     env,
     fs::{self, File},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
 };
+use uuid::Uuid;
+
+#[cfg(unix)]
+use std::{fs::OpenOptions, os::unix::io::AsRawFd};
+
+/// Name of the lock file created in each cache directory
+const LOCK_FILE: &str = ".lock";
 
 #[derive(Default, Debug, Clone)]
 pub struct FileSystemProviderCache {}
 
+/// Advisory lock (`flock`) on a file, released when dropped
+#[cfg(unix)]
+struct FileLock(File);
+
+#[cfg(unix)]
+impl FileLock {
+    fn acquire(path: &Path, exclusive: bool) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)?;
+        let operation = if exclusive {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_SH
+        };
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self(file))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::flock(self.0.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
+
+/// No lock without `flock`, the readers still never observe a partially
+/// written entry thanks to the rename in `store`
+#[cfg(not(unix))]
+struct FileLock;
+
+#[cfg(not(unix))]
+impl FileLock {
+    fn acquire(_path: &Path, _exclusive: bool) -> Result<Self> {
+        Ok(Self)
+    }
+}
+
+impl FileSystemProviderCache {
+    /// Directory holding the cached responses for an API at a given block
+    fn api_dir(chain: &str, block: u64, api: &str) -> Result<PathBuf> {
+        let home_dir = env::var("HOME")?;
+        Ok(Path::new(&home_dir)
+            .join(".tinyevm")
+            .join(chain)
+            .join(block.to_string())
+            .join(api))
+    }
+}
+
 impl ProviderCache for FileSystemProviderCache {
+    /// Write the response to a temporary file and rename it into place, so
+    /// that concurrent readers never observe a partially written entry
     fn store(
         &self,
         chain: &str,
         block: u64,
         api: &str,
-        request_hash: &str,
-        response: &str,
+        request_hash: &B256,
+        response: &[u8],
     ) -> Result<()> {
-        let home_dir = env::var("HOME")?;
-        let path = Path::new(&home_dir)
-            .join(".tinyevm")
-            .join(chain)
-            .join(block.to_string())
-            .join(api);
+        let path = Self::api_dir(chain, block, api)?;
         fs::create_dir_all(&path)?;
-        let mut file = File::create(path.join(request_hash))?;
-        file.write_all(response.as_bytes())?;
+
+        let name = hex::encode(request_hash);
+        let tmp_path = path.join(format!(
+            ".{}.{}.{}.tmp",
+            name,
+            std::process::id(),
+            Uuid::new_v4().simple()
+        ));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(response)?;
+        file.sync_all()?;
+
+        let _lock = FileLock::acquire(&path.join(LOCK_FILE), true)?;
+        if let Err(e) = fs::rename(&tmp_path, path.join(name)) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
         Ok(())
     }
 
-    fn get(
-        &self,
-        chain: &str,
-        block: u64,
-        api: &str,
-        request_hash: &str,
-    ) -> Result<String> {
-        let home_dir = env::var("HOME")?;
-        let path = Path::new(&home_dir)
-            .join(".tinyevm")
-            .join(chain)
-            .join(block.to_string())
-            .join(api)
-            .join(request_hash);
-        Ok(fs::read_to_string(path)?)
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>> {
+        let path = Self::api_dir(chain, block, api)?;
+        let entry = path.join(hex::encode(request_hash));
+        // Avoid creating lock files in directories of entries that were never stored
+        if !entry.exists() {
+            return Ok(fs::read(entry)?);
+        }
+        let _lock = FileLock::acquire(&path.join(LOCK_FILE), false)?;
+        Ok(fs::read(entry)?)
     }
 }
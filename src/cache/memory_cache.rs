@@ -0,0 +1,127 @@
+use super::{cache_key, CacheKey, ProviderCache};
+use eyre::{eyre, Result};
+use revm::primitives::B256;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+/// Default limit of the size of the responses kept by a
+/// [`MemoryProviderCache`], 64 MiB
+pub const DEFAULT_MEMORY_CACHE_CAPACITY: usize = 64 << 20;
+
+/// Entries of a [`MemoryProviderCache`], evicted least recently used first
+#[derive(Default, Debug)]
+struct Lru {
+    /// Response and last use of each entry
+    entries: HashMap<CacheKey, (Vec<u8>, u64)>,
+    /// Keys by last use, the least recently used first
+    by_use: BTreeMap<u64, CacheKey>,
+    /// Incremented on each use
+    clock: u64,
+    /// Total size of the responses
+    size: usize,
+}
+
+impl Lru {
+    fn get(&mut self, key: &CacheKey) -> Option<Vec<u8>> {
+        let (response, used) = self.entries.get_mut(key)?;
+        self.by_use.remove(used);
+        self.clock += 1;
+        *used = self.clock;
+        self.by_use.insert(self.clock, *key);
+        Some(response.clone())
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some((response, used)) = self.entries.remove(key) {
+            self.by_use.remove(&used);
+            self.size -= response.len();
+        }
+    }
+
+    fn insert(&mut self, key: CacheKey, response: Vec<u8>, capacity: usize) {
+        self.remove(&key);
+        if response.len() > capacity {
+            return;
+        }
+        while self.size + response.len() > capacity {
+            let Some((_, oldest)) = self.by_use.pop_first() else {
+                break;
+            };
+            if let Some((evicted, _)) = self.entries.remove(&oldest) {
+                self.size -= evicted.len();
+            }
+        }
+        self.clock += 1;
+        self.size += response.len();
+        self.by_use.insert(self.clock, key);
+        self.entries.insert(key, (response, self.clock));
+    }
+}
+
+/// In-memory provider cache holding at most `capacity` bytes of responses,
+/// the least recently used entries are evicted first. Clones share the same
+/// storage.
+#[derive(Debug, Clone)]
+pub struct MemoryProviderCache {
+    entries: Arc<Mutex<Lru>>,
+    capacity: usize,
+}
+
+impl Default for MemoryProviderCache {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_MEMORY_CACHE_CAPACITY)
+    }
+}
+
+impl MemoryProviderCache {
+    /// Cache keeping at most `capacity` bytes of responses
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            capacity,
+        }
+    }
+
+    /// Number of cached entries
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total size of the cached responses in bytes
+    pub fn size(&self) -> usize {
+        self.entries.lock().unwrap().size
+    }
+}
+
+impl ProviderCache for MemoryProviderCache {
+    fn store(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+        response: &[u8],
+    ) -> Result<()> {
+        let key = cache_key(chain, block, api, request_hash);
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, response.to_vec(), self.capacity);
+        Ok(())
+    }
+
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>> {
+        let key = cache_key(chain, block, api, request_hash);
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&key)
+            .ok_or_else(|| eyre!("Cache miss"))
+    }
+}
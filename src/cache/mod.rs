@@ -1,25 +1,204 @@
-use eyre::Result;
+use eyre::{eyre, Result};
+use revm::primitives::{alloy_primitives::Keccak256, keccak256, B256};
 
-#[cfg(not(feature = "redis"))]
+/// Write-behind wrapper for any provider cache
+pub mod async_cache;
 pub mod filesystem_cache;
-
+pub mod memory_cache;
+/// Cache backend implemented in Python
+#[cfg(feature = "python")]
+pub mod py_cache;
 #[cfg(feature = "redis")]
 pub mod redis_cache;
+/// Composition of a fast and a slow cache layer
+pub mod tiered_cache;
+
+pub use async_cache::AsyncProviderCache;
+pub use filesystem_cache::FileSystemProviderCache;
+pub use memory_cache::MemoryProviderCache;
+#[cfg(feature = "python")]
+pub use py_cache::PyProviderCache;
+#[cfg(feature = "redis")]
+pub use redis_cache::RedisProviderCache;
+pub use tiered_cache::TieredCache;
+
+/// Fixed-width key identifying a cached request, see [`cache_key`]
+pub(crate) type CacheKey = B256;
+
+/// Hash of the chain, the block, the API and the request hash of a cached
+/// request, computed without allocating
+pub(crate) fn cache_key(chain: &str, block: u64, api: &str, request_hash: &B256) -> CacheKey {
+    let mut hasher = Keccak256::new();
+    for part in [chain.as_bytes(), &block.to_be_bytes(), api.as_bytes()] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.update(request_hash);
+    hasher.finalize()
+}
+
+/// Keccak256 hash of the parts of a request, used as the fixed-width
+/// `request_hash` of the cache entries
+pub fn request_hash(parts: &[&[u8]]) -> B256 {
+    let mut hasher = Keccak256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize()
+}
 
+/// memory → filesystem, the memory layer keeps at most
+/// [`memory_cache::DEFAULT_MEMORY_CACHE_CAPACITY`] bytes
 #[cfg(not(feature = "redis"))]
-pub use filesystem_cache::FileSystemProviderCache as DefaultProviderCache;
+pub type BuiltinProviderCache =
+    AsyncProviderCache<TieredCache<MemoryProviderCache, FileSystemProviderCache>>;
+/// memory → redis → filesystem
 #[cfg(feature = "redis")]
-pub use redis_cache::RedisProviderCache as DefaultProviderCache;
+pub type BuiltinProviderCache = AsyncProviderCache<
+    TieredCache<MemoryProviderCache, TieredCache<RedisProviderCache, FileSystemProviderCache>>,
+>;
 
+/// Provider cache used by TinyEVM: either the builtin layers or a storage
+/// implemented in Python.
+///
+/// The Python backend is called synchronously, the builtin write-behind
+/// thread would need the GIL held by the caller.
+#[derive(Clone)]
+pub enum DefaultProviderCache {
+    Builtin(BuiltinProviderCache),
+    #[cfg(feature = "python")]
+    Python(TieredCache<MemoryProviderCache, PyProviderCache>),
+}
+
+impl DefaultProviderCache {
+    /// Use a Python object as the persistent storage, see [`PyProviderCache`]
+    #[cfg(feature = "python")]
+    pub fn python(backend: pyo3::Py<pyo3::PyAny>) -> Self {
+        Self::Python(TieredCache::new(
+            MemoryProviderCache::default(),
+            PyProviderCache::new(backend),
+        ))
+    }
+
+    /// Builtin layers using the redis `node` instead of `TINYEVM_REDIS_NODE`
+    #[cfg(feature = "redis")]
+    pub fn redis(node: &str) -> Result<Self> {
+        Ok(Self::Builtin(AsyncProviderCache::new(TieredCache::new(
+            MemoryProviderCache::default(),
+            TieredCache::new(
+                RedisProviderCache::new(node)?,
+                FileSystemProviderCache::default(),
+            ),
+        ))))
+    }
+}
+
+impl Default for DefaultProviderCache {
+    fn default() -> Self {
+        Self::Builtin(Default::default())
+    }
+}
+
+impl ProviderCache for DefaultProviderCache {
+    fn store(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+        response: &[u8],
+    ) -> Result<()> {
+        match self {
+            Self::Builtin(cache) => cache.store(chain, block, api, request_hash, response),
+            #[cfg(feature = "python")]
+            Self::Python(cache) => cache.store(chain, block, api, request_hash, response),
+        }
+    }
+
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>> {
+        match self {
+            Self::Builtin(cache) => cache.get(chain, block, api, request_hash),
+            #[cfg(feature = "python")]
+            Self::Python(cache) => cache.get(chain, block, api, request_hash),
+        }
+    }
+
+    fn flush(&self) -> Result<()> {
+        match self {
+            Self::Builtin(cache) => cache.flush(),
+            #[cfg(feature = "python")]
+            Self::Python(cache) => cache.flush(),
+        }
+    }
+}
+
+/// Storage of the remote responses. The responses are binary entries
+/// created with [`encode_entry`]
 pub trait ProviderCache: Clone + Default {
     fn store(
         &self,
         chain: &str,
         block: u64,
         api: &str,
-        request_hash: &str,
-        response: &str,
+        request_hash: &B256,
+        response: &[u8],
     ) -> Result<()>;
 
-    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &str) -> Result<String>;
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>>;
+
+    /// Block until all previously stored entries are persisted
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Version of the cache entry format, bump it when the serialization of any
+/// cached response changes so that stale entries are refetched
+pub const CACHE_FORMAT_VERSION: u32 = 2;
+
+/// Prefix of every cache entry
+const ENTRY_MAGIC: &str = "tinyevm";
+
+/// Checksum of a cache entry payload: first 8 bytes of its keccak256 hash, hex encoded
+fn entry_checksum(payload: &[u8]) -> String {
+    hex::encode(&keccak256(payload)[..8])
+}
+
+/// Wrap a binary response with a version header and checksum:
+/// `tinyevm:<version>:<checksum>:<payload>`
+pub fn encode_entry(payload: &[u8]) -> Vec<u8> {
+    let header = format!(
+        "{}:{}:{}:",
+        ENTRY_MAGIC,
+        CACHE_FORMAT_VERSION,
+        entry_checksum(payload)
+    );
+    [header.as_bytes(), payload].concat()
+}
+
+/// Validate an entry created by [`encode_entry`] and return its payload
+pub fn decode_entry(entry: &[u8]) -> Result<&[u8]> {
+    let mut parts = entry.splitn(4, |b| *b == b':');
+    let (magic, version, checksum, payload) =
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(magic), Some(version), Some(checksum), Some(payload)) => {
+                (magic, version, checksum, payload)
+            }
+            _ => return Err(eyre!("Missing cache entry header")),
+        };
+
+    if magic != ENTRY_MAGIC.as_bytes() {
+        return Err(eyre!("Missing cache entry header"));
+    }
+    if version != CACHE_FORMAT_VERSION.to_string().as_bytes() {
+        return Err(eyre!(
+            "Unsupported cache entry version {}, expecting {}",
+            String::from_utf8_lossy(version),
+            CACHE_FORMAT_VERSION
+        ));
+    }
+    if checksum != entry_checksum(payload).as_bytes() {
+        return Err(eyre!("Cache entry checksum mismatch"));
+    }
+    Ok(payload)
 }
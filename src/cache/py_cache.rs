@@ -0,0 +1,135 @@
+use super::ProviderCache;
+use eyre::{eyre, Result};
+use pyo3::{prelude::*, types::PyBytes};
+use revm::primitives::B256;
+use std::{fmt, sync::Arc};
+
+/// Object safe subset of [`ProviderCache`], implemented by the Python backend.
+///
+/// The backend is only reached through this trait object so that crates
+/// linking tinyevm without libpython (e.g. the Rust tests) do not reference
+/// any Python symbol unless a backend is actually created.
+trait CacheBackend: Send + Sync {
+    fn store(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+        response: &[u8],
+    ) -> Result<()>;
+
+    fn get(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+    ) -> Result<Option<Vec<u8>>>;
+
+    fn flush(&self) -> Result<()>;
+}
+
+impl CacheBackend for Py<PyAny> {
+    fn store(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+        response: &[u8],
+    ) -> Result<()> {
+        Python::with_gil(|py| {
+            let response = PyBytes::new(py, response);
+            self.call_method1(
+                py,
+                "store",
+                (chain, block, api, request_hash.as_slice(), response),
+            )?;
+            Ok(())
+        })
+    }
+
+    fn get(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+    ) -> Result<Option<Vec<u8>>> {
+        Python::with_gil(|py| {
+            let response: Option<Bound<'_, PyBytes>> = self
+                .call_method1(py, "get", (chain, block, api, request_hash.as_slice()))?
+                .extract(py)?;
+            Ok(response.map(|response| response.as_bytes().to_vec()))
+        })
+    }
+
+    fn flush(&self) -> Result<()> {
+        Python::with_gil(|py| {
+            if self.bind(py).hasattr("flush")? {
+                self.call_method0(py, "flush")?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// A provider cache backed by a Python object implementing:
+///
+/// - `store(chain, block, api, request_hash, response)`, `request_hash` and
+///   `response` are `bytes`
+/// - `get(chain, block, api, request_hash) -> Optional[bytes]`, `None` on cache miss
+/// - optionally `flush()`
+///
+/// Without a backend every lookup misses and nothing is stored.
+#[derive(Default, Clone)]
+pub struct PyProviderCache {
+    backend: Option<Arc<dyn CacheBackend>>,
+}
+
+impl fmt::Debug for PyProviderCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PyProviderCache")
+            .field("backend", &self.backend.is_some())
+            .finish()
+    }
+}
+
+impl PyProviderCache {
+    pub fn new(backend: Py<PyAny>) -> Self {
+        Self {
+            backend: Some(Arc::new(backend)),
+        }
+    }
+}
+
+impl ProviderCache for PyProviderCache {
+    fn store(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+        response: &[u8],
+    ) -> Result<()> {
+        match &self.backend {
+            Some(backend) => backend.store(chain, block, api, request_hash, response),
+            None => Ok(()),
+        }
+    }
+
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>> {
+        let backend = self.backend.as_ref().ok_or(eyre!("No cache backend"))?;
+        backend
+            .get(chain, block, api, request_hash)?
+            .ok_or(eyre!("Cache miss"))
+    }
+
+    fn flush(&self) -> Result<()> {
+        match &self.backend {
+            Some(backend) => backend.flush(),
+            None => Ok(()),
+        }
+    }
+}
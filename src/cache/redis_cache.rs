@@ -1,25 +1,155 @@
 use super::ProviderCache;
-use eyre::Result;
-use redis::{Client, Commands};
+use eyre::{eyre, Result};
+use r2d2::Pool;
+use redis::{cluster::ClusterClient, cmd, Client, ConnectionLike};
+use revm::primitives::B256;
 use std::env;
 
+/// Configuration for [`RedisProviderCache`]
+#[derive(Clone, Debug)]
+pub struct RedisCacheConfig {
+    /// Redis node URLs. In cluster mode these are the initial nodes used to
+    /// discover the cluster, otherwise only the first node is used
+    pub nodes: Vec<String>,
+    /// Connect to a redis-cluster instead of a single node
+    pub cluster: bool,
+    /// Namespace prepended to every key
+    pub key_prefix: String,
+    /// Optional expiration of each entry, in seconds
+    pub ttl: Option<u64>,
+    /// Max number of pooled connections
+    pub pool_size: u32,
+}
+
+impl Default for RedisCacheConfig {
+    fn default() -> Self {
+        Self {
+            nodes: vec![],
+            cluster: false,
+            key_prefix: "tinyevm".into(),
+            ttl: None,
+            pool_size: 8,
+        }
+    }
+}
+
+impl RedisCacheConfig {
+    /// Load the configuration from environment variables:
+    ///
+    /// - `TINYEVM_REDIS_NODE`: comma separated list of node URLs (required)
+    /// - `TINYEVM_REDIS_CLUSTER`: set to `1` or `true` to enable cluster mode
+    /// - `TINYEVM_REDIS_PREFIX`: key prefix, defaults to `tinyevm`
+    /// - `TINYEVM_REDIS_TTL`: entry expiration in seconds
+    /// - `TINYEVM_REDIS_POOL_SIZE`: max number of pooled connections
+    pub fn from_env() -> Result<Self> {
+        let default = Self::default();
+        let nodes = env::var("TINYEVM_REDIS_NODE")
+            .map_err(|_| eyre!("Redis node is required"))?
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let cluster = env::var("TINYEVM_REDIS_CLUSTER")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(default.cluster);
+        let key_prefix = env::var("TINYEVM_REDIS_PREFIX").unwrap_or(default.key_prefix);
+        let ttl = env::var("TINYEVM_REDIS_TTL")
+            .ok()
+            .map(|v| v.parse::<u64>())
+            .transpose()?;
+        let pool_size = env::var("TINYEVM_REDIS_POOL_SIZE")
+            .ok()
+            .map(|v| v.parse::<u32>())
+            .transpose()?
+            .unwrap_or(default.pool_size);
+
+        Ok(Self {
+            nodes,
+            cluster,
+            key_prefix,
+            ttl,
+            pool_size,
+        })
+    }
+}
+
+#[derive(Clone)]
+enum ConnectionPool {
+    Single(Pool<Client>),
+    Cluster(Pool<ClusterClient>),
+}
+
 #[derive(Clone)]
 pub struct RedisProviderCache {
-    client: Client,
+    pool: ConnectionPool,
+    key_prefix: String,
+    ttl: Option<u64>,
 }
 
 impl Default for RedisProviderCache {
     fn default() -> Self {
-        let node =
-            env::var("TINYEVM_REDIS_NODE").expect("Redis node is required");
-        RedisProviderCache::new(&node).unwrap()
+        let config = RedisCacheConfig::from_env().expect("Invalid redis configuration");
+        RedisProviderCache::with_config(&config).unwrap()
     }
 }
 
 impl RedisProviderCache {
+    /// Create a cache connecting to a single redis node with default settings
     pub fn new(node: &str) -> Result<Self> {
-        let client = Client::open(node)?;
-        Ok(Self { client })
+        Self::with_config(&RedisCacheConfig {
+            nodes: vec![node.to_string()],
+            ..Default::default()
+        })
+    }
+
+    pub fn with_config(config: &RedisCacheConfig) -> Result<Self> {
+        let pool = if config.cluster {
+            let client = ClusterClient::new(config.nodes.clone())?;
+            ConnectionPool::Cluster(
+                Pool::builder()
+                    .max_size(config.pool_size)
+                    .build_unchecked(client),
+            )
+        } else {
+            let node = config
+                .nodes
+                .first()
+                .ok_or(eyre!("Redis node is required"))?;
+            let client = Client::open(node.as_str())?;
+            ConnectionPool::Single(
+                Pool::builder()
+                    .max_size(config.pool_size)
+                    .build_unchecked(client),
+            )
+        };
+
+        Ok(Self {
+            pool,
+            key_prefix: config.key_prefix.clone(),
+            ttl: config.ttl,
+        })
+    }
+
+    fn key(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> String {
+        format!(
+            "{}_{}_{}_{}_{}",
+            self.key_prefix,
+            chain,
+            block,
+            api,
+            hex::encode(request_hash)
+        )
+    }
+
+    /// Run a command with a pooled connection
+    fn with_connection<R>(
+        &self,
+        f: impl FnOnce(&mut dyn ConnectionLike) -> Result<R>,
+    ) -> Result<R> {
+        match &self.pool {
+            ConnectionPool::Single(pool) => f(&mut *pool.get()?),
+            ConnectionPool::Cluster(pool) => f(&mut *pool.get()?),
+        }
     }
 }
 
@@ -29,31 +159,23 @@ impl ProviderCache for RedisProviderCache {
         chain: &str,
         block: u64,
         api: &str,
-        request_hash: &str,
-        response: &str,
+        request_hash: &B256,
+        response: &[u8],
     ) -> Result<()> {
-        let key = format!(
-            "{}_{}_{}_{}_{}",
-            "tinyevm", chain, block, api, request_hash
-        );
-        let mut conn = self.client.get_connection()?;
-        conn.set(key, response)?;
-        Ok(())
+        let key = self.key(chain, block, api, request_hash);
+        let mut command = cmd("SET");
+        command.arg(key).arg(response);
+        if let Some(ttl) = self.ttl {
+            command.arg("EX").arg(ttl);
+        }
+        self.with_connection(|conn| Ok(command.query::<()>(conn)?))
     }
 
-    fn get(
-        &self,
-        chain: &str,
-        block: u64,
-        api: &str,
-        request_hash: &str,
-    ) -> Result<String> {
-        let key = format!(
-            "{}_{}_{}_{}_{}",
-            "tinyevm", chain, block, api, request_hash
-        );
-        let mut conn = self.client.get_connection()?;
-        let val = conn.get(key)?;
-        Ok(val)
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>> {
+        let key = self.key(chain, block, api, request_hash);
+        // A missing key is Nil, which redis would also convert to an empty value
+        let response: Option<Vec<u8>> =
+            self.with_connection(|conn| Ok(cmd("GET").arg(key).query(conn)?))?;
+        response.ok_or_else(|| eyre!("Cache miss"))
     }
 }
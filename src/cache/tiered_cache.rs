@@ -0,0 +1,53 @@
+use super::ProviderCache;
+use eyre::Result;
+use revm::primitives::B256;
+use tracing::warn;
+
+/// A two-level provider cache: reads check the `fast` layer first and fall
+/// back to the `slow` layer, copying the entry into the `fast` layer on hit.
+/// Writes go to both layers.
+///
+/// More levels are built by nesting, e.g. memory → redis → filesystem:
+/// `TieredCache<MemoryProviderCache, TieredCache<RedisProviderCache, FileSystemProviderCache>>`
+#[derive(Default, Debug, Clone)]
+pub struct TieredCache<Fast: ProviderCache, Slow: ProviderCache> {
+    pub fast: Fast,
+    pub slow: Slow,
+}
+
+impl<Fast: ProviderCache, Slow: ProviderCache> TieredCache<Fast, Slow> {
+    pub fn new(fast: Fast, slow: Slow) -> Self {
+        Self { fast, slow }
+    }
+}
+
+impl<Fast: ProviderCache, Slow: ProviderCache> ProviderCache for TieredCache<Fast, Slow> {
+    fn store(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+        response: &[u8],
+    ) -> Result<()> {
+        self.fast.store(chain, block, api, request_hash, response)?;
+        self.slow.store(chain, block, api, request_hash, response)
+    }
+
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>> {
+        if let Ok(response) = self.fast.get(chain, block, api, request_hash) {
+            return Ok(response);
+        }
+
+        let response = self.slow.get(chain, block, api, request_hash)?;
+        if let Err(e) = self.fast.store(chain, block, api, request_hash, &response) {
+            warn!("Failed to write back cache entry for {}: {}", api, e);
+        }
+        Ok(response)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.fast.flush()?;
+        self.slow.flush()
+    }
+}
@@ -2,16 +2,32 @@ use revm::interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome};
 use revm::primitives::Log;
 use revm::{interpreter::Interpreter, Database, EvmContext, Inspector};
 
+use crate::fork_db::TrackCallDepth;
 use crate::instrument::bug_inspector::BugInspector;
+use crate::instrument::cheatcode_inspector::{CheatcodeInspector, CHEATCODE_ADDRESS};
 use crate::instrument::log_inspector::LogInspector;
 
-/// A chain of inspectors, ecch inspector will be executed in order.
+/// A chain of inspectors, ecch inspector will be executed in order. The
+/// database is kept informed of the depth of the frame being executed.
 pub struct ChainInspector {
     pub log_inspector: Option<LogInspector>,
     pub bug_inspector: Option<BugInspector>,
+    /// Runs the Foundry cheatcodes if set, the calls to the cheatcodes are
+    /// not seen by the other inspectors
+    pub cheatcode_inspector: Option<CheatcodeInspector>,
 }
 
-impl<DB: Database> Inspector<DB> for ChainInspector {
+impl<DB: Database + TrackCallDepth> Inspector<DB> for ChainInspector {
+    #[inline]
+    fn initialize_interp(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        if let Some(ins) = self.log_inspector.as_mut() {
+            ins.initialize_interp(interp, context);
+        }
+        if let Some(ins) = self.bug_inspector.as_mut() {
+            ins.initialize_interp(interp, context);
+        }
+    }
+
     #[inline]
     fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
         if let Some(ins) = self.log_inspector.as_mut() {
@@ -50,6 +66,14 @@ impl<DB: Database> Inspector<DB> for ChainInspector {
         context: &mut EvmContext<DB>,
         inputs: &mut CallInputs,
     ) -> Option<CallOutcome> {
+        let depth = context.journaled_state.depth() as usize;
+        context.db.set_call_depth(depth + 1);
+        // First, the pranks change the caller seen by the other inspectors
+        if let Some(ins) = self.cheatcode_inspector.as_mut() {
+            if let Some(outcome) = ins.call(context, inputs) {
+                return Some(outcome);
+            }
+        }
         if let Some(ins) = self.log_inspector.as_mut() {
             ins.call(context, inputs);
         }
@@ -67,6 +91,12 @@ impl<DB: Database> Inspector<DB> for ChainInspector {
         inputs: &CallInputs,
         outcome: CallOutcome,
     ) -> CallOutcome {
+        // Back to the depth of the caller
+        let depth = context.journaled_state.depth() as usize;
+        context.db.set_call_depth(depth);
+        if self.cheatcode_inspector.is_some() && inputs.target_address == CHEATCODE_ADDRESS {
+            return outcome;
+        }
         let mut outcome = outcome;
         if let Some(ins) = self.log_inspector.as_mut() {
             outcome = ins.call_end(context, inputs, outcome);
@@ -74,6 +104,10 @@ impl<DB: Database> Inspector<DB> for ChainInspector {
         if let Some(ins) = self.bug_inspector.as_mut() {
             outcome = ins.call_end(context, inputs, outcome);
         }
+        // Last, the other inspectors see the revert expected by the caller
+        if let Some(ins) = self.cheatcode_inspector.as_mut() {
+            outcome = ins.call_end(context, inputs, outcome);
+        }
         outcome
     }
 
@@ -84,6 +118,11 @@ impl<DB: Database> Inspector<DB> for ChainInspector {
         context: &mut EvmContext<DB>,
         inputs: &mut CreateInputs,
     ) -> Option<CreateOutcome> {
+        let depth = context.journaled_state.depth() as usize;
+        context.db.set_call_depth(depth + 1);
+        if let Some(ins) = self.cheatcode_inspector.as_mut() {
+            ins.create(context, inputs);
+        }
         if let Some(ins) = self.log_inspector.as_mut() {
             ins.create(context, inputs);
         }
@@ -101,11 +140,14 @@ impl<DB: Database> Inspector<DB> for ChainInspector {
         inputs: &CreateInputs,
         outcome: CreateOutcome,
     ) -> CreateOutcome {
+        let depth = context.journaled_state.depth() as usize;
+        context.db.set_call_depth(depth);
         let mut outcome = outcome;
-        if let Some(ins) = self.log_inspector.as_mut() {
+        // The bug inspector first, the traces get the overridden addresses
+        if let Some(ins) = self.bug_inspector.as_mut() {
             outcome = ins.create_end(context, inputs, outcome);
         }
-        if let Some(ins) = self.bug_inspector.as_mut() {
+        if let Some(ins) = self.log_inspector.as_mut() {
             outcome = ins.create_end(context, inputs, outcome);
         }
         outcome
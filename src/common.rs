@@ -1,3 +1,5 @@
+#[cfg(feature = "python")]
+use std::borrow::Cow;
 use std::cmp::Ordering;
 
 /// Common constants, data structures and functions to be used by both rust-evm and revm
@@ -5,10 +7,15 @@ use eyre::Result;
 use hex::ToHex;
 use num_bigint::BigInt;
 use primitive_types::H256;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
 use revm::interpreter::instructions::i256::i256_cmp;
 use ruint::aliases::U256;
 use sha3::{Digest, Keccak256};
 
+#[cfg(feature = "python")]
+use crate::{args::BytesArg, errors::PyEvmResult};
+
 /// Default max block gas limit
 pub const MAX_BLOCK_GAS: u64 = 1_000_000_000_000_000;
 /// U256 zero
@@ -20,12 +27,30 @@ pub const HZERO: H256 = H256::zero();
 pub const TX_GAS_LIMIT: u64 = 30_000_000;
 
 /// Get binary prefix by function signature
+#[cfg_attr(feature = "python", pyfunction)]
 pub fn fn_sig_to_prefix(fn_sig: &str) -> String {
     let ret = Keccak256::digest(fn_sig.as_bytes());
     let ret: String = ret.encode_hex();
     ret[..8].to_owned()
 }
 
+/// Keccak-256 hash of `data`, given as bytes or as a hex string
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "keccak256")]
+pub fn keccak256_bytes(data: BytesArg) -> PyEvmResult<Cow<'static, [u8]>> {
+    let hash = Keccak256::digest(data.into_bytes()?);
+    Ok(Cow::Owned(hash.to_vec()))
+}
+
+/// Hex encoded topic of an event by its signature, e.g.
+/// `Transfer(address,address,uint256)`
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn event_topic(event_sig: &str) -> String {
+    let ret: String = Keccak256::digest(event_sig.as_bytes()).encode_hex();
+    format!("0x{}", ret)
+}
+
 /// Decode hex string as vector of bytes, removing any `0x` prefix
 pub fn decode_hex_str(data: &str) -> Result<Vec<u8>> {
     if data.is_empty() {
@@ -0,0 +1,290 @@
+use eyre::{eyre, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use revm::primitives::SpecId;
+use ruint::aliases::U256;
+
+#[cfg(feature = "python")]
+use crate::{
+    args::{PyU256, U256Arg},
+    errors::PyEvmResult,
+    REVMConfig,
+};
+use crate::{
+    cache::DefaultProviderCache, instrument::InstrumentConfig, DEFAULT_BALANCE, TX_GAS_LIMIT,
+};
+
+/// Configuration of a new `TinyEVM`, replacing the environment variables and
+/// the setters called after its construction
+#[cfg_attr(feature = "python", pyclass(module = "tinyevm"))]
+#[derive(Debug)]
+pub struct TinyEVMConfig {
+    /// Url of the node to fork, no fork if `None`
+    pub fork_url: Option<String>,
+    /// Block to fork, the latest block if `None`
+    pub block_id: Option<u64>,
+    /// Max depth of the calls loading the accounts from the fork,
+    /// `TINYEVM_MAX_FORK_DEPTH` if `None`
+    pub max_fork_depth: Option<usize>,
+    /// Default gas limit for each transaction
+    pub tx_gas_limit: u64,
+    /// Chain id of the environment
+    pub chain_id: u64,
+    /// Name of the hardfork, e.g. `"SHANGHAI"` or `"LATEST"`
+    pub spec: String,
+    /// Balance of the owner account
+    pub default_balance: U256,
+    /// Python storage of the provider cache, see `TinyEVM.set_cache_backend`
+    #[cfg(feature = "python")]
+    pub cache_backend: Option<PyObject>,
+    /// Redis node of the builtin provider cache, `TINYEVM_REDIS_NODE` if
+    /// `None`. Requires the `redis` feature
+    pub redis_node: Option<String>,
+    /// Initial instrumentation options
+    pub instrument_config: InstrumentConfig,
+    /// Whether to log the traces of the EVM execution
+    pub trace_enabled: bool,
+}
+
+impl Default for TinyEVMConfig {
+    fn default() -> Self {
+        Self {
+            fork_url: None,
+            block_id: None,
+            max_fork_depth: None,
+            tx_gas_limit: TX_GAS_LIMIT,
+            chain_id: 1,
+            spec: "LATEST".into(),
+            default_balance: DEFAULT_BALANCE,
+            #[cfg(feature = "python")]
+            cache_backend: None,
+            redis_node: None,
+            instrument_config: InstrumentConfig::default(),
+            trace_enabled: false,
+        }
+    }
+}
+
+impl TinyEVMConfig {
+    /// Hardfork named by `spec`
+    pub fn spec_id(&self) -> Result<SpecId> {
+        let spec_id = match self.spec.to_uppercase().as_str() {
+            "FRONTIER" => SpecId::FRONTIER,
+            "HOMESTEAD" => SpecId::HOMESTEAD,
+            "TANGERINE" => SpecId::TANGERINE,
+            "SPURIOUS_DRAGON" => SpecId::SPURIOUS_DRAGON,
+            "BYZANTIUM" => SpecId::BYZANTIUM,
+            "CONSTANTINOPLE" => SpecId::CONSTANTINOPLE,
+            "PETERSBURG" => SpecId::PETERSBURG,
+            "ISTANBUL" => SpecId::ISTANBUL,
+            "BERLIN" => SpecId::BERLIN,
+            "LONDON" => SpecId::LONDON,
+            "MERGE" => SpecId::MERGE,
+            "SHANGHAI" => SpecId::SHANGHAI,
+            "CANCUN" => SpecId::CANCUN,
+            "LATEST" => SpecId::LATEST,
+            _ => return Err(eyre!("Unknown spec {}", self.spec)),
+        };
+        Ok(spec_id)
+    }
+
+    /// Provider cache replacing the builtin one, if configured
+    pub fn provider_cache(&self) -> Result<Option<DefaultProviderCache>> {
+        #[cfg(feature = "python")]
+        if let Some(backend) = &self.cache_backend {
+            let backend = Python::with_gil(|py| backend.clone_ref(py));
+            return Ok(Some(DefaultProviderCache::python(backend)));
+        }
+        match &self.redis_node {
+            #[cfg(feature = "redis")]
+            Some(node) => Ok(Some(DefaultProviderCache::redis(node)?)),
+            #[cfg(not(feature = "redis"))]
+            Some(_) => Err(eyre!("The redis cache requires the redis feature")),
+            None => Ok(None),
+        }
+    }
+
+    /// Copy of the configuration sharing the cache backend
+    #[cfg(feature = "python")]
+    pub fn clone_ref(&self, py: Python<'_>) -> Self {
+        Self {
+            fork_url: self.fork_url.clone(),
+            spec: self.spec.clone(),
+            cache_backend: self.cache_backend.as_ref().map(|b| b.clone_ref(py)),
+            redis_node: self.redis_node.clone(),
+            instrument_config: self.instrument_config.clone(),
+            ..*self
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl TinyEVMConfig {
+    /// Create a configuration, the options not given keep their default value
+    #[new]
+    #[pyo3(signature = (
+        fork_url = None,
+        block_id = None,
+        max_fork_depth = None,
+        tx_gas_limit = TX_GAS_LIMIT,
+        chain_id = 1,
+        spec = "LATEST",
+        default_balance = None,
+        cache_backend = None,
+        redis_node = None,
+        instrument_config = None,
+        trace_enabled = false
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        fork_url: Option<String>,
+        block_id: Option<u64>,
+        max_fork_depth: Option<usize>,
+        tx_gas_limit: u64,
+        chain_id: u64,
+        spec: &str,
+        default_balance: Option<U256Arg>,
+        cache_backend: Option<PyObject>,
+        redis_node: Option<String>,
+        instrument_config: Option<PyRef<REVMConfig>>,
+        trace_enabled: bool,
+    ) -> PyEvmResult<Self> {
+        let config = Self {
+            fork_url,
+            block_id,
+            max_fork_depth,
+            tx_gas_limit,
+            chain_id,
+            spec: spec.into(),
+            default_balance: match default_balance {
+                Some(balance) => balance.value()?,
+                None => DEFAULT_BALANCE,
+            },
+            cache_backend,
+            redis_node,
+            instrument_config: match instrument_config {
+                Some(config) => config.to_iconfig()?,
+                None => InstrumentConfig::default(),
+            },
+            trace_enabled,
+        };
+        config.spec_id()?;
+        Ok(config)
+    }
+
+    #[getter]
+    pub fn get_fork_url(&self) -> Option<String> {
+        self.fork_url.clone()
+    }
+
+    #[setter]
+    pub fn set_fork_url(&mut self, fork_url: Option<String>) {
+        self.fork_url = fork_url;
+    }
+
+    #[getter]
+    pub fn get_block_id(&self) -> Option<u64> {
+        self.block_id
+    }
+
+    #[setter]
+    pub fn set_block_id(&mut self, block_id: Option<u64>) {
+        self.block_id = block_id;
+    }
+
+    #[getter]
+    pub fn get_max_fork_depth(&self) -> Option<usize> {
+        self.max_fork_depth
+    }
+
+    #[setter]
+    pub fn set_max_fork_depth(&mut self, max_fork_depth: Option<usize>) {
+        self.max_fork_depth = max_fork_depth;
+    }
+
+    #[getter]
+    pub fn get_tx_gas_limit(&self) -> u64 {
+        self.tx_gas_limit
+    }
+
+    #[setter]
+    pub fn set_tx_gas_limit(&mut self, tx_gas_limit: u64) {
+        self.tx_gas_limit = tx_gas_limit;
+    }
+
+    #[getter]
+    pub fn get_chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    #[setter]
+    pub fn set_chain_id(&mut self, chain_id: u64) {
+        self.chain_id = chain_id;
+    }
+
+    #[getter]
+    pub fn get_spec(&self) -> String {
+        self.spec.clone()
+    }
+
+    #[setter]
+    pub fn set_spec(&mut self, spec: String) {
+        self.spec = spec;
+    }
+
+    #[getter]
+    pub fn get_redis_node(&self) -> Option<String> {
+        self.redis_node.clone()
+    }
+
+    #[setter]
+    pub fn set_redis_node(&mut self, redis_node: Option<String>) {
+        self.redis_node = redis_node;
+    }
+
+    #[getter]
+    pub fn get_trace_enabled(&self) -> bool {
+        self.trace_enabled
+    }
+
+    #[setter]
+    pub fn set_trace_enabled(&mut self, trace_enabled: bool) {
+        self.trace_enabled = trace_enabled;
+    }
+
+    /// Balance of the owner account
+    #[getter]
+    pub fn get_default_balance(&self) -> PyU256 {
+        PyU256(self.default_balance)
+    }
+
+    #[setter]
+    pub fn set_default_balance(&mut self, balance: U256Arg) -> PyEvmResult<()> {
+        self.default_balance = balance.value()?;
+        Ok(())
+    }
+
+    /// Python storage of the provider cache, see `TinyEVM.set_cache_backend`
+    #[getter]
+    pub fn get_cache_backend(&self, py: Python<'_>) -> Option<PyObject> {
+        self.cache_backend.as_ref().map(|b| b.clone_ref(py))
+    }
+
+    #[setter]
+    pub fn set_cache_backend(&mut self, backend: Option<PyObject>) {
+        self.cache_backend = backend;
+    }
+
+    /// Initial instrumentation options
+    #[getter]
+    pub fn get_instrument_config(&self) -> REVMConfig {
+        REVMConfig::from(&self.instrument_config)
+    }
+
+    #[setter]
+    pub fn set_instrument_config(&mut self, config: PyRef<REVMConfig>) -> PyEvmResult<()> {
+        self.instrument_config = config.to_iconfig()?;
+        Ok(())
+    }
+}
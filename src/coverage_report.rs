@@ -0,0 +1,184 @@
+use hashbrown::HashSet;
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{instrument::PcSet, source_map::SourceMap};
+
+/// Coverage of the two branches of a JUMPI
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BranchCoverage {
+    pub line: usize,
+    /// Whether the jump was taken
+    pub jumped: bool,
+    /// Whether the next instruction was executed after the JUMPI
+    pub fell_through: bool,
+}
+
+/// Coverage of a source file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+    content: String,
+    /// Number of executed instructions by line, for the lines with
+    /// instructions. The seen PCs are sets, so this is not the number of
+    /// times the line was executed
+    pub lines: BTreeMap<usize, usize>,
+    pub branches: Vec<BranchCoverage>,
+}
+
+impl FileCoverage {
+    /// Number of lines with instructions, and of the ones executed
+    pub fn line_counts(&self) -> (usize, usize) {
+        let hit = self.lines.values().filter(|hits| **hits > 0).count();
+        (self.lines.len(), hit)
+    }
+}
+
+/// Line and branch coverage of the sources, from the seen PCs and the
+/// jumps of the contracts with a source map
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// Coverage by source path, the coverage of the contracts sharing a
+    /// source is merged
+    pub files: BTreeMap<String, FileCoverage>,
+}
+
+/// Escape the text of an HTML element
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl CoverageReport {
+    /// Add the coverage of a contract, `seen_pcs` and `jumps` are the PCs
+    /// and the `(pc, destination)` of the jumps taken in its code
+    pub fn add(
+        &mut self,
+        source_map: &SourceMap,
+        seen_pcs: Option<&PcSet>,
+        jumps: Option<&HashSet<(usize, usize)>>,
+    ) {
+        for (path, content) in source_map.sources() {
+            self.files
+                .entry(path.to_string())
+                .or_insert_with(|| FileCoverage {
+                    content: content.to_string(),
+                    ..Default::default()
+                });
+        }
+        let seen = |pc: usize| seen_pcs.is_some_and(|pcs| pcs.contains(pc));
+
+        let mut lines = BTreeMap::new();
+        for (pc, location) in source_map.locations() {
+            let file = self.files.get_mut(&location.file).unwrap();
+            *file.lines.entry(location.line).or_default() += seen(pc) as usize;
+            lines.insert(pc, location);
+        }
+        for pc in source_map.jumpi_pcs() {
+            let Some(location) = lines.get(pc) else {
+                continue;
+            };
+            let file = self.files.get_mut(&location.file).unwrap();
+            file.branches.push(BranchCoverage {
+                line: location.line,
+                jumped: jumps.is_some_and(|jumps| jumps.iter().any(|(from, _)| from == pc)),
+                // The next instruction may also be a jump destination
+                fell_through: seen(*pc) && seen(pc + 1),
+            });
+        }
+    }
+
+    /// Report in the LCOV tracefile format, e.g. for `genhtml` or the
+    /// coverage extensions of the editors
+    pub fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+        for (path, file) in &self.files {
+            writeln!(lcov, "TN:").unwrap();
+            writeln!(lcov, "SF:{}", path).unwrap();
+            for (block, branch) in file.branches.iter().enumerate() {
+                for (i, taken) in [branch.jumped, branch.fell_through].iter().enumerate() {
+                    writeln!(
+                        lcov,
+                        "BRDA:{},{},{},{}",
+                        branch.line, block, i, *taken as u8
+                    )
+                    .unwrap();
+                }
+            }
+            let branches_hit = file
+                .branches
+                .iter()
+                .map(|b| b.jumped as usize + b.fell_through as usize)
+                .sum::<usize>();
+            writeln!(lcov, "BRF:{}", file.branches.len() * 2).unwrap();
+            writeln!(lcov, "BRH:{}", branches_hit).unwrap();
+            for (line, hits) in &file.lines {
+                writeln!(lcov, "DA:{},{}", line, hits).unwrap();
+            }
+            let (found, hit) = file.line_counts();
+            writeln!(lcov, "LF:{}", found).unwrap();
+            writeln!(lcov, "LH:{}", hit).unwrap();
+            writeln!(lcov, "end_of_record").unwrap();
+        }
+        lcov
+    }
+
+    /// Single page HTML report with the sources, the executed lines are
+    /// green and the lines with instructions not executed are red
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Coverage</title>\n<style>\n\
+             body { font-family: sans-serif; }\n\
+             table { border-collapse: collapse; font-family: monospace; }\n\
+             td { padding: 0 8px; white-space: pre; }\n\
+             .hit { background: #dfd; }\n\
+             .missed { background: #fdd; }\n\
+             .count { color: #888; text-align: right; }\n\
+             </style>\n</head>\n<body>\n<h1>Coverage</h1>\n<ul>\n",
+        );
+        for (i, (path, file)) in self.files.iter().enumerate() {
+            let (found, hit) = file.line_counts();
+            writeln!(
+                html,
+                "<li><a href=\"#file-{}\">{}</a>: {}/{} lines</li>",
+                i,
+                escape_html(path),
+                hit,
+                found
+            )
+            .unwrap();
+        }
+        html.push_str("</ul>\n");
+        for (i, (path, file)) in self.files.iter().enumerate() {
+            writeln!(html, "<h2 id=\"file-{}\">{}</h2>", i, escape_html(path)).unwrap();
+            html.push_str("<table>\n");
+            for (line, text) in file.content.lines().enumerate().map(|(i, t)| (i + 1, t)) {
+                let (class, count) = match file.lines.get(&line) {
+                    Some(0) => ("missed", "0".to_string()),
+                    Some(hits) => ("hit", hits.to_string()),
+                    None => ("", String::new()),
+                };
+                writeln!(
+                    html,
+                    "<tr class=\"{}\"><td class=\"count\">{}</td><td class=\"count\">{}</td><td>{}</td></tr>",
+                    class,
+                    line,
+                    count,
+                    escape_html(text)
+                )
+                .unwrap();
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
@@ -0,0 +1,177 @@
+// `create_exception!` of pyo3 0.22.0 checks the `gil-refs` feature of this crate
+#![allow(unexpected_cfgs)]
+
+use std::fmt::{Display, Formatter};
+
+use eyre::Result;
+#[cfg(feature = "python")]
+use pyo3::{create_exception, exceptions::PyRuntimeError, PyErr};
+use revm::primitives::Address;
+
+use crate::trim_prefix;
+
+#[cfg(feature = "python")]
+create_exception!(
+    tinyevm,
+    TinyEvmError,
+    PyRuntimeError,
+    "Base class of the errors raised by tinyevm"
+);
+#[cfg(feature = "python")]
+create_exception!(
+    tinyevm,
+    InvalidHexError,
+    TinyEvmError,
+    "Malformed hex encoded data"
+);
+#[cfg(feature = "python")]
+create_exception!(
+    tinyevm,
+    InvalidAddressError,
+    TinyEvmError,
+    "Malformed address"
+);
+#[cfg(feature = "python")]
+create_exception!(
+    tinyevm,
+    AccountNotFoundError,
+    TinyEvmError,
+    "Account missing from the state"
+);
+#[cfg(feature = "python")]
+create_exception!(
+    tinyevm,
+    SnapshotNotFoundError,
+    TinyEvmError,
+    "Unknown snapshot id or account without snapshot"
+);
+#[cfg(feature = "python")]
+create_exception!(
+    tinyevm,
+    ProviderError,
+    TinyEvmError,
+    "Request to the fork endpoint failed"
+);
+#[cfg(feature = "python")]
+create_exception!(
+    tinyevm,
+    AddressCollisionError,
+    TinyEvmError,
+    "Deployment to an address with code or nonce"
+);
+
+/// Failures raised as a dedicated Python exception
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    InvalidAddress(String),
+    AccountNotFound(Address),
+    /// Snapshot id or address of the account
+    SnapshotNotFound(String),
+    AddressCollision(Address),
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::InvalidAddress(address) => write!(f, "Invalid address: {}", address),
+            Failure::AccountNotFound(address) => write!(f, "Account not found: {:#x}", address),
+            Failure::SnapshotNotFound(id) => write!(f, "No snapshot found: {}", id),
+            Failure::AddressCollision(address) => {
+                write!(f, "Address collision for {:#x}", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Parse an address, with or without the `0x` prefix
+pub fn parse_address(address: &str) -> Result<Address> {
+    trim_prefix(address, "0x")
+        .parse()
+        .map_err(|_| Failure::InvalidAddress(address.to_string()).into())
+}
+
+/// Error of the Python API, raised as the exception matching the failure,
+/// or as a `TinyEvmError` otherwise
+#[derive(Debug)]
+pub struct PyEvmError(eyre::Report);
+
+/// Result of the Python API
+pub type PyEvmResult<T> = std::result::Result<T, PyEvmError>;
+
+impl From<eyre::Report> for PyEvmError {
+    fn from(error: eyre::Report) -> Self {
+        Self(error)
+    }
+}
+
+macro_rules! impl_from_error {
+    ($($error:ty),*) => {
+        $(
+            impl From<$error> for PyEvmError {
+                fn from(error: $error) -> Self {
+                    Self(error.into())
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "python")]
+impl_from_error![PyErr];
+
+impl_from_error![
+    Failure,
+    hex::FromHexError,
+    ruint::ParseError,
+    serde_cbor::Error,
+    std::io::Error,
+    std::num::ParseIntError,
+    uuid::Error
+];
+
+impl Display for PyEvmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl std::error::Error for PyEvmError {}
+
+impl PyEvmError {
+    /// The failure behind the error, if any
+    pub fn failure(&self) -> Option<&Failure> {
+        self.0.downcast_ref()
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<PyEvmError> for PyErr {
+    fn from(PyEvmError(error): PyEvmError) -> Self {
+        // Errors of Python callbacks are raised as they are
+        let error = match error.downcast::<PyErr>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        let message = format!("{:?}", error);
+        if let Some(failure) = error.downcast_ref::<Failure>() {
+            return match failure {
+                Failure::InvalidAddress(_) => InvalidAddressError::new_err(message),
+                Failure::AccountNotFound(_) => AccountNotFoundError::new_err(message),
+                Failure::SnapshotNotFound(_) => SnapshotNotFoundError::new_err(message),
+                Failure::AddressCollision(_) => AddressCollisionError::new_err(message),
+            };
+        }
+        if error.downcast_ref::<hex::FromHexError>().is_some() {
+            return InvalidHexError::new_err(message);
+        }
+        if error
+            .downcast_ref::<ethers_providers::ProviderError>()
+            .is_some()
+        {
+            return ProviderError::new_err(message);
+        }
+        TinyEvmError::new_err(message)
+    }
+}
@@ -0,0 +1,92 @@
+use ethers::{
+    abi::{Abi, Event, RawLog, Token},
+    types::H256,
+};
+use eyre::Result;
+use hashbrown::HashMap;
+use revm::primitives::{Address, B256};
+
+use crate::instrument::log_inspector::Log;
+
+/// Event decoded with its ABI
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodedEvent {
+    pub name: String,
+    /// `(name, value)` of the parameters in declaration order
+    pub params: Vec<(String, Token)>,
+}
+
+/// Non-anonymous event ABIs with their topic 0, registered for all the
+/// addresses or for a single address
+#[derive(Clone, Debug, Default)]
+pub struct EventDecoder {
+    global: Vec<(B256, Event)>,
+    by_address: HashMap<Address, Vec<(B256, Event)>>,
+}
+
+/// Parse a JSON ABI, either the list of items or a compiler artifact with
+/// an `abi` field
+fn parse_abi(abi: &str) -> Result<Abi> {
+    let value: serde_json::Value = serde_json::from_str(abi)?;
+    let items = match value {
+        serde_json::Value::Object(mut artifact) if artifact.contains_key("abi") => {
+            artifact.remove("abi").unwrap_or_default()
+        }
+        items => items,
+    };
+    Ok(serde_json::from_value(items)?)
+}
+
+impl EventDecoder {
+    /// Register the events of a JSON ABI for `address`, or for all the
+    /// addresses if `None`. The events of an address are tried first.
+    pub fn add_abi(&mut self, address: Option<Address>, abi: &str) -> Result<()> {
+        let events = parse_abi(abi)?
+            .events()
+            .filter(|event| !event.anonymous)
+            .map(|event| (B256::from(event.signature().0), event.clone()))
+            .collect::<Vec<_>>();
+        match address {
+            Some(address) => self.by_address.entry(address).or_default().extend(events),
+            None => self.global.extend(events),
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.global.is_empty() && self.by_address.is_empty()
+    }
+
+    /// Decode a log with the first matching event ABI
+    pub fn decode(&self, address: Address, topics: &[B256], data: &[u8]) -> Option<DecodedEvent> {
+        let topic = topics.first()?;
+        let raw = RawLog {
+            topics: topics.iter().map(|t| H256::from(t.0)).collect(),
+            data: data.to_vec(),
+        };
+        self.by_address
+            .get(&address)
+            .into_iter()
+            .flatten()
+            .chain(&self.global)
+            .filter(|(signature, _)| signature == topic)
+            .find_map(|(_, event)| {
+                let log = event.parse_log(raw.clone()).ok()?;
+                Some(DecodedEvent {
+                    name: event.name.clone(),
+                    params: log.params.into_iter().map(|p| (p.name, p.value)).collect(),
+                })
+            })
+    }
+
+    /// Set the decoded event of the logs
+    pub fn decode_logs(&self, logs: &mut [Log]) {
+        if self.is_empty() {
+            return;
+        }
+        for log in logs {
+            log.event = self.decode(log.address, &log.topics, &log.data);
+        }
+    }
+}
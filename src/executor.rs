@@ -0,0 +1,95 @@
+use revm::{
+    inspector_handle_register,
+    primitives::{BlockEnv, CfgEnv, HandlerCfg, SpecId, TxEnv},
+    Context, ContextWithHandlerCfg, Evm,
+};
+
+use crate::{chain_inspector::ChainInspector, TinyEvmDb};
+
+/// REVM executor between the transactions: the context and the handler
+/// configuration. The `Evm` running a transaction is built from it, as the
+/// closures of its handler are neither `Send` nor `Sync` while the context
+/// is both, so that an instance can be used by any thread
+pub struct Executor {
+    pub context: Context<ChainInspector, TinyEvmDb>,
+    handler_cfg: HandlerCfg,
+    /// Whether the inspectors are registered in the handler
+    inspector_enabled: bool,
+}
+
+impl Executor {
+    /// Executor of `context` with the inspectors, following `spec_id`
+    pub fn new(context: Context<ChainInspector, TinyEvmDb>, spec_id: SpecId) -> Self {
+        Self {
+            context,
+            handler_cfg: HandlerCfg::new(spec_id),
+            inspector_enabled: true,
+        }
+    }
+
+    pub fn spec_id(&self) -> SpecId {
+        self.handler_cfg.spec_id
+    }
+
+    /// Register the inspectors in the handler of the next transactions or
+    /// remove them
+    pub fn set_inspector_enabled(&mut self, enabled: bool) {
+        self.inspector_enabled = enabled;
+    }
+
+    pub fn db(&self) -> &TinyEvmDb {
+        &self.context.evm.db
+    }
+
+    pub fn db_mut(&mut self) -> &mut TinyEvmDb {
+        &mut self.context.evm.db
+    }
+
+    pub fn tx(&self) -> &TxEnv {
+        &self.context.evm.env.tx
+    }
+
+    pub fn tx_mut(&mut self) -> &mut TxEnv {
+        &mut self.context.evm.env.tx
+    }
+
+    pub fn block(&self) -> &BlockEnv {
+        &self.context.evm.env.block
+    }
+
+    pub fn block_mut(&mut self) -> &mut BlockEnv {
+        &mut self.context.evm.env.block
+    }
+
+    pub fn cfg(&self) -> &CfgEnv {
+        &self.context.evm.env.cfg
+    }
+
+    pub fn cfg_mut(&mut self) -> &mut CfgEnv {
+        &mut self.context.evm.env.cfg
+    }
+
+    /// Run `f` with the `Evm` of this executor and return the executor with
+    /// the resulting context
+    pub fn run<R>(self, f: impl FnOnce(&mut Evm<'_, ChainInspector, TinyEvmDb>) -> R) -> (Self, R) {
+        let builder = Evm::builder().with_context_with_handler_cfg(ContextWithHandlerCfg::new(
+            self.context,
+            self.handler_cfg,
+        ));
+        let mut evm = if self.inspector_enabled {
+            builder
+                .append_handler_register(inspector_handle_register)
+                .build()
+        } else {
+            builder.build()
+        };
+        let result = f(&mut evm);
+        let ContextWithHandlerCfg { context, cfg } = evm.into_context_with_handler_cfg();
+        let exe = Self {
+            context,
+            handler_cfg: cfg,
+            inspector_enabled: self.inspector_enabled,
+        };
+        (exe, result)
+    }
+}
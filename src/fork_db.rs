@@ -1,29 +1,59 @@
 use crate::cache::{DefaultProviderCache, ProviderCache};
 use crate::fork_provider::ForkProvider;
-use crate::CALL_DEPTH;
-use ethers::types::{Block, TxHash};
+use ethers::types::{Block, Transaction, TxHash};
 use eyre::{ContextCompat, Result};
-use hashbrown::hash_map::Entry;
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashMap;
 use primitive_types::H256;
-use revm::db::{AccountState, DbAccount};
+use revm::db::AccountState;
+use revm::interpreter::analysis::to_analysed;
 use revm::primitives::{
     keccak256, Account, AccountInfo, Address, Bytecode, HashMap as RevmHashMap, B256, KECCAK_EMPTY,
     U256,
 };
 use revm::{Database, DatabaseCommit};
 use std::env;
+use std::sync::Arc;
 use tracing::{debug, info, trace};
 
+/// Accounts shared with the copies of the database and with the account
+/// snapshots until they are modified, so that taking a snapshot does not
+/// copy the state
+pub type SharedAccounts = im::HashMap<Address, Arc<DbAccount>>;
+
+/// Account of a `ForkDB`, as revm's `DbAccount` but with a persistent
+/// storage map: a copy of the account shares the slots with the original,
+/// writing a slot copies only the path to it
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DbAccount {
+    pub info: AccountInfo,
+    /// If account is selfdestructed or newly created, storage will be cleared.
+    pub account_state: AccountState,
+    /// storage slots
+    pub storage: im::HashMap<U256, U256>,
+}
+
+impl DbAccount {
+    /// Info of the account, `None` if it does not exist
+    pub fn info(&self) -> Option<AccountInfo> {
+        if matches!(self.account_state, AccountState::NotExisting) {
+            None
+        } else {
+            Some(self.info.clone())
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct ForkDB<T: ProviderCache> {
     /// Account info where None means it is not existing. Not existing state is needed for Pre TANGERINE forks.
     /// `code` is always `None`, and bytecode can be found in `contracts`.
-    pub accounts: HashMap<Address, DbAccount>,
+    /// Modify the accounts with `account_mut`
+    pub accounts: SharedAccounts,
     /// Tracks all contracts by their code hash.
-    pub contracts: HashMap<B256, Bytecode>,
+    pub contracts: im::HashMap<B256, Bytecode>,
     /// All cached block hashes
-    pub block_hashes: HashMap<U256, B256>,
+    pub block_hashes: im::HashMap<U256, B256>,
 
     pub fork_enabled: bool,
     /// Web3 provider
@@ -31,13 +61,41 @@ pub struct ForkDB<T: ProviderCache> {
     /// Optional block ID to fetch data from, if not the latest
     block_id: Option<u64>,
     /// Address loaded remotely
-    pub remote_addresses: HashMap<Address, HashSet<U256>>,
+    pub remote_addresses: im::HashMap<Address, im::HashSet<U256>>,
     /// Addresses ignored by depth limit
-    pub ignored_addresses: HashSet<Address>,
+    pub ignored_addresses: im::HashSet<Address>,
     /// Block caches
-    block_cache: HashMap<u64, Block<TxHash>>,
+    block_cache: im::HashMap<u64, Block<TxHash>>,
     /// Max depth to consider when forking address
     max_fork_depth: usize,
+    /// Depth of the frame being executed, see `TrackCallDepth`
+    call_depth: usize,
+}
+
+/// Database told the depth of the frame being executed, so that it can limit
+/// the accounts loaded by the deep calls
+pub trait TrackCallDepth {
+    fn set_call_depth(&mut self, depth: usize);
+}
+
+impl<T: ProviderCache> TrackCallDepth for ForkDB<T> {
+    fn set_call_depth(&mut self, depth: usize) {
+        self.call_depth = depth;
+    }
+}
+
+/// State of a `ForkDB` without its provider, see `ForkDB::state`
+#[cfg(feature = "with-serde")]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ForkDbState {
+    pub accounts: SharedAccounts,
+    pub contracts: im::HashMap<B256, Bytecode>,
+    pub block_hashes: im::HashMap<U256, B256>,
+    pub block_id: Option<u64>,
+    pub remote_addresses: im::HashMap<Address, im::HashSet<U256>>,
+    pub ignored_addresses: im::HashSet<Address>,
+    pub block_cache: im::HashMap<u64, Block<TxHash>>,
+    pub max_fork_depth: usize,
 }
 
 impl Clone for ForkDB<DefaultProviderCache> {
@@ -53,6 +111,7 @@ impl Clone for ForkDB<DefaultProviderCache> {
             block_cache: self.block_cache.clone(),
             ignored_addresses: self.ignored_addresses.clone(),
             max_fork_depth: self.max_fork_depth,
+            call_depth: self.call_depth,
         }
     }
 }
@@ -99,6 +158,39 @@ impl<T: ProviderCache> ForkDB<T> {
         self.get_fork_block_by_number(number)
     }
 
+    /// Get a transaction from the remote endpoint
+    pub fn get_transaction(&mut self, hash: &TxHash) -> Result<Transaction> {
+        let provider = self
+            .provider
+            .as_mut()
+            .context("No provider to retrieve from remote endpoint")?;
+        provider
+            .get_transaction(hash, self.block_id)?
+            .context("Transaction does not exist")
+    }
+
+    /// Wait until all data loaded from the remote endpoint is persisted in the provider cache
+    pub fn flush_cache(&self) -> Result<()> {
+        match &self.provider {
+            Some(provider) => provider.flush_cache(),
+            None => Ok(()),
+        }
+    }
+
+    /// Replace the cache used by the provider, fork must be enabled
+    pub fn set_provider_cache(&mut self, cache: T) -> Result<()> {
+        self.provider
+            .as_mut()
+            .context("No provider to set the cache for")?
+            .set_cache(cache);
+        Ok(())
+    }
+
+    /// Set the max depth of the calls loading the accounts from the fork
+    pub fn set_max_fork_depth(&mut self, depth: usize) {
+        self.max_fork_depth = depth;
+    }
+
     pub fn create_with_provider(
         provider: Option<ForkProvider<T>>,
         mut block_id: Option<u64>,
@@ -120,16 +212,51 @@ impl<T: ProviderCache> ForkDB<T> {
             .unwrap_or_default();
 
         Self {
-            accounts: HashMap::new(),
-            contracts: HashMap::new(),
-            block_hashes: HashMap::new(),
+            accounts: Default::default(),
+            contracts: Default::default(),
+            block_hashes: Default::default(),
             provider,
             block_id,
             remote_addresses: Default::default(),
             fork_enabled,
-            block_cache: HashMap::new(),
+            block_cache: Default::default(),
             ignored_addresses: Default::default(),
             max_fork_depth,
+            call_depth: 0,
+        }
+    }
+
+    /// Copy of the state, which can be sent to another process
+    #[cfg(feature = "with-serde")]
+    pub fn state(&self) -> ForkDbState {
+        ForkDbState {
+            accounts: self.accounts.clone(),
+            contracts: self.contracts.clone(),
+            block_hashes: self.block_hashes.clone(),
+            block_id: self.block_id,
+            remote_addresses: self.remote_addresses.clone(),
+            ignored_addresses: self.ignored_addresses.clone(),
+            block_cache: self.block_cache.clone(),
+            max_fork_depth: self.max_fork_depth,
+        }
+    }
+
+    /// Restore a state copied with `state`, forking with `provider` at the
+    /// block of the state
+    #[cfg(feature = "with-serde")]
+    pub fn from_state(state: ForkDbState, provider: Option<ForkProvider<T>>) -> Self {
+        Self {
+            accounts: state.accounts,
+            contracts: state.contracts,
+            block_hashes: state.block_hashes,
+            fork_enabled: provider.is_some(),
+            provider,
+            block_id: state.block_id,
+            remote_addresses: state.remote_addresses,
+            ignored_addresses: state.ignored_addresses,
+            block_cache: state.block_cache,
+            max_fork_depth: state.max_fork_depth,
+            call_depth: 0,
         }
     }
 
@@ -142,14 +269,18 @@ impl<T: ProviderCache> ForkDB<T> {
     ) -> Result<()> {
         trace!("insert_account_storage {}", address);
         let _ = self.basic(address)?;
-        self.accounts
-            .entry(address)
-            .or_default()
-            .storage
-            .insert(slot, value);
+        self.account_mut(address).storage.insert(slot, value);
         Ok(())
     }
 
+    /// Insert account storage without loading the account from the fork
+    /// first, e.g. to seed local test state. A missing account is created
+    /// empty and is not loaded from the fork afterwards
+    pub fn insert_account_storage_local(&mut self, address: Address, slot: U256, value: U256) {
+        trace!("insert_account_storage_local {}", address);
+        self.account_mut(address).storage.insert(slot, value);
+    }
+
     /// replace account storage without overriding account info
     pub fn replace_account_storage(
         &mut self,
@@ -157,7 +288,7 @@ impl<T: ProviderCache> ForkDB<T> {
         storage: HashMap<U256, U256>,
     ) -> Result<()> {
         let _ = self.basic(address)?;
-        let account = self.accounts.entry(address).or_default();
+        let account = self.account_mut(address);
         account.storage = storage.into_iter().collect();
         account.account_state = AccountState::StorageCleared;
 
@@ -167,9 +298,18 @@ impl<T: ProviderCache> ForkDB<T> {
     /// Insert account info but not override storage
     pub fn insert_account_info(&mut self, address: Address, mut info: AccountInfo) {
         self.insert_contract(&mut info);
-        self.accounts.entry(address).or_default().info = info;
+        self.account_mut(address).info = info;
     }
 
+    /// Account to modify, created if missing. It is copied first if shared
+    /// with a snapshot
+    pub fn account_mut(&mut self, address: Address) -> &mut DbAccount {
+        Arc::make_mut(self.accounts.entry(address).or_default())
+    }
+
+    /// Store the code of `account` by its hash. The code of the account is
+    /// replaced by the stored one, analyzed once per hash so that revm does
+    /// not look for the jump destinations again on each call
     pub fn insert_contract(&mut self, account: &mut AccountInfo) {
         let mut changed = false;
         if let Some(code) = &account.code {
@@ -177,9 +317,12 @@ impl<T: ProviderCache> ForkDB<T> {
                 if account.code_hash == KECCAK_EMPTY {
                     account.code_hash = code.hash_slow();
                 }
-                self.contracts
+                let code = self
+                    .contracts
                     .entry(account.code_hash)
-                    .or_insert_with(|| code.clone());
+                    .or_insert_with(|| to_analysed(code.clone()))
+                    .clone();
+                account.code = Some(code);
                 changed = true;
             }
         }
@@ -204,7 +347,7 @@ impl<T: ProviderCache> Database for ForkDB<T> {
             return Ok(None);
         }
 
-        if CALL_DEPTH.get_or_default().get() > self.max_fork_depth {
+        if self.call_depth > self.max_fork_depth {
             self.ignored_addresses.insert(address);
             return Ok(None);
         }
@@ -250,16 +393,21 @@ impl<T: ProviderCache> Database for ForkDB<T> {
         let index = H256::from(index.to_be_bytes());
         trace!("retrieve storage {} {}", address, index);
 
-        let _ = self.basic(address)?;
+        if let Some(value) = self
+            .accounts
+            .get(&address)
+            .and_then(|account| account.storage.get(&uindex))
+        {
+            return Ok(*value);
+        }
 
-        if let Entry::Occupied(mut acc_entry) = self.accounts.entry(address) {
-            let acc_entry = acc_entry.get_mut();
-            if let Entry::Occupied(entry) = acc_entry.storage.entry(uindex) {
-                return Ok(*entry.get());
-            }
+        if !self.fork_enabled {
+            return Ok(U256::ZERO);
         }
 
-        if !self.remote_addresses.contains_key(&address) || !self.fork_enabled {
+        // Loads the account if missing, which tells whether it exists remotely
+        let _ = self.basic(address)?;
+        if !self.remote_addresses.contains_key(&address) {
             return Ok(U256::ZERO);
         }
 
@@ -278,11 +426,7 @@ impl<T: ProviderCache> Database for ForkDB<T> {
             .or_default()
             .insert(uindex);
 
-        self.accounts
-            .entry(address)
-            .or_default()
-            .storage
-            .insert(uindex, value);
+        self.account_mut(address).storage.insert(uindex, value);
         Ok(value)
     }
 
@@ -290,8 +434,8 @@ impl<T: ProviderCache> Database for ForkDB<T> {
     /// is calculated from the block number
     fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
         let unumber = number;
-        if let Entry::Occupied(entry) = self.block_hashes.entry(number) {
-            return Ok(*entry.get());
+        if let Some(hash) = self.block_hashes.get(&number) {
+            return Ok(*hash);
         }
 
         if !self.fork_enabled {
@@ -321,7 +465,7 @@ impl<T: ProviderCache> DatabaseCommit for ForkDB<T> {
             }
 
             if account.is_selfdestructed() {
-                let db_account = self.accounts.entry(address).or_default();
+                let db_account = self.account_mut(address);
                 db_account.storage.clear();
                 db_account.account_state = AccountState::NotExisting;
                 db_account.info = AccountInfo::default();
@@ -330,7 +474,7 @@ impl<T: ProviderCache> DatabaseCommit for ForkDB<T> {
             let is_newly_created = account.is_created();
             self.insert_contract(&mut account.info);
 
-            let db_account = self.accounts.entry(address).or_default();
+            let db_account = self.account_mut(address);
             db_account.info = account.info;
 
             db_account.account_state = if is_newly_created {
@@ -1,40 +1,60 @@
-use ethers::types::{Block, BlockId, Bytes, TxHash, H256};
+use ethers::types::{Block, BlockId, Bytes, Transaction, TxHash, H256};
 use ethers_providers::{Http, Middleware, Provider};
-use eyre::Result;
-use hex::FromHex;
+use eyre::{eyre, Result};
 use primitive_types::{H160, U256};
-use revm::primitives::Address;
+use revm::primitives::{Address, B256};
+use std::sync::Arc;
 use tokio::runtime::Runtime;
-use tracing::debug;
+use tracing::{debug, warn};
 
-use crate::cache::ProviderCache;
+use crate::cache::{decode_entry, encode_entry, request_hash, ProviderCache};
 
-#[derive(Debug)]
+/// A cached 32 bytes word, e.g. a number or a storage value
+fn check_word(bytes: &[u8]) -> Result<&[u8]> {
+    if bytes.len() != 32 {
+        return Err(eyre!("Expecting 32 bytes, got {}", bytes.len()));
+    }
+    Ok(bytes)
+}
+
+/// Number cached as a 32 bytes big endian word
+fn encode_u256(value: U256) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    value.to_big_endian(&mut bytes);
+    bytes
+}
+
+fn decode_u256(bytes: &[u8]) -> Result<U256> {
+    Ok(U256::from_big_endian(check_word(bytes)?))
+}
+
+#[derive(Debug, Clone)]
 pub struct ForkProvider<T: ProviderCache> {
     provider: Provider<Http>,
     cache: T,
-    runtime: Runtime,
-}
-
-impl<T: ProviderCache> Clone for ForkProvider<T> {
-    fn clone(&self) -> Self {
-        Self {
-            provider: self.provider.clone(),
-            runtime: Runtime::new().unwrap(),
-            cache: self.cache.clone(),
-        }
-    }
+    /// Shared by the copies of the provider, e.g. in the global snapshots
+    runtime: Arc<Runtime>,
 }
 
 impl<T: ProviderCache> ForkProvider<T> {
     pub fn new(provider: Provider<Http>, runtime: Runtime) -> Self {
+        Self::with_cache(provider, runtime, T::default())
+    }
+
+    /// Create a provider using `cache` for remote responses
+    pub fn with_cache(provider: Provider<Http>, runtime: Runtime, cache: T) -> Self {
         Self {
             provider,
-            runtime,
-            cache: T::default(),
+            runtime: Arc::new(runtime),
+            cache,
         }
     }
 
+    /// Replace the cache used for remote responses
+    pub fn set_cache(&mut self, cache: T) {
+        self.cache = cache;
+    }
+
     fn block_on<F: core::future::Future>(&self, f: F) -> F::Output {
         self.runtime.block_on(f)
     }
@@ -45,20 +65,49 @@ impl<T: ProviderCache> ForkProvider<T> {
         Ok(block_number.as_u64())
     }
 
+    /// Look up a cached response, returns `None` on cache miss or if the
+    /// entry is invalid, in which case it should be fetched again
+    fn get_cached<R>(
+        &self,
+        block_number: Option<u64>,
+        api: &str,
+        key: &B256,
+        parse: impl FnOnce(&[u8]) -> Result<R>,
+    ) -> Option<R> {
+        let cached = self.cache.get("eth", block_number?, api, key).ok()?;
+        match decode_entry(&cached).and_then(parse) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("Ignoring invalid cache entry {} {}: {}", api, key, e);
+                None
+            }
+        }
+    }
+
+    /// Store a response in the cache, only when the block number is known.
+    /// The response was fetched already, so a failed write is only logged
+    fn store_cached(&self, block_number: Option<u64>, api: &str, key: &B256, response: &[u8]) {
+        let Some(block_number) = block_number else {
+            return;
+        };
+        if let Err(e) = self
+            .cache
+            .store("eth", block_number, api, key, &encode_entry(response))
+        {
+            warn!("Failed to cache {} {}: {}", api, key, e);
+        }
+    }
+
     /// Get the nonce of an address
     pub fn get_transaction_count(
         &mut self,
         address: &Address,
         block_number: Option<u64>,
     ) -> Result<U256> {
-        let address_str = format!("{:x}", address);
-        if let Some(block_number) = block_number {
-            if let Ok(cached) =
-                self.cache
-                    .get("eth", block_number, "eth_getTransactionCount", &address_str)
-            {
-                return Ok(U256::from_str_radix(cached.as_str(), 16).unwrap());
-            }
+        let key = request_hash(&[address.as_slice()]);
+        let api = "eth_getTransactionCount";
+        if let Some(cached) = self.get_cached(block_number, api, &key, decode_u256) {
+            return Ok(cached);
         }
 
         let block_id = block_number.map(BlockId::from);
@@ -67,29 +116,16 @@ impl<T: ProviderCache> ForkProvider<T> {
             self.provider.get_transaction_count(addr, block_id).await
         })?;
 
-        if let Some(block_number) = block_number {
-            self.cache.store(
-                "eth",
-                block_number,
-                "eth_getTransactionCount",
-                &address_str,
-                &format!("{:x}", nonce),
-            )?;
-        }
-
+        self.store_cached(block_number, api, &key, &encode_u256(nonce));
         Ok(nonce)
     }
 
     /// Get the balance of an address
     pub fn get_balance(&mut self, address: &Address, block_number: Option<u64>) -> Result<U256> {
-        let address_str = format!("{:x}", address);
-        if let Some(block_number) = block_number {
-            if let Ok(cached) = self
-                .cache
-                .get("eth", block_number, "eth_getBalance", &address_str)
-            {
-                return Ok(U256::from_str_radix(cached.as_str(), 16).unwrap());
-            }
+        let key = request_hash(&[address.as_slice()]);
+        let api = "eth_getBalance";
+        if let Some(cached) = self.get_cached(block_number, api, &key, decode_u256) {
+            return Ok(cached);
         }
 
         let block_id = block_number.map(BlockId::from);
@@ -98,28 +134,17 @@ impl<T: ProviderCache> ForkProvider<T> {
             self.provider.get_balance(addr, block_id).await
         })?;
 
-        if let Some(block_number) = block_number {
-            self.cache.store(
-                "eth",
-                block_number,
-                "eth_getBalance",
-                &address_str,
-                &format!("{:x}", balance),
-            )?;
-        }
-
+        self.store_cached(block_number, api, &key, &encode_u256(balance));
         Ok(balance)
     }
 
     pub fn get_code(&mut self, address: &Address, block_number: Option<u64>) -> Result<Bytes> {
-        let address_str = format!("{:x}", address);
-        if let Some(block_number) = block_number {
-            if let Ok(cached) = self
-                .cache
-                .get("eth", block_number, "eth_getCode", &address_str)
-            {
-                return Ok(Bytes::from_hex(cached).unwrap());
-            }
+        let key = request_hash(&[address.as_slice()]);
+        let api = "eth_getCode";
+        if let Some(cached) =
+            self.get_cached(block_number, api, &key, |v| Ok(Bytes::from(v.to_vec())))
+        {
+            return Ok(cached);
         }
 
         let block_id = block_number.map(BlockId::from);
@@ -128,56 +153,66 @@ impl<T: ProviderCache> ForkProvider<T> {
             self.provider.get_code(addr, block_id).await
         })?;
 
-        if let Some(block_number) = block_number {
-            self.cache.store(
-                "eth",
-                block_number,
-                "eth_getCode",
-                &address_str,
-                &format!("{:x}", code),
-            )?;
-        }
+        self.store_cached(block_number, api, &key, &code);
         Ok(code)
     }
 
     pub fn get_block(&mut self, block_number: u64) -> Result<Option<Block<TxHash>>> {
-        if let Ok(cached) = self.cache.get(
-            "eth",
-            block_number,
-            "eth_getBlockByNumber",
-            &format!("{:x}", block_number),
-        ) {
-            return Ok(Some(serde_json::from_str(&cached).unwrap()));
+        let key = request_hash(&[&block_number.to_be_bytes()]);
+        let api = "eth_getBlockByNumber";
+        if let Some(cached) = self.get_cached(Some(block_number), api, &key, |v| {
+            Ok(serde_json::from_slice(v)?)
+        }) {
+            return Ok(Some(cached));
         }
 
         let block_id = BlockId::from(block_number);
         let block = self.block_on(async { self.provider.get_block(block_id).await })?;
 
-        let _ = self.cache.store(
-            "eth",
-            block_number,
-            "eth_getBlockByNumber",
-            &format!("{:x}", block_number),
-            &serde_json::to_string(&block)?,
-        );
+        // Not produced yet, the block is requested again next time
+        if let Some(block) = &block {
+            self.store_cached(Some(block_number), api, &key, &serde_json::to_vec(block)?);
+        }
         Ok(block)
     }
 
+    /// Get a transaction by hash, cached under the forked `block_number`
+    pub fn get_transaction(
+        &mut self,
+        hash: &TxHash,
+        block_number: Option<u64>,
+    ) -> Result<Option<Transaction>> {
+        let key = request_hash(&[hash.as_bytes()]);
+        let api = "eth_getTransactionByHash";
+        if let Some(cached) =
+            self.get_cached(block_number, api, &key, |v| Ok(serde_json::from_slice(v)?))
+        {
+            return Ok(cached);
+        }
+
+        let tx = self.block_on(async { self.provider.get_transaction(*hash).await })?;
+
+        self.store_cached(block_number, api, &key, &serde_json::to_vec(&tx)?);
+        Ok(tx)
+    }
+
+    /// Wait until all responses are persisted in the cache
+    pub fn flush_cache(&self) -> Result<()> {
+        self.cache.flush()
+    }
+
     pub fn get_storage_at(
         &mut self,
         address: &Address,
         index: &H256,
         block_number: Option<u64>,
     ) -> Result<H256> {
-        let store_key = format!("{:x}-{:x}", address, index);
-
-        if let Some(block_number) = block_number {
-            if let Ok(cached) = self
-                .cache
-                .get("eth", block_number, "eth_getStorageAt", &store_key)
-            {
-                return Ok(H256::from_slice(&hex::decode(cached).unwrap()));
-            }
+        let key = request_hash(&[address.as_slice(), index.as_bytes()]);
+        let api = "eth_getStorageAt";
+        if let Some(cached) = self.get_cached(block_number, api, &key, |v| {
+            Ok(H256::from_slice(check_word(v)?))
+        }) {
+            return Ok(cached);
         }
 
         let block_id = block_number.map(BlockId::from);
@@ -191,16 +226,7 @@ impl<T: ProviderCache> ForkProvider<T> {
             address, index, storage
         );
 
-        if let Some(block_number) = block_number {
-            self.cache.store(
-                "eth",
-                block_number,
-                "eth_getStorageAt",
-                &store_key,
-                &format!("{:x}", storage),
-            )?;
-        }
-
+        self.store_cached(block_number, api, &key, storage.as_bytes());
         Ok(storage)
     }
 }
@@ -1,12 +1,19 @@
 use primitive_types::{H160, H256};
-use revm::primitives::Address;
+use revm::primitives::{keccak256, Address, B256};
 use ruint::aliases::U256;
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
 use strum_macros::Display;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Display)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BugType {
     IntegerOverflow,
+    /// Overflow immediately followed by the `Panic(0x11)` revert inserted
+    /// by the Solidity >= 0.8 checked arithmetic
+    CheckedOverflowReverted,
     IntegerSubUnderflow,
     /// op2 for DIV, SDIV is zero
     IntegerDivByZero,
@@ -19,17 +26,77 @@ pub enum BugType {
     TxOriginDependency,
     /// Call(input_parameter_size, destination_address)
     Call(usize, H160),
+    /// DELEGATECALL to a target derived from calldata or from storage
+    /// written with calldata
+    DelegatecallToUntrusted(H160),
+    /// ArbitrarySend(value, destination_address): CALL sending ETH to a
+    /// destination derived from calldata
+    ArbitrarySend(U256, H160),
     RevertOrInvalid,
+    /// Status of CALL, CALLCODE or DELEGATECALL discarded without
+    /// reaching a JUMPI, position is the call site
+    UncheckedCallReturn,
     /// Jumpi(dest)
     Jumpi(usize),
     Sload(U256),
     /// storage key, value
     Sstore(U256, U256),
+    /// SELFDESTRUCT(beneficiary) in a frame which never compared `CALLER`
+    /// with another value, i.e. reachable by any caller
+    UnprotectedSelfdestruct(H160),
+    /// StorageCollision(slot): slot of the proxy storage accessed both by
+    /// the proxy code and by the implementation code, see
+    /// `InstrumentConfig::storage_collision`
+    StorageCollision(U256),
+    /// SubcallOutOfGas(callee, forwarded_gas): a subcall ran out of gas while
+    /// its caller completed successfully, e.g. griefing with the 63/64 rule
+    SubcallOutOfGas(H160, u64),
+    /// SSTORE, TSTORE, LOG, CREATE, CREATE2, SELFDESTRUCT or CALL with
+    /// value attempted in a frame entered through STATICCALL, which
+    /// reverts the frame, e.g. a view function modifying the state
+    StaticStateModification,
+    /// Solidity `Panic(0x01)`, a failed `assert`
+    AssertionFailure,
+    /// Solidity `Panic(0x11)`, checked arithmetic overflow or underflow
+    PanicOverflow,
+    /// Solidity `Panic(0x12)`, division or modulo by zero
+    PanicDivisionByZero,
+    /// Solidity `Panic(0x32)`, array index out of bounds
+    PanicIndexOutOfBounds,
+    /// Panic(code): other Solidity `Panic(uint256)` codes
+    Panic(U256),
     Unclassified,
 }
 
+/// Selector of the Solidity `Panic(uint256)` error
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Error code of Solidity `Panic(uint256)` revert data
+pub fn panic_code(output: &[u8]) -> Option<U256> {
+    match output.split_at_checked(4) {
+        Some((selector, code)) if selector == PANIC_SELECTOR && code.len() == 32 => {
+            Some(U256::from_be_slice(code))
+        }
+        _ => None,
+    }
+}
+
+impl BugType {
+    /// Bug type of a revert with the Solidity `Panic(code)` error
+    pub fn from_panic_code(code: U256) -> Self {
+        match code.try_into() {
+            Ok(0x01u8) => BugType::AssertionFailure,
+            Ok(0x11u8) => BugType::PanicOverflow,
+            Ok(0x12u8) => BugType::PanicDivisionByZero,
+            Ok(0x32u8) => BugType::PanicIndexOutOfBounds,
+            _ => BugType::Panic(code),
+        }
+    }
+}
+
 /// Bug
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bug {
     pub bug_type: BugType,
     pub opcode: u8,
@@ -37,6 +104,50 @@ pub struct Bug {
     pub position: usize,
     /// Direct contract address in which this operation is executed
     pub address_index: isize,
+    /// Calldata bytes `[start, end)` influencing the operands of the operation
+    pub calldata_taint: Option<(usize, usize)>,
+    /// Number of times the bug was recorded, only above 1 when
+    /// `InstrumentConfig::dedup_bugs` is enabled
+    pub count: usize,
+    /// External calls active when the bug was recorded, outermost first
+    pub call_stack: Arc<[CallFrame]>,
+    /// Identifier stable across runs, see `Bug::stable_id`. Zero until the
+    /// bug is recorded
+    pub id: B256,
+}
+
+/// An external call frame, for DELEGATECALL and CALLCODE the caller is the
+/// contract whose storage is used and the callee the contract whose code runs
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CallFrame {
+    pub caller: Address,
+    pub callee: Address,
+    /// First 4 bytes of the call input, if any
+    pub selector: Option<[u8; 4]>,
+}
+
+/// A contract created by CREATE or CREATE2 during a transaction
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Deployment {
+    pub creator: Address,
+    /// Salt of CREATE2, `None` for CREATE
+    pub salt: Option<U256>,
+    pub init_code_hash: B256,
+    /// Address of the created contract, `None` if the creation failed
+    pub address: Option<Address>,
+    /// Hash of the runtime code, `None` if the creation failed
+    pub code_hash: Option<B256>,
+    /// Whether the creation was undone by the revert of an enclosing frame
+    pub reverted: bool,
+}
+
+impl Deployment {
+    /// Whether the contract exists after the transaction
+    pub fn is_created(&self) -> bool {
+        self.address.is_some() && self.code_hash.is_some() && !self.reverted
+    }
 }
 
 pub type BugData = VecDeque<Bug>;
@@ -49,8 +160,22 @@ impl Bug {
             opcode,
             position,
             address_index,
+            calldata_taint: None,
+            count: 1,
+            call_stack: Arc::new([]),
+            id: B256::ZERO,
         }
     }
+
+    /// Hash of the bug type name, of the code the bug was found in and of
+    /// the position. The values carried by the bug type are left out so
+    /// that the same finding keeps the same identifier.
+    pub fn stable_id(bug_type: &BugType, code_hash: B256, position: usize) -> B256 {
+        let mut data = bug_type.to_string().into_bytes();
+        data.extend_from_slice(code_hash.as_slice());
+        data.extend_from_slice(&(position as u64).to_be_bytes());
+        keccak256(data)
+    }
 }
 
 impl std::fmt::Display for Bug {
@@ -111,24 +236,310 @@ impl From<(usize, usize, bool, U256, isize)> for MissedBranch {
     }
 }
 
+/// Concrete operands of a comparison, for input-to-state mutation
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CmpOperands {
+    /// Program counter of the comparison
+    pub pc: usize,
+    /// One of EQ, LT, GT, SLT, SGT
+    pub opcode: u8,
+    /// First operand (top of the stack)
+    pub a: U256,
+    /// Second operand
+    pub b: U256,
+    /// Address of the contract in which this operation is executed
+    pub address_index: isize,
+}
+
+/// Max number of comparison operands kept in `Heuristics::cmp_operands`
+pub const MAX_CMP_OPERANDS: usize = 256;
+
+/// Coverage of a function, i.e. of the frames entered with its selector
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SelectorCoverage {
+    /// PCs executed
+    pub pcs: HashSet<usize>,
+    /// Branches not taken
+    pub missed_branches: Vec<MissedBranch>,
+}
+
+/// Storage slots `(address, slot)` accessed by a transaction
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorageFootprint {
+    /// Value of the slots read before being written by the transaction
+    pub reads: HashMap<(Address, U256), U256>,
+    /// Last value written to the slots
+    pub writes: HashMap<(Address, U256), U256>,
+}
+
+impl StorageFootprint {
+    pub fn record_read(&mut self, address: Address, slot: U256, value: U256) {
+        if !self.writes.contains_key(&(address, slot)) {
+            self.reads.entry((address, slot)).or_insert(value);
+        }
+    }
+
+    pub fn record_write(&mut self, address: Address, slot: U256, value: U256) {
+        self.writes.insert((address, slot), value);
+    }
+
+    /// Slots written by one of the footprints and accessed by the other,
+    /// sorted
+    pub fn conflicts(&self, other: &StorageFootprint) -> Vec<(Address, U256)> {
+        let written_by = |a: &StorageFootprint, b: &StorageFootprint| {
+            a.writes
+                .keys()
+                .filter(|k| b.reads.contains_key(*k) || b.writes.contains_key(*k))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        let mut slots = written_by(self, other);
+        slots.extend(written_by(other, self));
+        slots.sort_unstable();
+        slots.dedup();
+        slots
+    }
+
+    pub fn clear(&mut self) {
+        self.reads.clear();
+        self.writes.clear();
+    }
+}
+
+/// Gas spent by the opcodes of a transaction by category, the
+/// intrinsic gas and the refund are not included
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GasBreakdown {
+    /// Opcodes other than the ones below, without memory expansion
+    pub execution: u64,
+    /// Memory expansion of all the opcodes
+    pub memory: u64,
+    /// SLOAD of slots not accessed before in the transaction
+    pub sload_cold: u64,
+    pub sload_warm: u64,
+    /// SSTORE of slots not accessed before in the transaction
+    pub sstore_cold: u64,
+    pub sstore_warm: u64,
+    /// CALL, CALLCODE, DELEGATECALL, STATICCALL, CREATE and CREATE2,
+    /// without the gas forwarded to the new frame
+    pub calls: u64,
+}
+
+impl GasBreakdown {
+    /// Gas spent by the opcodes of all the categories
+    pub fn total(&self) -> u64 {
+        self.execution
+            + self.memory
+            + self.sload_cold
+            + self.sload_warm
+            + self.sstore_cold
+            + self.sstore_warm
+            + self.calls
+    }
+}
+
+/// Preimages of the KECCAK256 outputs of a transaction. Once the capacity
+/// is reached, the least recently hashed preimages are evicted. The clones
+/// share the entries until they are modified
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "with-serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "HashMap<H256, Vec<u8>>", into = "HashMap<H256, Vec<u8>>")
+)]
+pub struct Sha3Mapping {
+    /// Preimage and last use of each output
+    entries: im::HashMap<H256, (Vec<u8>, u64)>,
+    /// Outputs by last use
+    recency: im::OrdMap<u64, H256>,
+    /// Last use of the most recent output
+    tick: u64,
+}
+
+impl Sha3Mapping {
+    /// Preimage of `output`
+    pub fn get(&self, output: &H256) -> Option<&Vec<u8>> {
+        self.entries.get(output).map(|(preimage, _)| preimage)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Outputs and their preimages, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&H256, &Vec<u8>)> {
+        self.entries
+            .iter()
+            .map(|(output, (preimage, _))| (output, preimage))
+    }
+
+    /// Remove all the outputs
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.tick = 0;
+    }
+
+    /// Record the `preimage` of `output`, evicting the least recently
+    /// recorded outputs beyond `capacity`
+    pub fn insert(&mut self, output: H256, preimage: &[u8], capacity: usize) {
+        self.tick += 1;
+        if let Some((_, last_use)) = self.entries.get(&output) {
+            self.recency.remove(last_use);
+        }
+        self.entries.insert(output, (preimage.to_vec(), self.tick));
+        self.recency.insert(self.tick, output);
+        while self.entries.len() > capacity.max(1) {
+            let Some((last_use, oldest)) = self.recency.get_min().copied() else {
+                break;
+            };
+            self.recency.remove(&last_use);
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+impl From<HashMap<H256, Vec<u8>>> for Sha3Mapping {
+    fn from(entries: HashMap<H256, Vec<u8>>) -> Self {
+        let mut mapping = Self::default();
+        for (output, preimage) in entries {
+            mapping.insert(output, &preimage, usize::MAX);
+        }
+        mapping
+    }
+}
+
+impl From<Sha3Mapping> for HashMap<H256, Vec<u8>> {
+    fn from(mapping: Sha3Mapping) -> Self {
+        mapping
+            .entries
+            .into_iter()
+            .map(|(output, (preimage, _))| (output, preimage))
+            .collect()
+    }
+}
+
+/// Addresses of a transaction indexed in order of appearance, the index
+/// of an address is found without scanning the list. Bugs, missed
+/// branches and comparison operands refer to their contract by index
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "with-serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "Vec<Address>", into = "Vec<Address>")
+)]
+pub struct AddressTable {
+    addresses: Vec<Address>,
+    index: HashMap<Address, usize>,
+}
+
+impl AddressTable {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            addresses: Vec::with_capacity(capacity),
+            index: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Index of `address`, the address is appended if not seen before
+    pub fn intern(&mut self, address: Address) -> usize {
+        *self.index.entry(address).or_insert_with(|| {
+            self.addresses.push(address);
+            self.addresses.len() - 1
+        })
+    }
+
+    /// Index of `address`, `None` if it is not in the table
+    pub fn index_of(&self, address: &Address) -> Option<usize> {
+        self.index.get(address).copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.addresses.clear();
+        self.index.clear();
+    }
+}
+
+impl std::ops::Deref for AddressTable {
+    type Target = [Address];
+
+    fn deref(&self) -> &[Address] {
+        &self.addresses
+    }
+}
+
+impl From<Vec<Address>> for AddressTable {
+    fn from(addresses: Vec<Address>) -> Self {
+        let mut table = Self::with_capacity(addresses.len());
+        for address in addresses {
+            table.intern(address);
+        }
+        table
+    }
+}
+
+impl From<AddressTable> for Vec<Address> {
+    fn from(table: AddressTable) -> Self {
+        table.addresses
+    }
+}
+
 /// Storing heuristics code coverage data
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Heuristics {
     /// Whether to skip `record_missed_branch` when jumpi occurs
-    #[cfg_attr(feature = "with-serde", serde(skip_serializing))]
+    #[cfg_attr(feature = "with-serde", serde(skip_serializing, default))]
     pub skip: bool,
     /// List of jumpi destinations
     pub coverage: VecDeque<usize>,
     /// Current distance
-    #[cfg_attr(feature = "with-serde", serde(skip_serializing))]
+    #[cfg_attr(feature = "with-serde", serde(skip_serializing, default))]
     pub distance: U256,
     /// Missed branches
     pub missed_branches: Vec<MissedBranch>,
-    /// Mapping from SHA3 output to input. This is for reverse lookup of slot mapping
-    pub sha3_mapping: HashMap<H256, Vec<u8>>,
-    // Addresses the transaction was executed on
-    pub seen_addresses: Vec<Address>,
+    /// `(prev_pc, dest_pc, distance)` of the `missed_branches`, to skip the
+    /// duplicates without scanning the list
+    #[cfg_attr(feature = "with-serde", serde(skip))]
+    missed_branch_keys: HashSet<(usize, usize, U256)>,
+    /// Mapping from SHA3 output to input. This is for reverse lookup of slot
+    /// mapping, bounded by `InstrumentConfig::sha3_mapping_capacity`
+    pub sha3_mapping: Sha3Mapping,
+    /// Addresses the transaction was executed on, see `record_seen_address`
+    pub seen_addresses: AddressTable,
+    /// Last comparison operands, only when `InstrumentConfig::record_cmp_operands` is enabled
+    pub cmp_operands: VecDeque<CmpOperands>,
+    /// Number of executions of each opcode (indexed by opcode) by contract
+    /// address, only when `InstrumentConfig::record_opcode_histogram` is enabled
+    pub opcode_counts: HashMap<Address, Vec<u64>>,
+    /// Coverage of the frames entered with a function selector, by contract
+    /// address and selector. Only when `InstrumentConfig::coverage_by_selector`
+    /// is enabled
+    pub selector_coverage: HashMap<Address, HashMap<u32, SelectorCoverage>>,
+    /// Deepest call frame reached, the transaction frame has depth 1
+    pub max_call_depth: u64,
+    /// Max number of items on the EVM stack of any frame
+    pub max_stack_depth: usize,
+    /// Max memory size in bytes of any frame
+    pub max_memory_size: usize,
+    /// Storage read and written by the transaction, only when
+    /// `InstrumentConfig::record_storage_footprint` is enabled
+    pub storage_footprint: StorageFootprint,
+    /// Gas spent by category, only when `InstrumentConfig::record_gas_breakdown`
+    /// is enabled
+    pub gas_breakdown: GasBreakdown,
+    /// Gas spent by the opcodes executed at each contract address, without
+    /// the gas of the frames they call. Only when
+    /// `InstrumentConfig::record_gas_by_address` is enabled
+    pub gas_by_address: HashMap<Address, u64>,
 }
 
 impl Default for Heuristics {
@@ -138,8 +549,18 @@ impl Default for Heuristics {
             coverage: VecDeque::with_capacity(32), // Set some initial capacity to avoid some data copying
             distance: U256::MAX,
             missed_branches: Vec::with_capacity(32),
-            sha3_mapping: HashMap::with_capacity(32),
-            seen_addresses: Vec::with_capacity(32),
+            missed_branch_keys: HashSet::with_capacity(32),
+            sha3_mapping: Sha3Mapping::default(),
+            seen_addresses: AddressTable::with_capacity(32),
+            cmp_operands: VecDeque::new(),
+            opcode_counts: HashMap::new(),
+            selector_coverage: HashMap::new(),
+            max_call_depth: 0,
+            max_stack_depth: 0,
+            max_memory_size: 0,
+            storage_footprint: StorageFootprint::default(),
+            gas_breakdown: GasBreakdown::default(),
+            gas_by_address: HashMap::new(),
         }
     }
 }
@@ -153,14 +574,67 @@ impl Heuristics {
     /// Reset Heuristics data
     pub fn reset(&mut self) {
         self.skip = true;
-        self.coverage = VecDeque::with_capacity(32);
+        self.coverage.clear();
         self.distance = U256::MAX;
-        self.missed_branches = Vec::with_capacity(32);
+        self.missed_branches.clear();
+        self.missed_branch_keys.clear();
+        self.cmp_operands.clear();
+        self.opcode_counts.clear();
+        self.selector_coverage.clear();
+        self.max_call_depth = 0;
+        self.max_stack_depth = 0;
+        self.max_memory_size = 0;
+        self.storage_footprint.clear();
+        self.gas_breakdown = GasBreakdown::default();
+        self.gas_by_address.clear();
+    }
+
+    /// Reset to the `Heuristics::default()` data, including the SHA3
+    /// mappings and the seen addresses, keeping the allocated buffers
+    pub fn reset_in_place(&mut self) {
+        self.reset();
+        self.sha3_mapping.clear();
+        self.seen_addresses.clear();
+    }
+
+    /// Update the maxima of the call depth, stack depth and memory size
+    pub fn record_pressure(&mut self, call_depth: u64, stack_depth: usize, memory_size: usize) {
+        self.max_call_depth = self.max_call_depth.max(call_depth);
+        self.max_stack_depth = self.max_stack_depth.max(stack_depth);
+        self.max_memory_size = self.max_memory_size.max(memory_size);
+    }
+
+    /// Count an execution of `opcode` at `address`
+    pub fn record_opcode(&mut self, address: Address, opcode: u8) {
+        let counts = self
+            .opcode_counts
+            .entry(address)
+            .or_insert_with(|| vec![0; 256]);
+        counts[opcode as usize] += 1;
     }
 
-    /// Record Sha3 mapping
-    pub fn record_sha3_mapping(&mut self, input: &[u8], output: H256) {
-        self.sha3_mapping.insert(output, input.to_vec());
+    /// Number of executions of each opcode, summed over all addresses
+    pub fn opcode_histogram(&self) -> Vec<u64> {
+        let mut histogram = vec![0; 256];
+        for counts in self.opcode_counts.values() {
+            for (total, count) in histogram.iter_mut().zip(counts) {
+                *total += count;
+            }
+        }
+        histogram
+    }
+
+    /// Record the operands of a comparison, keeping the last `MAX_CMP_OPERANDS`
+    pub fn record_cmp_operands(&mut self, operands: CmpOperands) {
+        if self.cmp_operands.len() >= MAX_CMP_OPERANDS {
+            self.cmp_operands.pop_front();
+        }
+        self.cmp_operands.push_back(operands);
+    }
+
+    /// Record Sha3 mapping, keeping the `capacity` most recent outputs
+    pub fn record_sha3_mapping(&mut self, input: &[u8], output: H256, capacity: usize) {
+        self.sha3_mapping.insert(output, input, capacity);
     }
 
     /// Record missing branch data
@@ -169,13 +643,19 @@ impl Heuristics {
         prev_pc: usize,
         dest_pc: usize,
         cond: bool,
+        distance: U256,
         address_index: isize,
     ) {
-        let distance = self.distance;
+        // The public list may have been modified or deserialized without the keys
+        if self.missed_branch_keys.len() != self.missed_branches.len() {
+            self.missed_branch_keys = self
+                .missed_branches
+                .iter()
+                .map(|b| (b.prev_pc, b.dest_pc, b.distance))
+                .collect();
+        }
 
-        if self.missed_branches.iter_mut().any(|x| {
-            matches!(x, MissedBranch { prev_pc: p, dest_pc: d, distance: dist, .. } if *p == prev_pc && *d == dest_pc && *dist == distance)
-        }) {
+        if !self.missed_branch_keys.insert((prev_pc, dest_pc, distance)) {
             return;
         }
 
@@ -186,9 +666,12 @@ impl Heuristics {
             distance,
             address_index,
         ));
-        // if self.missed_branchs.len() > 2 {
-        //     self.missed_branchs.drain(0..self.missed_branchs.len() - 2);
-        // }
+    }
+
+    /// Index of `address` in `seen_addresses`, the address is appended if
+    /// not seen before
+    pub fn record_seen_address(&mut self, address: Address) -> usize {
+        self.seen_addresses.intern(address)
     }
 }
 
@@ -212,8 +695,73 @@ pub struct InstrumentConfig {
     pub target_address: Address,
     /// Whether to record SHA3 mappings
     pub record_sha3_mapping: bool,
+    /// Maximum length of the inputs recorded in `heuristics.sha3_mapping`,
+    /// only the last bytes of longer inputs are kept. Raise it, e.g. to 64,
+    /// to record the whole `key . slot` preimages of the mapping slots
+    pub sha3_preimage_max_len: usize,
+    /// Maximum number of outputs in `heuristics.sha3_mapping`, the least
+    /// recently hashed are evicted first
+    pub sha3_mapping_capacity: usize,
+    /// Whether to record the operands of EQ, LT, GT, SLT and SGT. Follows
+    /// `record_branch_for_target_only`
+    pub record_cmp_operands: bool,
+    /// Size of the bug buffer, once full the oldest entries are evicted
+    /// when SLOAD or SSTORE are recorded
+    pub bug_buffer_size: usize,
+    /// Record a bug with the same type, position and address only once
+    /// and count its occurrences in `Bug::count`
+    pub dedup_bugs: bool,
+    /// Whether to report the overflows, underflows, divisions and modulos
+    /// by zero and the possible truncations
+    pub detect_arithmetic: bool,
+    /// Whether to report the uses of the block values and of `ORIGIN`
+    pub detect_dependency: bool,
+    /// Whether to report the SLOADs and SSTOREs
+    pub detect_storage: bool,
+    /// `(proxy, implementation)`: report the proxy storage slots, other than
+    /// the EIP-1967 slots, accessed by both the proxy and the implementation code
+    pub storage_collision: Option<(Address, Address)>,
+    /// Report every `ORIGIN`, instead of only the ones compared with
+    /// another value than `CALLER`, i.e. used for authorization
+    pub report_all_tx_origin: bool,
+    /// Whether to track the calldata bytes influencing the operands, see
+    /// `Bug::calldata_taint`. The `DelegatecallToUntrusted` and
+    /// `ArbitrarySend` detectors depend on it
+    pub calldata_taint: bool,
+    /// Whether to count the executed opcodes by address in `heuristics.opcode_counts`
+    pub record_opcode_histogram: bool,
+    /// Whether to record the coverage by function selector in
+    /// `heuristics.selector_coverage`
+    pub coverage_by_selector: bool,
+    /// Whether to record the storage read and written by each transaction
+    /// in `heuristics.storage_footprint`, see `TinyEVM::find_tod_pairs`
+    pub record_storage_footprint: bool,
+    /// Whether to record the gas spent by category in `heuristics.gas_breakdown`
+    pub record_gas_breakdown: bool,
+    /// Whether to record the gas spent at each address in `heuristics.gas_by_address`
+    pub record_gas_by_address: bool,
+    /// Addresses for which no bug, coverage or heuristics is recorded,
+    /// e.g. well-known dependencies of the target
+    pub excluded_addresses: HashSet<Address>,
+}
+
+impl InstrumentConfig {
+    /// Whether the taint of the values must be propagated: for the calldata
+    /// taint or to tell the `ORIGIN` compared with `CALLER`
+    pub fn needs_taint(&self) -> bool {
+        self.calldata_taint || self.detect_dependency && !self.report_all_tx_origin
+    }
 }
 
+/// Default value of `InstrumentConfig::bug_buffer_size`
+pub const DEFAULT_BUG_BUFFER_SIZE: usize = 256;
+
+/// Default value of `InstrumentConfig::sha3_preimage_max_len`
+pub const DEFAULT_SHA3_PREIMAGE_MAX_LEN: usize = 32;
+
+/// Default value of `InstrumentConfig::sha3_mapping_capacity`
+pub const DEFAULT_SHA3_MAPPING_CAPACITY: usize = 4096;
+
 impl Default for InstrumentConfig {
     fn default() -> InstrumentConfig {
         InstrumentConfig {
@@ -223,6 +771,23 @@ impl Default for InstrumentConfig {
             record_branch_for_target_only: false,
             target_address: Default::default(),
             record_sha3_mapping: true,
+            sha3_preimage_max_len: DEFAULT_SHA3_PREIMAGE_MAX_LEN,
+            sha3_mapping_capacity: DEFAULT_SHA3_MAPPING_CAPACITY,
+            record_cmp_operands: false,
+            bug_buffer_size: DEFAULT_BUG_BUFFER_SIZE,
+            dedup_bugs: false,
+            detect_arithmetic: true,
+            detect_dependency: true,
+            detect_storage: true,
+            storage_collision: None,
+            report_all_tx_origin: false,
+            calldata_taint: true,
+            record_opcode_histogram: false,
+            coverage_by_selector: false,
+            record_storage_footprint: false,
+            record_gas_breakdown: false,
+            record_gas_by_address: false,
+            excluded_addresses: HashSet::new(),
         }
     }
 }
@@ -1,32 +1,255 @@
 use hashbrown::{HashMap, HashSet};
+use hex_literal::hex;
 use primitive_types::{H160, H256};
 use revm::{
-    interpreter::{CreateInputs, CreateOutcome, Interpreter, OpCode},
-    primitives::{Address, U256},
+    interpreter::{
+        gas, CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, InstructionResult,
+        Interpreter, InterpreterAction, OpCode,
+    },
+    primitives::{keccak256, Address, CreateScheme, B256, U256},
     Database, EvmContext, Inspector,
 };
-use tracing::{debug, warn};
+use std::sync::Arc;
+use tracing::{debug, trace, warn};
 
 use crate::i256_diff;
 
-use super::{Bug, BugData, BugType, Heuristics, InstrumentConfig};
+use super::{
+    opcode_hook::OpcodeHooks,
+    panic_code,
+    taint::{Taint, TaintTracker},
+    Bug, BugData, BugType, CallFrame, CmpOperands, Deployment, Heuristics, InstrumentConfig, PcSet,
+    SelectorCoverage,
+};
+
+/// EIP-1967 proxy slots: `keccak256("eip1967.proxy.<name>") - 1` for the
+/// implementation, admin and beacon
+const EIP1967_SLOTS: [U256; 3] = [
+    U256::from_be_bytes(hex!(
+        "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc"
+    )),
+    U256::from_be_bytes(hex!(
+        "b53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103"
+    )),
+    U256::from_be_bytes(hex!(
+        "a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50"
+    )),
+];
+
+/// Max number of KECCAK256 inputs kept per transaction for the branch
+/// distance of hash comparisons
+const MAX_KECCAK_PREIMAGES: usize = 4096;
+
+/// Max number of steps between an overflow and the panic revert of the
+/// compiler inserted check
+const CHECKED_OVERFLOW_MAX_STEPS: u64 = 64;
+
+/// Max number of comparisons kept for the distance of the branches
+const MAX_CMP_DISTANCES: usize = 16;
+
+/// Max number of stack inputs of an opcode, taken by CALL and CALLCODE
+const MAX_OPCODE_INPUTS: usize = 7;
+
+/// Stack inputs of an opcode, stored inline so that capturing them on every
+/// step does not allocate
+#[derive(Debug, Clone, Copy, Default)]
+struct OpcodeInputs<T> {
+    items: [T; MAX_OPCODE_INPUTS],
+    len: usize,
+}
+
+impl<T: Copy> OpcodeInputs<T> {
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn push(&mut self, item: T) {
+        self.items[self.len] = item;
+        self.len += 1;
+    }
+}
+
+impl<T> std::ops::Deref for OpcodeInputs<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items[..self.len]
+    }
+}
+
+/// Branch distance of a comparison of the frame at `depth`, its result
+/// was pushed at `stack_index`
+struct CmpDistance {
+    depth: u64,
+    pc: usize,
+    stack_index: usize,
+    distance: U256,
+}
+
+/// Opcode which just executed, passed to the handlers of `step_end`
+#[derive(Clone, Copy)]
+struct StepEnd<'a> {
+    interp: &'a Interpreter,
+    op: OpCode,
+    /// Target address of the frame
+    address: Address,
+    address_index: isize,
+    pc: usize,
+    depth: u64,
+}
+
+/// Handler of an opcode in `step_end`
+type StepHandler = fn(&mut BugInspector, StepEnd<'_>);
+
+/// Handlers of `step_end` indexed by opcode. Only the detectors enabled in
+/// the instrument config have handlers, so that the other opcodes and the
+/// disabled detectors cost a single lookup per step
+struct StepHandlers {
+    handlers: [Option<StepHandler>; 256],
+    /// Opcodes whose stack inputs are read by their handler, captured in `step`
+    inputs: [bool; 256],
+    /// `(detect_arithmetic, detect_dependency, record_sha3_mapping, storage)`
+    /// of the config the handlers were selected for, `None` until the first
+    /// frame. `storage` is whether any of the SLOAD and SSTORE checks is on
+    flags: Option<(bool, bool, bool, bool)>,
+}
+
+impl Default for StepHandlers {
+    fn default() -> Self {
+        Self {
+            handlers: [None; 256],
+            inputs: [false; 256],
+            flags: None,
+        }
+    }
+}
+
+impl StepHandlers {
+    fn get(&self, op: OpCode) -> Option<StepHandler> {
+        self.handlers[op.get() as usize]
+    }
+
+    fn set(&mut self, ops: &[OpCode], handler: StepHandler) {
+        for op in ops {
+            self.handlers[op.get() as usize] = Some(handler);
+        }
+    }
+
+    /// Whether the handler of `op` reads its stack inputs
+    fn reads_inputs(&self, op: OpCode) -> bool {
+        self.inputs[op.get() as usize]
+    }
+
+    /// Set the handler of `ops`, which reads their stack inputs
+    fn set_with_inputs(&mut self, ops: &[OpCode], handler: StepHandler) {
+        self.set(ops, handler);
+        for op in ops {
+            self.inputs[op.get() as usize] = true;
+        }
+    }
+
+    /// Select the handlers again if the detectors enabled in `config` changed
+    fn update(&mut self, config: &InstrumentConfig) {
+        let flags = (
+            config.detect_arithmetic,
+            config.detect_dependency,
+            config.record_sha3_mapping,
+            config.detect_storage
+                || config.record_storage_footprint
+                || config.storage_collision.is_some(),
+        );
+        if self.flags == Some(flags) {
+            return;
+        }
+        self.flags = Some(flags);
+        self.handlers = [None; 256];
+        self.inputs = [false; 256];
+        let (arithmetic, dependency, sha3_mapping, storage) = flags;
+
+        self.set_with_inputs(&[OpCode::LT], BugInspector::on_lt);
+        self.set_with_inputs(&[OpCode::GT], BugInspector::on_gt);
+        self.set_with_inputs(&[OpCode::SLT], BugInspector::on_slt);
+        self.set_with_inputs(&[OpCode::SGT], BugInspector::on_sgt);
+        self.set_with_inputs(&[OpCode::EQ], BugInspector::on_eq);
+        self.set_with_inputs(
+            &[
+                OpCode::CALL,
+                OpCode::CALLCODE,
+                OpCode::DELEGATECALL,
+                OpCode::STATICCALL,
+            ],
+            BugInspector::on_call,
+        );
+        self.set_with_inputs(&[OpCode::JUMPI], BugInspector::on_jumpi);
+        self.set(&[OpCode::REVERT, OpCode::INVALID], BugInspector::on_revert);
+        self.set_with_inputs(&[OpCode::SELFDESTRUCT], BugInspector::on_selfdestruct);
+        self.set(&[OpCode::CREATE, OpCode::CREATE2], BugInspector::on_create);
+        if storage {
+            self.set_with_inputs(&[OpCode::SSTORE], BugInspector::on_sstore);
+            self.set_with_inputs(&[OpCode::SLOAD], BugInspector::on_sload);
+        }
+        if arithmetic {
+            self.set_with_inputs(&[OpCode::ADD], BugInspector::on_add);
+            self.set_with_inputs(&[OpCode::MUL], BugInspector::on_mul);
+            self.set_with_inputs(&[OpCode::SUB], BugInspector::on_sub);
+            self.set_with_inputs(&[OpCode::MOD, OpCode::SMOD], BugInspector::on_mod);
+            self.set_with_inputs(&[OpCode::DIV, OpCode::SDIV], BugInspector::on_div);
+            self.set_with_inputs(&[OpCode::ADDMOD, OpCode::MULMOD], BugInspector::on_addmod);
+            self.set_with_inputs(&[OpCode::EXP], BugInspector::on_exp);
+            self.set_with_inputs(&[OpCode::AND], BugInspector::on_and);
+        }
+        if dependency {
+            self.set(&[OpCode::ORIGIN], BugInspector::on_origin);
+            self.set(
+                &[
+                    OpCode::BLOBHASH,
+                    OpCode::COINBASE,
+                    OpCode::TIMESTAMP,
+                    OpCode::NUMBER,
+                    OpCode::DIFFICULTY,
+                ],
+                BugInspector::on_block_value,
+            );
+        }
+        if sha3_mapping {
+            self.set_with_inputs(&[OpCode::KECCAK256], BugInspector::on_keccak256);
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct BugInspector {
     /// Change the created address to another address
     pub create_address_overrides: HashMap<Address, Address>,
-    pub bug_data: BugData,
-    pub heuristics: Heuristics,
+    // The bugs, heuristics and seen PCs are shared with the responses
+    // and copied on write, i.e. only when a response is still alive
+    pub bug_data: Arc<BugData>,
+    pub heuristics: Arc<Heuristics>,
     // Mapping from contract address to a set of PCs seen in the execution
-    pub pcs_by_address: HashMap<Address, HashSet<usize>>,
+    pub pcs_by_address: Arc<HashMap<Address, PcSet>>,
+    // Mapping from contract address to the (pc, destination) of the jumps taken
+    pub jumps_by_address: HashMap<Address, HashSet<(usize, usize)>>,
     pub instrument_config: InstrumentConfig,
     // Holding the addresses created in the current transaction,
     // must be cleared by transaction caller before or after each transaction
     pub created_addresses: Vec<Address>,
+    // Contracts created by CREATE and CREATE2 in the current transaction,
+    // cleared together with `created_addresses`
+    pub deployments: Vec<Deployment>,
+    /// Number of `deployments` when each frame being executed started,
+    /// outermost first
+    frame_deployments: Vec<usize>,
     // Managed addresses: contract -> addresses created by any transaction from the contract
     pub managed_addresses: HashMap<Address, Vec<Address>>,
     /// Stack inputs of the current opcodes. Only updated when the opcode is interesting
-    inputs: Vec<U256>,
+    inputs: OpcodeInputs<U256>,
+    /// Taints of `inputs`
+    input_taints: OpcodeInputs<Taint>,
+    /// Calldata bytes influencing any of `inputs`
+    inputs_calldata: Option<(usize, usize)>,
+    taint: TaintTracker,
+    /// Whether `taint` is updated, see `InstrumentConfig::needs_taint`
+    needs_taint: bool,
     /// Current opcode
     opcode: Option<OpCode>,
     // Current program counter
@@ -35,6 +258,153 @@ pub struct BugInspector {
     step_index: u64,
     last_index_sub: u64,
     last_index_eq: u64,
+    /// Statuses of external calls not yet checked by a JUMPI
+    call_statuses: Vec<CallStatus>,
+    /// Last integer overflow: (depth, step index, pc, address index)
+    last_overflow: Option<(u64, u64, usize, isize)>,
+    /// Depths of the frames in which `CALLER` was compared with EQ
+    caller_checked_depths: Vec<u64>,
+    /// External calls currently executing, outermost first
+    call_stack: Vec<CallFrame>,
+    /// Shared copy of `call_stack` attached to the bugs, rebuilt after the
+    /// call stack changes
+    call_stack_snapshot: Option<Arc<[CallFrame]>>,
+    /// (pc, opcode) of the instruction which started each frame of `call_stack`
+    call_sites: Vec<(usize, u8)>,
+    /// Full inputs of the KECCAK256 of the transaction, by hash
+    keccak_preimages: HashMap<U256, Vec<u8>>,
+    /// `ORIGIN` bugs reported once the value is compared with anything but `CALLER`
+    pending_tx_origin: Vec<(u64, Bug)>,
+    /// Subcalls which ran out of gas, reported once their caller frame at
+    /// the given depth completes successfully
+    subcalls_out_of_gas: Vec<(u64, Bug)>,
+    storage_collision: StorageCollisionTracker,
+    opcode_hooks: Option<OpcodeHooks>,
+    /// First error returned by the opcode hook, the hook is not called again
+    /// until the error is taken
+    opcode_hook_error: Option<eyre::Report>,
+    /// Last comparisons of the frames, most recent last
+    cmp_distances: Vec<CmpDistance>,
+    /// `(depth, code hash)` of the frames being executed, for the bug identifiers
+    code_hashes: Vec<(u64, B256)>,
+    /// `(remaining gas, memory size, cold slot)` before the current opcode,
+    /// only when `record_gas_breakdown` or `record_gas_by_address` is enabled
+    gas_before: (u64, usize, bool),
+    step_handlers: StepHandlers,
+}
+
+/// Proxy storage slots accessed by the proxy code and by the
+/// implementation code, kept across transactions
+#[derive(Default)]
+struct StorageCollisionTracker {
+    /// `(proxy, implementation)` the slots are tracked for
+    contracts: Option<(Address, Address)>,
+    proxy_slots: HashSet<U256>,
+    implementation_slots: HashSet<U256>,
+    reported: HashSet<U256>,
+}
+
+impl StorageCollisionTracker {
+    /// Record an access to `slot` of `storage_address` by `code_address`,
+    /// returns true if the slot is a newly found collision
+    fn access(&mut self, storage_address: Address, code_address: Address, slot: U256) -> bool {
+        let Some((proxy, implementation)) = self.contracts else {
+            return false;
+        };
+        if storage_address != proxy || EIP1967_SLOTS.contains(&slot) {
+            return false;
+        }
+        let (slots, other) = if code_address == proxy {
+            (&mut self.proxy_slots, &self.implementation_slots)
+        } else if code_address == implementation {
+            (&mut self.implementation_slots, &self.proxy_slots)
+        } else {
+            return false;
+        };
+        slots.insert(slot);
+        other.contains(&slot) && self.reported.insert(slot)
+    }
+}
+
+/// Tracks the status pushed by an external call through the caller's stack
+struct CallStatus {
+    /// Journal depth of the caller frame
+    depth: u64,
+    /// Call site
+    pc: usize,
+    opcode: u8,
+    address_index: isize,
+    /// Stack positions (from the bottom) holding the status or a value derived from it
+    slots: Vec<usize>,
+}
+
+enum CallStatusUse {
+    Pending,
+    /// The status reached a JUMPI, or escaped to memory, storage or logs
+    Checked,
+    /// All copies of the status were popped or left on the stack when the frame returned
+    Unchecked,
+    /// The frame reverted, the call has no effect
+    Ignored,
+}
+
+impl CallStatus {
+    /// Update the tracked slots before `op` executes on a stack of `stack_len` items
+    fn update(&mut self, op: OpCode, stack_len: usize) -> CallStatusUse {
+        let code = op.get();
+        match op {
+            OpCode::STOP | OpCode::RETURN | OpCode::SELFDESTRUCT => {
+                return CallStatusUse::Unchecked;
+            }
+            OpCode::REVERT | OpCode::INVALID => return CallStatusUse::Ignored,
+            OpCode::JUMPI if stack_len >= 2 => {
+                if self.slots.contains(&(stack_len - 2)) {
+                    return CallStatusUse::Checked;
+                }
+                self.slots.retain(|s| *s < stack_len - 2);
+            }
+            _ if (0x80..=0x8f).contains(&code) => {
+                let n = (code - 0x7f) as usize;
+                if stack_len >= n && self.slots.contains(&(stack_len - n)) {
+                    self.slots.push(stack_len);
+                }
+            }
+            _ if (0x90..=0x9f).contains(&code) => {
+                let n = (code - 0x8f) as usize;
+                if stack_len > n {
+                    let (a, b) = (stack_len - 1, stack_len - 1 - n);
+                    for s in self.slots.iter_mut() {
+                        if *s == a {
+                            *s = b;
+                        } else if *s == b {
+                            *s = a;
+                        }
+                    }
+                }
+            }
+            _ => {
+                let num_inputs = op.inputs() as usize;
+                if stack_len < num_inputs {
+                    return CallStatusUse::Pending;
+                }
+                let first_input = stack_len - num_inputs;
+                let consumed = self.slots.iter().any(|s| *s >= first_input);
+                self.slots.retain(|s| *s < first_input);
+                if consumed && op != OpCode::POP {
+                    if op.outputs() == 0 {
+                        return CallStatusUse::Checked;
+                    }
+                    self.slots.push(first_input);
+                }
+            }
+        }
+
+        if self.slots.is_empty() {
+            CallStatusUse::Unchecked
+        } else {
+            CallStatusUse::Pending
+        }
+    }
 }
 
 impl BugInspector {
@@ -42,6 +412,67 @@ impl BugInspector {
         self.instrument_config.enabled
     }
 
+    /// Record the distance of a comparison as the current distance
+    fn record_cmp_distance(&mut self, depth: u64, pc: usize, stack_index: usize, distance: U256) {
+        Arc::make_mut(&mut self.heuristics).distance = distance;
+        if self.cmp_distances.len() >= MAX_CMP_DISTANCES {
+            self.cmp_distances.remove(0);
+        }
+        self.cmp_distances.push(CmpDistance {
+            depth,
+            pc,
+            stack_index,
+            distance,
+        });
+    }
+
+    /// Distance of the condition of a JUMPI of the frame at `depth`,
+    /// the condition was at `stack_index`. Comparisons of other frames
+    /// are ignored, the last comparison of the frame is used if none
+    /// pushed the condition.
+    fn branch_distance(&self, depth: u64, stack_index: usize) -> U256 {
+        let mut frame_cmps = self.cmp_distances.iter().rev().filter(|c| c.depth == depth);
+        frame_cmps
+            .clone()
+            .find(|c| c.stack_index == stack_index)
+            .or_else(|| frame_cmps.next())
+            .map_or(U256::MAX, |c| {
+                trace!("Distance of the comparison at pc {}", c.pc);
+                c.distance
+            })
+    }
+
+    /// Code hash of the frame being executed
+    fn code_hash(&self) -> B256 {
+        self.code_hashes
+            .last()
+            .map_or(B256::ZERO, |(_, hash)| *hash)
+    }
+
+    /// Stable identifier of a bug of the frame being executed
+    fn bug_id(&self, bug: &Bug) -> B256 {
+        Bug::stable_id(&bug.bug_type, self.code_hash(), bug.position)
+    }
+
+    /// Whether nothing should be recorded for the frames running at `address`
+    fn is_excluded(&self, address: Address) -> bool {
+        self.instrument_config.excluded_addresses.contains(&address)
+    }
+
+    /// Clear the bugs and the heuristics of the last transaction. The
+    /// buffers still shared with a response are replaced, the others are
+    /// cleared in place to keep their capacity
+    pub fn clear_results(&mut self) {
+        match Arc::get_mut(&mut self.bug_data) {
+            Some(bug_data) => bug_data.clear(),
+            None => self.bug_data = Default::default(),
+        }
+        match Arc::get_mut(&mut self.heuristics) {
+            Some(heuristics) => heuristics.reset_in_place(),
+            None => self.heuristics = Default::default(),
+        }
+    }
+
     pub fn inc_step_index(&mut self) {
         self.step_index += 1;
     }
@@ -57,9 +488,8 @@ impl BugInspector {
         // make sure target_address is the first address added
         if self.instrument_config.record_branch_for_target_only {
             if self.heuristics.seen_addresses.is_empty() {
-                self.heuristics
-                    .seen_addresses
-                    .push(self.instrument_config.target_address);
+                Arc::make_mut(&mut self.heuristics)
+                    .record_seen_address(self.instrument_config.target_address);
             }
 
             if self.instrument_config.target_address == address {
@@ -67,355 +497,767 @@ impl BugInspector {
             }
         }
 
-        let idx = self
-            .heuristics
-            .seen_addresses
-            .iter()
-            .position(|a| *a == address);
-        if let Some(i) = idx {
-            return i as isize;
-        }
-
-        self.heuristics.seen_addresses.push(address);
-        self.heuristics.seen_addresses.len() as isize - 1
+        Arc::make_mut(&mut self.heuristics).record_seen_address(address) as isize
     }
 
     /// Record the program counter for the given contract address
     pub fn record_pc(&mut self, address: Address, pc: usize) {
-        let pcs = self.pcs_by_address.entry(address).or_default();
+        // Most PCs were seen before, avoid copying the shared set for them
+        if self
+            .pcs_by_address
+            .get(&address)
+            .is_some_and(|pcs| pcs.contains(pc))
+        {
+            return;
+        }
+        let pcs = Arc::make_mut(&mut self.pcs_by_address)
+            .entry(address)
+            .or_default();
         pcs.insert(pc);
     }
 
-    pub fn add_bug(&mut self, bug: Bug) {
-        match bug.bug_type {
-            BugType::Jumpi(dest) => {
-                if self.instrument_config.heuristics {
-                    // March 15 bug patch: keep last 256 elements
-                    self.heuristics.coverage.push_back(dest);
-                    if self.heuristics.coverage.len() > 256 {
-                        self.heuristics.coverage.pop_front();
-                    }
-                }
+    /// Follow the statuses of external calls made by the frame at `depth`
+    fn track_call_statuses(&mut self, op: OpCode, stack_len: usize, depth: u64) {
+        let mut unchecked = vec![];
+        self.call_statuses.retain_mut(|status| {
+            if status.depth != depth {
+                return true;
             }
-            BugType::Sload(_key) => {
-                if self.bug_data.len() > 256 {
-                    // this will lead to poor performance
-                    // self.bug_data.retain(|front| {
-                    //     !(front.address_index == address_idx
-                    //         && matches!(front.bug_type, BugType::Sload(k) if k == key))
-                    // });
-                    self.bug_data.pop_front();
+            match status.update(op, stack_len) {
+                CallStatusUse::Pending => true,
+                CallStatusUse::Checked | CallStatusUse::Ignored => false,
+                CallStatusUse::Unchecked => {
+                    unchecked.push(Bug::new(
+                        BugType::UncheckedCallReturn,
+                        status.opcode,
+                        status.pc,
+                        status.address_index,
+                    ));
+                    false
                 }
-                self.bug_data.push_back(bug);
-            }
-            BugType::Sstore(_key, _) => {
-                if self.bug_data.len() > 256 {
-                    // self.bug_data.retain(|front| {
-                    //     !(front.address_index == address_idx
-                    //         && matches!(front.bug_type, BugType::Sstore(k, _) if k == key))
-                    // });
-                    self.bug_data.pop_front();
-                }
-                self.bug_data.push_back(bug);
             }
-            _ => self.bug_data.push_back(bug),
+        });
+        for bug in unchecked {
+            self.add_bug(bug);
         }
     }
-}
 
-impl<DB> Inspector<DB> for BugInspector
-where
-    DB: Database,
-{
-    #[inline]
-    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
-        if !self.enabled() {
+    fn add_overflow_bug(&mut self, opcode: u8, pc: usize, address_index: isize, depth: u64) {
+        self.last_overflow = Some((depth, self.step_index, pc, address_index));
+        let bug = Bug::new(BugType::IntegerOverflow, opcode, pc, address_index);
+        self.add_bug(bug);
+    }
+
+    /// Reclassify the last overflow of the frame at `depth` when the frame
+    /// reverts with `Panic(0x11)`, i.e. the overflow was caught by the
+    /// compiler inserted check
+    fn check_overflow_reverted(&mut self, output: &[u8], depth: u64) {
+        let Some((overflow_depth, step_index, pc, address_index)) = self.last_overflow else {
+            return;
+        };
+        if overflow_depth != depth
+            || self.step_index > step_index + CHECKED_OVERFLOW_MAX_STEPS
+            || panic_code(output) != Some(U256::from(0x11))
+        {
+            return;
+        }
+        self.last_overflow = None;
+        let id = Bug::stable_id(&BugType::CheckedOverflowReverted, self.code_hash(), pc);
+        let Some(bug) = Arc::make_mut(&mut self.bug_data)
+            .iter_mut()
+            .rev()
+            .find(|b| {
+                b.bug_type == BugType::IntegerOverflow
+                    && b.position == pc
+                    && b.address_index == address_index
+            })
+        else {
+            return;
+        };
+        if bug.count == 1 {
+            bug.bug_type = BugType::CheckedOverflowReverted;
+            bug.id = id;
             return;
         }
+        // Deduplicated entry, only the last occurrence is reclassified
+        bug.count -= 1;
+        let mut reverted = bug.clone();
+        reverted.bug_type = BugType::CheckedOverflowReverted;
+        reverted.count = 1;
+        reverted.id = id;
+        self.add_bug(reverted);
+    }
 
-        let _ = interp;
-        let _ = context;
-        let opcode = interp.current_opcode();
-        let opcode = OpCode::new(opcode);
-        self.opcode = opcode;
-        self.pc = interp.program_counter();
+    /// Report `slot` of `address` if it is accessed by both the proxy and
+    /// the implementation code, see `InstrumentConfig::storage_collision`
+    fn check_storage_collision(
+        &mut self,
+        address: Address,
+        slot: U256,
+        opcode: u8,
+        address_index: isize,
+    ) {
+        let contracts = self.instrument_config.storage_collision;
+        if contracts.is_none() {
+            return;
+        }
+        if self.storage_collision.contracts != contracts {
+            self.storage_collision = StorageCollisionTracker {
+                contracts,
+                ..Default::default()
+            };
+        }
+        // The code running is the callee of the current call, which differs
+        // from the storage address for DELEGATECALL
+        let code_address = self.call_stack.last().map_or(address, |frame| frame.callee);
+        if self.storage_collision.access(address, code_address, slot) {
+            let bug = Bug::new(
+                BugType::StorageCollision(slot),
+                opcode,
+                self.pc,
+                address_index,
+            );
+            self.add_bug(bug);
+        }
+    }
 
-        if let Some(OpCode::EQ) = opcode {
-            self.last_index_eq = self.step_index;
+    /// Resolve the pending `ORIGIN` of the frame at `depth` when the value is
+    /// compared: against `CALLER` it only rejects contract callers, against
+    /// any other value it is used for authorization
+    fn check_tx_origin_use(&mut self, compared_with_caller: bool, depth: u64) {
+        // Pending bugs of returned frames are dropped by `call_end`, the
+        // remaining ones of this frame are the last
+        let Some(first) = self.pending_tx_origin.iter().position(|(d, _)| *d == depth) else {
+            return;
+        };
+        let bugs = self.pending_tx_origin.split_off(first);
+        if !compared_with_caller {
+            for (_, bug) in bugs {
+                self.add_bug(bug);
+            }
         }
+    }
 
-        if let Some(OpCode::SUB) = opcode {
-            self.last_index_sub = self.step_index;
+    /// Report the opcodes modifying the state in a static frame, which
+    /// halt before `step_end`
+    fn check_static_state_modification(&mut self, interp: &Interpreter, op: OpCode) {
+        let modifies_state = match op {
+            OpCode::SSTORE
+            | OpCode::TSTORE
+            | OpCode::LOG0
+            | OpCode::LOG1
+            | OpCode::LOG2
+            | OpCode::LOG3
+            | OpCode::LOG4
+            | OpCode::CREATE
+            | OpCode::CREATE2
+            | OpCode::SELFDESTRUCT => true,
+            OpCode::CALL => interp.stack().peek(2).is_ok_and(|value| !value.is_zero()),
+            _ => false,
+        };
+        if modifies_state {
+            let address_index = self.record_seen_address(interp.contract().target_address);
+            let bug = Bug::new(
+                BugType::StaticStateModification,
+                op.get(),
+                self.pc,
+                address_index,
+            );
+            self.add_bug(bug);
         }
+    }
 
-        self.inputs.clear();
-        if let Some(
-            op @ (OpCode::JUMPI
-            | OpCode::CALL
+    /// Add the gas spent by the opcode which just executed to
+    /// `heuristics.gas_breakdown` and `heuristics.gas_by_address`
+    fn record_gas(&mut self, interp: &Interpreter, op: OpCode, address: Address) {
+        let (gas_before, memory_before, cold) = self.gas_before;
+        let mut cost = gas_before.saturating_sub(interp.gas.remaining());
+        // The gas forwarded to a new frame is spent by its opcodes
+        match &interp.next_action {
+            InterpreterAction::Call { inputs } => {
+                let stipend = if inputs.transfers_value() {
+                    gas::CALL_STIPEND
+                } else {
+                    0
+                };
+                cost = cost.saturating_sub(inputs.gas_limit.saturating_sub(stipend));
+            }
+            InterpreterAction::Create { inputs } => {
+                cost = cost.saturating_sub(inputs.gas_limit);
+            }
+            _ => {}
+        }
+        let memory = gas::memory_gas_for_len(interp.shared_memory.len())
+            .saturating_sub(gas::memory_gas_for_len(memory_before))
+            .min(cost);
+        let cost = cost - memory;
+
+        let heuristics = Arc::make_mut(&mut self.heuristics);
+        if self.instrument_config.record_gas_by_address {
+            *heuristics.gas_by_address.entry(address).or_default() += cost + memory;
+        }
+        if !self.instrument_config.record_gas_breakdown {
+            return;
+        }
+        let breakdown = &mut heuristics.gas_breakdown;
+        breakdown.memory += memory;
+        let category = match op {
+            OpCode::SLOAD if cold => &mut breakdown.sload_cold,
+            OpCode::SLOAD => &mut breakdown.sload_warm,
+            OpCode::SSTORE if cold => &mut breakdown.sstore_cold,
+            OpCode::SSTORE => &mut breakdown.sstore_warm,
+            OpCode::CALL
             | OpCode::CALLCODE
             | OpCode::DELEGATECALL
             | OpCode::STATICCALL
-            | OpCode::SSTORE
-            | OpCode::SLOAD
-            | OpCode::ADD
-            | OpCode::SUB
-            | OpCode::MUL
-            | OpCode::DIV
-            | OpCode::SDIV
-            | OpCode::MOD
-            | OpCode::SMOD
-            | OpCode::EXP
-            | OpCode::LT
-            | OpCode::SLT
-            | OpCode::GT
-            | OpCode::SGT
-            | OpCode::EQ
-            | OpCode::AND
-            | OpCode::ADDMOD
-            | OpCode::MULMOD
-            | OpCode::KECCAK256),
-        ) = opcode
-        {
-            let num_inputs = op.inputs();
-            for i in 0..num_inputs {
-                if let Ok(v) = interp.stack().peek(i as usize) {
-                    self.inputs.push(v);
-                } else {
-                    break;
-                }
+            | OpCode::CREATE
+            | OpCode::CREATE2 => &mut breakdown.calls,
+            _ => &mut breakdown.execution,
+        };
+        *category += cost;
+    }
+
+    /// Start the deployments of the frame entered by the caller at `depth`
+    fn enter_frame_deployments(&mut self, depth: u64) {
+        self.frame_deployments.truncate(depth as usize);
+        self.frame_deployments.push(self.deployments.len());
+    }
+
+    /// Mark the deployments of the frame which just completed as reverted,
+    /// with the ones of its subframes, if it failed
+    fn exit_frame_deployments(&mut self, depth: u64, success: bool) {
+        let Some(start) = self.frame_deployments.get(depth as usize).copied() else {
+            return;
+        };
+        self.frame_deployments.truncate(depth as usize);
+        if !success {
+            for deployment in self.deployments.iter_mut().skip(start) {
+                deployment.reverted = true;
             }
         }
+    }
 
-        self.inc_step_index();
+    fn resolve_subcalls_out_of_gas(&mut self, depth: u64, success: bool) {
+        let Some(first) = self
+            .subcalls_out_of_gas
+            .iter()
+            .position(|(d, _)| *d > depth)
+        else {
+            return;
+        };
+        let bugs = self.subcalls_out_of_gas.split_off(first);
+        if success {
+            for (_, bug) in bugs {
+                self.add_bug(bug);
+            }
+        }
     }
 
-    #[inline]
-    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
-        if !self.enabled() {
+    /// Coverage of the selector the current frame was entered with
+    fn selector_coverage_mut(&mut self, address: Address) -> Option<&mut SelectorCoverage> {
+        let selector = self.call_stack.last()?.selector?;
+        Some(
+            Arc::make_mut(&mut self.heuristics)
+                .selector_coverage
+                .entry(address)
+                .or_default()
+                .entry(u32::from_be_bytes(selector))
+                .or_default(),
+        )
+    }
+
+    /// Set or remove the hook called on the selected opcodes
+    pub fn set_opcode_hooks(&mut self, hooks: Option<OpcodeHooks>) {
+        self.opcode_hooks = hooks;
+        self.opcode_hook_error = None;
+    }
+
+    /// Error returned by the opcode hook since the last call
+    pub fn take_opcode_hook_error(&mut self) -> Option<eyre::Report> {
+        self.opcode_hook_error.take()
+    }
+
+    fn call_opcode_hooks(&mut self, interp: &Interpreter) {
+        let Some(hooks) = self.opcode_hooks.as_mut() else {
+            return;
+        };
+        let opcode = interp.current_opcode();
+        if !hooks.is_selected(opcode) || self.opcode_hook_error.is_some() {
             return;
         }
+        let num_inputs = OpCode::new(opcode).map_or(0, |op| op.inputs() as usize);
+        let mut stack = OpcodeInputs::<U256>::default();
+        for v in (0..num_inputs).map_while(|i| interp.stack().peek(i).ok()) {
+            stack.push(v);
+        }
         let address = interp.contract().target_address;
-        let address_index = self.record_seen_address(address);
-        let opcode = self.opcode;
-        let pc = self.pc;
-
-        if self.instrument_config.pcs_by_address {
-            self.record_pc(address, pc);
+        let pc = interp.program_counter();
+        if let Err(e) = hooks.call(address, pc, opcode, interp.gas().remaining(), &stack) {
+            self.opcode_hook_error = Some(e);
         }
+    }
 
-        match opcode {
-            Some(op @ OpCode::ADD) => {
-                if let Ok(r) = interp.stack().peek(0) {
-                    if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
-                        if r < *a || r < *b {
-                            let bug =
-                                Bug::new(BugType::IntegerOverflow, op.get(), pc, address_index);
-                            self.add_bug(bug);
-                        }
-                    }
+    /// Record a bug, the calldata taint of the current operands is added
+    /// unless already set
+    pub fn add_bug(&mut self, mut bug: Bug) {
+        if bug.id == B256::ZERO {
+            bug.id = self.bug_id(&bug);
+        }
+        let address = usize::try_from(bug.address_index)
+            .ok()
+            .and_then(|i| self.heuristics.seen_addresses.get(i));
+        if address.is_some_and(|address| self.is_excluded(*address)) {
+            return;
+        }
+        if bug.calldata_taint.is_none() {
+            bug.calldata_taint = self.inputs_calldata;
+        }
+        if bug.call_stack.is_empty() {
+            bug.call_stack = self
+                .call_stack_snapshot
+                .get_or_insert_with(|| self.call_stack.as_slice().into())
+                .clone();
+        }
+        if let BugType::Jumpi(dest) = bug.bug_type {
+            if self.instrument_config.heuristics {
+                // March 15 bug patch: keep last 256 elements
+                Arc::make_mut(&mut self.heuristics).coverage.push_back(dest);
+                if self.heuristics.coverage.len() > 256 {
+                    Arc::make_mut(&mut self.heuristics).coverage.pop_front();
                 }
             }
-            Some(op @ OpCode::MUL) => {
-                if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
-                    if mul_overflow(*a, *b) {
-                        let bug = Bug::new(BugType::IntegerOverflow, op.get(), pc, address_index);
-                        self.add_bug(bug);
-                    }
-                }
+            return;
+        }
+
+        if self.instrument_config.dedup_bugs {
+            if let Some(seen) = Arc::make_mut(&mut self.bug_data)
+                .iter_mut()
+                .rev()
+                .find(|b| {
+                    b.position == bug.position
+                        && b.address_index == bug.address_index
+                        && b.bug_type == bug.bug_type
+                })
+            {
+                seen.count += 1;
+                return;
             }
-            Some(op @ OpCode::SUB) => {
-                if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
-                    if a < b {
-                        let bug =
-                            Bug::new(BugType::IntegerSubUnderflow, op.get(), pc, address_index);
-                        self.add_bug(bug);
-                    }
-                }
+        }
+
+        if matches!(bug.bug_type, BugType::Sload(_) | BugType::Sstore(..)) {
+            // Storage accesses are frequent, evict the oldest entries instead
+            // of growing the buffer
+            let capacity = self.instrument_config.bug_buffer_size.max(1);
+            while self.bug_data.len() >= capacity {
+                Arc::make_mut(&mut self.bug_data).pop_front();
             }
-            Some(op @ (OpCode::SMOD | OpCode::MOD)) => {
-                if let Some(b) = self.inputs.get(1) {
-                    if *b == U256::ZERO {
-                        let bug = Bug::new(BugType::IntegerModByZero, op.get(), pc, address_index);
-                        self.add_bug(bug);
-                    }
+        }
+        Arc::make_mut(&mut self.bug_data).push_back(bug);
+    }
+}
+
+/// Handlers of `step_end`, see `StepHandlers`
+impl BugInspector {
+    /// Overflow of ADD
+    fn on_add(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp,
+            op,
+            address_index,
+            pc,
+            depth,
+            ..
+        } = step;
+        if let Ok(r) = interp.stack().peek(0) {
+            if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
+                if r < *a || r < *b {
+                    self.add_overflow_bug(op.get(), pc, address_index, depth);
                 }
             }
-            Some(op @ (OpCode::DIV | OpCode::SDIV)) => {
-                if let Some(b) = self.inputs.get(1) {
-                    if *b == U256::ZERO {
-                        let bug = Bug::new(BugType::IntegerDivByZero, op.get(), pc, address_index);
-                        self.add_bug(bug);
-                    }
-                }
+        }
+    }
+
+    /// Overflow of MUL
+    fn on_mul(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address_index,
+            pc,
+            depth,
+            ..
+        } = step;
+        if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
+            if mul_overflow(*a, *b) {
+                self.add_overflow_bug(op.get(), pc, address_index, depth);
             }
-            Some(op @ (OpCode::ADDMOD | OpCode::MULMOD)) => {
-                if let Some(n) = self.inputs.get(2) {
-                    if n == &U256::ZERO {
-                        let bug = Bug::new(BugType::IntegerModByZero, op.get(), pc, address_index);
-                        self.add_bug(bug);
-                    }
-                }
+        }
+    }
+
+    /// Underflow of SUB
+    fn on_sub(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address_index,
+            pc,
+            ..
+        } = step;
+        if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
+            if a < b {
+                let bug = Bug::new(BugType::IntegerSubUnderflow, op.get(), pc, address_index);
+                self.add_bug(bug);
             }
-            Some(op @ OpCode::EXP) => {
-                // todo_cl check for overflow
-                if let (Some(a), Some(b), Ok(r)) = (
-                    self.inputs.first(),
-                    self.inputs.get(1),
-                    interp.stack().peek(0),
-                ) {
-                    if exp_overflow(*a, *b, r) {
-                        let bug = Bug::new(BugType::IntegerOverflow, op.get(), pc, address_index);
-                        self.add_bug(bug);
-                    }
-                }
+        }
+    }
+
+    /// Modulo by zero of MOD and SMOD
+    fn on_mod(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address_index,
+            pc,
+            ..
+        } = step;
+        if let Some(b) = self.inputs.get(1) {
+            if *b == U256::ZERO {
+                let bug = Bug::new(BugType::IntegerModByZero, op.get(), pc, address_index);
+                self.add_bug(bug);
             }
-            Some(OpCode::LT) => {
-                if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
-                    let distance = if a >= b {
-                        a.overflowing_sub(*b).0.saturating_add(U256::from(1))
-                    } else {
-                        b.overflowing_sub(*a).0
-                    };
-                    self.heuristics.distance = distance;
-                }
+        }
+    }
+
+    /// Division by zero of DIV and SDIV
+    fn on_div(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address_index,
+            pc,
+            ..
+        } = step;
+        if let Some(b) = self.inputs.get(1) {
+            if *b == U256::ZERO {
+                let bug = Bug::new(BugType::IntegerDivByZero, op.get(), pc, address_index);
+                self.add_bug(bug);
             }
-            Some(OpCode::GT) => {
-                if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
-                    let distance = if a >= b {
-                        a.overflowing_sub(*b).0
-                    } else {
-                        b.overflowing_sub(*a).0.saturating_add(U256::from(1))
-                    };
-                    self.heuristics.distance = distance;
-                }
+        }
+    }
+
+    /// Modulo by zero of ADDMOD and MULMOD
+    fn on_addmod(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address_index,
+            pc,
+            ..
+        } = step;
+        if let Some(n) = self.inputs.get(2) {
+            if n == &U256::ZERO {
+                let bug = Bug::new(BugType::IntegerModByZero, op.get(), pc, address_index);
+                self.add_bug(bug);
             }
-            Some(OpCode::SLT) => {
-                if let (Some(a), Some(b), Ok(r)) = (
-                    self.inputs.first(),
-                    self.inputs.get(1),
-                    interp.stack().peek(0),
-                ) {
-                    let mut distance = if a >= b {
-                        a.overflowing_sub(*b).0
-                    } else {
-                        b.overflowing_sub(*a).0
-                    };
-                    if r == U256::ZERO {
-                        distance = distance.saturating_add(U256::from(1));
-                    }
-                    self.heuristics.distance = distance;
-                }
+        }
+    }
+
+    /// Overflow of EXP
+    fn on_exp(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp,
+            op,
+            address_index,
+            pc,
+            depth,
+            ..
+        } = step;
+        if let (Some(a), Some(b), Ok(r)) = (
+            self.inputs.first(),
+            self.inputs.get(1),
+            interp.stack().peek(0),
+        ) {
+            if exp_overflow(*a, *b, r) {
+                self.add_overflow_bug(op.get(), pc, address_index, depth);
             }
-            Some(OpCode::SGT) => {
-                if let (Some(a), Some(b), Ok(r)) = (
-                    self.inputs.first(),
-                    self.inputs.get(1),
-                    interp.stack().peek(0),
-                ) {
-                    let (mut distance, _) = i256_diff(a, b);
-                    if r == U256::ZERO {
-                        distance = distance.saturating_add(U256::from(1));
-                    }
-                    self.heuristics.distance = distance;
-                }
+        }
+    }
+
+    /// Branch distance of LT
+    fn on_lt(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp, pc, depth, ..
+        } = step;
+        if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
+            let distance = if a >= b {
+                a.overflowing_sub(*b).0.saturating_add(U256::from(1))
+            } else {
+                b.overflowing_sub(*a).0
+            };
+            self.record_cmp_distance(depth, pc, interp.stack().len() - 1, distance);
+        }
+    }
+
+    /// Branch distance of GT
+    fn on_gt(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp, pc, depth, ..
+        } = step;
+        if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
+            let distance = if a >= b {
+                a.overflowing_sub(*b).0
+            } else {
+                b.overflowing_sub(*a).0.saturating_add(U256::from(1))
+            };
+            self.record_cmp_distance(depth, pc, interp.stack().len() - 1, distance);
+        }
+    }
+
+    /// Branch distance of SLT
+    fn on_slt(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp, pc, depth, ..
+        } = step;
+        if let (Some(a), Some(b), Ok(r)) = (
+            self.inputs.first(),
+            self.inputs.get(1),
+            interp.stack().peek(0),
+        ) {
+            let mut distance = if a >= b {
+                a.overflowing_sub(*b).0
+            } else {
+                b.overflowing_sub(*a).0
+            };
+            if r == U256::ZERO {
+                distance = distance.saturating_add(U256::from(1));
             }
-            Some(OpCode::EQ) => {
-                if let (Some(a), Some(b), Ok(r)) = (
-                    self.inputs.first(),
-                    self.inputs.get(1),
-                    interp.stack().peek(0),
-                ) {
-                    let mut distance = if a > b {
-                        a.overflowing_sub(*b).0
-                    } else {
-                        b.overflowing_sub(*a).0
-                    };
-                    if r != U256::ZERO {
-                        distance = U256::from(1);
-                    }
-                    self.heuristics.distance = distance;
-                }
+            self.record_cmp_distance(depth, pc, interp.stack().len() - 1, distance);
+        }
+    }
+
+    /// Branch distance of SGT
+    fn on_sgt(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp, pc, depth, ..
+        } = step;
+        if let (Some(a), Some(b), Ok(r)) = (
+            self.inputs.first(),
+            self.inputs.get(1),
+            interp.stack().peek(0),
+        ) {
+            let (mut distance, _) = i256_diff(a, b);
+            if r == U256::ZERO {
+                distance = distance.saturating_add(U256::from(1));
             }
-            Some(op @ OpCode::AND) => {
-                if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
-                    // check if there is an possible truncation
-
-                    // For AND operator, if either side of the operands equals
-                    // u8, u16, ..., and the other side is larger than this
-                    // operand, generate possible integer truncation signal
-                    let mut i = 1;
-                    let possible_overflow = loop {
-                        if i == 32 {
-                            break false;
-                        }
+            self.record_cmp_distance(depth, pc, interp.stack().len() - 1, distance);
+        }
+    }
 
-                        let r = U256::MAX >> (i * 8);
+    /// Branch distance of EQ, and the comparisons of `CALLER` and `ORIGIN`
+    fn on_eq(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp, pc, depth, ..
+        } = step;
+        if let (Some(a), Some(b), Ok(r)) = (
+            self.inputs.first(),
+            self.inputs.get(1),
+            interp.stack().peek(0),
+        ) {
+            let mut distance = if a > b {
+                a.overflowing_sub(*b).0
+            } else {
+                b.overflowing_sub(*a).0
+            };
+            if r != U256::ZERO {
+                distance = U256::from(1);
+            } else if let (Some(a), Some(b)) =
+                (self.keccak_preimages.get(a), self.keccak_preimages.get(b))
+            {
+                // Comparison of hashes, e.g. of strings or byte arrays:
+                // the distance of the hashes does not guide the inputs
+                distance = U256::from(preimage_distance(a, b));
+            }
 
-                        if r == *a && b > a {
-                            break true;
-                        }
+            let caller = U256::from_be_slice(interp.contract().caller.as_slice());
+            if (*a == caller || *b == caller) && !self.caller_checked_depths.contains(&depth) {
+                self.caller_checked_depths.push(depth);
+            }
+            self.record_cmp_distance(depth, pc, interp.stack().len() - 1, distance);
+        }
+        if let (Some(a), Some(b)) = (self.input_taints.first(), self.input_taints.get(1)) {
+            if a.origin || b.origin {
+                self.check_tx_origin_use(a.origin && b.caller || a.caller && b.origin, depth);
+            }
+        }
+    }
 
-                        if r == *b && a > b {
-                            break true;
-                        }
-                        i += 1;
-                    };
-                    if possible_overflow {
-                        let bug = Bug::new(
-                            BugType::PossibleIntegerTruncation,
-                            op.get(),
-                            pc,
-                            address_index,
-                        );
-                        self.add_bug(bug);
-                    }
+    /// Possible truncation by a mask
+    fn on_and(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address_index,
+            pc,
+            ..
+        } = step;
+        if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
+            // check if there is an possible truncation
+
+            // For AND operator, if either side of the operands equals
+            // u8, u16, ..., and the other side is larger than this
+            // operand, generate possible integer truncation signal
+            let mut i = 1;
+            let possible_overflow = loop {
+                if i == 32 {
+                    break false;
                 }
-            }
-            Some(op @ OpCode::SSTORE) => {
-                if let (Some(key), Some(value)) = (self.inputs.first(), self.inputs.get(1)) {
-                    let bug = Bug::new(
-                        BugType::Sstore(*key, *value),
-                        op.get(),
-                        self.pc,
-                        address_index,
-                    );
-                    self.add_bug(bug);
+
+                let r = U256::MAX >> (i * 8);
+
+                if r == *a && b > a {
+                    break true;
                 }
-            }
-            Some(op @ OpCode::SLOAD) => {
-                if let Some(key) = self.inputs.first() {
-                    let bug = Bug::new(BugType::Sload(*key), op.get(), self.pc, address_index);
-                    self.add_bug(bug);
+
+                if r == *b && a > b {
+                    break true;
                 }
+                i += 1;
+            };
+            if possible_overflow {
+                let bug = Bug::new(
+                    BugType::PossibleIntegerTruncation,
+                    op.get(),
+                    pc,
+                    address_index,
+                );
+                self.add_bug(bug);
             }
-            Some(op @ OpCode::ORIGIN) => {
+        }
+    }
+
+    /// Storage write
+    fn on_sstore(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address,
+            address_index,
+            ..
+        } = step;
+        if let (Some(key), Some(value)) = (self.inputs.first(), self.inputs.get(1)) {
+            let (key, value) = (*key, *value);
+            if self.instrument_config.detect_storage {
                 let bug = Bug::new(
-                    BugType::TxOriginDependency,
+                    BugType::Sstore(key, value),
                     op.get(),
                     self.pc,
                     address_index,
                 );
                 self.add_bug(bug);
             }
+            if self.instrument_config.record_storage_footprint {
+                Arc::make_mut(&mut self.heuristics)
+                    .storage_footprint
+                    .record_write(address, key, value);
+            }
+            self.check_storage_collision(address, key, op.get(), address_index);
+        }
+    }
 
-            Some(
-                op @ (OpCode::CALL | OpCode::CALLCODE | OpCode::DELEGATECALL | OpCode::STATICCALL),
-            ) => {
-                let in_len = {
-                    if matches!(op, OpCode::CALL | OpCode::CALLCODE) {
-                        self.inputs.get(4)
-                    } else {
-                        self.inputs.get(3)
-                    }
-                };
-                let address = self.inputs.get(1);
+    /// Storage read
+    fn on_sload(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp,
+            op,
+            address,
+            address_index,
+            ..
+        } = step;
+        if let Some(key) = self.inputs.first() {
+            let key = *key;
+            if self.instrument_config.detect_storage {
+                let bug = Bug::new(BugType::Sload(key), op.get(), self.pc, address_index);
+                self.add_bug(bug);
+            }
+            if self.instrument_config.record_storage_footprint {
+                if let Ok(value) = interp.stack().peek(0) {
+                    Arc::make_mut(&mut self.heuristics)
+                        .storage_footprint
+                        .record_read(address, key, value);
+                }
+            }
+            self.check_storage_collision(address, key, op.get(), address_index);
+        }
+    }
+
+    /// Use of `ORIGIN`, reported once compared with anything but `CALLER`
+    fn on_origin(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address_index,
+            depth,
+            ..
+        } = step;
+        let bug = Bug::new(
+            BugType::TxOriginDependency,
+            op.get(),
+            self.pc,
+            address_index,
+        );
+        if self.instrument_config.report_all_tx_origin {
+            self.add_bug(bug);
+        } else {
+            self.pending_tx_origin.push((depth, bug));
+        }
+    }
+
+    /// External call, its status is tracked until it is checked
+    fn on_call(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp,
+            op,
+            address_index,
+            pc,
+            depth,
+            ..
+        } = step;
+        let in_len = {
+            if matches!(op, OpCode::CALL | OpCode::CALLCODE) {
+                self.inputs.get(4)
+            } else {
+                self.inputs.get(3)
+            }
+        };
+        let callee = self.inputs.get(1);
+
+        if let (Some(in_len), Some(callee)) = (in_len, callee) {
+            let callee_bytes: [u8; 32] = callee.to_be_bytes();
+            let callee = H160::from_slice(&callee_bytes[12..]);
+            let in_len = usize::try_from(in_len).unwrap();
+            let bug = Bug::new(
+                BugType::Call(in_len, callee),
+                op.get(),
+                self.pc,
+                address_index,
+            );
+            self.add_bug(bug);
+
+            if op == OpCode::DELEGATECALL
+                && self.input_taints.get(1).is_some_and(|t| t.is_tainted())
+            {
+                let bug = Bug::new(
+                    BugType::DelegatecallToUntrusted(callee),
+                    op.get(),
+                    self.pc,
+                    address_index,
+                );
+                self.add_bug(bug);
+            }
 
-                if let (Some(in_len), Some(callee)) = (in_len, address) {
-                    let callee_bytes: [u8; 32] = callee.to_be_bytes();
-                    let callee = H160::from_slice(&callee_bytes[12..]);
-                    let in_len = usize::try_from(in_len).unwrap();
+            if let (
+                OpCode::CALL,
+                Some(value),
+                Some(Taint {
+                    calldata: Some(_), ..
+                }),
+            ) = (op, self.inputs.get(2), self.input_taints.get(1))
+            {
+                if *value != U256::ZERO {
                     let bug = Bug::new(
-                        BugType::Call(in_len, callee),
+                        BugType::ArbitrarySend(*value, callee),
                         op.get(),
                         self.pc,
                         address_index,
@@ -423,127 +1265,500 @@ where
                     self.add_bug(bug);
                 }
             }
-            Some(op @ OpCode::JUMPI) => {
-                // Check for missed branches
-                let target_address = self.instrument_config.target_address;
-                macro_rules! update_heuritics {
-                    // (prev_pc, dest_pc_if_cond_is_true, cond)
-                    ($prev_pc: ident, $dest_pc: expr, $cond: expr) => {
-                        if !self.instrument_config.record_branch_for_target_only
-                            || address == target_address
-                        {
-                            let heuristics = &mut self.heuristics;
-                            heuristics.record_missed_branch(
-                                $prev_pc,
-                                $dest_pc,
-                                $cond,
-                                address_index,
-                            );
-                            let target = if $cond { $dest_pc } else { $prev_pc + 1 };
-                            let bug =
-                                Bug::new(BugType::Jumpi(target), op.get(), $prev_pc, address_index);
-                            self.add_bug(bug);
+        }
+
+        // The status is pushed once the call returns, track it from there
+        if op != OpCode::STATICCALL && interp.instruction_result == InstructionResult::CallOrCreate
+        {
+            self.call_statuses.push(CallStatus {
+                depth,
+                pc,
+                opcode: op.get(),
+                address_index,
+                slots: vec![interp.stack().len()],
+            });
+        }
+    }
+
+    /// Missed branch and coverage of a JUMPI
+    fn on_jumpi(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp,
+            op,
+            address,
+            address_index,
+            pc,
+            depth,
+            ..
+        } = step;
+        // Check for missed branches
+        let target_address = self.instrument_config.target_address;
+        macro_rules! update_heuritics {
+            // (prev_pc, dest_pc_if_cond_is_true, cond, distance)
+            ($prev_pc: ident, $dest_pc: expr, $cond: expr, $distance: expr) => {
+                if !self.instrument_config.record_branch_for_target_only
+                    || address == target_address
+                {
+                    let heuristics = Arc::make_mut(&mut self.heuristics);
+                    let num_missed = heuristics.missed_branches.len();
+                    heuristics.record_missed_branch(
+                        $prev_pc,
+                        $dest_pc,
+                        $cond,
+                        $distance,
+                        address_index,
+                    );
+                    if self.instrument_config.coverage_by_selector
+                        && self.heuristics.missed_branches.len() > num_missed
+                    {
+                        let missed = self.heuristics.missed_branches[num_missed];
+                        if let Some(coverage) = self.selector_coverage_mut(address) {
+                            coverage.missed_branches.push(missed);
                         }
-                    };
+                    }
+                    let target = if $cond { $dest_pc } else { $prev_pc + 1 };
+                    let bug = Bug::new(BugType::Jumpi(target), op.get(), $prev_pc, address_index);
+                    self.add_bug(bug);
                 }
+            };
+        }
 
-                // NOTE: invalid jumps are ignored
-                if let (Some(counter), Some(cond)) = (self.inputs.first(), self.inputs.get(1)) {
-                    // Check for distance in peephole optimized if-statement
-                    if self.possibly_if_equal() {
-                        let max = U256::MAX;
-                        let mut half = U256::MAX;
-                        half.set_bit(31, false);
-                        let h = &mut self.heuristics;
-                        h.distance = {
-                            // smallest distance from the `value` to U256::MAX and 0
-                            if *cond > half {
-                                max - cond + U256::from(1)
-                            } else {
-                                *cond
-                            }
-                        };
+        // NOTE: invalid jumps are ignored
+        if let (Some(counter), Some(cond)) = (self.inputs.first(), self.inputs.get(1)) {
+            // Check for distance in peephole optimized if-statement
+            let distance = if self.possibly_if_equal() {
+                let max = U256::MAX;
+                let mut half = U256::MAX;
+                half.set_bit(31, false);
+                let h = Arc::make_mut(&mut self.heuristics);
+                h.distance = {
+                    // smallest distance from the `value` to U256::MAX and 0
+                    if *cond > half {
+                        max - cond + U256::from(1)
+                    } else {
+                        *cond
                     }
+                };
+                h.distance
+            } else {
+                // The condition was below the destination, both were popped
+                self.branch_distance(depth, interp.stack().len())
+            };
 
-                    let dest = usize::try_from(counter).unwrap();
-                    let cond = *cond != U256::ZERO;
-                    update_heuritics!(pc, dest, cond);
-                }
+            let dest = usize::try_from(counter).unwrap();
+            let cond = *cond != U256::ZERO;
+            update_heuritics!(pc, dest, cond, distance);
+        }
+    }
+
+    /// REVERT or INVALID, classified by the panic code
+    fn on_revert(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp,
+            op,
+            address_index,
+            pc,
+            depth,
+            ..
+        } = step;
+        let mut bug_type = BugType::RevertOrInvalid;
+        if let (OpCode::REVERT, InterpreterAction::Return { result }) = (op, &interp.next_action) {
+            self.check_overflow_reverted(&result.output, depth);
+            if let Some(code) = panic_code(&result.output) {
+                bug_type = BugType::from_panic_code(code);
             }
-            Some(op @ OpCode::BLOBHASH) => {
-                let bug = Bug::new(BugType::BlockValueDependency, op.get(), pc, address_index);
-                self.add_bug(bug);
+        }
+        let bug = Bug::new(bug_type, op.get(), pc, address_index);
+        self.add_bug(bug);
+    }
+
+    /// SELFDESTRUCT, unprotected unless the caller was checked
+    fn on_selfdestruct(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            op,
+            address_index,
+            pc,
+            depth,
+            ..
+        } = step;
+        let bug_type = match self.inputs.first() {
+            Some(beneficiary) if !self.caller_checked_depths.contains(&depth) => {
+                let beneficiary: [u8; 32] = beneficiary.to_be_bytes();
+                BugType::UnprotectedSelfdestruct(H160::from_slice(&beneficiary[12..]))
             }
-            Some(op @ OpCode::COINBASE) => {
-                let bug = Bug::new(BugType::BlockValueDependency, op.get(), pc, address_index);
-                self.add_bug(bug);
+            _ => BugType::Unclassified,
+        };
+        let bug = Bug::new(bug_type, op.get(), pc, address_index);
+        self.add_bug(bug);
+    }
+
+    /// CREATE and CREATE2, the created address is recorded
+    fn on_create(&mut self, step: StepEnd<'_>) {
+        let StepEnd {
+            interp,
+            op,
+            address_index,
+            pc,
+            ..
+        } = step;
+        let bug = Bug::new(BugType::Unclassified, op.get(), pc, address_index);
+        self.add_bug(bug);
+        if let Ok(created_address) = interp.stack.peek(0) {
+            let bytes: [u8; 32] = created_address.to_be_bytes();
+            let created_address = Address::from_slice(&bytes[12..]);
+            self.record_seen_address(created_address);
+        }
+    }
+
+    /// Preimage of KECCAK256, for the SHA3 mappings
+    fn on_keccak256(&mut self, step: StepEnd<'_>) {
+        let StepEnd { interp, .. } = step;
+        if let (Some(offset), Some(size), Ok(output)) = (
+            self.inputs.first(),
+            self.inputs.get(1),
+            interp.stack().peek(0),
+        ) {
+            let offset = offset.as_limbs()[0] as usize;
+            let size = size.as_limbs()[0] as usize;
+            let input = &interp.shared_memory.context_memory()[offset..offset + size];
+            let max_len = self.instrument_config.sha3_preimage_max_len;
+            let preimage = &input[input.len().saturating_sub(max_len)..];
+            if self.keccak_preimages.len() < MAX_KECCAK_PREIMAGES {
+                self.keccak_preimages.insert(output, input.to_vec());
             }
-            Some(op @ OpCode::TIMESTAMP) => {
-                let bug = Bug::new(BugType::TimestampDependency, op.get(), pc, address_index);
-                self.add_bug(bug);
+            let output = H256::from_slice(&output.to_be_bytes::<32>());
+            let capacity = self.instrument_config.sha3_mapping_capacity;
+            Arc::make_mut(&mut self.heuristics).record_sha3_mapping(preimage, output, capacity);
+        }
+    }
+
+    /// Dependency on a value of the block
+    fn on_block_value(&mut self, step: StepEnd<'_>) {
+        let bug_type = match step.op {
+            OpCode::TIMESTAMP => BugType::TimestampDependency,
+            OpCode::NUMBER => BugType::BlockNumberDependency,
+            _ => BugType::BlockValueDependency,
+        };
+        let bug = Bug::new(bug_type, step.op.get(), step.pc, step.address_index);
+        self.add_bug(bug);
+    }
+}
+
+impl<DB> Inspector<DB> for BugInspector
+where
+    DB: Database,
+{
+    #[inline]
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        self.call_opcode_hooks(interp);
+        if !self.enabled() {
+            return;
+        }
+
+        let opcode = interp.current_opcode();
+        let opcode = OpCode::new(opcode);
+        self.opcode = opcode;
+        self.pc = interp.program_counter();
+
+        if let Some(OpCode::EQ) = opcode {
+            self.last_index_eq = self.step_index;
+        }
+
+        if let Some(OpCode::SUB) = opcode {
+            self.last_index_sub = self.step_index;
+        }
+
+        let depth = context.journaled_state.depth();
+        if self.is_excluded(interp.contract().target_address) {
+            // Keep tracking the taint, the frame may return tainted data
+            if let Some(op) = opcode.filter(|_| self.needs_taint) {
+                self.taint.step(interp, op, depth);
             }
-            Some(op @ OpCode::NUMBER) => {
-                let bug = Bug::new(BugType::BlockNumberDependency, op.get(), pc, address_index);
-                self.add_bug(bug);
+            return;
+        }
+        if let Some(op) = opcode {
+            if !self.call_statuses.is_empty() {
+                self.track_call_statuses(op, interp.stack().len(), depth);
             }
-            Some(op @ OpCode::DIFFICULTY) => {
-                let bug = Bug::new(BugType::BlockValueDependency, op.get(), pc, address_index);
-                self.add_bug(bug);
+            if interp.is_static {
+                self.check_static_state_modification(interp, op);
             }
-            Some(op @ (OpCode::REVERT | OpCode::INVALID)) => {
-                let bug = Bug::new(BugType::RevertOrInvalid, op.get(), pc, address_index);
-                self.add_bug(bug);
+            if self.instrument_config.record_gas_breakdown
+                || self.instrument_config.record_gas_by_address
+            {
+                let cold = matches!(op, OpCode::SLOAD | OpCode::SSTORE)
+                    && interp.stack().peek(0).is_ok_and(|slot| {
+                        context
+                            .journaled_state
+                            .state
+                            .get(&interp.contract().target_address)
+                            .and_then(|account| account.storage.get(&slot))
+                            .is_none_or(|slot| slot.is_cold)
+                    });
+                self.gas_before = (interp.gas.remaining(), interp.shared_memory.len(), cold);
             }
-            Some(op @ (OpCode::SELFDESTRUCT | OpCode::CREATE | OpCode::CREATE2)) => {
-                let bug = Bug::new(BugType::Unclassified, op.get(), pc, address_index);
-                self.add_bug(bug);
-                if matches!(op, OpCode::CREATE | OpCode::CREATE2) {
-                    if let Ok(created_address) = interp.stack.peek(0) {
-                        let bytes: [u8; 32] = created_address.to_be_bytes();
-                        let created_address = Address::from_slice(&bytes[12..]);
-                        self.record_seen_address(created_address);
+        }
+
+        // Only the inputs read by the handlers of the enabled detectors
+        if let Some(op) = opcode.filter(|op| self.step_handlers.reads_inputs(*op)) {
+            self.inputs.clear();
+            self.input_taints.clear();
+            let num_inputs = op.inputs();
+            for i in 0..num_inputs {
+                if let Ok(v) = interp.stack().peek(i as usize) {
+                    self.inputs.push(v);
+                    if self.needs_taint {
+                        self.input_taints.push(self.taint.peek(depth, i as usize));
                     }
+                } else {
+                    break;
                 }
             }
-            Some(OpCode::KECCAK256) => {
-                if self.instrument_config.record_sha3_mapping {
-                    if let (Some(offset), Some(size), Ok(output)) = (
-                        self.inputs.first(),
-                        self.inputs.get(1),
-                        interp.stack().peek(0),
-                    ) {
-                        let offset = offset.as_limbs()[0] as usize;
-                        let size = size.as_limbs()[0] as usize;
-                        let input = &interp.shared_memory.context_memory()[offset..offset + size];
-                        // get only last 32 bytes
-                        let last_32 = {
-                            if input.len() > 32 {
-                                &input[input.len() - 32..]
-                            } else {
-                                input
-                            }
-                        };
-                        let output = H256::from_slice(&output.to_be_bytes::<32>());
-                        self.heuristics.record_sha3_mapping(last_32, output);
-                    }
+            self.inputs_calldata = self
+                .input_taints
+                .iter()
+                .fold(Taint::default(), |acc, t| acc | *t)
+                .calldata;
+        } else {
+            self.inputs_calldata = None;
+            if !self.needs_taint {
+                // No handler reads the inputs and the taint is not tracked,
+                // e.g. when only the coverage is recorded
+                self.inc_step_index();
+                return;
+            }
+        }
+
+        if let Some(op) = opcode.filter(|_| self.needs_taint) {
+            self.taint.step(interp, op, depth);
+        }
+
+        self.inc_step_index();
+    }
+
+    #[inline]
+    fn step_end(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        if !self.enabled() {
+            return;
+        }
+        let address = interp.contract().target_address;
+        if self.is_excluded(address) {
+            return;
+        }
+        let address_index = self.record_seen_address(address);
+        let opcode = self.opcode;
+        let pc = self.pc;
+        let depth = context.journaled_state.depth();
+
+        if self.instrument_config.pcs_by_address {
+            self.record_pc(address, pc);
+            if matches!(opcode, Some(OpCode::JUMP | OpCode::JUMPI))
+                && interp.program_counter() != pc + 1
+            {
+                self.jumps_by_address
+                    .entry(address)
+                    .or_default()
+                    .insert((pc, interp.program_counter()));
+            }
+        }
+
+        if self.instrument_config.heuristics {
+            Arc::make_mut(&mut self.heuristics).record_pressure(
+                depth,
+                interp.stack().len(),
+                interp.shared_memory.len(),
+            );
+        }
+
+        if self.instrument_config.coverage_by_selector {
+            if let Some(coverage) = self.selector_coverage_mut(address) {
+                coverage.pcs.insert(pc);
+            }
+        }
+
+        if self.instrument_config.record_opcode_histogram {
+            if let Some(op) = opcode {
+                Arc::make_mut(&mut self.heuristics).record_opcode(address, op.get());
+            }
+        }
+
+        if self.instrument_config.record_gas_breakdown
+            || self.instrument_config.record_gas_by_address
+        {
+            if let Some(op) = opcode {
+                self.record_gas(interp, op, address);
+            }
+        }
+
+        if let Some(op @ (OpCode::EQ | OpCode::LT | OpCode::GT | OpCode::SLT | OpCode::SGT)) =
+            opcode
+        {
+            if self.instrument_config.record_cmp_operands
+                && (!self.instrument_config.record_branch_for_target_only
+                    || address == self.instrument_config.target_address)
+            {
+                if let (Some(a), Some(b)) = (self.inputs.first(), self.inputs.get(1)) {
+                    Arc::make_mut(&mut self.heuristics).record_cmp_operands(CmpOperands {
+                        pc,
+                        opcode: op.get(),
+                        a: *a,
+                        b: *b,
+                        address_index,
+                    });
                 }
             }
-            _ => (),
+        }
+
+        if let Some(op) = opcode {
+            if let Some(handler) = self.step_handlers.get(op) {
+                let step = StepEnd {
+                    interp,
+                    op,
+                    address,
+                    address_index,
+                    pc,
+                    depth,
+                };
+                handler(self, step);
+            }
         }
     }
 
+    #[inline]
+    fn initialize_interp(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        // Drop the state left by frames at this depth or deeper, which
+        // already returned, or by previous transactions
+        let depth = context.journaled_state.depth();
+        self.call_statuses.retain(|status| status.depth < depth);
+        self.caller_checked_depths.retain(|d| *d < depth);
+        self.pending_tx_origin.retain(|(d, _)| *d < depth);
+        self.cmp_distances.retain(|c| c.depth < depth);
+        self.code_hashes.retain(|(d, _)| *d < depth);
+        let code_hash = interp.contract.hash.unwrap_or_else(|| {
+            // Init code of CREATE and CREATE2
+            keccak256(interp.contract.bytecode.original_byte_slice())
+        });
+        self.code_hashes.push((depth, code_hash));
+        self.taint.enter_frame(depth);
+        // The config cannot change during a transaction
+        self.step_handlers.update(&self.instrument_config);
+        self.needs_taint = self.instrument_config.needs_taint();
+    }
+
+    #[inline]
+    fn call(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &mut CallInputs,
+    ) -> Option<CallOutcome> {
+        let (caller, callee) = match inputs.scheme {
+            CallScheme::DelegateCall | CallScheme::CallCode => {
+                (inputs.target_address, inputs.bytecode_address)
+            }
+            _ => (inputs.caller, inputs.target_address),
+        };
+        let selector = inputs.input.get(..4).map(|s| s.try_into().unwrap());
+        // Frames left by a previous transaction which did not complete
+        let depth = context.journaled_state.depth();
+        if depth == 0 {
+            self.subcalls_out_of_gas.clear();
+            self.keccak_preimages.clear();
+        }
+        self.call_stack.truncate(depth as usize);
+        self.call_sites.truncate(depth as usize);
+        self.call_stack.push(CallFrame {
+            caller,
+            callee,
+            selector,
+        });
+        self.call_sites
+            .push((self.pc, self.opcode.map_or(0, |op| op.get())));
+        self.call_stack_snapshot = None;
+        self.enter_frame_deployments(depth);
+        None
+    }
+
+    #[inline]
+    fn create(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        _inputs: &mut CreateInputs,
+    ) -> Option<CreateOutcome> {
+        self.enter_frame_deployments(context.journaled_state.depth());
+        None
+    }
+
+    #[inline]
+    fn call_end(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &CallInputs,
+        outcome: CallOutcome,
+    ) -> CallOutcome {
+        // Depth of the caller, the frame of this call was already exited
+        let depth = context.journaled_state.depth();
+        self.resolve_subcalls_out_of_gas(depth, outcome.result.result.is_ok());
+        self.exit_frame_deployments(depth, outcome.result.result.is_ok());
+        // `ORIGIN` never compared in the returned frame
+        self.pending_tx_origin.retain(|(d, _)| *d <= depth);
+        self.code_hashes.retain(|(d, _)| *d <= depth);
+
+        let frame = self.call_stack.pop();
+        let call_site = self.call_sites.pop();
+        if self.enabled() && depth > 0 && is_out_of_gas(outcome.result.result) {
+            if let (Some(frame), Some((pc, opcode))) = (frame, call_site) {
+                let address_index = self.record_seen_address(frame.caller);
+                let mut bug = Bug::new(
+                    BugType::SubcallOutOfGas(
+                        H160::from(inputs.bytecode_address.0 .0),
+                        inputs.gas_limit,
+                    ),
+                    opcode,
+                    pc,
+                    address_index,
+                );
+                bug.call_stack = self.call_stack.as_slice().into();
+                bug.id = self.bug_id(&bug);
+                self.subcalls_out_of_gas.push((depth, bug));
+            }
+        }
+        self.call_stack_snapshot = None;
+        outcome
+    }
+
     #[inline]
     fn create_end(
         &mut self,
         context: &mut EvmContext<DB>,
-        _inputs: &CreateInputs,
+        inputs: &CreateInputs,
         outcome: CreateOutcome,
     ) -> CreateOutcome {
+        let depth = context.journaled_state.depth();
+        self.resolve_subcalls_out_of_gas(depth, outcome.result.result.is_ok());
+        self.exit_frame_deployments(depth, outcome.result.result.is_ok());
+        self.pending_tx_origin.retain(|(d, _)| *d <= depth);
+        self.code_hashes.retain(|(d, _)| *d <= depth);
         if !self.enabled() {
             return outcome;
         }
 
         let CreateOutcome { result, address } = &outcome;
+        // Deployments by the CREATE and CREATE2 opcodes, not by the transaction
+        if depth > 0 {
+            let address = address.map(|a| *self.create_address_overrides.get(&a).unwrap_or(&a));
+            self.deployments.push(Deployment {
+                creator: inputs.caller,
+                salt: match inputs.scheme {
+                    CreateScheme::Create2 { salt } => Some(salt),
+                    CreateScheme::Create => None,
+                },
+                init_code_hash: keccak256(&inputs.init_code),
+                address,
+                code_hash: result.is_ok().then(|| keccak256(&result.output)),
+                reverted: false,
+            });
+            self.created_addresses.extend(address);
+        }
         if let Some(address) = address {
             if let Some(override_address) = self.create_address_overrides.get(address) {
                 debug!(
@@ -567,6 +1782,24 @@ where
     }
 }
 
+/// Number of differing bytes between two KECCAK256 inputs, bytes past the
+/// end of the shorter input count as differing
+fn preimage_distance(a: &[u8], b: &[u8]) -> usize {
+    let differing = a.iter().zip(b).filter(|(x, y)| x != y).count();
+    differing + a.len().abs_diff(b.len())
+}
+
+fn is_out_of_gas(result: InstructionResult) -> bool {
+    matches!(
+        result,
+        InstructionResult::OutOfGas
+            | InstructionResult::MemoryOOG
+            | InstructionResult::MemoryLimitOOG
+            | InstructionResult::PrecompileOOG
+            | InstructionResult::InvalidOperandOOG
+    )
+}
+
 fn mul_overflow(a: U256, b: U256) -> bool {
     let zero = U256::ZERO;
     if a == zero || b == zero {
@@ -0,0 +1,283 @@
+use hex_literal::hex;
+use revm::{
+    interpreter::{
+        CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, Gas, InstructionResult,
+        InterpreterResult,
+    },
+    primitives::{Address, Bytes, B256, U256},
+    Database, EvmContext, Inspector,
+};
+
+use crate::response::{decode_revert_reason, ERROR_SELECTOR};
+
+/// Address of the Foundry and hevm cheatcodes,
+/// `address(uint160(uint256(keccak256("hevm cheat code"))))`
+pub const CHEATCODE_ADDRESS: Address =
+    Address::new(hex!("7109709ecfa91a80626ff3989d68f67f5b1dd12d"));
+
+const WARP: [u8; 4] = hex!("e5d6bf02");
+const ROLL: [u8; 4] = hex!("1f7b4f30");
+const DEAL: [u8; 4] = hex!("c88a5e6d");
+const LOAD: [u8; 4] = hex!("667f9d70");
+const STORE: [u8; 4] = hex!("70ca10bb");
+const PRANK: [u8; 4] = hex!("ca669fa7");
+const PRANK_WITH_ORIGIN: [u8; 4] = hex!("47e50cce");
+const START_PRANK: [u8; 4] = hex!("06447d56");
+const START_PRANK_WITH_ORIGIN: [u8; 4] = hex!("45b56078");
+const STOP_PRANK: [u8; 4] = hex!("90c5013b");
+const EXPECT_REVERT: [u8; 4] = hex!("f4844814");
+const EXPECT_REVERT_BYTES: [u8; 4] = hex!("f28dceb3");
+const EXPECT_REVERT_BYTES4: [u8; 4] = hex!("c31eb0e0");
+
+/// Output of a call which reverted as expected, long enough for the
+/// return values of most functions to be decoded, as in Foundry
+static EXPECTED_REVERT_OUTPUT: [u8; 320] = [0; 320];
+
+/// `msg.sender`, and `tx.origin` if set, of the next calls of the frame at
+/// `depth`
+struct Prank {
+    depth: u64,
+    sender: Address,
+    origin: Option<Address>,
+    /// Only for the next call, otherwise until `stopPrank`
+    single: bool,
+}
+
+/// Revert expected from the next call of the frame at `depth`
+struct ExpectedRevert {
+    depth: u64,
+    /// Revert data, or its selector if 4 bytes long, any data if `None`
+    data: Option<Bytes>,
+    /// Whether the call expected to revert started
+    armed: bool,
+}
+
+/// Inspector running the Foundry cheatcodes called at `CHEATCODE_ADDRESS`,
+/// so that forge tests and exploit PoCs execute unmodified. Supports
+/// `warp`, `roll`, `deal`, `load`, `store`, `prank`, `startPrank`,
+/// `stopPrank` and `expectRevert`, the other cheatcodes revert.
+#[derive(Default)]
+pub struct CheatcodeInspector {
+    prank: Option<Prank>,
+    /// `(depth, origin)` to restore when the pranked call of the frame at
+    /// `depth` returns
+    pranked_origin: Option<(u64, Address)>,
+    expected_revert: Option<ExpectedRevert>,
+}
+
+/// ABI encoding of the Solidity `Error(message)` error
+fn revert_output(message: &str) -> Bytes {
+    let mut output = ERROR_SELECTOR.to_vec();
+    output.extend(U256::from(32).to_be_bytes::<32>());
+    output.extend(U256::from(message.len()).to_be_bytes::<32>());
+    output.extend(message.as_bytes());
+    output.resize(4 + 64 + message.len().div_ceil(32) * 32, 0);
+    output.into()
+}
+
+/// Whether the revert data `output` matches the expected `data`
+fn revert_matches(output: &[u8], data: &[u8]) -> bool {
+    output == data
+        || (data.len() == 4 && output.starts_with(data))
+        || decode_revert_reason(output).is_some_and(|reason| reason.as_bytes() == data)
+}
+
+impl CheatcodeInspector {
+    /// Run the cheatcode of `input` called by the frame at `depth`, returns
+    /// its output or the reason it failed
+    fn apply<DB: Database>(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        input: &[u8],
+        depth: u64,
+    ) -> Result<Bytes, String> {
+        let (selector, args) = input
+            .split_first_chunk::<4>()
+            .ok_or("Missing cheatcode selector")?;
+        let word = |i: usize| {
+            args.get(i * 32..(i + 1) * 32)
+                .map(U256::from_be_slice)
+                .ok_or("Invalid cheatcode arguments")
+        };
+        let address = |i: usize| word(i).map(|w| Address::from_word(B256::from(w)));
+
+        match *selector {
+            WARP => context.env.block.timestamp = word(0)?,
+            ROLL => context.env.block.number = word(0)?,
+            DEAL => {
+                let (address, balance) = (address(0)?, word(1)?);
+                let (account, _) = context
+                    .load_account(address)
+                    .map_err(|_| "Failed to load the account")?;
+                account.info.balance = balance;
+                account.mark_touch();
+            }
+            LOAD => {
+                let (address, slot) = (address(0)?, word(1)?);
+                context
+                    .load_account(address)
+                    .map_err(|_| "Failed to load the account")?;
+                let (value, _) = context
+                    .sload(address, slot)
+                    .map_err(|_| "Failed to load the storage")?;
+                return Ok(Bytes::copy_from_slice(&value.to_be_bytes::<32>()));
+            }
+            STORE => {
+                let (address, slot, value) = (address(0)?, word(1)?, word(2)?);
+                context
+                    .load_account(address)
+                    .map_err(|_| "Failed to load the account")?;
+                context
+                    .sstore(address, slot, value)
+                    .map_err(|_| "Failed to store the storage")?;
+                // Otherwise the storage of the account is not committed
+                context.journaled_state.touch(&address);
+            }
+            PRANK | START_PRANK | PRANK_WITH_ORIGIN | START_PRANK_WITH_ORIGIN => {
+                let with_origin = matches!(*selector, PRANK_WITH_ORIGIN | START_PRANK_WITH_ORIGIN);
+                self.prank = Some(Prank {
+                    depth,
+                    sender: address(0)?,
+                    origin: if with_origin { Some(address(1)?) } else { None },
+                    single: matches!(*selector, PRANK | PRANK_WITH_ORIGIN),
+                });
+            }
+            STOP_PRANK => self.prank = None,
+            EXPECT_REVERT | EXPECT_REVERT_BYTES | EXPECT_REVERT_BYTES4 => {
+                let data = match *selector {
+                    EXPECT_REVERT_BYTES4 => {
+                        Some(Bytes::copy_from_slice(&args[..4.min(args.len())]))
+                    }
+                    EXPECT_REVERT_BYTES => {
+                        let offset = usize::try_from(word(0)?).map_err(|_| "Invalid offset")?;
+                        let len =
+                            usize::try_from(word(offset / 32)?).map_err(|_| "Invalid length")?;
+                        let start = offset + 32;
+                        let data = start
+                            .checked_add(len)
+                            .and_then(|end| args.get(start..end))
+                            .ok_or("Invalid cheatcode arguments")?;
+                        Some(Bytes::copy_from_slice(data))
+                    }
+                    _ => None,
+                };
+                self.expected_revert = Some(ExpectedRevert {
+                    depth,
+                    data,
+                    armed: false,
+                });
+            }
+            _ => return Err(format!("Unsupported cheatcode 0x{}", hex::encode(selector))),
+        }
+        Ok(Bytes::new())
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CheatcodeInspector {
+    #[inline]
+    fn call(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &mut CallInputs,
+    ) -> Option<CallOutcome> {
+        let depth = context.journaled_state.depth();
+        if depth == 0 {
+            // Left by a previous transaction
+            *self = Self::default();
+        }
+
+        if inputs.target_address == CHEATCODE_ADDRESS {
+            let (result, output) = match self.apply(context, &inputs.input, depth) {
+                Ok(output) => (InstructionResult::Return, output),
+                Err(message) => (InstructionResult::Revert, revert_output(&message)),
+            };
+            return Some(CallOutcome {
+                result: InterpreterResult {
+                    result,
+                    output,
+                    gas: Gas::new(inputs.gas_limit),
+                },
+                memory_offset: inputs.return_memory_offset.clone(),
+            });
+        }
+
+        if matches!(inputs.scheme, CallScheme::Call | CallScheme::StaticCall) {
+            if let Some(prank) = self.prank.as_ref().filter(|p| p.depth == depth) {
+                inputs.caller = prank.sender;
+                if let Some(origin) = prank.origin {
+                    self.pranked_origin = Some((depth, context.env.tx.caller));
+                    context.env.tx.caller = origin;
+                }
+                if prank.single {
+                    self.prank = None;
+                }
+            }
+        }
+        if let Some(expected) = self
+            .expected_revert
+            .as_mut()
+            .filter(|e| e.depth == depth && !e.armed)
+        {
+            expected.armed = true;
+        }
+        None
+    }
+
+    #[inline]
+    fn call_end(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &CallInputs,
+        mut outcome: CallOutcome,
+    ) -> CallOutcome {
+        if inputs.target_address == CHEATCODE_ADDRESS {
+            return outcome;
+        }
+        // Depth of the caller, the frame of this call was already exited
+        let depth = context.journaled_state.depth();
+        if let Some((_, origin)) = self.pranked_origin.filter(|(d, _)| *d == depth) {
+            context.env.tx.caller = origin;
+            self.pranked_origin = None;
+        }
+
+        if !self
+            .expected_revert
+            .as_ref()
+            .is_some_and(|e| e.depth == depth && e.armed)
+        {
+            return outcome;
+        }
+        let expected = self.expected_revert.take().unwrap();
+        let result = &mut outcome.result;
+        if !result.result.is_revert() {
+            result.result = InstructionResult::Revert;
+            result.output = revert_output("call did not revert as expected");
+        } else if expected
+            .data
+            .as_ref()
+            .is_some_and(|data| !revert_matches(&result.output, data))
+        {
+            result.output = revert_output("call reverted with unexpected data");
+        } else {
+            result.result = InstructionResult::Return;
+            result.output = Bytes::from_static(&EXPECTED_REVERT_OUTPUT);
+        }
+        outcome
+    }
+
+    #[inline]
+    fn create(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &mut CreateInputs,
+    ) -> Option<CreateOutcome> {
+        let depth = context.journaled_state.depth();
+        if let Some(prank) = self.prank.as_ref().filter(|p| p.depth == depth) {
+            inputs.caller = prank.sender;
+            if prank.single {
+                self.prank = None;
+            }
+        }
+        None
+    }
+}
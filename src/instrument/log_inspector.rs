@@ -1,16 +1,14 @@
-use crate::CALL_DEPTH;
-use lazy_static::lazy_static;
+use super::trace_hook::{TraceEvent, TraceHook};
+use crate::event_abi::DecodedEvent;
+use hashbrown::{HashMap, HashSet};
 use revm::{
-    interpreter::{CallInputs, CallOutcome, CallScheme, CallValue, InstructionResult},
-    primitives::{Address, Bytes, Log as EvmLog, B256, U256},
-    Database, EvmContext, Inspector,
+    interpreter::{
+        opcode, CallInputs, CallOutcome, CallScheme, CallValue, CreateInputs, CreateOutcome,
+        InstructionResult, Interpreter, InterpreterResult, OpCode,
+    },
+    primitives::{Address, Bytes, CreateScheme, Log as EvmLog, B256, U256},
+    Database, EvmContext, Inspector, JournalEntry,
 };
-use std::cell::Cell;
-use thread_local::ThreadLocal;
-
-lazy_static! {
-    static ref COUNTER: ThreadLocal<Cell<usize>> = ThreadLocal::new();
-}
 
 #[derive(Debug, Clone)]
 pub struct CallTrace {
@@ -21,8 +19,32 @@ pub struct CallTrace {
     pub depth: usize,
     pub return_data: Option<Bytes>,
     pub is_static: bool,
+    pub scheme: CallScheme,
+    /// Scheme of a contract creation, `None` for a message call. The input
+    /// of a creation is the init code, its return data the deployed code
+    /// and `to` the created address, zero until the creation succeeds.
+    pub create_scheme: Option<CreateScheme>,
+    /// Gas limit of the call
+    pub gas_limit: u64,
+    /// Gas spent by the call, set once it returns
+    pub gas_used: u64,
     pub status: Option<InstructionResult>,
     pub id: usize,
+    /// Id of the trace of the calling frame, `None` for the transaction
+    pub parent: Option<usize>,
+    /// Ids of the traces of the calls made by this frame, in call order
+    pub children: Vec<usize>,
+    /// Storage written by this frame, excluding its subcalls
+    pub storage_changes: Vec<StorageChange>,
+}
+
+/// Storage write of an SSTORE
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageChange {
+    pub address: Address,
+    pub slot: U256,
+    pub old: U256,
+    pub new: U256,
 }
 
 #[derive(Debug, Clone)]
@@ -32,17 +54,341 @@ pub struct Log {
     pub address: Address,
     pub topics: Vec<B256>,
     pub data: Bytes,
+    /// Event decoded with the registered ABIs, see `EventDecoder`
+    pub event: Option<DecodedEvent>,
+    /// Emitted by a frame which reverted, or in one of its subcalls, hence
+    /// missing from the transaction receipt
+    pub reverted: bool,
+}
+
+/// Execution step recorded by the struct logger, see `LogInspector::struct_log_enabled`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructLog {
+    pub pc: usize,
+    pub op: u8,
+    /// Gas remaining before the step
+    pub gas: u64,
+    pub gas_cost: u64,
+    /// Depth of the frame, starting at 1 as in Geth
+    pub depth: u64,
+    /// Stack before the step, the top last
+    pub stack: Option<Vec<U256>>,
+    /// Memory of the frame before the step
+    pub memory: Option<Vec<u8>>,
+}
+
+impl StructLog {
+    /// Entry of the `structLogs` returned by the Geth `debug_traceTransaction`
+    pub fn to_geth_json(&self) -> serde_json::Value {
+        let mut log = serde_json::json!({
+            "pc": self.pc,
+            "op": OpCode::new(self.op).map_or("INVALID", |op| op.as_str()),
+            "gas": self.gas,
+            "gasCost": self.gas_cost,
+            "depth": self.depth,
+        });
+        if let Some(stack) = &self.stack {
+            log["stack"] = stack.iter().map(|v| format!("{:#x}", v)).collect();
+        }
+        if let Some(memory) = &self.memory {
+            log["memory"] = memory.chunks(32).map(hex::encode).collect();
+        }
+        log
+    }
+}
+
+/// State of a frame captured when reaching a breakpoint, before the
+/// instruction at the breakpoint executes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BreakpointHit {
+    pub address: Address,
+    pub pc: usize,
+    pub depth: u64,
+    /// Stack, the top last
+    pub stack: Vec<U256>,
+    /// `(offset, bytes)` of the memory ranges registered with the breakpoint
+    pub memory: Vec<(usize, Vec<u8>)>,
+    /// Slots of the address storage accessed so far by the transaction,
+    /// with their current value, sorted by slot
+    pub storage: Vec<(U256, U256)>,
+}
+
+/// Selection of the call traces returned by `LogInspector::filtered_traces`,
+/// a trace is kept if it matches all the criteria
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    /// Keep the calls from or to these addresses, all calls if empty
+    pub addresses: HashSet<Address>,
+    /// Keep the calls up to this depth, the transaction being at depth 0
+    pub max_depth: Option<usize>,
+    /// Keep only the calls transferring value
+    pub value_transfers_only: bool,
+    /// Keep only the calls which did not succeed
+    pub failed_only: bool,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, trace: &CallTrace) -> bool {
+        (self.addresses.is_empty()
+            || self.addresses.contains(&trace.from)
+            || self.addresses.contains(&trace.to))
+            && self.max_depth.is_none_or(|max| trace.depth <= max)
+            && (!self.value_transfers_only || trace.value > U256::ZERO)
+            && (!self.failed_only || !trace.status.is_some_and(|s| s.is_ok()))
+    }
 }
 
 /// An inspector that collects call traces.
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub struct LogInspector {
     /// Traced enabled?
     pub trace_enabled: bool,
     /// The collected traces
     pub traces: Vec<CallTrace>,
+    /// Selection of the traces returned to the user
+    pub trace_filter: TraceFilter,
+    /// Number of bytes of the inputs and the return data kept in the traces,
+    /// the rest is dropped without copying. `None` keeps them whole
+    pub max_trace_data_len: Option<usize>,
     /// EVM events/logs collected during execution
     pub logs: Vec<Log>,
+    /// Indices in `traces` of the frames not returned yet, the innermost last
+    open_traces: Vec<usize>,
+    /// `(address, slot, value)` of the SSTORE being executed
+    pending_sstore: Option<(Address, U256, U256)>,
+    /// Whether to record every execution step in `struct_logs`
+    pub struct_log_enabled: bool,
+    /// Whether the struct logs include the stack
+    pub struct_log_stack: bool,
+    /// Whether the struct logs include the memory
+    pub struct_log_memory: bool,
+    /// Steps recorded by the struct logger
+    pub struct_logs: Vec<StructLog>,
+    /// Breakpoints `(address, pc)` with the memory ranges `(offset, length)`
+    /// to capture when they are reached
+    pub breakpoints: HashMap<(Address, usize), Vec<(usize, usize)>>,
+    /// States captured at the breakpoints
+    pub breakpoint_hits: Vec<BreakpointHit>,
+    trace_hook: Option<Box<dyn TraceHook>>,
+    /// First error returned by the trace hook, the hook is not called again
+    /// until the next transaction
+    trace_hook_error: Option<eyre::Report>,
+    /// Whether the trace hook aborted the transaction, all the frames then
+    /// revert at their next step
+    aborted: bool,
+    /// Id of the next trace or log, shared so that they can be ordered
+    next_id: usize,
+}
+
+impl LogInspector {
+    /// Set or remove the hook called on the traces and events as they complete
+    pub fn set_trace_hook(&mut self, hook: Option<Box<dyn TraceHook>>) {
+        self.trace_hook = hook;
+        self.reset_trace_hook();
+    }
+
+    /// Clear the abort and the error of the trace hook, before a transaction
+    pub fn reset_trace_hook(&mut self) {
+        self.trace_hook_error = None;
+        self.aborted = false;
+    }
+
+    /// Error returned by the trace hook since the last transaction started
+    pub fn take_trace_hook_error(&mut self) -> Option<eyre::Report> {
+        self.trace_hook_error.take()
+    }
+
+    /// Pass the event selected by `event` among the traces and logs to the hook
+    fn call_trace_hook(&mut self, event: impl for<'a> FnOnce(&'a Self) -> TraceEvent<'a>) {
+        if self.aborted {
+            return;
+        }
+        let Some(mut hook) = self.trace_hook.take() else {
+            return;
+        };
+        match hook.call(event(self)) {
+            Ok(proceed) => self.aborted = !proceed,
+            Err(e) => {
+                self.trace_hook_error = Some(e);
+                self.aborted = true;
+            }
+        }
+        self.trace_hook = Some(hook);
+    }
+
+    /// Capture the state of the frame if it reached a breakpoint
+    fn check_breakpoint<DB: Database>(&mut self, interp: &Interpreter, context: &EvmContext<DB>) {
+        let address = interp.contract.target_address;
+        let pc = interp.program_counter();
+        let Some(ranges) = self.breakpoints.get(&(address, pc)) else {
+            return;
+        };
+        let memory = interp.shared_memory.context_memory();
+        let memory = ranges
+            .iter()
+            .map(|(offset, len)| {
+                // Memory past its size reads as zeros
+                let mut bytes = memory.get(*offset..).unwrap_or_default().to_vec();
+                bytes.resize(*len, 0);
+                (*offset, bytes)
+            })
+            .collect();
+        let mut storage: Vec<_> = context
+            .journaled_state
+            .state
+            .get(&address)
+            .into_iter()
+            .flat_map(|account| &account.storage)
+            .map(|(slot, value)| (*slot, value.present_value))
+            .collect();
+        storage.sort();
+        self.breakpoint_hits.push(BreakpointHit {
+            address,
+            pc,
+            depth: context.journaled_state.depth(),
+            stack: interp.stack.data().clone(),
+            memory,
+            storage,
+        });
+    }
+
+    /// Attach the write of an SSTORE which just executed to the trace of
+    /// its frame. The previous value is taken from the journal, no entry
+    /// is journaled when the value does not change.
+    fn record_storage_change<DB: Database>(
+        &mut self,
+        interp: &Interpreter,
+        context: &EvmContext<DB>,
+        address: Address,
+        slot: U256,
+        new: U256,
+    ) {
+        let old = match context
+            .journaled_state
+            .journal
+            .last()
+            .and_then(|j| j.last())
+        {
+            Some(JournalEntry::StorageChanged {
+                address: a,
+                key,
+                had_value,
+            }) if *a == address && *key == slot => *had_value,
+            _ if interp.instruction_result == InstructionResult::Continue => new,
+            _ => return,
+        };
+        let depth = context.journaled_state.depth() as usize;
+        if let Some(trace) = self
+            .open_traces
+            .last()
+            .map(|i| &mut self.traces[*i])
+            .filter(|trace| trace.depth + 1 == depth)
+        {
+            trace.storage_changes.push(StorageChange {
+                address,
+                slot,
+                old,
+                new,
+            });
+        }
+    }
+
+    /// Add the trace of a call or creation made at `depth`, the depth of
+    /// the caller, setting its id, depth and parent
+    fn open_trace(&mut self, depth: usize, mut trace: CallTrace) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        // Frames left open by a previous transaction which did not complete
+        self.open_traces.truncate(depth);
+        trace.parent = self.open_traces.last().map(|i| {
+            let parent = &mut self.traces[*i];
+            parent.children.push(id);
+            parent.id
+        });
+        trace.id = id;
+        trace.depth = depth;
+        if let Some(max_len) = self.max_trace_data_len {
+            trace.input.truncate(max_len);
+        }
+
+        self.open_traces.push(self.traces.len());
+        self.traces.push(trace);
+    }
+
+    /// Complete the trace of the frame returning to `depth`, with the
+    /// created address if the frame is a successful creation
+    fn close_trace(&mut self, depth: usize, result: &InterpreterResult, created: Option<Address>) {
+        // Drop the frames which never returned, e.g. if the tracing was
+        // enabled during the call
+        self.open_traces.truncate(depth + 1);
+        match self.open_traces.last() {
+            Some(i) if self.traces[*i].depth == depth => {}
+            _ => return,
+        }
+        let index = self.open_traces.pop().unwrap();
+        let trace = &mut self.traces[index];
+        // Shares the buffer of the output
+        let mut return_data = result.output.clone();
+        if let Some(max_len) = self.max_trace_data_len {
+            return_data.truncate(max_len);
+        }
+        trace.return_data = Some(return_data);
+        trace.status = Some(result.result);
+        trace.gas_used = result.gas.spent();
+        if !result.is_ok() {
+            // The logs emitted since the frame was entered
+            let id = trace.id;
+            for log in self.logs.iter_mut().rev().take_while(|log| log.id > id) {
+                log.reverted = true;
+            }
+        } else if let Some(address) = created {
+            trace.to = address;
+        }
+        self.call_trace_hook(|s| TraceEvent::Call(&s.traces[index]));
+    }
+
+    /// Traces matching `trace_filter`, the parent of a trace becomes its
+    /// closest ancestor kept
+    pub fn filtered_traces(&self) -> Vec<CallTrace> {
+        if self.trace_filter == TraceFilter::default() {
+            return self.traces.clone();
+        }
+        let mut traces: Vec<CallTrace> = vec![];
+        // Closest trace kept among each trace and its ancestors
+        let mut kept_ancestors: HashMap<usize, Option<usize>> = HashMap::new();
+        for trace in &self.traces {
+            let parent = trace
+                .parent
+                .and_then(|parent| kept_ancestors.get(&parent).copied().flatten());
+            if !self.trace_filter.matches(trace) {
+                kept_ancestors.insert(trace.id, parent);
+                continue;
+            }
+            if let Some(parent) = parent.and_then(|id| traces.iter_mut().find(|t| t.id == id)) {
+                parent.children.push(trace.id);
+            }
+            kept_ancestors.insert(trace.id, Some(trace.id));
+            traces.push(CallTrace {
+                parent,
+                children: vec![],
+                ..trace.clone()
+            });
+        }
+        traces
+    }
+
+    /// Take the traces kept by the trace filter, leaving no traces
+    pub fn take_filtered_traces(&mut self) -> Vec<CallTrace> {
+        if self.trace_filter == TraceFilter::default() {
+            return std::mem::take(&mut self.traces);
+        }
+        let traces = self.filtered_traces();
+        self.traces.clear();
+        traces
+    }
 }
 
 impl<DB> Inspector<DB> for LogInspector
@@ -50,27 +396,73 @@ where
     DB: Database,
 {
     #[inline]
-    fn log(&mut self, _context: &mut EvmContext<DB>, evm_log: &EvmLog) {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        if self.aborted {
+            interp.instruction_result = InstructionResult::Revert;
+            return;
+        }
+        if !self.breakpoints.is_empty() {
+            self.check_breakpoint(interp, context);
+        }
+        if self.trace_enabled && interp.current_opcode() == opcode::SSTORE {
+            if let (Ok(slot), Ok(value)) = (interp.stack.peek(0), interp.stack.peek(1)) {
+                self.pending_sstore = Some((interp.contract.target_address, slot, value));
+            }
+        }
+        if !self.struct_log_enabled {
+            return;
+        }
+        self.struct_logs.push(StructLog {
+            pc: interp.program_counter(),
+            op: interp.current_opcode(),
+            gas: interp.gas.remaining(),
+            gas_cost: 0,
+            depth: context.journaled_state.depth(),
+            stack: self.struct_log_stack.then(|| interp.stack.data().clone()),
+            memory: self
+                .struct_log_memory
+                .then(|| interp.shared_memory.context_memory().to_vec()),
+        });
+    }
+
+    #[inline]
+    fn step_end(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        if let Some((address, slot, new)) = self.pending_sstore.take() {
+            self.record_storage_change(interp, context, address, slot, new);
+        }
+        if !self.struct_log_enabled {
+            return;
+        }
+        if let Some(log) = self.struct_logs.last_mut() {
+            log.gas_cost = log.gas.saturating_sub(interp.gas.remaining());
+        }
+    }
+
+    #[inline]
+    fn log(&mut self, context: &mut EvmContext<DB>, evm_log: &EvmLog) {
         if !self.trace_enabled {
             return;
         }
-        let cell = COUNTER.get_or_default();
-        let id = cell.get();
-        cell.set(cell.get() + 1);
-        let depth = CALL_DEPTH.get_or_default().get();
+        let id = self.next_id;
+        self.next_id += 1;
+        let depth = context.journaled_state.depth() as usize;
         self.logs.push(Log {
             id,
             depth,
             address: evm_log.address,
             topics: evm_log.topics().to_vec(),
             data: evm_log.data.data.clone(),
+            event: None,
+            reverted: false,
         });
+        let index = self.logs.len() - 1;
+        self.call_trace_hook(|s| TraceEvent::Log(&s.logs[index]));
     }
 
     #[inline]
     fn call(
         &mut self,
-        _context: &mut EvmContext<DB>,
+        context: &mut EvmContext<DB>,
         inputs: &mut CallInputs,
     ) -> Option<CallOutcome> {
         if self.trace_enabled {
@@ -82,32 +474,33 @@ where
                 _ => (inputs.caller, inputs.target_address),
             };
 
-            let cell = COUNTER.get_or_default();
-            let id = cell.get();
-            cell.set(id + 1);
-
-            let cell = CALL_DEPTH.get_or_default();
-            let depth = cell.get();
-            cell.set(depth + 1);
-
             let value = match inputs.value {
                 CallValue::Transfer(value) => value,
                 _ => U256::ZERO, // double check this
             };
 
-            let trace = CallTrace {
-                id,
-                from,
-                to,
-                value,
-                input: inputs.input.clone(),
-                depth,
-                return_data: None,
-                is_static,
-                status: None,
-            };
-
-            self.traces.push(trace);
+            self.open_trace(
+                context.journaled_state.depth() as usize,
+                CallTrace {
+                    from,
+                    to,
+                    value,
+                    // Shares the buffer of the calldata
+                    input: inputs.input.clone(),
+                    is_static,
+                    scheme: inputs.scheme,
+                    create_scheme: None,
+                    gas_limit: inputs.gas_limit,
+                    depth: 0,
+                    return_data: None,
+                    gas_used: 0,
+                    status: None,
+                    id: 0,
+                    parent: None,
+                    children: vec![],
+                    storage_changes: vec![],
+                },
+            );
         }
         None
     }
@@ -115,21 +508,61 @@ where
     #[inline]
     fn call_end(
         &mut self,
-        _context: &mut EvmContext<DB>,
+        context: &mut EvmContext<DB>,
         _inputs: &CallInputs,
         result: CallOutcome,
     ) -> CallOutcome {
         if self.trace_enabled {
-            let cell = CALL_DEPTH.get_or_default();
-            cell.set(cell.get() - 1);
-            let depth = cell.get();
-            let call_trace = self
-                .traces
-                .iter_mut()
-                .find(|c| c.depth == depth)
-                .expect("Bad state: Call end without start?");
-            call_trace.return_data = Some(result.output().clone());
-            call_trace.status = Some(result.result.result);
+            // Depth of the caller, the frame of this call was already exited
+            let depth = context.journaled_state.depth() as usize;
+            self.close_trace(depth, &result.result, None);
+        }
+
+        result
+    }
+
+    #[inline]
+    fn create(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        inputs: &mut CreateInputs,
+    ) -> Option<CreateOutcome> {
+        if self.trace_enabled {
+            self.open_trace(
+                context.journaled_state.depth() as usize,
+                CallTrace {
+                    from: inputs.caller,
+                    to: Address::ZERO,
+                    value: inputs.value,
+                    input: inputs.init_code.clone(),
+                    is_static: false,
+                    scheme: CallScheme::Call,
+                    create_scheme: Some(inputs.scheme),
+                    gas_limit: inputs.gas_limit,
+                    depth: 0,
+                    return_data: None,
+                    gas_used: 0,
+                    status: None,
+                    id: 0,
+                    parent: None,
+                    children: vec![],
+                    storage_changes: vec![],
+                },
+            );
+        }
+        None
+    }
+
+    #[inline]
+    fn create_end(
+        &mut self,
+        context: &mut EvmContext<DB>,
+        _inputs: &CreateInputs,
+        result: CreateOutcome,
+    ) -> CreateOutcome {
+        if self.trace_enabled {
+            let depth = context.journaled_state.depth() as usize;
+            self.close_trace(depth, &result.result, result.address);
         }
 
         result
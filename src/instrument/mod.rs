@@ -1,4 +1,10 @@
 pub mod bug;
 pub use bug::*;
 pub mod bug_inspector;
+pub mod cheatcode_inspector;
 pub mod log_inspector;
+pub mod opcode_hook;
+pub mod pc_set;
+pub use pc_set::*;
+pub mod taint;
+pub mod trace_hook;
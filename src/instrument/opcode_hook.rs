@@ -0,0 +1,117 @@
+use eyre::Result;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use revm::primitives::{Address, U256};
+
+#[cfg(feature = "python")]
+use crate::args::PyU256;
+
+/// Custom oracle invoked before the selected opcodes execute. `Send` and
+/// `Sync` as the instance holding it
+pub trait OpcodeHook: Send + Sync {
+    /// `gas` is the gas remaining in the frame before the opcode, `stack`
+    /// holds the inputs of the opcode, top of the stack first
+    fn call(
+        &mut self,
+        address: Address,
+        pc: usize,
+        opcode: u8,
+        gas: u64,
+        stack: &[U256],
+    ) -> Result<()>;
+}
+
+impl<F> OpcodeHook for F
+where
+    F: FnMut(Address, usize, u8, u64, &[U256]) -> Result<()> + Send + Sync,
+{
+    fn call(
+        &mut self,
+        address: Address,
+        pc: usize,
+        opcode: u8,
+        gas: u64,
+        stack: &[U256],
+    ) -> Result<()> {
+        self(address, pc, opcode, gas, stack)
+    }
+}
+
+/// Python callable `callback(address, pc, opcode, stack)`, the address is
+/// hex encoded and the stack a list of ints. Only reached through
+/// `dyn OpcodeHook`, see `PyProviderCache` for the reason.
+#[cfg(feature = "python")]
+impl OpcodeHook for Py<PyAny> {
+    fn call(
+        &mut self,
+        address: Address,
+        pc: usize,
+        opcode: u8,
+        _gas: u64,
+        stack: &[U256],
+    ) -> Result<()> {
+        let stack: Vec<PyU256> = stack.iter().map(PyU256::from).collect();
+        Python::with_gil(|py| {
+            self.call1(py, (format!("{:#x}", address), pc, opcode, stack))?;
+            Ok(())
+        })
+    }
+}
+
+/// Python callable `callback(address, pc, opcode, gas)`, the address is hex
+/// encoded
+#[cfg(feature = "python")]
+pub struct PyStepHook(pub Py<PyAny>);
+
+#[cfg(feature = "python")]
+impl OpcodeHook for PyStepHook {
+    fn call(
+        &mut self,
+        address: Address,
+        pc: usize,
+        opcode: u8,
+        gas: u64,
+        _stack: &[U256],
+    ) -> Result<()> {
+        Python::with_gil(|py| {
+            self.0
+                .call1(py, (format!("{:#x}", address), pc, opcode, gas))?;
+            Ok(())
+        })
+    }
+}
+
+/// A hook with the allowlist of opcodes it is invoked on
+pub struct OpcodeHooks {
+    hook: Box<dyn OpcodeHook>,
+    opcodes: [bool; 256],
+}
+
+impl OpcodeHooks {
+    pub fn new(hook: Box<dyn OpcodeHook>, opcodes: &[u8]) -> Self {
+        let mut allowlist = [false; 256];
+        for op in opcodes {
+            allowlist[*op as usize] = true;
+        }
+        Self {
+            hook,
+            opcodes: allowlist,
+        }
+    }
+
+    #[inline]
+    pub fn is_selected(&self, opcode: u8) -> bool {
+        self.opcodes[opcode as usize]
+    }
+
+    pub fn call(
+        &mut self,
+        address: Address,
+        pc: usize,
+        opcode: u8,
+        gas: u64,
+        stack: &[U256],
+    ) -> Result<()> {
+        self.hook.call(address, pc, opcode, gas, stack)
+    }
+}
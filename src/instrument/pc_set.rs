@@ -0,0 +1,113 @@
+use std::fmt::{Debug, Formatter};
+
+/// Set of program counters stored as a bitset, bit `pc % 64` of word
+/// `pc / 64` is set if `pc` is in the set. The size is bounded by the
+/// highest PC, i.e. by the code size, which makes cloning and merging
+/// the sets of large contracts cheap compared to a hash set.
+#[derive(Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PcSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl PcSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a PC, return `true` if it was not in the set
+    pub fn insert(&mut self, pc: usize) -> bool {
+        let (word, bit) = (pc / 64, 1 << (pc % 64));
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let added = self.words[word] & bit == 0;
+        if added {
+            self.words[word] |= bit;
+            self.len += 1;
+        }
+        added
+    }
+
+    pub fn contains(&self, pc: usize) -> bool {
+        self.words
+            .get(pc / 64)
+            .is_some_and(|word| word & (1 << (pc % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Highest PC of the set
+    pub fn max(&self) -> Option<usize> {
+        let (i, word) = self
+            .words
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, w)| **w != 0)?;
+        Some(i * 64 + 63 - word.leading_zeros() as usize)
+    }
+
+    /// PCs of the set in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+
+    /// Add all the PCs of `other`
+    pub fn union_with(&mut self, other: &PcSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+        self.len = self.words.iter().map(|w| w.count_ones() as usize).sum();
+    }
+
+    /// Whether the set contains a PC which is not in `previous`
+    pub fn has_new(&self, previous: &PcSet) -> bool {
+        self.words
+            .iter()
+            .enumerate()
+            .any(|(i, word)| word & !previous.words.get(i).copied().unwrap_or_default() != 0)
+    }
+}
+
+impl Debug for PcSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<usize> for PcSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut pcs = Self::new();
+        pcs.extend(iter);
+        pcs
+    }
+}
+
+impl Extend<usize> for PcSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for pc in iter {
+            self.insert(pc);
+        }
+    }
+}
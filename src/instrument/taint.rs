@@ -0,0 +1,361 @@
+use hashbrown::{HashMap, HashSet};
+use revm::{
+    interpreter::{Interpreter, OpCode},
+    primitives::{Address, U256},
+};
+use std::ops::BitOr;
+
+/// Range of calldata bytes `[start, end)`
+pub type CalldataRange = (usize, usize);
+
+/// Origin of a value influenced by the transaction sender
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Taint {
+    /// Calldata bytes the value is derived from
+    pub calldata: Option<CalldataRange>,
+    /// Loaded from a storage slot previously written with a tainted value
+    pub storage: bool,
+    /// Derived from `ORIGIN`
+    pub origin: bool,
+    /// Derived from `CALLER`
+    pub caller: bool,
+}
+
+impl Taint {
+    pub const STORAGE: Taint = Taint {
+        calldata: None,
+        storage: true,
+        origin: false,
+        caller: false,
+    };
+
+    pub const ORIGIN: Taint = Taint {
+        calldata: None,
+        storage: false,
+        origin: true,
+        caller: false,
+    };
+
+    pub const CALLER: Taint = Taint {
+        calldata: None,
+        storage: false,
+        origin: false,
+        caller: true,
+    };
+
+    pub fn calldata(start: usize, end: usize) -> Self {
+        Self {
+            calldata: Some((start, end)),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the value is derived from calldata or from tainted storage,
+    /// `origin` and `caller` only record where the value comes from
+    pub fn is_tainted(&self) -> bool {
+        self.calldata.is_some() || self.storage
+    }
+}
+
+impl BitOr for Taint {
+    type Output = Taint;
+
+    /// Union of the taints, calldata ranges are merged into the smallest
+    /// range covering both
+    fn bitor(self, rhs: Taint) -> Taint {
+        let calldata = match (self.calldata, rhs.calldata) {
+            (Some((a_start, a_end)), Some((b_start, b_end))) => {
+                Some((a_start.min(b_start), a_end.max(b_end)))
+            }
+            (a, b) => a.or(b),
+        };
+        Taint {
+            calldata,
+            storage: self.storage || rhs.storage,
+            origin: self.origin || rhs.origin,
+            caller: self.caller || rhs.caller,
+        }
+    }
+}
+
+/// Taint of the memory bytes `[start, end)`
+#[derive(Debug, Clone, Copy)]
+struct MemoryTaint {
+    start: usize,
+    end: usize,
+    taint: Taint,
+    /// Set when the bytes are copied from calldata starting at this offset,
+    /// so that reading a part of them reports the exact calldata bytes
+    calldata_offset: Option<usize>,
+}
+
+impl MemoryTaint {
+    /// The part of this entry within `[start, end)`
+    fn slice(&self, start: usize, end: usize) -> MemoryTaint {
+        let start = start.max(self.start);
+        let end = end.min(self.end);
+        let mut taint = self.taint;
+        let calldata_offset = self
+            .calldata_offset
+            .map(|offset| offset + start - self.start);
+        if let Some(offset) = calldata_offset {
+            taint.calldata = Some((offset, offset + end - start));
+        }
+        MemoryTaint {
+            start,
+            end,
+            taint,
+            calldata_offset,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FrameTaint {
+    stack: Vec<Taint>,
+    memory: Vec<MemoryTaint>,
+}
+
+impl FrameTaint {
+    fn read_memory(&self, start: usize, len: usize) -> Taint {
+        let end = start.saturating_add(len);
+        self.memory
+            .iter()
+            .filter(|m| m.start < end && m.end > start)
+            .fold(Taint::default(), |acc, m| acc | m.slice(start, end).taint)
+    }
+
+    fn clear_memory(&mut self, start: usize, len: usize) {
+        let end = start.saturating_add(len);
+        if !self.memory.iter().any(|m| m.start < end && m.end > start) {
+            return;
+        }
+        let mut memory = Vec::with_capacity(self.memory.len() + 1);
+        for m in self.memory.drain(..) {
+            if m.end <= start || m.start >= end {
+                memory.push(m);
+                continue;
+            }
+            if m.start < start {
+                memory.push(m.slice(m.start, start));
+            }
+            if m.end > end {
+                memory.push(m.slice(end, m.end));
+            }
+        }
+        self.memory = memory;
+    }
+
+    fn write_memory(&mut self, start: usize, len: usize, taint: Taint) {
+        self.clear_memory(start, len);
+        if len > 0 && taint != Taint::default() {
+            self.memory.push(MemoryTaint {
+                start,
+                end: start.saturating_add(len),
+                taint,
+                calldata_offset: None,
+            });
+        }
+    }
+
+    fn copy_calldata(&mut self, dest: usize, offset: usize, len: usize) {
+        self.clear_memory(dest, len);
+        if len > 0 {
+            self.memory.push(MemoryTaint {
+                start: dest,
+                end: dest.saturating_add(len),
+                taint: Taint::calldata(offset, offset.saturating_add(len)),
+                calldata_offset: Some(offset),
+            });
+        }
+    }
+
+    fn copy_memory(&mut self, dest: usize, src: usize, len: usize) {
+        let end = src.saturating_add(len);
+        let copied: Vec<_> = self
+            .memory
+            .iter()
+            .filter(|m| m.start < end && m.end > src)
+            .map(|m| {
+                let mut m = m.slice(src, end);
+                m.start = m.start - src + dest;
+                m.end = m.end - src + dest;
+                m
+            })
+            .collect();
+        self.clear_memory(dest, len);
+        self.memory.extend(copied);
+    }
+}
+
+/// Lightweight taint propagation through the stack and memory of each
+/// frame, and through storage
+#[derive(Debug, Default)]
+pub struct TaintTracker {
+    /// Taints of each frame, indexed by journal depth - 1
+    frames: Vec<FrameTaint>,
+    /// Storage slots written with a tainted value, kept across transactions
+    tainted_slots: HashMap<Address, HashSet<U256>>,
+}
+
+fn as_usize(value: U256) -> Option<usize> {
+    usize::try_from(value).ok()
+}
+
+impl TaintTracker {
+    /// Start a new frame at `depth`, dropping the frames which already returned
+    pub fn enter_frame(&mut self, depth: u64) {
+        let depth = depth as usize;
+        self.frames.truncate(depth.saturating_sub(1));
+        self.frames.resize_with(depth, Default::default);
+    }
+
+    fn frame_mut(&mut self, depth: u64) -> &mut FrameTaint {
+        let depth = depth as usize;
+        if self.frames.len() < depth {
+            self.frames.resize_with(depth, Default::default);
+        }
+        &mut self.frames[depth - 1]
+    }
+
+    /// Taint of the `n`-th stack item from the top, before the current
+    /// opcode executes
+    pub fn peek(&self, depth: u64, n: usize) -> Taint {
+        self.frames
+            .get((depth as usize).wrapping_sub(1))
+            .and_then(|frame| {
+                let stack = &frame.stack;
+                stack.len().checked_sub(n + 1).map(|i| stack[i])
+            })
+            .unwrap_or_default()
+    }
+
+    /// Propagate the taints for the opcode about to be executed
+    pub fn step(&mut self, interp: &Interpreter, op: OpCode, depth: u64) {
+        let stack = interp.stack();
+        let stack_len = stack.len();
+        let code = op.get();
+        let arg = |n: usize| stack.peek(n).ok().and_then(as_usize);
+
+        let storage_taint = match op {
+            OpCode::SLOAD => {
+                let address = interp.contract().target_address;
+                let tainted = stack.peek(0).is_ok_and(|key| {
+                    self.tainted_slots
+                        .get(&address)
+                        .is_some_and(|slots| slots.contains(&key))
+                });
+                if tainted {
+                    Taint::STORAGE
+                } else {
+                    Taint::default()
+                }
+            }
+            OpCode::SSTORE => {
+                let address = interp.contract().target_address;
+                let value_taint = self.peek(depth, 1);
+                if let Ok(key) = stack.peek(0) {
+                    let slots = self.tainted_slots.entry(address).or_default();
+                    if value_taint.is_tainted() {
+                        slots.insert(key);
+                    } else {
+                        slots.remove(&key);
+                    }
+                }
+                Taint::default()
+            }
+            _ => Taint::default(),
+        };
+
+        // Values pushed outside of the instructions, e.g. the call status,
+        // are untainted
+        let frame = self.frame_mut(depth);
+        frame.stack.resize(stack_len, Taint::default());
+        let num_inputs = op.inputs() as usize;
+        if stack_len < num_inputs {
+            return;
+        }
+
+        // Taint of the output for opcodes reading calldata, memory or storage
+        let output = match op {
+            OpCode::CALLDATALOAD => Some(
+                arg(0)
+                    .map(|offset| Taint::calldata(offset, offset.saturating_add(32)))
+                    .unwrap_or_default(),
+            ),
+            OpCode::MLOAD => Some(
+                arg(0)
+                    .map(|offset| frame.read_memory(offset, 32))
+                    .unwrap_or_default(),
+            ),
+            OpCode::KECCAK256 => Some(match (arg(0), arg(1)) {
+                (Some(offset), Some(len)) => frame.read_memory(offset, len),
+                _ => Taint::default(),
+            }),
+            OpCode::SLOAD => Some(storage_taint),
+            OpCode::ORIGIN => Some(Taint::ORIGIN),
+            OpCode::CALLER => Some(Taint::CALLER),
+            _ => None,
+        };
+
+        match op {
+            OpCode::MSTORE | OpCode::MSTORE8 => {
+                let len = if op == OpCode::MSTORE { 32 } else { 1 };
+                if let (Some(offset), Some(value)) = (arg(0), frame.stack.get(stack_len - 2)) {
+                    frame.write_memory(offset, len, *value);
+                }
+            }
+            OpCode::CALLDATACOPY => {
+                if let (Some(dest), Some(offset), Some(len)) = (arg(0), arg(1), arg(2)) {
+                    frame.copy_calldata(dest, offset, len);
+                }
+            }
+            OpCode::CODECOPY | OpCode::RETURNDATACOPY => {
+                if let (Some(dest), Some(len)) = (arg(0), arg(2)) {
+                    frame.clear_memory(dest, len);
+                }
+            }
+            OpCode::EXTCODECOPY => {
+                if let (Some(dest), Some(len)) = (arg(1), arg(3)) {
+                    frame.clear_memory(dest, len);
+                }
+            }
+            OpCode::MCOPY => {
+                if let (Some(dest), Some(src), Some(len)) = (arg(0), arg(1), arg(2)) {
+                    frame.copy_memory(dest, src, len);
+                }
+            }
+            _ => (),
+        }
+
+        let shadow = &mut frame.stack;
+        match op {
+            _ if (0x80..=0x8f).contains(&code) => {
+                let n = (code - 0x7f) as usize;
+                shadow.push(shadow[stack_len - n]);
+            }
+            _ if (0x90..=0x9f).contains(&code) => {
+                let n = (code - 0x8f) as usize;
+                shadow.swap(stack_len - 1, stack_len - 1 - n);
+            }
+            OpCode::CALL
+            | OpCode::CALLCODE
+            | OpCode::DELEGATECALL
+            | OpCode::STATICCALL
+            | OpCode::CREATE
+            | OpCode::CREATE2 => {
+                // The result is pushed once the sub call returns
+                shadow.truncate(stack_len - num_inputs);
+            }
+            _ => {
+                let taint = shadow
+                    .drain(stack_len - num_inputs..)
+                    .fold(Taint::default(), |acc, t| acc | t);
+                let taint = output.unwrap_or(taint);
+                for _ in 0..op.outputs() {
+                    shadow.push(taint);
+                }
+            }
+        }
+    }
+}
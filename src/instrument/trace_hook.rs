@@ -0,0 +1,50 @@
+use eyre::Result;
+#[cfg(feature = "python")]
+use pyo3::{prelude::*, IntoPyObjectExt};
+
+use super::log_inspector::{CallTrace, Log};
+#[cfg(feature = "python")]
+use crate::response::{PyCallTrace, PyLog};
+
+/// Call trace or event passed to the trace hook once complete
+#[derive(Debug, Clone, Copy)]
+pub enum TraceEvent<'a> {
+    /// A call or contract creation which returned, with its status and
+    /// return data
+    Call(&'a CallTrace),
+    /// An emitted event
+    Log(&'a Log),
+}
+
+/// Observer of the call traces and events as they complete, e.g. to
+/// monitor long transactions. `Send` and `Sync` as the instance holding it
+pub trait TraceHook: Send + Sync {
+    /// Return `false` to abort the transaction, which then reverts
+    fn call(&mut self, event: TraceEvent<'_>) -> Result<bool>;
+}
+
+impl<F> TraceHook for F
+where
+    F: FnMut(TraceEvent<'_>) -> Result<bool> + Send + Sync,
+{
+    fn call(&mut self, event: TraceEvent<'_>) -> Result<bool> {
+        self(event)
+    }
+}
+
+/// Python callable `callback(event)` with a `PyCallTrace` or a `PyLog`,
+/// returning `False` to abort the transaction. Only reached through
+/// `dyn TraceHook`, see `PyProviderCache` for the reason.
+#[cfg(feature = "python")]
+impl TraceHook for Py<PyAny> {
+    fn call(&mut self, event: TraceEvent<'_>) -> Result<bool> {
+        Python::with_gil(|py| {
+            let event = match event {
+                TraceEvent::Call(trace) => PyCallTrace::from(trace.clone()).into_py_any(py)?,
+                TraceEvent::Log(log) => PyLog::from(log.clone()).into_py_any(py)?,
+            };
+            let proceed: Option<bool> = self.call1(py, (event,))?.extract(py)?;
+            Ok(proceed.unwrap_or(true))
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use revm::primitives::{Address, Bytes, ExecutionResult, TransactTo};
+
+use crate::{TinyEVM, UZERO};
+
+/// View call whose output is checked after every transaction
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Invariant {
+    pub contract: Address,
+    /// Calldata of the view call, e.g. the selector of a property function
+    pub data: Vec<u8>,
+    /// ABI encoded output expected from the call
+    pub expected: Vec<u8>,
+}
+
+/// Invariant not holding after a transaction
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InvariantViolation {
+    /// Index of the invariant in the registration order
+    pub index: usize,
+    pub invariant: Invariant,
+    /// Whether the view call completed without reverting
+    pub success: bool,
+    /// Output or revert data of the view call
+    pub output: Vec<u8>,
+}
+
+impl TinyEVM {
+    /// Execute the view calls of the invariants without committing them
+    /// and return the invariants not holding. The calls are not instrumented.
+    pub fn check_invariants(&mut self) -> Vec<InvariantViolation> {
+        if self.invariants.is_empty() {
+            return vec![];
+        }
+        let enabled = self.instrument_config_mut().enabled;
+        self.instrument_config_mut().enabled = false;
+
+        let mut violations = vec![];
+        let invariants = std::mem::take(&mut self.invariants);
+        for (index, invariant) in invariants.iter().enumerate() {
+            {
+                let owner = self.owner;
+                let tx_gas_limit = self.tx_gas_limit;
+                let tx = self.tx_mut();
+                tx.caller = owner;
+                tx.transact_to = TransactTo::Call(invariant.contract);
+                tx.data = invariant.data.clone().into();
+                tx.value = UZERO;
+                tx.gas_limit = tx_gas_limit;
+            }
+            let (success, output) = match self.transact() {
+                Ok(result) => match result.result {
+                    ExecutionResult::Success { output, .. } => (true, output.into_data()),
+                    ExecutionResult::Revert { output, .. } => (false, output),
+                    ExecutionResult::Halt { .. } => (false, Bytes::new()),
+                },
+                Err(_) => (false, Bytes::new()),
+            };
+            if !success || output[..] != invariant.expected[..] {
+                violations.push(InvariantViolation {
+                    index,
+                    invariant: invariant.clone(),
+                    success,
+                    output: output.to_vec(),
+                });
+            }
+        }
+
+        self.invariants = invariants;
+        self.instrument_config_mut().enabled = enabled;
+        violations
+    }
+}
@@ -1,61 +1,133 @@
 use crate::{fork_provider::ForkProvider, response::RevmResult};
 use ::revm::{
-    db::DbAccount,
     primitives::{
-        keccak256, AccountInfo, Address, Bytecode, CfgEnv, Env, ExecutionResult, HaltReason,
-        TransactTo,
+        keccak256, AccountInfo, Address, Bytecode, CfgEnv, EVMResult, Env, ExecutionResult,
+        HaltReason, SpecId, TransactTo,
     },
-    Evm,
+    Context, EvmContext,
 };
+use analysis::ControlFlowGraph;
+#[cfg(feature = "python")]
+use analysis::{analyze_bytecode, BytecodeAnalysis};
+#[cfg(feature = "python")]
+use args::{AddressArg, BytesArg, PyU256, U256Arg};
 use cache::DefaultProviderCache;
 use chain_inspector::ChainInspector;
+use config::TinyEVMConfig;
+use coverage_report::CoverageReport;
 use dotenv::dotenv;
+use errors::{parse_address, Failure};
+#[cfg(feature = "python")]
+use errors::{PyEvmError, PyEvmResult};
+use ethers::types::TxHash;
 use ethers_providers::{Http, Provider};
+use event_abi::EventDecoder;
+use executor::Executor;
 use eyre::{eyre, ContextCompat, Result};
-use fork_db::ForkDB;
+#[cfg(feature = "with-serde")]
+use fork_db::ForkDbState;
+use fork_db::{DbAccount, ForkDB, TrackCallDepth};
 use hashbrown::{HashMap, HashSet};
-use lazy_static::lazy_static;
-use num_bigint::BigInt;
-use pyo3::prelude::*;
-use response::{Response, SeenPcsMap, WrappedBug, WrappedHeuristics, WrappedMissedBranch};
-use revm::{
-    inspector_handle_register,
-    primitives::{TxEnv, B256},
-    Database,
+use invariant::Invariant;
+#[cfg(feature = "python")]
+use parallel::ParallelTx;
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyCFunction, PyIterator, PyList},
+    IntoPyObjectExt,
 };
-use thread_local::ThreadLocal;
+use response::Response;
+#[cfg(feature = "python")]
+use response::{
+    CoverageBuffer, DeployResult, ExitReason, SeenPcsMap, WrappedAccount, WrappedAccountDiff,
+    WrappedBreakpointHit, WrappedBug, WrappedCallFrame, WrappedCmpOperands, WrappedDeployment,
+    WrappedHeuristics, WrappedInvariantViolation, WrappedMissedBranch, WrappedSelectorCoverage,
+    WrappedTodPair,
+};
+#[cfg(feature = "python")]
+use revm::primitives::B256;
+use revm::{primitives::TxEnv, Database};
+use source_map::{SourceLocation, SourceMap};
+use state_diff::AccountDiff;
+#[cfg(feature = "python")]
+use tod::TodTx;
 use tokio::runtime::Runtime;
 use uuid::Uuid;
 
+/// ABI encoding of calls and decoding of outputs
+#[cfg(feature = "python")]
+pub mod abi;
+/// Static bytecode analysis
+pub mod analysis;
+/// Arguments of the Python API accepting several types
+#[cfg(feature = "python")]
+pub mod args;
 /// Caching for Web3 provider
-mod cache;
+pub mod cache;
 mod chain_inspector;
 /// Common functions shared by both EVMs
 mod common;
+/// Options of a new `TinyEVM`
+pub mod config;
+/// LCOV and HTML reports of the source coverage
+pub mod coverage_report;
 
 // /// Create inspector for overriding address creation
 // mod create_inspector;
+/// Python exceptions of the failures
+pub mod errors;
+/// ABI decoding of the emitted events
+pub mod event_abi;
+/// REVM executor which can be moved between threads
+pub mod executor;
 /// Database for REVM
 pub mod fork_db;
 /// Cache for the fork requests
 pub mod fork_provider;
 pub mod instrument;
+/// View-call invariants checked after every transaction
+pub mod invariant;
+/// Independent calls executed in parallel
+pub mod parallel;
+/// Pool of executors sharing a warmed fork
+#[cfg(feature = "python")]
+pub mod pool;
 /// Provide response data structure from EVM
 pub mod response;
+/// Mapping of PCs to Solidity sources
+pub mod source_map;
+/// Accounts changed by a transaction
+pub mod state_diff;
+/// Python type stubs of the module
+#[cfg(feature = "python")]
+pub mod stubs;
+/// Transaction-order dependency analysis
+pub mod tod;
+/// Per-instance configuration of the logs
+pub mod trace_config;
 pub use common::*;
 use hex::ToHex;
 use instrument::{
-    bug_inspector::BugInspector, log_inspector::LogInspector, BugData, Heuristics, InstrumentConfig,
+    bug_inspector::BugInspector,
+    cheatcode_inspector::{CheatcodeInspector, CHEATCODE_ADDRESS},
+    log_inspector::LogInspector,
+    opcode_hook::OpcodeHooks,
+    trace_hook::TraceHook,
+    BugData, Heuristics, InstrumentConfig, PcSet,
 };
+#[cfg(feature = "python")]
+use instrument::{log_inspector::TraceFilter, opcode_hook::PyStepHook};
 use ruint::aliases::U256;
-use std::{cell::Cell, mem::replace, str::FromStr};
-use tracing::{debug, info, trace};
-
-lazy_static! {
-    pub static ref CALL_DEPTH: ThreadLocal<Cell<usize>> = ThreadLocal::new();
-}
+#[cfg(feature = "python")]
+use std::borrow::Cow;
+use std::{mem::replace, str::FromStr, sync::Arc};
+#[cfg(feature = "python")]
+use trace_config::TraceConfig;
+use tracing::{debug, dispatcher::DefaultGuard, info, trace, Dispatch};
 
 /// Macro to define const string(s)
+#[cfg(feature = "python")]
 macro_rules! define_static_string {
     ($(($name:ident, $value: tt)),*) => {
         $(
@@ -65,6 +137,7 @@ macro_rules! define_static_string {
 }
 
 // Define some const strings used locally
+#[cfg(feature = "python")]
 define_static_string![
     (GAS_PRICE, "gas_price"),
     (ORIGIN, "origin"),
@@ -84,57 +157,97 @@ pub type TinyEvmDb = ForkDB<DefaultProviderCache>;
 
 pub struct TinyEvmContext {}
 
-/// TinyEVM is a Python wrapper for REVM
-#[pyclass(unsendable)]
+/// A transaction to execute when warming up the provider cache
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+#[derive(Clone, Debug)]
+pub enum WarmupTx {
+    /// Hash of an on-chain transaction, replayed with its original sender,
+    /// target, value, input and gas limit
+    #[cfg_attr(feature = "python", pyo3(transparent))]
+    Hash(String),
+    /// `(contract, calldata)` pair sent by the owner, calldata is hex encoded
+    Call(String, Option<String>),
+}
+
+/// TinyEVM is a Python wrapper for REVM. An instance has no state shared
+/// with the others and can be moved to another thread
+#[cfg_attr(feature = "python", pyclass(module = "tinyevm"))]
 pub struct TinyEVM {
     /// REVM instance
-    pub exe: Option<Evm<'static, ChainInspector, TinyEvmDb>>,
+    pub exe: Option<Executor>,
     pub owner: Address,
     /// Default gas limit for each transaction
-    #[pyo3(get, set)]
     tx_gas_limit: u64,
-    /// Snapshots of account state
-    pub snapshots: HashMap<Address, DbAccount>,
+    /// Snapshots of account state, shared with the state until modified
+    pub snapshots: HashMap<Address, Arc<DbAccount>>,
     /// Optional fork url
     pub fork_url: Option<String>,
     /// Snapshot of global states
     global_snapshot: HashMap<Uuid, ForkDB<DefaultProviderCache>>,
+    /// Global snapshots of the `with` blocks entered, innermost last
+    scopes: Vec<Uuid>,
+    /// Source maps of the contracts by address
+    source_maps: HashMap<Address, SourceMap>,
+    /// Invariants checked after every transaction
+    invariants: Vec<Invariant>,
+    /// Event ABIs used to decode the emitted logs
+    event_decoder: EventDecoder,
+    /// Subscriber of the logs of this instance, if tracing is enabled
+    tracing: Option<Dispatch>,
 }
 
-static mut TRACE_ENABLED: bool = false;
-
-/// Enable printing of trace logs for debugging
-#[pyfunction]
-pub fn enable_tracing() -> Result<()> {
-    use tracing_subscriber::{fmt, EnvFilter};
-
-    if unsafe { !TRACE_ENABLED } {
-        let subscriber = fmt::Subscriber::builder()
-            .with_env_filter(EnvFilter::from("tinyevm=trace,revm=trace"))
-            .finish();
-
-        // Set the subscriber as the global default.
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("Setting default subscriber failed");
-
-        unsafe {
-            TRACE_ENABLED = true;
-        }
-    }
-
-    Ok(())
+/// State of a `TinyEVM`, see `TinyEVM::state`. Python objects such as the
+/// hooks and the provider cache backend are not part of it
+#[cfg(feature = "with-serde")]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TinyEvmState {
+    pub db: ForkDbState,
+    pub env: Env,
+    pub spec_id: SpecId,
+    pub instrument_config: InstrumentConfig,
+    pub trace_enabled: bool,
+    pub owner: Address,
+    pub tx_gas_limit: u64,
+    pub fork_url: Option<String>,
+    pub snapshots: HashMap<Address, Arc<DbAccount>>,
 }
 
 // Implementations for use in Rust
 impl TinyEVM {
-    pub fn exe_mut(&mut self) -> &mut Evm<'static, ChainInspector, TinyEvmDb> {
+    pub fn exe_mut(&mut self) -> &mut Executor {
         self.exe.as_mut().unwrap()
     }
 
+    /// Run the current transaction without committing its changes
+    pub fn transact(&mut self) -> EVMResult<<TinyEvmDb as Database>::Error> {
+        let (exe, result) = self.exe.take().unwrap().run(|evm| evm.transact());
+        self.exe = Some(exe);
+        result
+    }
+
     pub fn tx_mut(&mut self) -> &mut TxEnv {
         self.exe_mut().tx_mut()
     }
 
+    /// Gas charged for the current transaction before its execution: the
+    /// base cost, the calldata, the access list and the initcode
+    fn intrinsic_gas(&self) -> u64 {
+        let exe = self.exe.as_ref().unwrap();
+        let tx = exe.tx();
+        ::revm::interpreter::gas::validate_initial_tx_gas(
+            exe.spec_id(),
+            &tx.data,
+            tx.transact_to.is_create(),
+            &tx.access_list,
+        )
+    }
+
+    /// Set the subscriber of this instance as the default one until the
+    /// guard is dropped, entered by each of the Python methods
+    fn trace_guard(&self) -> Option<DefaultGuard> {
+        self.tracing.as_ref().map(tracing::dispatcher::set_default)
+    }
+
     fn db(&self) -> &ForkDB<DefaultProviderCache> {
         &self.exe.as_ref().unwrap().context.evm.db
     }
@@ -146,15 +259,36 @@ impl TinyEVM {
     pub fn instrument_config_mut(&mut self) -> &mut InstrumentConfig {
         &mut self.bug_inspector_mut().instrument_config
     }
-    fn log_inspector(&self) -> &LogInspector {
-        self.exe
-            .as_ref()
-            .unwrap()
-            .context
-            .external
-            .log_inspector
-            .as_ref()
-            .unwrap()
+
+    /// Register the inspectors in the executor or remove them. Without the
+    /// inspectors the transactions run at the speed of plain revm: no bugs,
+    /// heuristics, traces or logs are recorded, `force_address` is ignored
+    /// and the call depth used by `max_fork_depth` is not tracked
+    pub fn set_inspector_enabled(&mut self, enabled: bool) {
+        self.exe_mut().set_inspector_enabled(enabled);
+    }
+
+    /// Set whether the calls to `CHEATCODE_ADDRESS` run the Foundry
+    /// cheatcodes, see `CheatcodeInspector`. A code is set at the address
+    /// when enabled, as Solidity checks that the callee has code
+    pub fn set_cheatcodes_enabled(&mut self, enabled: bool) {
+        if enabled && self.db().accounts.get(&CHEATCODE_ADDRESS).is_none() {
+            let code = Bytecode::new_raw(vec![0x00].into());
+            let info = AccountInfo {
+                code_hash: keccak256(code.bytecode()),
+                code: Some(code),
+                ..Default::default()
+            };
+            self.db_mut().insert_account_info(CHEATCODE_ADDRESS, info);
+        }
+        let external = &mut self.exe.as_mut().unwrap().context.external;
+        external.cheatcode_inspector = enabled.then(CheatcodeInspector::default);
+    }
+
+    /// Whether the Foundry cheatcodes are enabled
+    pub fn cheatcodes_enabled(&self) -> bool {
+        let external = &self.exe.as_ref().unwrap().context.external;
+        external.cheatcode_inspector.is_some()
     }
 
     fn log_inspector_mut(&mut self) -> &mut LogInspector {
@@ -198,7 +332,7 @@ impl TinyEVM {
         &self.bug_inspector().heuristics
     }
 
-    pub fn pcs_by_address(&self) -> &HashMap<Address, HashSet<usize>> {
+    pub fn pcs_by_address(&self) -> &HashMap<Address, PcSet> {
         &self.bug_inspector().pcs_by_address
     }
 
@@ -206,6 +340,32 @@ impl TinyEVM {
         &self.bug_inspector().created_addresses
     }
 
+    /// Set or remove the hook called before the selected opcodes execute
+    pub fn set_opcode_hooks(&mut self, hooks: Option<OpcodeHooks>) {
+        self.bug_inspector_mut().set_opcode_hooks(hooks);
+    }
+
+    /// Raise the error returned by the opcode hook in the last transaction
+    pub fn check_opcode_hook(&mut self) -> Result<()> {
+        match self.bug_inspector_mut().take_opcode_hook_error() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Set or remove the hook called on the traces and events as they complete
+    pub fn set_boxed_trace_hook(&mut self, hook: Option<Box<dyn TraceHook>>) {
+        self.log_inspector_mut().set_trace_hook(hook);
+    }
+
+    /// Raise the error returned by the trace hook in the last transaction
+    pub fn check_trace_hook(&mut self) -> Result<()> {
+        match self.log_inspector_mut().take_trace_hook_error() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
     /// Create a new TinyEVM instance without fork
     pub fn new_offline() -> Result<Self> {
         Self::new_instance(None, None, false)
@@ -214,8 +374,8 @@ impl TinyEVM {
     /// Set account balance, if the account does not exist, will create one
     pub fn set_account_balance(&mut self, address: Address, balance: U256) -> Result<()> {
         let db = &mut self.exe.as_mut().unwrap().context.evm.db;
-        if let Some(account) = db.accounts.get_mut(&address) {
-            account.info.balance = balance;
+        if db.accounts.contains_key(&address) {
+            db.account_mut(address).info.balance = balance;
         } else {
             let account = AccountInfo::from_balance(balance);
             db.insert_account_info(address, account);
@@ -257,6 +417,48 @@ impl TinyEVM {
         Ok(())
     }
 
+    /// Result of the transaction which just completed, with the data
+    /// collected by the inspectors. The buffers of the inspectors are moved
+    /// out, the shared ones such as the heuristics are not copied
+    fn take_revm_result(
+        &mut self,
+        result: Result<ExecutionResult>,
+        state_diff: Vec<AccountDiff>,
+        ignored_addresses: HashSet<Address>,
+    ) -> RevmResult {
+        let intrinsic_gas = self.intrinsic_gas();
+        let blob_gas_used = self.exe.as_ref().unwrap().tx().get_total_blob_gas();
+
+        let bug_inspector = self.bug_inspector_mut();
+        let bug_data = bug_inspector.bug_data.clone();
+        let heuristics = bug_inspector.heuristics.clone();
+        let seen_pcs = bug_inspector.pcs_by_address.clone();
+        let deployments = std::mem::take(&mut bug_inspector.deployments);
+
+        let log_inspector = self.log_inspector_mut();
+        let mut transient_logs = std::mem::take(&mut log_inspector.logs);
+        let struct_logs = std::mem::take(&mut log_inspector.struct_logs);
+        let breakpoint_hits = std::mem::take(&mut log_inspector.breakpoint_hits);
+        let traces = log_inspector.take_filtered_traces();
+        self.event_decoder.decode_logs(&mut transient_logs);
+
+        RevmResult {
+            result,
+            bug_data,
+            heuristics,
+            seen_pcs,
+            deployments,
+            struct_logs,
+            breakpoint_hits,
+            traces,
+            transient_logs,
+            ignored_addresses,
+            intrinsic_gas,
+            blob_gas_used,
+            state_diff,
+        }
+    }
+
     /// Deploy the contract for the `owner`.
     pub fn deploy_helper(
         &mut self,
@@ -266,6 +468,7 @@ impl TinyEVM {
         tx_gas_limit: Option<u64>,
         force_address: Option<Address>, // not supported yet
     ) -> Result<Response> {
+        let _guard = self.trace_guard();
         trace!(
             "deploy_helper: {:?}, {:?}, {:?}",
             contract_bytecode.encode_hex::<String>(),
@@ -273,18 +476,19 @@ impl TinyEVM {
             value,
         );
 
-        CALL_DEPTH.get_or_default().set(0);
+        self.db_mut().set_call_depth(0);
 
         // Reset instrumentation,
-        self.clear_instrumentation();
+        self.clear_instrumentation_helper();
 
-        self.bug_inspector_mut().pcs_by_address.clear(); // If don't want to trace the deploy PCs
+        self.bug_inspector_mut().pcs_by_address = Default::default(); // If don't want to trace the deploy PCs
+        self.bug_inspector_mut().jumps_by_address.clear();
 
         {
             let tx = self.exe.as_mut().unwrap().tx_mut();
             tx.caller = owner;
             tx.transact_to = TransactTo::Create;
-            tx.data = contract_bytecode.clone().into();
+            tx.data = contract_bytecode.into();
             tx.value = value;
             tx.gas_limit = tx_gas_limit.unwrap_or(self.tx_gas_limit);
         }
@@ -309,7 +513,7 @@ impl TinyEVM {
                 .create_address_overrides
                 .insert(address, force_address);
         }
-        let result = self.exe.as_mut().unwrap().transact_commit();
+        let (result, state_diff) = self.transact_commit_with_diff();
 
         trace!("deploy result: {:?}", result);
 
@@ -328,15 +532,9 @@ impl TinyEVM {
         };
 
         if collision {
-            return Err(eyre!(
-                "Address collision for {}",
-                address.encode_hex::<String>()
-            ))?;
+            return Err(Failure::AddressCollision(address))?;
         }
 
-        let bug_data = self.bug_data().clone();
-        let heuristics = self.heuristics().clone();
-        let seen_pcs = self.pcs_by_address().clone();
         let addresses = self.created_addresses().clone();
         info!(
             "created addresses from deployment: {:?} for calculated address {:?}",
@@ -348,22 +546,11 @@ impl TinyEVM {
                 .insert(address, addresses);
         }
 
-        let logs = self.log_inspector().logs.clone();
-        let traces = self.log_inspector().traces.clone();
-
-        trace!("deploy result: {:?}", result);
-
-        let revm_result = RevmResult {
-            result: result.map_err(|e| eyre!(e)),
-            bug_data,
-            heuristics,
-            seen_pcs,
-            traces,
-            transient_logs: logs,
-            ignored_addresses: Default::default(),
-        };
-
-        Ok(revm_result.into())
+        let revm_result =
+            self.take_revm_result(result.map_err(|e| eyre!(e)), state_diff, Default::default());
+        let mut resp = Response::from(revm_result);
+        resp.invariant_violations = self.check_invariants();
+        Ok(resp)
     }
 
     /// Send a `transact_call` to a `contract` from the `sender` with raw
@@ -376,9 +563,10 @@ impl TinyEVM {
         value: U256,
         tx_gas_limit: Option<u64>,
     ) -> Response {
+        let _guard = self.trace_guard();
         // Reset instrumentation,
-        self.clear_instrumentation();
-        CALL_DEPTH.get_or_default().set(0);
+        self.clear_instrumentation_helper();
+        self.db_mut().set_call_depth(0);
 
         {
             let tx_gas_limit = tx_gas_limit.unwrap_or(self.tx_gas_limit);
@@ -390,7 +578,7 @@ impl TinyEVM {
             tx.gas_limit = tx_gas_limit;
         }
 
-        let result = self.exe_mut().transact_commit();
+        let (result, state_diff) = self.transact_commit_with_diff();
 
         let addresses = self.created_addresses().clone();
         info!(
@@ -406,28 +594,12 @@ impl TinyEVM {
                 .insert(contract, addresses);
         }
 
-        let bug_data = self.bug_data().clone();
-        let heuristics = self.heuristics().clone();
-        let seen_pcs = self.pcs_by_address().clone();
-
-        let db = &self.db();
-        let ignored_addresses = db.ignored_addresses.clone();
-        let ignored_addresses = ignored_addresses.into_iter().map(Into::into).collect();
-
-        let log_inspector = self.log_inspector();
-        let logs = log_inspector.logs.clone();
-        let traces = log_inspector.traces.clone();
-
-        let revm_result = RevmResult {
-            result: result.map_err(|e| eyre!(e)),
-            bug_data,
-            heuristics,
-            seen_pcs,
-            traces,
-            transient_logs: logs,
-            ignored_addresses,
-        };
-        Response::from(revm_result)
+        let ignored_addresses = self.db().ignored_addresses.iter().copied().collect();
+        let revm_result =
+            self.take_revm_result(result.map_err(|e| eyre!(e)), state_diff, ignored_addresses);
+        let mut resp = Response::from(revm_result);
+        resp.invariant_violations = self.check_invariants();
+        resp
     }
 
     /// Set code of an account
@@ -473,13 +645,134 @@ impl TinyEVM {
         if let Some(account) = account {
             let code = &account.code;
             if let Some(code) = code {
-                return Ok(code.bytecode().to_vec());
+                return Ok(code.original_byte_slice().to_vec());
             }
         }
 
         Ok(vec![])
     }
 
+    /// Bitset of the PCs visited at an address: bit `pc % 8` of byte `pc / 8`
+    /// is set if `pc` was executed. The bitset covers the whole code of the
+    /// account so that bitsets of the same contract have the same size.
+    pub fn coverage_bitmap(&mut self, addr: Address) -> Result<Vec<u8>> {
+        let code_len = self.get_code_by_address(addr)?.len();
+        let pcs = self.pcs_by_address().get(&addr);
+        let max_pc = pcs.and_then(PcSet::max);
+        let len = max_pc.map_or(code_len, |pc| code_len.max(pc + 1));
+
+        let mut bitmap = vec![0u8; len.div_ceil(8)];
+        for pc in pcs.into_iter().flat_map(PcSet::iter) {
+            bitmap[pc / 8] |= 1 << (pc % 8);
+        }
+        Ok(bitmap)
+    }
+
+    /// Branches `(jumpi pc, target pc)` of the code at an address never
+    /// taken by any transaction so far, see `BytecodeAnalysis::uncovered_branches`
+    pub fn uncovered_branches_by_address(&mut self, addr: Address) -> Result<Vec<(usize, usize)>> {
+        let analysis = analysis::analyze(&self.get_code_by_address(addr)?);
+        Ok(match self.pcs_by_address().get(&addr) {
+            Some(seen_pcs) => analysis.uncovered_branches(seen_pcs),
+            None => analysis.uncovered_branches(&PcSet::new()),
+        })
+    }
+
+    /// Control-flow graph of the code at an address, with the edges of the
+    /// jumps taken by any transaction so far, see `analysis::control_flow_graph`
+    pub fn control_flow_graph_by_address(&mut self, addr: Address) -> Result<ControlFlowGraph> {
+        let code = self.get_code_by_address(addr)?;
+        Ok(match self.bug_inspector().jumps_by_address.get(&addr) {
+            Some(jumps) => analysis::control_flow_graph(&code, jumps),
+            None => analysis::control_flow_graph(&code, &HashSet::new()),
+        })
+    }
+
+    /// Register an invariant checked after every deployment and contract
+    /// call, the violations are reported in `Response::invariant_violations`
+    pub fn push_invariant(&mut self, invariant: Invariant) {
+        self.invariants.push(invariant);
+    }
+
+    /// Set the solc source map of the code at an address, `sources` are
+    /// `(path, content)` ordered by source id. The map must be set again if
+    /// the code changes.
+    pub fn set_source_map_by_address(
+        &mut self,
+        addr: Address,
+        source_map: &str,
+        sources: Vec<(String, String)>,
+    ) -> Result<()> {
+        let code = self.get_code_by_address(addr)?;
+        if code.is_empty() {
+            return Err(eyre!("No code at address {:?}", addr));
+        }
+        let source_map = SourceMap::new(&code, source_map, sources)?;
+        self.source_maps.insert(addr, source_map);
+        Ok(())
+    }
+
+    /// Source location of a PC of the code at an address
+    pub fn source_location_by_address(&self, addr: Address, pc: usize) -> Option<SourceLocation> {
+        self.source_maps.get(&addr)?.location(pc)
+    }
+
+    /// Source location of a PC of the address at `address_index` in
+    /// `heuristics.seen_addresses`
+    fn source_location_by_index(
+        &self,
+        heuristics: &Heuristics,
+        address_index: isize,
+        pc: usize,
+    ) -> Option<SourceLocation> {
+        let addr = heuristics
+            .seen_addresses
+            .get(usize::try_from(address_index).ok()?)?;
+        self.source_location_by_address(*addr, pc)
+    }
+
+    /// Line and branch coverage of the sources of the contracts with a
+    /// source map, from the PCs and the jumps seen since the last deployment.
+    /// Requires `InstrumentConfig.pcs_by_address`
+    pub fn coverage_report(&self) -> CoverageReport {
+        let bug_inspector = self.bug_inspector();
+        let mut report = CoverageReport::default();
+        for (address, source_map) in &self.source_maps {
+            report.add(
+                source_map,
+                bug_inspector.pcs_by_address.get(address),
+                bug_inspector.jumps_by_address.get(address),
+            );
+        }
+        report
+    }
+
+    /// Source locations of the bugs of a response, in the order of `bug_data`
+    pub fn bug_source_locations(&self, resp: &Response) -> Vec<Option<SourceLocation>> {
+        resp.bug_data
+            .iter()
+            .map(|bug| {
+                self.source_location_by_index(&resp.heuristics, bug.address_index, bug.position)
+            })
+            .collect()
+    }
+
+    /// Source locations of the JUMPIs of the missed branches of a response,
+    /// in the order of `heuristics.missed_branches`
+    pub fn missed_branch_source_locations(&self, resp: &Response) -> Vec<Option<SourceLocation>> {
+        resp.heuristics
+            .missed_branches
+            .iter()
+            .map(|branch| {
+                self.source_location_by_index(
+                    &resp.heuristics,
+                    branch.address_index,
+                    branch.prev_pc,
+                )
+            })
+            .collect()
+    }
+
     /// Get Eth balance for an account
     pub fn get_eth_balance(&mut self, addr: Address) -> Result<U256> {
         let db = self.db_mut();
@@ -487,6 +780,32 @@ impl TinyEVM {
         Ok(account.unwrap_or_default().balance)
     }
 
+    /// Accounts in the state sorted by address. Only the accounts loaded so
+    /// far are included when forking
+    pub fn account_infos(&self) -> Vec<(Address, AccountInfo)> {
+        let mut accounts: Vec<_> = self
+            .db()
+            .accounts
+            .iter()
+            .filter_map(|(address, account)| account.info().map(|info| (*address, info)))
+            .collect();
+        accounts.sort_by_key(|(address, _)| *address);
+        accounts
+    }
+
+    /// Storage slots of an account sorted by slot, empty if the account does
+    /// not exist. Only the slots loaded so far are included when forking
+    pub fn storage_of(&self, addr: Address) -> Vec<(U256, U256)> {
+        let mut storage: Vec<_> = self
+            .db()
+            .accounts
+            .get(&addr)
+            .map(|account| account.storage.iter().map(|(k, v)| (*k, *v)).collect())
+            .unwrap_or_default();
+        storage.sort_by_key(|(slot, _)| *slot);
+        storage
+    }
+
     /// Get storage by address and index
     pub fn get_storage_by_address(&self, addr: Address, index: U256) -> Result<U256> {
         let db = &self.db();
@@ -512,6 +831,13 @@ impl TinyEVM {
         Ok(())
     }
 
+    /// Set storage by address and index without loading the account from
+    /// the fork, see `ForkDB::insert_account_storage_local`
+    pub fn set_local_storage_by_address(&mut self, addr: Address, index: U256, value: U256) {
+        self.db_mut()
+            .insert_account_storage_local(addr, index, value);
+    }
+
     /// Clone account from one address to another. If `delete` is true, the original account will be deleted.
     pub fn clone_account(&mut self, from: Address, to: Address, delete: bool) -> Result<()> {
         let db = &mut self.exe.as_mut().unwrap().context.evm.db;
@@ -528,32 +854,100 @@ impl TinyEVM {
         Ok(())
     }
 
+    /// Execute the transactions against the fork so that every account, slot
+    /// and block they touch is stored in the provider cache. The EVM state is
+    /// restored afterwards.
+    pub fn warm_cache_helper(&mut self, txs: &[WarmupTx]) -> Result<Vec<Response>> {
+        let _guard = self.trace_guard();
+        if !self.db().fork_enabled {
+            return Err(eyre!("Warming up the cache requires fork to be enabled"));
+        }
+
+        let snapshot = self.db().clone();
+        let owner = self.owner;
+
+        let responses = txs
+            .iter()
+            .map(|tx| match tx {
+                WarmupTx::Hash(hash) => {
+                    let hash = TxHash::from_str(trim_prefix(hash, "0x"))?;
+                    let tx = self.db_mut().get_transaction(&hash)?;
+                    let sender = Address::from(tx.from.0);
+                    let value = U256::from_limbs(tx.value.0);
+                    let gas_limit = Some(tx.gas.low_u64());
+                    match tx.to {
+                        Some(to) => Ok(self.contract_call_helper(
+                            Address::from(to.0),
+                            sender,
+                            tx.input.to_vec(),
+                            value,
+                            gas_limit,
+                        )),
+                        None => {
+                            self.deploy_helper(sender, tx.input.to_vec(), value, gas_limit, None)
+                        }
+                    }
+                }
+                WarmupTx::Call(contract, data) => {
+                    let contract = parse_address(contract)?;
+                    let data = data.as_deref().map(decode_hex_str).transpose()?;
+                    Ok(self.contract_call_helper(
+                        contract,
+                        owner,
+                        data.unwrap_or_default(),
+                        U256::ZERO,
+                        None,
+                    ))
+                }
+            })
+            .collect::<Result<Vec<_>>>();
+
+        let _ = replace(self.db_mut(), snapshot);
+        self.db().flush_cache()?;
+
+        responses
+    }
+
     pub fn new_instance(
         fork_url: Option<String>,
         block_id: Option<u64>,
         enable_call_trace: bool, // Whether to show call and event traces
     ) -> Result<Self> {
+        Self::with_config(&TinyEVMConfig {
+            fork_url,
+            block_id,
+            trace_enabled: enable_call_trace,
+            ..Default::default()
+        })
+    }
+
+    /// Create an instance with the options of `config`
+    pub fn with_config(config: &TinyEVMConfig) -> Result<Self> {
         dotenv().ok();
         let owner = Address::default();
+        let fork_url = config.fork_url.clone();
+        let block_id = config.block_id;
 
         // Create a new REVM instance with default configurations
 
         let mut cfg_env = CfgEnv::default();
         cfg_env.disable_eip3607 = true;
         cfg_env.disable_block_gas_limit = true;
+        cfg_env.chain_id = config.chain_id;
 
         let fork_enabled = fork_url.is_some();
 
         let mut db = match fork_url {
             Some(ref url) => {
                 info!("Starting EVM from fork {} and block: {:?}", url, block_id);
-                let runtime = Runtime::new().expect("Create runtime failed");
-                let provider = Provider::<Http>::try_from(url)?;
-                let provider = ForkProvider::new(provider, runtime);
+                let provider = fork_provider(url, config.provider_cache()?)?;
                 ForkDB::create_with_provider(Some(provider), block_id)
             }
             None => ForkDB::create(),
         };
+        if let Some(depth) = config.max_fork_depth {
+            db.set_max_fork_depth(depth);
+        }
 
         let mut env = Env {
             cfg: cfg_env,
@@ -582,41 +976,176 @@ impl TinyEVM {
 
         // Add owner account
         let account = AccountInfo {
-            balance: DEFAULT_BALANCE,
+            balance: config.default_balance,
             ..Default::default()
         };
 
         db.insert_account_info(owner, account);
-        // let mut builder = Evm::builder();
-        let log_inspector = LogInspector {
-            trace_enabled: enable_call_trace,
-            ..LogInspector::default()
-        };
 
-        let bug_inspector = BugInspector::default();
+        let mut tinyevm = Self::from_parts(
+            db,
+            env,
+            config.spec_id()?,
+            config.instrument_config.clone(),
+            config.trace_enabled,
+        );
+        tinyevm.owner = owner;
+        tinyevm.fork_url = fork_url;
+        tinyevm.tx_gas_limit = config.tx_gas_limit;
+
+        Ok(tinyevm)
+    }
+
+    /// Instance executing with `db` and `env`, which are moved into the
+    /// executor, and inspectors configured with `instrument_config`
+    fn from_parts(
+        db: TinyEvmDb,
+        env: Env,
+        spec_id: SpecId,
+        instrument_config: InstrumentConfig,
+        trace_enabled: bool,
+    ) -> Self {
+        let mut log_inspector = LogInspector::default();
+        log_inspector.trace_enabled = trace_enabled;
+
+        let mut bug_inspector = BugInspector::default();
+        bug_inspector.instrument_config = instrument_config;
 
         let inspector = ChainInspector {
             log_inspector: Some(log_inspector),
             bug_inspector: Some(bug_inspector),
+            cheatcode_inspector: None,
         };
 
-        let exe = Evm::builder()
-            .modify_env(|e| *e = Box::new(env.clone()))
-            .with_db(db.clone())
-            .with_external_context(inspector)
-            .append_handler_register(inspector_handle_register)
-            .build();
-        let tinyevm = Self {
+        let context = Context::new(EvmContext::new_with_env(db, Box::new(env)), inspector);
+        let exe = Executor::new(context, spec_id);
+        Self {
             exe: Some(exe),
-            owner,
-            fork_url,
+            owner: Address::default(),
+            fork_url: None,
             tx_gas_limit: TX_GAS_LIMIT,
             snapshots: HashMap::with_capacity(32),
             global_snapshot: Default::default(),
+            scopes: vec![],
+            source_maps: Default::default(),
+            invariants: vec![],
+            event_decoder: Default::default(),
+            tracing: None,
+        }
+    }
+
+    /// Clear the results of the inspectors of the previous transaction
+    pub fn clear_instrumentation_helper(&mut self) {
+        let bug_inspector = self.bug_inspector_mut();
+        bug_inspector.clear_results();
+        bug_inspector.created_addresses.clear();
+        bug_inspector.deployments.clear();
+        self.log_inspector_mut().traces.clear();
+        self.log_inspector_mut().struct_logs.clear();
+        self.log_inspector_mut().breakpoint_hits.clear();
+        self.log_inspector_mut().reset_trace_hook();
+        self.log_inspector_mut().logs.clear();
+    }
+
+    /// Take a global snapshot of all the accounts, shared with the state
+    /// until modified
+    pub fn take_global_snapshot_helper(&mut self) -> Uuid {
+        let snapshot = self.db().clone();
+        let id = Uuid::new_v4();
+        self.global_snapshot.insert(id, snapshot);
+        id
+    }
+
+    /// Restore the global snapshot `id`, which is dropped unless
+    /// `keep_snapshot` is set
+    pub fn restore_global_snapshot_helper(&mut self, id: &Uuid, keep_snapshot: bool) -> Result<()> {
+        let snapshot = if keep_snapshot {
+            self.global_snapshot.get(id).cloned()
+        } else {
+            self.global_snapshot.remove(id)
         };
+        let snapshot = snapshot.ok_or_else(|| Failure::SnapshotNotFound(id.to_string()))?;
+        *self.db_mut() = snapshot;
+        Ok(())
+    }
 
+    /// Take a global snapshot restored by the matching `exit_scope`
+    pub fn enter_scope(&mut self) -> Result<()> {
+        let id = self.take_global_snapshot_helper();
+        self.scopes.push(id);
+        Ok(())
+    }
+
+    /// Restore the global snapshot of the innermost scope
+    pub fn exit_scope(&mut self) -> Result<()> {
+        let id = self.scopes.pop().context("No scope entered")?;
+        self.restore_global_snapshot_helper(&id, false)
+    }
+
+    /// Copy of the state, which can be sent to another process
+    #[cfg(feature = "with-serde")]
+    pub fn state(&self) -> TinyEvmState {
+        let exe = self.exe.as_ref().unwrap();
+        TinyEvmState {
+            db: self.db().state(),
+            env: exe.context.evm.env.as_ref().clone(),
+            spec_id: exe.spec_id(),
+            instrument_config: self.bug_inspector().instrument_config.clone(),
+            trace_enabled: exe
+                .context
+                .external
+                .log_inspector
+                .as_ref()
+                .unwrap()
+                .trace_enabled,
+            owner: self.owner,
+            tx_gas_limit: self.tx_gas_limit,
+            fork_url: self.fork_url.clone(),
+            snapshots: self.snapshots.clone(),
+        }
+    }
+
+    /// Restore a state copied with `state`, reconnecting to the fork url if any
+    #[cfg(feature = "with-serde")]
+    pub fn from_state(state: TinyEvmState) -> Result<Self> {
+        let provider = match state.fork_url {
+            Some(ref url) => Some(fork_provider(url, None)?),
+            None => None,
+        };
+        let mut tinyevm = Self::from_parts(
+            ForkDB::from_state(state.db, provider),
+            state.env,
+            state.spec_id,
+            state.instrument_config,
+            state.trace_enabled,
+        );
+        tinyevm.owner = state.owner;
+        tinyevm.tx_gas_limit = state.tx_gas_limit;
+        tinyevm.fork_url = state.fork_url;
+        tinyevm.snapshots = state.snapshots;
         Ok(tinyevm)
     }
+
+    /// New instance with a copy of the state, the env and the configuration,
+    /// sharing the fork block and the in-memory provider cache. Python
+    /// objects such as the hooks are not copied
+    pub fn clone_instance(&self) -> Result<Self> {
+        Self::from_seed(self.seed())
+    }
+}
+
+/// Provider of the chain state at `url`, using the builtin cache if `cache`
+/// is `None`
+fn fork_provider(
+    url: &str,
+    cache: Option<DefaultProviderCache>,
+) -> Result<ForkProvider<DefaultProviderCache>> {
+    let runtime = Runtime::new().expect("Create runtime failed");
+    let provider = Provider::<Http>::try_from(url)?;
+    Ok(match cache {
+        Some(cache) => ForkProvider::with_cache(provider, runtime, cache),
+        None => ForkProvider::new(provider, runtime),
+    })
 }
 
 impl Default for TinyEVM {
@@ -625,72 +1154,350 @@ impl Default for TinyEVM {
     }
 }
 
+/// Deployment of `deploy` and `deploy_async`
+#[cfg(feature = "python")]
+struct Deployment {
+    /// The owner of the instance if not set
+    owner: Option<Address>,
+    /// Deploy code followed by the constructor arguments
+    bytecode: Vec<u8>,
+    value: U256,
+    init_value: Option<U256>,
+    force_address: Option<Address>,
+}
+
+#[cfg(feature = "python")]
+impl Deployment {
+    fn new(
+        contract_deploy_code: BytesArg,
+        owner: Option<AddressArg>,
+        data: Option<BytesArg>,
+        value: Option<U256Arg>,
+        init_value: Option<U256Arg>,
+        deploy_to_address: Option<AddressArg>,
+    ) -> Result<Self> {
+        let mut bytecode = contract_deploy_code.into_bytes()?;
+        if let Some(data) = data {
+            bytecode.extend(data.into_bytes()?);
+        }
+        Ok(Self {
+            owner: owner.map(|owner| owner.address()).transpose()?,
+            bytecode,
+            value: value
+                .map(|value| value.value())
+                .transpose()?
+                .unwrap_or_default(),
+            init_value: init_value.map(|value| value.value()).transpose()?,
+            force_address: deploy_to_address
+                .map(|address| address.address())
+                .transpose()?,
+        })
+    }
+
+    /// Deploy on `vm`, then set the initial balance of the contract if any
+    fn run(&self, vm: &mut TinyEVM) -> Result<Response> {
+        let owner = self.owner.unwrap_or(vm.owner);
+        let resp = vm.deploy_helper(
+            owner,
+            self.bytecode.clone(),
+            self.value,
+            None,
+            self.force_address,
+        )?;
+        if let (true, Some(balance)) = (resp.success, self.init_value) {
+            vm.set_account_balance(Address::from_slice(&resp.data), balance)?;
+        }
+        Ok(resp)
+    }
+}
+
+#[cfg(feature = "python")]
+impl TinyEVM {
+    /// Awaitable of the response of `job`, run on the instance `slf` in the
+    /// default executor of the running event loop and converted by
+    /// `into_py`. The GIL is released while the transaction executes, so the
+    /// loop keeps running
+    fn run_in_executor<'py, F>(
+        slf: &Bound<'py, Self>,
+        job: F,
+        into_py: fn(Python<'_>, Response) -> PyResult<PyObject>,
+    ) -> PyResult<Bound<'py, PyAny>>
+    where
+        F: Fn(&mut TinyEVM) -> Result<Response> + Send + Sync + 'static,
+    {
+        let py = slf.py();
+        let vm = slf.clone().unbind();
+        let job = PyCFunction::new_closure(py, None, None, move |args, _| -> PyResult<PyObject> {
+            let py = args.py();
+            let mut vm = vm.bind(py).try_borrow_mut()?;
+            let vm = &mut *vm;
+            let _guard = vm.trace_guard();
+            let resp = py.allow_threads(|| job(vm)).map_err(PyEvmError::from)?;
+            vm.check_opcode_hook().map_err(PyEvmError::from)?;
+            vm.check_trace_hook().map_err(PyEvmError::from)?;
+            into_py(py, resp)
+        })?;
+        let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+        event_loop.call_method1("run_in_executor", (py.None(), job))
+    }
+}
+
 // Implementations for use in Python and Rust
+#[cfg(feature = "python")]
 #[pymethods]
 impl TinyEVM {
-    /// Create a new TinyEVM instance
+    /// Create a new TinyEVM instance with the options of `config`,
+    /// `fork_url` and `block_id` override the ones of `config` if provided
     #[new]
-    #[pyo3(signature = (fork_url = None, block_id = None))]
-    pub fn new(fork_url: Option<String>, block_id: Option<u64>) -> Result<Self> {
-        Self::new_instance(fork_url, block_id, false)
+    #[pyo3(signature = (fork_url = None, block_id = None, config = None))]
+    pub fn new(
+        fork_url: Option<String>,
+        block_id: Option<u64>,
+        config: Option<PyRef<TinyEVMConfig>>,
+    ) -> PyEvmResult<Self> {
+        let mut config = match config {
+            Some(config) => config.clone_ref(config.py()),
+            None => TinyEVMConfig::default(),
+        };
+        if fork_url.is_some() {
+            config.fork_url = fork_url;
+        }
+        if block_id.is_some() {
+            config.block_id = block_id;
+        }
+        Ok(Self::with_config(&config)?)
     }
 
     /// Get addresses loaded remotely as string
-    pub fn get_forked_addresses(&self) -> Result<Vec<String>> {
+    pub fn get_forked_addresses(&self) -> PyEvmResult<Vec<String>> {
+        let _guard = self.trace_guard();
         let db = &self.exe.as_ref().unwrap().context.evm.db;
         let addresses = &db.remote_addresses;
         addresses.keys().map(|a| Ok(format!("0x{:x}", a))).collect()
     }
 
     /// Get remotely loaded slot indices by address
-    pub fn get_forked_slots(&self, address: String) -> Result<Vec<BigInt>> {
-        let address = Address::from_str(&address)?;
+    pub fn get_forked_slots(&self, address: AddressArg) -> PyEvmResult<Vec<PyU256>> {
+        let _guard = self.trace_guard();
+        let address = address.address()?;
         let db = &self.exe.as_ref().unwrap().context.evm.db;
         db.remote_addresses.get(&address).map_or_else(
             || Ok(vec![]),
-            |slots| Ok(slots.iter().map(ruint_u256_to_bigint).collect::<Vec<_>>()),
+            |slots| Ok(slots.iter().map(PyU256::from).collect::<Vec<_>>()),
         )
     }
 
     /// Toggle for enable mode, only makes sense when fork_url is set
     pub fn toggle_enable_fork(&mut self, enabled: bool) {
+        let _guard = self.trace_guard();
         let db = &mut self.exe.as_mut().unwrap().context.evm.db;
         db.fork_enabled = enabled;
     }
 
+    /// Toggle the inspectors, disabling them runs the transactions without
+    /// any instrumentation, see `set_inspector_enabled`
+    pub fn toggle_inspector(&mut self, enabled: bool) {
+        let _guard = self.trace_guard();
+        self.set_inspector_enabled(enabled);
+    }
+
+    /// Toggle the Foundry cheatcodes at `0x7109709ECfa91a80626fF3989D68f67F5b1DD12D`:
+    /// `warp`, `roll`, `deal`, `load`, `store`, `prank`, `startPrank`,
+    /// `stopPrank` and `expectRevert`, see `set_cheatcodes_enabled`
+    pub fn toggle_cheatcodes(&mut self, enabled: bool) {
+        let _guard = self.trace_guard();
+        self.set_cheatcodes_enabled(enabled);
+    }
+
     /// Set whether to log the traces of the EVM execution
     pub fn set_evm_tracing(&mut self, enabled: bool) {
+        let _guard = self.trace_guard();
         let log_inspector = self.log_inspector_mut();
         log_inspector.trace_enabled = enabled;
     }
 
+    /// Print the logs of this instance at `level`, or filtered by the
+    /// `filter` directives in the `RUST_LOG` format. They are written to
+    /// `path` instead of stdout if provided, as JSON lines if `json` is set.
+    /// If `logger` is given, the logs are sent to the Python logger of this
+    /// name instead, `TRACE` being logged at level 5, below `logging.DEBUG`.
+    /// Replaces the previous configuration
+    #[pyo3(signature = (level = "trace", filter = None, path = None, json = false, logger = None))]
+    pub fn enable_tracing(
+        &mut self,
+        level: &str,
+        filter: Option<String>,
+        path: Option<String>,
+        json: bool,
+        logger: Option<String>,
+    ) -> PyEvmResult<()> {
+        let config = TraceConfig {
+            level: level.into(),
+            filter,
+            path,
+            json,
+            logger,
+        };
+        self.tracing = Some(config.dispatch()?);
+        Ok(())
+    }
+
+    /// Stop printing the logs of this instance
+    pub fn disable_tracing(&mut self) {
+        self.tracing = None;
+    }
+
+    /// Capture the stack, the storage and the memory ranges `(offset,
+    /// length)` of the frames reaching `pc` in the code at `address`, see
+    /// `Response.breakpoint_hits`
+    #[pyo3(signature = (address, pc, memory_ranges=vec![]))]
+    pub fn add_breakpoint(
+        &mut self,
+        address: AddressArg,
+        pc: usize,
+        memory_ranges: Vec<(usize, usize)>,
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let address = address.address()?;
+        self.log_inspector_mut()
+            .breakpoints
+            .insert((address, pc), memory_ranges);
+        Ok(())
+    }
+
+    /// Remove all the breakpoints
+    pub fn clear_breakpoints(&mut self) {
+        let _guard = self.trace_guard();
+        self.log_inspector_mut().breakpoints.clear();
+    }
+
+    /// Select the call traces returned in the responses of the next
+    /// transactions, by default all the traces are returned:
+    /// - `addresses`: calls from or to any of these addresses
+    /// - `max_depth`: calls up to this depth, the transaction being at depth 0
+    /// - `value_transfers_only`: calls transferring value
+    /// - `failed_only`: calls which did not succeed
+    #[pyo3(signature = (addresses=vec![], max_depth=None, value_transfers_only=false, failed_only=false))]
+    pub fn set_trace_filter(
+        &mut self,
+        addresses: Vec<AddressArg>,
+        max_depth: Option<usize>,
+        value_transfers_only: bool,
+        failed_only: bool,
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addresses = addresses
+            .iter()
+            .map(AddressArg::address)
+            .collect::<Result<_, _>>()?;
+        self.log_inspector_mut().trace_filter = TraceFilter {
+            addresses,
+            max_depth,
+            value_transfers_only,
+            failed_only,
+        };
+        Ok(())
+    }
+
+    /// Keep at most `max_len` bytes of the inputs and the return data of
+    /// the next call traces, `None` keeps them whole
+    #[pyo3(signature = (max_len=None))]
+    pub fn set_trace_data_limit(&mut self, max_len: Option<usize>) {
+        let _guard = self.trace_guard();
+        self.log_inspector_mut().max_trace_data_len = max_len;
+    }
+
+    /// Set whether to record every execution step, with the stack and the
+    /// memory if `stack` and `memory` are set, see `Response.struct_logs`
+    #[pyo3(signature = (enabled, stack=true, memory=false))]
+    pub fn set_struct_logging(&mut self, enabled: bool, stack: bool, memory: bool) {
+        let _guard = self.trace_guard();
+        let log_inspector = self.log_inspector_mut();
+        log_inspector.struct_log_enabled = enabled;
+        log_inspector.struct_log_stack = stack;
+        log_inspector.struct_log_memory = memory;
+    }
+
     /// Get the current fork toggle status
     pub fn is_fork_enabled(&self) -> bool {
+        let _guard = self.trace_guard();
         let db = &self.exe.as_ref().unwrap().context.evm.db;
         db.fork_enabled
     }
 
+    /// Default gas limit for each transaction
+    #[getter]
+    pub fn get_tx_gas_limit(&self) -> u64 {
+        self.tx_gas_limit
+    }
+
+    #[setter]
+    pub fn set_tx_gas_limit(&mut self, tx_gas_limit: u64) {
+        self.tx_gas_limit = tx_gas_limit;
+    }
+
     /// Deploy a contract using contract deploy binary
     ///
-    /// - `contract_deploy_code`: contract deploy binary array encoded as hex string
-    /// - `owner`: owner address as a 20-byte array encoded as hex string
-    #[pyo3(signature = (contract_deploy_code, owner=None))]
+    /// - `contract_deploy_code`: contract deploy binary as bytes or encoded as hex string
+    /// - `owner`: owner address as 20 bytes or encoded as hex string
+    /// - `data`: (Optional, default empty) Constructor arguments as bytes or encoded as hex string
+    /// - `value`: (Optional, default 0) value sent with the contract creation, U256 as int or hex
+    /// - `init_value`: (Optional) U256 as int or hex string. Override the initial balance of the contract to this value
+    /// - `deploy_to_address`: (Optional) deploy the contract to this address instead of the `CREATE` address
+    ///
+    /// Returns a `DeployResult`
+    #[pyo3(signature = (contract_deploy_code, owner=None, data=None, value=None, init_value=None, deploy_to_address=None))]
+    #[allow(clippy::too_many_arguments)]
     pub fn deploy(
         &mut self,
-        contract_deploy_code: String,
-        owner: Option<String>,
-    ) -> Result<Response> {
-        let owner = owner
-            .map(|address| Address::from_str(&address))
-            .unwrap_or(Ok(self.owner))?;
-        self.deploy_helper(
-            // Address::from_str(&owner.unwrap_or_default())?,
+        py: Python<'_>,
+        contract_deploy_code: BytesArg,
+        owner: Option<AddressArg>,
+        data: Option<BytesArg>,
+        value: Option<U256Arg>,
+        init_value: Option<U256Arg>,
+        deploy_to_address: Option<AddressArg>,
+    ) -> PyEvmResult<Py<DeployResult>> {
+        let _guard = self.trace_guard();
+        let deployment = Deployment::new(
+            contract_deploy_code,
             owner,
-            hex::decode(contract_deploy_code)?,
-            U256::default(),
-            None,
-            None,
-        )
+            data,
+            value,
+            init_value,
+            deploy_to_address,
+        )?;
+        let resp = deployment.run(self)?;
+        self.check_opcode_hook()?;
+        self.check_trace_hook()?;
+        Ok(DeployResult::new_py(py, resp)?)
+    }
+
+    /// Awaitable of the `DeployResult` of `deploy`, with the same
+    /// parameters. The deployment runs in the default executor of the
+    /// running event loop, see `contract_call_async`
+    #[pyo3(signature = (contract_deploy_code, owner=None, data=None, value=None, init_value=None, deploy_to_address=None))]
+    pub fn deploy_async<'py>(
+        slf: &Bound<'py, Self>,
+        contract_deploy_code: BytesArg,
+        owner: Option<AddressArg>,
+        data: Option<BytesArg>,
+        value: Option<U256Arg>,
+        init_value: Option<U256Arg>,
+        deploy_to_address: Option<AddressArg>,
+    ) -> PyEvmResult<Bound<'py, PyAny>> {
+        let deployment = Deployment::new(
+            contract_deploy_code,
+            owner,
+            data,
+            value,
+            init_value,
+            deploy_to_address,
+        )?;
+        let job = move |vm: &mut TinyEVM| deployment.run(vm);
+        Ok(Self::run_in_executor(slf, job, |py, resp| {
+            Ok(DeployResult::new_py(py, resp)?.into_any())
+        })?)
     }
 
     /// Deploy a contract using contract deploy binary If the account already
@@ -699,47 +1506,51 @@ impl TinyEVM {
     ///
     /// For optional arguments, you can use the empty string as inputs to use the default values.
     ///
-    /// - `contract_deploy_code`: contract deploy binary array encoded as hex string
-    /// - `owner`: Owner address as a 20-byte array encoded as hex string
-    /// - `data`: (Optional, default empty) Constructor arguments encoded as hex string.
-    /// - `value`: (Optional, default 0) a U256. Set the value to be included in the contract creation transaction.
+    /// - `contract_deploy_code`: contract deploy binary as bytes or encoded as hex string
+    /// - `owner`: Owner address as 20 bytes or encoded as hex string
+    /// - `data`: (Optional, default empty) Constructor arguments as bytes or encoded as hex string.
+    /// - `value`: (Optional, default 0) a U256 as int or hex string. Set the value to be included in the contract creation transaction.
     /// - `deploy_to_address`: when provided, change the address of the deployed contract to this address, otherwise deploy to a an address created using `owner.CREATE2(a_fixed_salt, codehash)`.
-
+    ///
     ///   - This requires the constructor to be payable.
     ///   - The transaction sender (owner) must have enough balance
-    /// - `init_value`: (Optional) BigInt. Override the initial balance of the contract to this value.
+    /// - `init_value`: (Optional) U256 as int or hex string. Override the initial balance of the contract to this value.
     ///
-    /// Returns a list consisting of 4 items `[reason, address-as-byte-array, bug_data, heuristics]`
+    /// Returns a `DeployResult`
     #[pyo3(signature = (contract_deploy_code, salt=None, owner=None, data=None, value=None, init_value=None, deploy_to_address=None))]
     #[allow(clippy::too_many_arguments)]
     pub fn deterministic_deploy(
         &mut self,
-        contract_deploy_code: String, // variable length
+        py: Python<'_>,
+        contract_deploy_code: BytesArg, // variable length
         salt: Option<String>, // h256 as hex string, has no effect if deploy_to_address is provided
-        owner: Option<String>, // h160 as hex string
-        data: Option<String>, // variable length
-        value: Option<BigInt>,
-        init_value: Option<BigInt>,
-        deploy_to_address: Option<String>,
-    ) -> Result<Response> {
+        owner: Option<AddressArg>, // h160
+        data: Option<BytesArg>, // variable length
+        value: Option<U256Arg>,
+        init_value: Option<U256Arg>,
+        deploy_to_address: Option<AddressArg>,
+    ) -> PyEvmResult<Py<DeployResult>> {
+        let _guard = self.trace_guard();
         let owner = {
             if let Some(owner) = owner {
-                let owner = &owner;
-                Address::from_str(trim_prefix(owner, "0x"))?
+                owner.address()?
             } else {
                 self.owner
             }
         };
 
-        let contract_deploy_code = hex::decode(contract_deploy_code)?;
+        let contract_deploy_code = contract_deploy_code.into_bytes()?;
         let data = {
             if let Some(data) = data {
-                hex::decode(data)?
+                data.into_bytes()?
             } else {
                 vec![]
             }
         };
-        let value = value.unwrap_or_default();
+        let value = value
+            .map(|value| value.value())
+            .transpose()?
+            .unwrap_or_default();
         let mut contract_bytecode = contract_deploy_code.to_vec();
         contract_bytecode.extend(data);
 
@@ -752,7 +1563,7 @@ impl TinyEVM {
         };
 
         let force_address: Address = deploy_to_address
-            .map(|s| Address::from_str(&s))
+            .map(|address| address.address())
             .transpose()?
             .unwrap_or_else(|| {
                 let codehash = keccak256(&contract_bytecode);
@@ -760,65 +1571,61 @@ impl TinyEVM {
             });
 
         let resp = {
-            let resp = self.deploy_helper(
-                owner,
-                contract_bytecode,
-                bigint_to_ruint_u256(&value)?,
-                None,
-                Some(force_address),
-            )?;
+            let resp =
+                self.deploy_helper(owner, contract_bytecode, value, None, Some(force_address))?;
+            self.check_opcode_hook()?;
+            self.check_trace_hook()?;
 
             if resp.success {
                 if let Some(balance) = init_value {
                     let address = Address::from_slice(&resp.data);
-                    self.set_account_balance(address, bigint_to_ruint_u256(&balance)?)?;
+                    self.set_account_balance(address, balance.value()?)?;
                 }
             }
 
             resp
         };
 
-        Ok(resp)
+        Ok(DeployResult::new_py(py, resp)?)
     }
 
-    /// - `contract` null ended c string of contract address encoded as hex
-    /// - `sender` null ended c string of sender address (20 bytes) encoded as hex
-    /// - `data` null ended c string of encoded contract method plus parameters
-    /// - `value` value send in the transaction, U256 as hex
+    /// - `contract` contract address as 20 bytes or encoded as hex
+    /// - `sender` sender address as 20 bytes or encoded as hex
+    /// - `data` encoded contract method plus parameters, as bytes or encoded as hex
+    /// - `value` value send in the transaction, U256 as int or hex
     ///
     /// Returns c string of Json encoded response consists of a list of four elements:
     /// `[reason, data, bug_data, heuristics]`
     #[pyo3(signature = (contract, sender=None, data=None, value=None))]
     pub fn contract_call(
         &mut self,
-        contract: String,
-        sender: Option<String>,
-        data: Option<String>,
-        value: Option<BigInt>,
-    ) -> Result<Response> {
+        contract: AddressArg,
+        sender: Option<AddressArg>,
+        data: Option<BytesArg>,
+        value: Option<U256Arg>,
+    ) -> PyEvmResult<Response> {
+        let _guard = self.trace_guard();
         let sender = {
             if let Some(sender) = sender {
-                let sender = &sender;
-                Address::from_str(trim_prefix(sender, "0x"))?
+                sender.address()?
             } else {
                 self.owner
             }
         };
 
-        let contract = {
-            let contract = &contract;
-            Address::from_str(trim_prefix(contract, "0x"))?
-        };
+        let contract = contract.address()?;
 
         let data = {
             if let Some(data) = data {
-                hex::decode(data)?
+                data.into_bytes()?
             } else {
                 vec![]
             }
         };
-        let value = value.unwrap_or_default();
-        let value = bigint_to_ruint_u256(&value)?;
+        let value = value
+            .map(|value| value.value())
+            .transpose()?
+            .unwrap_or_default();
         debug!(
             "contract_call: contract {} sender {} data {} value {}",
             contract,
@@ -828,12 +1635,145 @@ impl TinyEVM {
         );
 
         let resp = self.contract_call_helper(contract, sender, data, value, None);
+        self.check_opcode_hook()?;
+        self.check_trace_hook()?;
 
         Ok(resp)
     }
 
+    /// Awaitable of the response of `contract_call`, the call runs in the
+    /// default executor of the running event loop. An instance runs one
+    /// call or deployment at a time, awaiting another one in the meantime
+    /// raises a `RuntimeError`
+    #[pyo3(signature = (contract, sender=None, data=None, value=None))]
+    pub fn contract_call_async<'py>(
+        slf: &Bound<'py, Self>,
+        contract: AddressArg,
+        sender: Option<AddressArg>,
+        data: Option<BytesArg>,
+        value: Option<U256Arg>,
+    ) -> PyEvmResult<Bound<'py, PyAny>> {
+        let contract = contract.address()?;
+        let sender = sender.map(|sender| sender.address()).transpose()?;
+        let data = data
+            .map(|data| data.into_bytes())
+            .transpose()?
+            .unwrap_or_default();
+        let value = value
+            .map(|value| value.value())
+            .transpose()?
+            .unwrap_or_default();
+        let job = move |vm: &mut TinyEVM| {
+            let sender = sender.unwrap_or(vm.owner);
+            Ok(vm.contract_call_helper(contract, sender, data.clone(), value, None))
+        };
+        Ok(Self::run_in_executor(slf, job, |py, resp| {
+            resp.into_py_any(py)
+        })?)
+    }
+
+    /// Warm up the provider cache for strictly offline runs later.
+    ///
+    /// - `txs`: list of on-chain transaction hashes, or `(contract, calldata)`
+    ///   pairs sent by the owner. Calldata is hex encoded and optional.
+    ///
+    /// The transactions are executed once against the fork, all the
+    /// accounts, storage slots and blocks they touch are persisted in the
+    /// provider cache, then the EVM state is restored. Returns the response
+    /// of each transaction.
+    pub fn warm_cache(&mut self, txs: Vec<WarmupTx>) -> PyEvmResult<Vec<Response>> {
+        let _guard = self.trace_guard();
+        Ok(self.warm_cache_helper(&txs)?)
+    }
+
+    /// Use a Python object as the storage of the provider cache, replacing
+    /// the builtin filesystem/redis cache. Only makes sense when fork_url is set.
+    ///
+    /// - `backend`: object with methods `store(chain, block, api, request_hash, response)`
+    ///   and `get(chain, block, api, request_hash)` returning the stored
+    ///   bytes or `None`, and optionally `flush()`. `request_hash` is the 32
+    ///   bytes keccak256 hash of the request. Pass `None` to restore the
+    ///   builtin cache.
+    ///
+    /// Snapshots taken before keep the previous cache.
+    #[pyo3(signature = (backend = None))]
+    pub fn set_cache_backend(&mut self, backend: Option<PyObject>) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let cache = match backend {
+            Some(backend) => DefaultProviderCache::python(backend),
+            None => DefaultProviderCache::default(),
+        };
+        Ok(self.db_mut().set_provider_cache(cache)?)
+    }
+
+    /// Call a Python function before each execution of the selected opcodes,
+    /// e.g. to implement custom oracles.
+    ///
+    /// - `callback`: `callback(address, pc, opcode, stack)` where `address`
+    ///   is the hex encoded contract address and `stack` the list of inputs
+    ///   of the opcode, top of the stack first. Pass `None` to remove the hook.
+    /// - `opcodes`: allowlist of opcodes the callback is called on, required
+    ///   to keep the overhead bounded
+    ///
+    /// An exception raised by the callback stops further calls and is
+    /// raised by the `deploy` or `contract_call` which executed the opcode.
+    #[pyo3(signature = (callback = None, opcodes = vec![]))]
+    pub fn set_opcode_hook(
+        &mut self,
+        callback: Option<PyObject>,
+        opcodes: Vec<u8>,
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let hooks = match callback {
+            Some(_) if opcodes.is_empty() => {
+                return Err(eyre!("An allowlist of opcodes is required"))?;
+            }
+            Some(callback) => Some(OpcodeHooks::new(Box::new(callback), &opcodes)),
+            None => None,
+        };
+        self.set_opcode_hooks(hooks);
+        Ok(())
+    }
+
+    /// Call a Python function before each execution of the selected opcodes
+    /// with `callback(address, pc, opcode, gas)`, where `address` is the hex
+    /// encoded contract address and `gas` the gas remaining in the frame.
+    /// It replaces the hook of `set_opcode_hook`, and the same allowlist and
+    /// exceptions rules apply. Pass `None` to remove the hook.
+    #[pyo3(signature = (callback = None, opcodes = vec![]))]
+    pub fn set_step_hook(
+        &mut self,
+        callback: Option<PyObject>,
+        opcodes: Vec<u8>,
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let hooks = match callback {
+            Some(_) if opcodes.is_empty() => {
+                return Err(eyre!("An allowlist of opcodes is required"))?;
+            }
+            Some(callback) => Some(OpcodeHooks::new(Box::new(PyStepHook(callback)), &opcodes)),
+            None => None,
+        };
+        self.set_opcode_hooks(hooks);
+        Ok(())
+    }
+
+    /// Set a Python callback called with each `PyCallTrace` once the call
+    /// returns and each `PyLog` once emitted, while the transaction runs.
+    /// Requires `set_evm_tracing(True)`. Pass `None` to remove the callback.
+    ///
+    /// The transaction reverts if the callback returns `False` or raises an
+    /// exception, which is then raised by the `deploy` or `contract_call`.
+    #[pyo3(signature = (callback = None))]
+    pub fn set_trace_hook(&mut self, callback: Option<PyObject>) {
+        let _guard = self.trace_guard();
+        let hook = callback.map(|callback| Box::new(callback) as Box<dyn TraceHook>);
+        self.set_boxed_trace_hook(hook);
+    }
+
     /// Reset EVM state
-    pub fn reset(&mut self) -> Result<()> {
+    pub fn reset(&mut self) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
         self.owner = Address::ZERO;
         // TODO reset db and env
 
@@ -850,38 +1790,228 @@ impl TinyEVM {
     }
 
     /// Return account's balance in wei
-    pub fn get_balance(&mut self, addr: String) -> Result<BigInt> {
-        let addr = Address::from_str(trim_prefix(&addr, "0x"))?;
+    pub fn get_balance(&mut self, addr: AddressArg) -> PyEvmResult<PyU256> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
 
         let balance = self.get_eth_balance(addr)?;
-        let balance = ruint_u256_to_bigint(&balance);
 
-        Ok(balance)
+        Ok(PyU256(balance))
     }
 
     /// Set account's balance
-    pub fn set_balance(&mut self, addr: String, balance: BigInt) -> Result<()> {
-        let addr = Address::from_str(trim_prefix(&addr, "0x"))?;
+    pub fn set_balance(&mut self, addr: AddressArg, balance: U256Arg) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
 
-        let balance = bigint_to_ruint_u256(&balance)?;
+        let balance = balance.value()?;
 
-        self.set_account_balance(addr, balance)
+        Ok(self.set_account_balance(addr, balance)?)
     }
 
     /// Get account's code
-    pub fn get_code(&mut self, addr: String) -> Result<String> {
-        let addr = Address::from_str(&addr)?;
+    pub fn get_code(&mut self, addr: AddressArg) -> PyEvmResult<String> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
 
         let code: String = self.get_code_by_address(addr)?.encode_hex();
         Ok(code)
     }
 
+    /// Coverage of an address as a read-only buffer of the seen-PC bitset,
+    /// see `coverage_bitmap`. It supports the buffer protocol, e.g.
+    /// `numpy.unpackbits(numpy.frombuffer(buf, dtype=numpy.uint8), bitorder="little")`
+    pub fn coverage_buffer(&mut self, addr: AddressArg) -> PyEvmResult<CoverageBuffer> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        Ok(CoverageBuffer::new(self.coverage_bitmap(addr)?))
+    }
+
+    /// Branches `(jumpi pc, target pc)` of the code at an address not
+    /// taken yet. Requires `REVMConfig.pcs_by_address`
+    pub fn uncovered_branches(&mut self, addr: AddressArg) -> PyEvmResult<Vec<(usize, usize)>> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        Ok(self.uncovered_branches_by_address(addr)?)
+    }
+
+    /// Basic blocks and edges of the code at an address. The jumps with a
+    /// destination not known statically are added once taken, this requires
+    /// `REVMConfig.pcs_by_address`
+    pub fn extract_cfg(&mut self, addr: AddressArg) -> PyEvmResult<ControlFlowGraph> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        Ok(self.control_flow_graph_by_address(addr)?)
+    }
+
+    /// Decode the events emitted by `address`, or by any address if
+    /// `None`, with a JSON ABI, see `PyLog.event` and `PyLog.args`. The
+    /// ABI is the list of items or a compiler artifact with an `abi` field.
+    #[pyo3(signature = (abi, address=None))]
+    pub fn add_event_abi(&mut self, abi: String, address: Option<AddressArg>) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let address = match address {
+            Some(address) => Some(address.address()?),
+            None => None,
+        };
+        Ok(self.event_decoder.add_abi(address, &abi)?)
+    }
+
+    /// Set the solc source map (`srcmap-runtime`) of the code at an
+    /// address. `sources` is a list of `(path, content)` ordered by source id.
+    pub fn set_source_map(
+        &mut self,
+        addr: AddressArg,
+        source_map: String,
+        sources: Vec<(String, String)>,
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        Ok(self.set_source_map_by_address(addr, &source_map, sources)?)
+    }
+
+    /// Source location of a PC of the code at an address, requires `set_source_map`
+    pub fn source_location(
+        &self,
+        addr: AddressArg,
+        pc: usize,
+    ) -> PyEvmResult<Option<SourceLocation>> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        Ok(self.source_location_by_address(addr, pc))
+    }
+
+    /// Source locations of a list of PCs, e.g. the coverage of an address
+    pub fn source_locations(
+        &self,
+        addr: AddressArg,
+        pcs: Vec<usize>,
+    ) -> PyEvmResult<Vec<Option<SourceLocation>>> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        Ok(pcs
+            .into_iter()
+            .map(|pc| self.source_location_by_address(addr, pc))
+            .collect())
+    }
+
+    /// Source locations of the bugs of a response, in the order of `bug_data`
+    pub fn bug_locations(&self, resp: PyRef<Response>) -> Vec<Option<SourceLocation>> {
+        let _guard = self.trace_guard();
+        self.bug_source_locations(&resp)
+    }
+
+    /// Source locations of the JUMPIs of the missed branches of a response
+    pub fn missed_branch_locations(&self, resp: PyRef<Response>) -> Vec<Option<SourceLocation>> {
+        let _guard = self.trace_guard();
+        self.missed_branch_source_locations(&resp)
+    }
+
+    /// LCOV report of the coverage of the sources set by `set_source_map`,
+    /// written to `path` if provided
+    #[pyo3(signature = (path=None))]
+    pub fn coverage_lcov(&self, path: Option<String>) -> PyEvmResult<String> {
+        let _guard = self.trace_guard();
+        let lcov = self.coverage_report().to_lcov();
+        if let Some(path) = path {
+            std::fs::write(path, &lcov)?;
+        }
+        Ok(lcov)
+    }
+
+    /// HTML page with the coverage of the sources set by `set_source_map`,
+    /// written to `path` if provided
+    #[pyo3(signature = (path=None))]
+    pub fn coverage_html(&self, path: Option<String>) -> PyEvmResult<String> {
+        let _guard = self.trace_guard();
+        let html = self.coverage_report().to_html();
+        if let Some(path) = path {
+            std::fs::write(path, &html)?;
+        }
+        Ok(html)
+    }
+
+    /// Register a view call to `contract` with `data`, e.g. the selector
+    /// of a property function, expected to return the `expected` output
+    /// after every deployment and contract call. The data and the output
+    /// are bytes or hex encoded. Violations are reported in
+    /// `Response.invariant_violations`.
+    pub fn add_invariant(
+        &mut self,
+        contract: AddressArg,
+        data: BytesArg,
+        expected: BytesArg,
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        self.push_invariant(Invariant {
+            contract: contract.address()?,
+            data: data.into_bytes()?,
+            expected: expected.into_bytes()?,
+        });
+        Ok(())
+    }
+
+    /// Remove all the invariants
+    pub fn clear_invariants(&mut self) {
+        let _guard = self.trace_guard();
+        self.invariants.clear();
+    }
+
+    /// Run a sequence of transactions `(sender, contract, data, value)`,
+    /// `data` as bytes or hex encoded, and return the pairs of transactions
+    /// whose outcome changes when they are reordered, see `find_tod_pairs`
+    pub fn find_tod(
+        &mut self,
+        txs: Vec<(AddressArg, AddressArg, BytesArg, U256Arg)>,
+    ) -> PyEvmResult<Vec<WrappedTodPair>> {
+        let _guard = self.trace_guard();
+        let txs = txs
+            .into_iter()
+            .map(|(sender, contract, data, value)| {
+                Ok(TodTx {
+                    caller: sender.address()?,
+                    contract: contract.address()?,
+                    data: data.into_bytes()?,
+                    value: value.value()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(self.find_tod_pairs(&txs)?.iter().map(Into::into).collect())
+    }
+
+    /// Run the independent calls `(sender, contract, data, value)` in
+    /// parallel from the current state, which is not modified, and return
+    /// their responses in order. The hooks and the invariants of this
+    /// instance are not used, see `run_parallel`
+    pub fn execute_parallel(
+        &self,
+        py: Python<'_>,
+        txs: Vec<(AddressArg, AddressArg, BytesArg, U256Arg)>,
+    ) -> PyEvmResult<Vec<Response>> {
+        let _guard = self.trace_guard();
+        let txs = txs
+            .into_iter()
+            .map(|(sender, contract, data, value)| {
+                Ok(ParallelTx {
+                    caller: sender.address()?,
+                    contract: contract.address()?,
+                    data: data.into_bytes()?,
+                    value: value.value()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let seed = self.seed();
+        // The workers take the GIL to use a cache backend implemented in Python
+        Ok(py.allow_threads(|| seed.run_parallel(&txs))?)
+    }
+
     /// Set account's code (runtime-binary). Will create the account
     /// if it does not exist
-    pub fn set_code(&mut self, addr: String, data: String) -> Result<()> {
-        let addr = Address::from_str(&addr)?;
+    pub fn set_code(&mut self, addr: AddressArg, data: BytesArg) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
 
-        let data = hex::decode(data)?;
+        let data = data.into_bytes()?;
         self.set_code_by_address(addr, data)?;
         Ok(())
     }
@@ -901,7 +2031,8 @@ impl TinyEVM {
     /// - `block_gas_limit`: U256 as hex string
     /// - `block_base_fee_per_gas`: U256 as hex string
     /// - `block_hashes`: not supported
-    pub fn get_env_value_by_field(&self, field: String) -> Result<String> {
+    pub fn get_env_value_by_field(&self, field: String) -> PyEvmResult<String> {
+        let _guard = self.trace_guard();
         let exe = &self.exe.as_ref().unwrap();
         macro_rules! hex2str {
             ($val:expr) => {
@@ -920,7 +2051,7 @@ impl TinyEVM {
             BLOCK_BASE_FEE_PER_GAS => hex2str!(exe.block().basefee),
             ORIGIN => format!("0x{}", hex::encode(exe.tx().caller)),
             BLOCK_COINBASE => format!("0x{}", hex::encode(exe.block().coinbase)),
-            _ => return Err(eyre!("Unknown field: {}", &field)),
+            _ => return Err(eyre!("Unknown field: {}", &field))?,
         };
         Ok(r)
     }
@@ -940,7 +2071,8 @@ impl TinyEVM {
     /// - `block_gas_limit`: U256 as hex string
     /// - `block_base_fee_per_gas`: U256 as hex string
     /// - `block_hashes`: not supported
-    pub fn set_env_field_value(&mut self, field: String, value: String) -> Result<()> {
+    pub fn set_env_field_value(&mut self, field: String, value: String) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
         self.set_env_field_value_inner(&field, &value)
     }
 
@@ -948,26 +2080,29 @@ impl TinyEVM {
     /// Supported fields:
     ///
     /// - `config`: A json string serialized for [`InstrumentConfig`](https://github.com/sbip-sg/revm/blob/6f7ac687a22f67462999ca132ede8d116bd7feb9/crates/revm/src/bug.rs#L153)
-    pub fn configure(&mut self, config: &REVMConfig) -> Result<()> {
+    pub fn configure(&mut self, config: &REVMConfig) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
         let config = config.to_iconfig()?;
         self.bug_inspector_mut().instrument_config = config;
         Ok(())
     }
 
     /// Get current runtime instrumentation configuration
-    pub fn get_instrument_config(&self) -> Result<REVMConfig> {
+    pub fn get_instrument_config(&self) -> PyEvmResult<REVMConfig> {
+        let _guard = self.trace_guard();
         let r = &self.bug_inspector().instrument_config;
         Ok(REVMConfig::from(r))
     }
 
     /// Set EVM env field value. Value is hex encoded string
-    pub fn set_env_field_value_inner(&mut self, field: &str, value: &str) -> Result<()> {
+    pub fn set_env_field_value_inner(&mut self, field: &str, value: &str) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
         debug!("set_env_field_value_inner: {} {}", field, value);
 
         let value = trim_prefix(value, "0x");
 
         let to_u256 = |v: &str| U256::from_str_radix(v, 16);
-        let to_address = |v: &str| Address::from_str(v);
+        let to_address = |v: &str| parse_address(v);
 
         macro_rules! set_env_field {
             ($field:ident, $value:expr, $env:ident, $method:ident) => {{
@@ -996,26 +2131,27 @@ impl TinyEVM {
 
     /// API to set tx origin, after this method call, tx.origin will always return the set address.
     /// This function should be called after EVM executor is created.
-    pub fn set_tx_origin(&mut self, address: String) -> Result<()> {
-        let address = &address;
-        self.set_env_field_value_inner(ORIGIN, address)
+    pub fn set_tx_origin(&mut self, address: AddressArg) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let address = format!("{:#x}", address.address()?);
+        self.set_env_field_value_inner(ORIGIN, &address)
     }
 
     /// API to get the owner (default sender) address
-    pub fn get_owner(&self) -> Result<String> {
+    pub fn get_owner(&self) -> PyEvmResult<String> {
+        let _guard = self.trace_guard();
         Ok(format!("{:#066x}", self.owner))
     }
 
     /// Set the owner (default sender) address
-    pub fn set_owner(&mut self, owner: String) -> Result<()> {
-        let owner = &owner;
-        let owner = Address::from_str(trim_prefix(owner, "0x"))?;
-        self.owner = owner;
+    pub fn set_owner(&mut self, owner: AddressArg) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        self.owner = owner.address()?;
         Ok(())
     }
 
     // /// Get current env from EVM executor as JSON string
-    // pub fn get_env(&mut self) -> Result<String> {
+    // pub fn get_env(&mut self) -> PyEvmResult<String> {
     //     let env = self.exe.env.clone();
     // serde json has been removed
     //     let s = serde_json::to_string(&env)?;
@@ -1024,149 +2160,220 @@ impl TinyEVM {
 
     /// Set account's storage by index
     ///
-    /// - `addr`: H160 address as hex string
-    /// - `index`: H256 as hex string
-    /// - `value`: H256 as hex string
+    /// - `addr`: H160 address as bytes or hex string
+    /// - `index`: H256 as int or hex string
+    /// - `value`: H256 as int or hex string
+    /// - `local_only`: do not load the account from the fork, a missing
+    ///   account is created empty
+    #[pyo3(signature = (addr, index, value, local_only=false))]
     pub fn set_storage(
         &mut self,
-        addr: String,  // address as H160, encoded as hex
-        index: String, // index as H256, encoded as hex
-        value: String, // value as H256, encoded as hex
-    ) -> Result<()> {
-        let addr = &addr;
-        let index = &index;
-        let value = &value;
-        let addr = Address::from_str(trim_prefix(addr, "0x"))?;
-        let value = U256::from_str_radix(trim_prefix(value, "0x"), 16)?;
-        let index = U256::from_str_radix(trim_prefix(index, "0x"), 16)?;
-
-        self.set_storage_by_address(addr, index, value)
+        addr: AddressArg,
+        index: U256Arg,
+        value: U256Arg,
+        local_only: bool,
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        let value = value.value()?;
+        let index = index.value()?;
+
+        if local_only {
+            self.set_local_storage_by_address(addr, index, value);
+            return Ok(());
+        }
+        Ok(self.set_storage_by_address(addr, index, value)?)
     }
 
     /// Get account's storage by index
     ///
-    /// - `addr`: H160 address as hex string
-    /// - `index`: H256 as hex string
+    /// - `addr`: H160 address as bytes or hex string
+    /// - `index`: H256 as int or hex string
     ///
-    /// Returns H256 as hex string
-    pub fn get_storage(
-        &self,
-        addr: String,  // address as H160, encoded as hex
-        index: String, // index as H256, encoded as hex
-    ) -> Result<BigInt> {
-        let addr = Address::from_str(trim_prefix(&addr, "0x"))?;
-
-        let index = &index;
-        let index = U256::from_str_radix(trim_prefix(index, "0x"), 16)?;
+    /// Returns the value as int
+    pub fn get_storage(&self, addr: AddressArg, index: U256Arg) -> PyEvmResult<PyU256> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        let index = index.value()?;
 
         let s = self.get_storage_by_address(addr, index)?;
 
-        Ok(ruint_u256_to_bigint(&s))
+        Ok(PyU256(s))
+    }
+
+    /// Iterator over the accounts in the state as `WrappedAccount`, sorted
+    /// by address. Only the accounts loaded so far are included when forking
+    pub fn accounts<'py>(&self, py: Python<'py>) -> PyEvmResult<Bound<'py, PyIterator>> {
+        let _guard = self.trace_guard();
+        let accounts: Vec<WrappedAccount> = self
+            .account_infos()
+            .iter()
+            .map(|(address, info)| (address, info).into())
+            .collect();
+        Ok(PyList::new(py, accounts)?.as_any().try_iter()?)
+    }
+
+    /// Iterator over the `(slot, value)` pairs of the storage of an account
+    /// as ints, sorted by slot. Only the slots loaded so far are included
+    /// when forking
+    /// - `addr`: H160 address as bytes or hex string
+    pub fn storage_items<'py>(
+        &self,
+        py: Python<'py>,
+        addr: AddressArg,
+    ) -> PyEvmResult<Bound<'py, PyIterator>> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        let items: Vec<(PyU256, PyU256)> = self
+            .storage_of(addr)
+            .iter()
+            .map(|(slot, value)| (PyU256(*slot), PyU256(*value)))
+            .collect();
+        Ok(PyList::new(py, items)?.as_any().try_iter()?)
     }
 
     /// Reset storage by account
-    pub fn reset_storage_by_account(&mut self, addr: String) -> Result<()> {
-        let addr = Address::from_str(&addr)?;
-        self.reset_storage(addr)
+    pub fn reset_storage_by_account(&mut self, addr: AddressArg) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
+        Ok(self.reset_storage(addr)?)
     }
 
     /// Remove account
     pub fn remove_account(
         &mut self,
-        addr: String, // address as H160, encoded as hex
-    ) -> Result<()> {
-        let addr = Address::from_str(&addr)?;
+        addr: AddressArg, // address as H160, encoded as hex
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addr = addr.address()?;
         let db = &mut self.exe.as_mut().unwrap().context.evm.db;
         db.accounts.remove(&addr);
         Ok(())
     }
 
     /// Take a snapshot of an account, raise error if account does not exist in db
-    pub fn take_snapshot(&mut self, address: String) -> Result<()> {
-        let addr = Address::from_str(&address)?;
+    pub fn take_snapshot(&mut self, address: AddressArg) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addr = address.address()?;
         let db = self.db();
         if let Some(account) = db.accounts.get(&addr) {
             self.snapshots.insert(addr, account.clone());
             Ok(())
         } else {
-            Err(eyre!("Account not found"))
+            Err(Failure::AccountNotFound(addr))?
         }
     }
 
     /// Copy an account from snapshot to another address, the target address will
     /// be overridden. Raise error if account to be copied from does not exist in
     /// db
-    pub fn copy_snapshot(&mut self, from: String, to: String) -> Result<()> {
-        let from = Address::from_str(&from)?;
-        let to = Address::from_str(&to)?;
+    pub fn copy_snapshot(&mut self, from: AddressArg, to: AddressArg) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let from = from.address()?;
+        let to = to.address()?;
 
         let db = &mut self.exe.as_mut().unwrap().context.evm.db;
 
         let account = self
             .snapshots
             .get(&from)
-            .context("No snapshot found")?
+            .ok_or_else(|| Failure::SnapshotNotFound(format!("{:#x}", from)))?
             .clone();
         db.accounts.insert(to, account);
         Ok(())
     }
 
     pub fn clear_instrumentation(&mut self) {
-        let bug_inspector = self.bug_inspector_mut();
-        bug_inspector.bug_data.clear();
-        bug_inspector.created_addresses.clear();
-        bug_inspector.heuristics = Default::default();
-        self.log_inspector_mut().traces.clear();
-        self.log_inspector_mut().logs.clear();
+        let _guard = self.trace_guard();
+        self.clear_instrumentation_helper();
     }
 
     /// Restore a snapshot for an account, raise error if there is no snapshot for the account
-    pub fn restore_snapshot(&mut self, address: String) -> Result<()> {
-        let addr = Address::from_str(&address)?;
+    pub fn restore_snapshot(&mut self, address: AddressArg) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
+        let addr = address.address()?;
 
         let account = {
             self.snapshots
                 .get(&addr)
-                .context("No snapshot found")?
+                .ok_or_else(|| Failure::SnapshotNotFound(format!("{:#x}", addr)))?
                 .clone()
         };
         self.db_mut().accounts.insert(addr, account);
         Ok(())
     }
 
+    /// Roll back the state changes made in a `with` block on exit, including
+    /// when an exception is raised, the blocks can be nested
+    pub fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyEvmResult<PyRefMut<'_, Self>> {
+        let _guard = slf.trace_guard();
+        slf.enter_scope()?;
+        Ok(slf)
+    }
+
+    pub fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> PyEvmResult<bool> {
+        let _guard = self.trace_guard();
+        self.exit_scope()?;
+        // Let the exception propagate
+        Ok(false)
+    }
+
     /// Take global snapshot of all accounts
-    pub fn take_global_snapshot(&mut self) -> Result<String> {
-        let db = self.db();
-        let snapshot = db.clone();
-        let id = Uuid::new_v4();
-        self.global_snapshot.insert(id, snapshot);
-        Ok(id.to_string())
+    pub fn take_global_snapshot(&mut self) -> PyEvmResult<String> {
+        let _guard = self.trace_guard();
+        Ok(self.take_global_snapshot_helper().to_string())
     }
 
     pub fn restore_global_snapshot(
         &mut self,
         snapshot_id: String,
         keep_snapshot: bool,
-    ) -> Result<()> {
+    ) -> PyEvmResult<()> {
+        let _guard = self.trace_guard();
         let id = Uuid::parse_str(&snapshot_id)?;
+        Ok(self.restore_global_snapshot_helper(&id, keep_snapshot)?)
+    }
 
-        if keep_snapshot {
-            let snapshot = self.global_snapshot.get(&id).context("No snapshot found")?;
-            *self.db_mut() = snapshot.clone();
-        } else {
-            let snapshot = self
-                .global_snapshot
-                .remove(&id)
-                .context("No snapshot found")?;
-            let _ = replace(self.db_mut(), snapshot);
-        }
+    /// CBOR encoding of the accounts, the environment and the
+    /// instrumentation config, so that a warmed up instance can be sent to
+    /// another process instead of forking again from the RPC endpoint. The
+    /// hooks, invariants, source maps, event ABIs and global snapshots are
+    /// not included
+    #[cfg(feature = "with-serde")]
+    pub fn to_bytes(&self) -> PyEvmResult<Cow<'static, [u8]>> {
+        let _guard = self.trace_guard();
+        Ok(Cow::Owned(serde_cbor::to_vec(&self.state())?))
+    }
+
+    /// Decode an instance encoded with `to_bytes`
+    #[cfg(feature = "with-serde")]
+    #[staticmethod]
+    pub fn from_bytes(data: &[u8]) -> PyEvmResult<Self> {
+        Ok(Self::from_state(serde_cbor::from_slice(data)?)?)
+    }
 
+    /// Support for pickle, see `to_bytes`
+    #[cfg(feature = "with-serde")]
+    pub fn __getstate__(&self) -> PyEvmResult<Cow<'static, [u8]>> {
+        let _guard = self.trace_guard();
+        self.to_bytes()
+    }
+
+    #[cfg(feature = "with-serde")]
+    pub fn __setstate__(&mut self, state: &[u8]) -> PyEvmResult<()> {
+        *self = Self::from_bytes(state)?;
         Ok(())
     }
 }
 
 /// Configuration class for instrumentation, this is a wrapper for
 /// REVM::InstrumentConfig
+#[cfg(feature = "python")]
 #[pyclass(set_all, get_all)]
 pub struct REVMConfig {
     /// Enable the bug detector instrumentation
@@ -1185,6 +2392,50 @@ pub struct REVMConfig {
     pub target_address: Option<String>,
     /// Whether to record SHA3 mappings
     pub record_sha3_mapping: bool,
+    /// Maximum length of the recorded SHA3 inputs, longer inputs keep
+    /// their last bytes
+    pub sha3_preimage_max_len: usize,
+    /// Maximum number of SHA3 mappings, the least recently hashed are
+    /// evicted first
+    pub sha3_mapping_capacity: usize,
+    /// Whether to record the operands of comparisons in `heuristics.cmp_operands`
+    pub record_cmp_operands: bool,
+    /// Size of the bug buffer, once full SLOAD and SSTORE records evict the
+    /// oldest bugs
+    pub bug_buffer_size: usize,
+    /// Record repeated bugs (same type, position and address) once, with
+    /// their number of occurrences in `count`
+    pub dedup_bugs: bool,
+    /// Whether to report the arithmetic bugs: overflows, underflows,
+    /// divisions and modulos by zero and possible truncations
+    pub detect_arithmetic: bool,
+    /// Whether to report the block value and `ORIGIN` dependencies
+    pub detect_dependency: bool,
+    /// Whether to report the `Sload` and `Sstore` bugs
+    pub detect_storage: bool,
+    /// `(proxy, implementation)` addresses: report the proxy storage slots
+    /// accessed by both contracts, excluding the EIP-1967 slots
+    pub storage_collision: Option<(String, String)>,
+    /// Report every `ORIGIN` as `TxOriginDependency`, by default only the
+    /// values compared with something else than `CALLER` are reported
+    pub report_all_tx_origin: bool,
+    /// Whether to track the calldata bytes influencing the operands of the
+    /// bugs, required to report `DelegatecallToUntrusted` and `ArbitrarySend`
+    pub calldata_taint: bool,
+    /// Whether to count the executed opcodes, see `Response.opcode_histogram`
+    pub record_opcode_histogram: bool,
+    /// Whether to record the coverage of each function selector, see
+    /// `heuristics.selector_coverage`
+    pub coverage_by_selector: bool,
+    /// Whether to record the storage read and written by each transaction,
+    /// always enabled by `find_tod`
+    pub record_storage_footprint: bool,
+    /// Whether to record the gas spent by category, see `Response.gas_breakdown`
+    pub record_gas_breakdown: bool,
+    /// Whether to record the gas spent at each address, see `Response.gas_by_address`
+    pub record_gas_by_address: bool,
+    /// Addresses for which no bug, coverage or heuristics is recorded
+    pub excluded_addresses: Vec<String>,
     /// The block id to fork
     pub fork_block_id: Option<String>,
     /// The endpoints to use
@@ -1193,6 +2444,7 @@ pub struct REVMConfig {
     pub fork_network_id: Option<String>,
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl REVMConfig {
     /// Create a new REVMConfig instance with same default settings as REVM::InstrumentConfig
@@ -1203,15 +2455,28 @@ impl REVMConfig {
     }
 }
 
+#[cfg(feature = "python")]
 impl REVMConfig {
     /// Convert from `InstrumentConfig`
     fn to_iconfig(&self) -> Result<InstrumentConfig> {
         let target_address = if let Some(addr) = &self.target_address {
             let addr = trim_prefix(addr, "0x");
-            Address::from_str(addr)?
+            parse_address(addr)?
         } else {
             Address::default()
         };
+        let storage_collision = match &self.storage_collision {
+            Some((proxy, implementation)) => {
+                Some((parse_address(proxy)?, parse_address(implementation)?))
+            }
+            None => None,
+        };
+
+        let excluded_addresses = self
+            .excluded_addresses
+            .iter()
+            .map(|address| parse_address(address))
+            .collect::<Result<_, _>>()?;
 
         Ok(InstrumentConfig {
             enabled: self.enabled,
@@ -1220,6 +2485,23 @@ impl REVMConfig {
             heuristics: self.heuristics,
             record_branch_for_target_only: self.record_branch_for_target_only,
             record_sha3_mapping: self.record_sha3_mapping,
+            sha3_preimage_max_len: self.sha3_preimage_max_len,
+            sha3_mapping_capacity: self.sha3_mapping_capacity,
+            record_cmp_operands: self.record_cmp_operands,
+            bug_buffer_size: self.bug_buffer_size,
+            dedup_bugs: self.dedup_bugs,
+            detect_arithmetic: self.detect_arithmetic,
+            detect_dependency: self.detect_dependency,
+            detect_storage: self.detect_storage,
+            storage_collision,
+            report_all_tx_origin: self.report_all_tx_origin,
+            calldata_taint: self.calldata_taint,
+            record_opcode_histogram: self.record_opcode_histogram,
+            coverage_by_selector: self.coverage_by_selector,
+            record_storage_footprint: self.record_storage_footprint,
+            record_gas_breakdown: self.record_gas_breakdown,
+            record_gas_by_address: self.record_gas_by_address,
+            excluded_addresses,
         })
     }
 
@@ -1232,6 +2514,29 @@ impl REVMConfig {
             record_branch_for_target_only: config.record_branch_for_target_only,
             target_address: Some(format!("{:#066x}", config.target_address)),
             record_sha3_mapping: config.record_sha3_mapping,
+            sha3_preimage_max_len: config.sha3_preimage_max_len,
+            sha3_mapping_capacity: config.sha3_mapping_capacity,
+            record_cmp_operands: config.record_cmp_operands,
+            bug_buffer_size: config.bug_buffer_size,
+            dedup_bugs: config.dedup_bugs,
+            detect_arithmetic: config.detect_arithmetic,
+            detect_dependency: config.detect_dependency,
+            detect_storage: config.detect_storage,
+            storage_collision: config.storage_collision.map(|(proxy, implementation)| {
+                (format!("{:#x}", proxy), format!("{:#x}", implementation))
+            }),
+            report_all_tx_origin: config.report_all_tx_origin,
+            calldata_taint: config.calldata_taint,
+            record_opcode_histogram: config.record_opcode_histogram,
+            coverage_by_selector: config.coverage_by_selector,
+            record_storage_footprint: config.record_storage_footprint,
+            record_gas_breakdown: config.record_gas_breakdown,
+            record_gas_by_address: config.record_gas_by_address,
+            excluded_addresses: config
+                .excluded_addresses
+                .iter()
+                .map(|address| format!("{:#x}", address))
+                .collect(),
             fork_block_id: None,
             fork_endpoints: vec![],
             fork_network_id: None,
@@ -1239,22 +2544,68 @@ impl REVMConfig {
     }
 }
 
+#[cfg(feature = "python")]
 impl Default for REVMConfig {
     fn default() -> Self {
         Self::new()
     }
 }
 
-/// The Python module we provide
-#[pymodule]
+/// The Python module we provide. It has no process-global state and its
+/// classes are `Sync`, so it runs without the GIL on free-threaded Python
+#[cfg(feature = "python")]
+#[pymodule(gil_used = false)]
 fn tinyevm(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_function(wrap_pyfunction!(enable_tracing, m)?)?;
+    m.add_function(wrap_pyfunction!(analyze_bytecode, m)?)?;
+    m.add_function(wrap_pyfunction!(fn_sig_to_prefix, m)?)?;
+    m.add_function(wrap_pyfunction!(keccak256_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(event_topic, m)?)?;
+    m.add_function(wrap_pyfunction!(abi::encode_call, m)?)?;
+    m.add_function(wrap_pyfunction!(abi::decode_output, m)?)?;
+    m.add_function(wrap_pyfunction!(stubs::generate_stubs, m)?)?;
     m.add_class::<TinyEVM>()?;
+    m.add_class::<TinyEVMConfig>()?;
+    m.add_class::<pool::TinyEVMPool>()?;
     m.add_class::<Response>()?;
+    m.add_class::<DeployResult>()?;
     m.add_class::<WrappedBug>()?;
+    m.add_class::<WrappedCallFrame>()?;
     m.add_class::<WrappedMissedBranch>()?;
+    m.add_class::<WrappedCmpOperands>()?;
     m.add_class::<WrappedHeuristics>()?;
+    m.add_class::<WrappedAccount>()?;
+    m.add_class::<WrappedAccountDiff>()?;
+    m.add_class::<ExitReason>()?;
+    m.add_class::<WrappedSelectorCoverage>()?;
     m.add_class::<SeenPcsMap>()?;
+    m.add_class::<CoverageBuffer>()?;
     m.add_class::<REVMConfig>()?;
+    m.add_class::<BytecodeAnalysis>()?;
+    m.add_class::<ControlFlowGraph>()?;
+    m.add_class::<SourceLocation>()?;
+    m.add_class::<WrappedTodPair>()?;
+    m.add_class::<WrappedInvariantViolation>()?;
+    m.add_class::<WrappedDeployment>()?;
+    m.add_class::<WrappedBreakpointHit>()?;
+    let py = m.py();
+    m.add("TinyEvmError", py.get_type::<errors::TinyEvmError>())?;
+    m.add("InvalidHexError", py.get_type::<errors::InvalidHexError>())?;
+    m.add(
+        "InvalidAddressError",
+        py.get_type::<errors::InvalidAddressError>(),
+    )?;
+    m.add(
+        "AccountNotFoundError",
+        py.get_type::<errors::AccountNotFoundError>(),
+    )?;
+    m.add(
+        "SnapshotNotFoundError",
+        py.get_type::<errors::SnapshotNotFoundError>(),
+    )?;
+    m.add("ProviderError", py.get_type::<errors::ProviderError>())?;
+    m.add(
+        "AddressCollisionError",
+        py.get_type::<errors::AddressCollisionError>(),
+    )?;
     Ok(())
 }
@@ -0,0 +1,110 @@
+use eyre::Result;
+use rayon::prelude::*;
+use revm::primitives::{Address, Env, SpecId};
+use ruint::aliases::U256;
+use std::sync::Arc;
+use tracing::Dispatch;
+
+use crate::{
+    cache::DefaultProviderCache,
+    fork_db::{DbAccount, ForkDB},
+    instrument::InstrumentConfig,
+    response::Response,
+    TinyEVM,
+};
+
+/// Call of `TinyEVM::run_parallel`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParallelTx {
+    pub caller: Address,
+    pub contract: Address,
+    pub data: Vec<u8>,
+    pub value: U256,
+}
+
+/// State, env and configuration of an instance, which can be sent to
+/// other threads to create copies of the instance there. The accounts are
+/// shared with the instance until they are modified
+#[derive(Clone)]
+pub struct InstanceSeed {
+    db: ForkDB<DefaultProviderCache>,
+    env: Env,
+    spec_id: SpecId,
+    instrument_config: InstrumentConfig,
+    trace_enabled: bool,
+    cheatcodes_enabled: bool,
+    owner: Address,
+    tx_gas_limit: u64,
+    fork_url: Option<String>,
+    snapshots: hashbrown::HashMap<Address, Arc<DbAccount>>,
+    tracing: Option<Dispatch>,
+}
+
+impl InstanceSeed {
+    /// Run each call of `txs` on a new instance created from this seed, in
+    /// parallel on the rayon thread pool. The responses are in the order
+    /// of `txs`
+    pub fn run_parallel(&self, txs: &[ParallelTx]) -> Result<Vec<Response>> {
+        txs.par_iter()
+            .map(|tx| {
+                let mut vm = TinyEVM::from_seed(self.clone())?;
+                let data = tx.data.clone();
+                Ok(vm.contract_call_helper(tx.contract, tx.caller, data, tx.value, None))
+            })
+            .collect()
+    }
+}
+
+impl TinyEVM {
+    /// Copy of the state and the configuration to create instances in other
+    /// threads. Python objects such as the hooks are not copied
+    pub fn seed(&self) -> InstanceSeed {
+        let exe = self.exe.as_ref().unwrap();
+        InstanceSeed {
+            db: self.db().clone(),
+            env: exe.context.evm.env.as_ref().clone(),
+            spec_id: exe.spec_id(),
+            instrument_config: self.bug_inspector().instrument_config.clone(),
+            trace_enabled: exe
+                .context
+                .external
+                .log_inspector
+                .as_ref()
+                .unwrap()
+                .trace_enabled,
+            cheatcodes_enabled: self.cheatcodes_enabled(),
+            owner: self.owner,
+            tx_gas_limit: self.tx_gas_limit,
+            fork_url: self.fork_url.clone(),
+            snapshots: self.snapshots.clone(),
+            tracing: self.tracing.clone(),
+        }
+    }
+
+    /// New instance with the state and the configuration of `seed`
+    pub fn from_seed(seed: InstanceSeed) -> Result<Self> {
+        let mut tinyevm = Self::from_parts(
+            seed.db,
+            seed.env,
+            seed.spec_id,
+            seed.instrument_config,
+            seed.trace_enabled,
+        );
+        tinyevm.set_cheatcodes_enabled(seed.cheatcodes_enabled);
+        tinyevm.owner = seed.owner;
+        tinyevm.tx_gas_limit = seed.tx_gas_limit;
+        tinyevm.fork_url = seed.fork_url;
+        tinyevm.snapshots = seed.snapshots;
+        tinyevm.tracing = seed.tracing;
+        Ok(tinyevm)
+    }
+
+    /// Run the independent calls `txs` in parallel, each from the current
+    /// state, and return their responses in the order of `txs`. The state
+    /// of this instance is not modified. Each call runs on its own copy of
+    /// the instance, see `seed`, so the invariants, the source maps, the
+    /// event ABIs and the seen PCs of this instance are not used
+    pub fn run_parallel(&self, txs: &[ParallelTx]) -> Result<Vec<Response>> {
+        self.seed().run_parallel(txs)
+    }
+}
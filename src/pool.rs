@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex};
+
+use eyre::eyre;
+use pyo3::prelude::*;
+
+use crate::{errors::PyEvmResult, parallel::InstanceSeed, TinyEVM};
+
+/// Executors of a `TinyEVMPool`
+struct Executors {
+    /// Executors available for `checkout`
+    idle: Vec<Py<TinyEVM>>,
+    /// Executors returned by `checkout` and not checked in yet
+    busy: Vec<Py<TinyEVM>>,
+}
+
+/// Pool of pre-built executors forking the same block and sharing the
+/// in-memory provider cache, so that the fork is warmed up once for all the
+/// workers. The pool can be shared by threads, each one checking out its
+/// executors
+#[pyclass(module = "tinyevm")]
+pub struct TinyEVMPool {
+    /// Warmed fork copied by each executor of the pool and on `checkin`,
+    /// the accounts are shared with the executors until they modify them
+    seed: Arc<InstanceSeed>,
+    executors: Mutex<Executors>,
+}
+
+#[pymethods]
+impl TinyEVMPool {
+    /// Create `size` executors forking `fork_url` at `block_id` if provided
+    #[new]
+    #[pyo3(signature = (size, fork_url = None, block_id = None))]
+    pub fn new(
+        py: Python<'_>,
+        size: usize,
+        fork_url: Option<String>,
+        block_id: Option<u64>,
+    ) -> PyEvmResult<Self> {
+        let seed = Arc::new(TinyEVM::new_instance(fork_url, block_id, false)?.seed());
+        let idle = (0..size)
+            .map(|_| Ok(Py::new(py, TinyEVM::from_seed((*seed).clone())?)?))
+            .collect::<PyEvmResult<_>>()?;
+        Ok(Self {
+            seed,
+            executors: Mutex::new(Executors { idle, busy: vec![] }),
+        })
+    }
+
+    /// Number of executors of the pool
+    #[getter]
+    pub fn size(&self) -> usize {
+        let executors = self.executors.lock().unwrap();
+        executors.idle.len() + executors.busy.len()
+    }
+
+    /// Number of executors available for `checkout`
+    #[getter]
+    pub fn available(&self) -> usize {
+        self.executors.lock().unwrap().idle.len()
+    }
+
+    /// Take an executor out of the pool, fails if all of them are checked out
+    pub fn checkout(&self, py: Python<'_>) -> PyEvmResult<Py<TinyEVM>> {
+        let mut executors = self.executors.lock().unwrap();
+        let vm = match executors.idle.pop() {
+            Some(vm) => vm,
+            None => Err(eyre!(
+                "All the {} executors are checked out",
+                executors.busy.len()
+            ))?,
+        };
+        executors.busy.push(vm.clone_ref(py));
+        Ok(vm)
+    }
+
+    /// Return an executor to the pool. Its state is reset to the state of
+    /// the pool, the provider cache it warmed up is kept
+    pub fn checkin(&self, py: Python<'_>, vm: Py<TinyEVM>) -> PyEvmResult<()> {
+        let mut executors = self.executors.lock().unwrap();
+        let index = match executors.busy.iter().position(|busy| busy.is(&vm)) {
+            Some(index) => index,
+            None => Err(eyre!("The executor is not checked out from this pool"))?,
+        };
+        *vm.bind(py).try_borrow_mut().map_err(PyErr::from)? =
+            TinyEVM::from_seed((*self.seed).clone())?;
+        let vm = executors.busy.swap_remove(index);
+        executors.idle.push(vm);
+        Ok(())
+    }
+}
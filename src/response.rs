@@ -1,24 +1,51 @@
+#[cfg(feature = "python")]
+use ethers::abi::Token;
 use eyre::Result;
 use hashbrown::{HashMap, HashSet};
 use hex::ToHex;
 use num_bigint::BigInt;
-use pyo3::{exceptions::PyValueError, prelude::*};
-use revm::primitives::{Address, ExecutionResult, Output};
+#[cfg(feature = "python")]
+use pyo3::{
+    exceptions::{PyBufferError, PyKeyError, PyValueError},
+    ffi,
+    prelude::*,
+    pyclass_init::PyClassInitializer,
+    types::{PyBytes, PyDict, PyIterator, PyList, PyTuple},
+    IntoPyObjectExt,
+};
+use revm::primitives::{
+    alloy_primitives::Bloom, keccak256, AccountInfo, Address, Bytes, CreateScheme, ExecutionResult,
+    HaltReason, Output,
+};
 use ruint::aliases::U256;
+#[cfg(feature = "python")]
+use std::{
+    borrow::Cow,
+    ffi::{c_int, c_void},
+};
 use std::{
     fmt::{Display, Formatter},
-    str::FromStr,
+    sync::{Arc, OnceLock},
 };
+use strum_macros::Display;
 
 use std::collections::HashMap as StdHashMap;
+#[cfg(feature = "python")]
 use std::collections::HashSet as StdHashSet;
 
+#[cfg(feature = "python")]
+use crate::{args::AddressArg, trim_prefix};
 use crate::{
+    event_abi::DecodedEvent,
     instrument::{
         bug::*,
-        log_inspector::{CallTrace, Log},
+        log_inspector::{BreakpointHit, CallTrace, Log, StructLog},
+        pc_set::PcSet,
     },
-    ruint_u256_to_bigint, trim_prefix,
+    invariant::InvariantViolation,
+    ruint_u256_to_bigint,
+    state_diff::AccountDiff,
+    tod::TodPair,
 };
 use primitive_types::H160;
 
@@ -27,21 +54,33 @@ pub struct RevmResult {
     /// Tx result
     pub result: Result<ExecutionResult, eyre::Error>,
     /// Bug data
-    pub bug_data: BugData,
+    pub bug_data: Arc<BugData>,
     /// Heuristics data
-    pub heuristics: Heuristics,
+    pub heuristics: Arc<Heuristics>,
     /// Map of seen pcs: from address to a set of PCs
-    pub seen_pcs: HashMap<Address, HashSet<usize>>,
+    pub seen_pcs: Arc<HashMap<Address, PcSet>>,
+    /// Contracts created by CREATE and CREATE2
+    pub deployments: Vec<Deployment>,
+    /// Steps recorded by the struct logger
+    pub struct_logs: Vec<StructLog>,
+    /// States captured at the breakpoints
+    pub breakpoint_hits: Vec<BreakpointHit>,
     /// Call traces
     pub traces: Vec<CallTrace>,
     /// Transient logs (including logs for reverted calls)
     pub transient_logs: Vec<Log>,
     /// Ignored addresses from ForkDb
     pub ignored_addresses: HashSet<Address>,
+    /// Gas charged before the execution of the transaction
+    pub intrinsic_gas: u64,
+    /// EIP-4844 blob gas of the transaction
+    pub blob_gas_used: u64,
+    /// Accounts touched by the transaction
+    pub state_diff: Vec<AccountDiff>,
 }
 
 /// WrappedBug is a wrapper around Bug for use by Python
-#[pyclass(get_all)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 #[derive(Debug)]
 pub struct WrappedBug {
     /// BugType as a map from string to string. Numerical values are hex encoded
@@ -52,10 +91,37 @@ pub struct WrappedBug {
     pub position: usize,
     /// Index of the contract address in seen_addresses
     pub address_index: isize,
+    /// Calldata bytes `(start, end)` influencing the operands, if any
+    pub calldata_taint: Option<(usize, usize)>,
+    /// Number of occurrences, see `REVMConfig::dedup_bugs`
+    pub count: usize,
+    /// External calls active when the bug was recorded, outermost first
+    pub call_stack: Vec<WrappedCallFrame>,
+    /// Hex encoded identifier, stable across runs
+    pub id: String,
+}
+
+/// Wrapper around `CallFrame`, all fields are hex encoded
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedCallFrame {
+    pub caller: String,
+    pub callee: String,
+    pub selector: Option<String>,
+}
+
+impl From<&CallFrame> for WrappedCallFrame {
+    fn from(frame: &CallFrame) -> Self {
+        Self {
+            caller: format!("0x{}", frame.caller.encode_hex::<String>()),
+            callee: format!("0x{}", frame.callee.encode_hex::<String>()),
+            selector: frame.selector.map(hex::encode),
+        }
+    }
 }
 
 /// Wrapper around Missed Branch
-#[pyclass(get_all)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 #[derive(Clone, Debug)]
 pub struct WrappedMissedBranch {
     /// Previous program counter
@@ -68,8 +134,20 @@ pub struct WrappedMissedBranch {
     pub address_index: isize,
 }
 
+/// Wrapper around `CmpOperands`
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedCmpOperands {
+    /// Program counter of the comparison
+    pub pc: usize,
+    pub opcode: u8,
+    pub a: BigInt,
+    pub b: BigInt,
+    pub address_index: isize,
+}
+
 /// Wrapper around Heuristics
-#[pyclass(get_all)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 #[derive(Clone, Debug)]
 pub struct WrappedHeuristics {
     /// List of jumpi destinations
@@ -82,6 +160,261 @@ pub struct WrappedHeuristics {
     pub seen_addresses: Vec<String>,
     /// extra data from constructor (the distance of missed branch)
     pub extra_data: BigInt,
+    /// Operands of the last comparisons
+    pub cmp_operands: Vec<WrappedCmpOperands>,
+    /// Deepest call frame reached, the transaction frame has depth 1
+    pub max_call_depth: u64,
+    /// Max number of items on the EVM stack
+    pub max_stack_depth: usize,
+    /// Max memory size in bytes
+    pub max_memory_size: usize,
+    /// Coverage by contract address and by function selector, both hex encoded
+    pub selector_coverage: StdHashMap<String, StdHashMap<String, WrappedSelectorCoverage>>,
+}
+
+/// Wrapper around `TodPair`
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedTodPair {
+    pub first: usize,
+    pub second: usize,
+    /// `(address, slot)`, the address is hex encoded
+    pub slots: Vec<(String, BigInt)>,
+}
+
+impl From<&TodPair> for WrappedTodPair {
+    fn from(pair: &TodPair) -> Self {
+        Self {
+            first: pair.first,
+            second: pair.second,
+            slots: pair
+                .slots
+                .iter()
+                .map(|(address, slot)| {
+                    (
+                        format!("0x{}", address.encode_hex::<String>()),
+                        ruint_u256_to_bigint(slot),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Wrapper around `InvariantViolation`, the bytes are hex encoded
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedInvariantViolation {
+    /// Index of the invariant in the registration order
+    pub index: usize,
+    pub contract: String,
+    pub data: String,
+    pub expected: String,
+    /// Whether the view call completed without reverting
+    pub success: bool,
+    pub output: String,
+}
+
+impl From<&InvariantViolation> for WrappedInvariantViolation {
+    fn from(violation: &InvariantViolation) -> Self {
+        Self {
+            index: violation.index,
+            contract: format!("0x{}", violation.invariant.contract.encode_hex::<String>()),
+            data: hex::encode(&violation.invariant.data),
+            expected: hex::encode(&violation.invariant.expected),
+            success: violation.success,
+            output: hex::encode(&violation.output),
+        }
+    }
+}
+
+/// Wrapper around `Deployment`, the addresses and the hash are hex encoded
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedDeployment {
+    pub creator: String,
+    /// Salt of CREATE2, `None` for CREATE
+    pub salt: Option<BigInt>,
+    pub init_code_hash: String,
+    /// Address of the created contract, `None` if the creation failed
+    pub address: Option<String>,
+    /// Hash of the runtime code, `None` if the creation failed
+    pub code_hash: Option<String>,
+    /// Whether the creation was undone by the revert of an enclosing frame
+    pub reverted: bool,
+}
+
+impl From<&Deployment> for WrappedDeployment {
+    fn from(deployment: &Deployment) -> Self {
+        Self {
+            creator: format!("0x{}", deployment.creator.encode_hex::<String>()),
+            salt: deployment.salt.as_ref().map(ruint_u256_to_bigint),
+            init_code_hash: format!("0x{}", deployment.init_code_hash.encode_hex::<String>()),
+            address: deployment
+                .address
+                .map(|address| format!("0x{}", address.encode_hex::<String>())),
+            code_hash: deployment
+                .code_hash
+                .map(|hash| format!("0x{}", hash.encode_hex::<String>())),
+            reverted: deployment.reverted,
+        }
+    }
+}
+
+/// Wrapper around `AccountDiff`, the address, the hashes and the code are
+/// hex encoded
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedAccountDiff {
+    pub address: String,
+    /// `(before, after)` balance
+    pub balance: (BigInt, BigInt),
+    /// `(before, after)` nonce
+    pub nonce: (u64, u64),
+    /// `(before, after)` code hash
+    pub code_hash: (String, String),
+    /// Code after the transaction if it changed
+    pub code: Option<String>,
+    /// `(slot, before, after)` of the slots whose value changed
+    pub storage: Vec<(BigInt, BigInt, BigInt)>,
+    pub selfdestructed: bool,
+}
+
+impl From<&AccountDiff> for WrappedAccountDiff {
+    fn from(diff: &AccountDiff) -> Self {
+        let (balance_before, balance_after) = &diff.balance;
+        let (hash_before, hash_after) = &diff.code_hash;
+        Self {
+            address: format!("0x{}", diff.address.encode_hex::<String>()),
+            balance: (
+                ruint_u256_to_bigint(balance_before),
+                ruint_u256_to_bigint(balance_after),
+            ),
+            nonce: diff.nonce,
+            code_hash: (
+                format!("0x{}", hash_before.encode_hex::<String>()),
+                format!("0x{}", hash_after.encode_hex::<String>()),
+            ),
+            code: diff
+                .code
+                .as_ref()
+                .map(|code| format!("0x{}", code.encode_hex::<String>())),
+            storage: diff
+                .storage
+                .iter()
+                .map(|(slot, before, after)| {
+                    (
+                        ruint_u256_to_bigint(slot),
+                        ruint_u256_to_bigint(before),
+                        ruint_u256_to_bigint(after),
+                    )
+                })
+                .collect(),
+            selfdestructed: diff.selfdestructed,
+        }
+    }
+}
+
+/// View of an account in the state, the address and the code hash are hex
+/// encoded
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedAccount {
+    pub address: String,
+    pub balance: BigInt,
+    pub nonce: u64,
+    pub code_hash: String,
+}
+
+impl From<(&Address, &AccountInfo)> for WrappedAccount {
+    fn from((address, info): (&Address, &AccountInfo)) -> Self {
+        Self {
+            address: format!("0x{}", address.encode_hex::<String>()),
+            balance: ruint_u256_to_bigint(&info.balance),
+            nonce: info.nonce,
+            code_hash: format!("0x{}", info.code_hash.encode_hex::<String>()),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl WrappedAccount {
+    fn __repr__(&self) -> String {
+        format!(
+            "WrappedAccount(address={}, balance={}, nonce={}, code_hash={})",
+            self.address, self.balance, self.nonce, self.code_hash
+        )
+    }
+}
+
+/// Wrapper around `BreakpointHit`, the address and the memory are hex encoded
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedBreakpointHit {
+    pub address: String,
+    pub pc: usize,
+    pub depth: u64,
+    /// Stack, the top last
+    pub stack: Vec<BigInt>,
+    /// `(offset, bytes)` of the captured memory ranges
+    pub memory: Vec<(usize, String)>,
+    /// `(slot, value)` of the storage accessed so far, sorted by slot
+    pub storage: Vec<(BigInt, BigInt)>,
+}
+
+impl From<&BreakpointHit> for WrappedBreakpointHit {
+    fn from(hit: &BreakpointHit) -> Self {
+        Self {
+            address: format!("0x{}", hit.address.encode_hex::<String>()),
+            pc: hit.pc,
+            depth: hit.depth,
+            stack: hit.stack.iter().map(ruint_u256_to_bigint).collect(),
+            memory: hit
+                .memory
+                .iter()
+                .map(|(offset, bytes)| (*offset, hex::encode(bytes)))
+                .collect(),
+            storage: hit
+                .storage
+                .iter()
+                .map(|(slot, value)| (ruint_u256_to_bigint(slot), ruint_u256_to_bigint(value)))
+                .collect(),
+        }
+    }
+}
+
+/// Wrapper around `SelectorCoverage`
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug)]
+pub struct WrappedSelectorCoverage {
+    /// PCs executed, sorted
+    pub pcs: Vec<usize>,
+    /// Branches not taken
+    pub missed_branches: Vec<WrappedMissedBranch>,
+}
+
+impl From<&MissedBranch> for WrappedMissedBranch {
+    fn from(x: &MissedBranch) -> Self {
+        WrappedMissedBranch {
+            prev_pc: x.prev_pc,
+            dest_pc: x.dest_pc,
+            cond: x.cond,
+            distance: ruint_u256_to_bigint(&x.distance),
+            address_index: x.address_index,
+        }
+    }
+}
+
+impl From<&SelectorCoverage> for WrappedSelectorCoverage {
+    fn from(coverage: &SelectorCoverage) -> Self {
+        let mut pcs: Vec<_> = coverage.pcs.iter().copied().collect();
+        pcs.sort_unstable();
+        Self {
+            pcs,
+            missed_branches: coverage.missed_branches.iter().map(Into::into).collect(),
+        }
+    }
 }
 
 impl Display for WrappedHeuristics {
@@ -90,43 +423,76 @@ impl Display for WrappedHeuristics {
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl WrappedHeuristics {
     /// Get the string respresentation
     fn __str__(&self) -> String {
         format!("{:?}", self)
     }
+
+    /// Decode heuristics encoded with `Response.heuristics_bytes`
+    #[cfg(feature = "with-serde")]
+    #[staticmethod]
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let heuristics: Heuristics = serde_cbor::from_slice(data)?;
+        Ok(heuristics.into())
+    }
 }
 
 impl From<Heuristics> for WrappedHeuristics {
     fn from(heuristics: Heuristics) -> Self {
+        (&heuristics).into()
+    }
+}
+
+impl From<&Heuristics> for WrappedHeuristics {
+    fn from(heuristics: &Heuristics) -> Self {
         let coverage = heuristics.coverage.iter().copied().collect();
-        let missed_branches = heuristics
-            .missed_branches
+        let missed_branches = heuristics.missed_branches.iter().map(Into::into).collect();
+        let mut sha3_mapping = StdHashMap::new();
+        for (k, v) in heuristics.sha3_mapping.iter() {
+            sha3_mapping.insert(format!("0x{:x}", k), v.clone());
+        }
+        let seen_addresses = heuristics
+            .seen_addresses
+            .iter()
+            .map(|addr| format!("0x{}", addr.encode_hex::<String>()))
+            .collect();
+        let extra_data = ruint_u256_to_bigint(&heuristics.distance);
+        let selector_coverage = heuristics
+            .selector_coverage
+            .iter()
+            .map(|(address, coverage)| {
+                let coverage = coverage
+                    .iter()
+                    .map(|(selector, c)| (format!("0x{:08x}", selector), c.into()))
+                    .collect();
+                (format!("0x{}", address.encode_hex::<String>()), coverage)
+            })
+            .collect();
+        let cmp_operands = heuristics
+            .cmp_operands
             .iter()
-            .map(|x| WrappedMissedBranch {
-                prev_pc: x.prev_pc,
-                dest_pc: x.dest_pc,
-                cond: x.cond,
-                distance: ruint_u256_to_bigint(&x.distance),
+            .map(|x| WrappedCmpOperands {
+                pc: x.pc,
+                opcode: x.opcode,
+                a: ruint_u256_to_bigint(&x.a),
+                b: ruint_u256_to_bigint(&x.b),
                 address_index: x.address_index,
             })
             .collect();
-        let mut sha3_mapping = StdHashMap::new();
-        for (k, v) in heuristics.sha3_mapping {
-            sha3_mapping.insert(format!("0x{:x}", k), v);
-        }
-        let mut seen_addresses = Vec::new();
-        for addr in heuristics.seen_addresses {
-            seen_addresses.push(format!("0x{}", addr.encode_hex::<String>()));
-        }
-        let extra_data = ruint_u256_to_bigint(&heuristics.distance);
         Self {
             coverage,
             missed_branches,
             sha3_mapping,
             seen_addresses,
             extra_data,
+            cmp_operands,
+            max_call_depth: heuristics.max_call_depth,
+            max_stack_depth: heuristics.max_stack_depth,
+            max_memory_size: heuristics.max_memory_size,
+            selector_coverage,
         }
     }
 }
@@ -180,9 +546,85 @@ fn hash_map_from_bug_type(bug_type: &BugType) -> StdHashMap<String, String> {
                 format!("0x{}", destination_address.encode_hex::<String>()),
             );
         }
+        BugType::DelegatecallToUntrusted(destination_address) => {
+            map.insert("type".into(), "DelegatecallToUntrusted".into());
+            map.insert(
+                "dest".to_string(),
+                format!("0x{}", destination_address.encode_hex::<String>()),
+            );
+        }
+        BugType::ArbitrarySend(value, destination_address) => {
+            map.insert("type".into(), "ArbitrarySend".into());
+            map.insert(
+                "value".into(),
+                format!(
+                    "0x{}",
+                    value
+                        .to_be_bytes::<{ U256::BYTES }>()
+                        .encode_hex::<String>()
+                ),
+            );
+            map.insert(
+                "dest".to_string(),
+                format!("0x{}", destination_address.encode_hex::<String>()),
+            );
+        }
+        BugType::UnprotectedSelfdestruct(beneficiary) => {
+            map.insert("type".into(), "UnprotectedSelfdestruct".into());
+            map.insert(
+                "beneficiary".to_string(),
+                format!("0x{}", beneficiary.encode_hex::<String>()),
+            );
+        }
+        BugType::StorageCollision(slot) => {
+            map.insert("type".into(), "StorageCollision".into());
+            map.insert(
+                "slot".into(),
+                format!(
+                    "0x{}",
+                    slot.to_be_bytes::<{ U256::BYTES }>().encode_hex::<String>()
+                ),
+            );
+        }
+        BugType::SubcallOutOfGas(callee, gas) => {
+            map.insert("type".into(), "SubcallOutOfGas".into());
+            map.insert(
+                "callee".to_string(),
+                format!("0x{}", callee.encode_hex::<String>()),
+            );
+            map.insert("gas".into(), gas.to_string());
+        }
+        BugType::Panic(code) => {
+            map.insert("type".into(), "Panic".into());
+            map.insert(
+                "code".into(),
+                format!(
+                    "0x{}",
+                    code.to_be_bytes::<{ U256::BYTES }>().encode_hex::<String>()
+                ),
+            );
+        }
+        BugType::StaticStateModification => {
+            map.insert("type".into(), "StaticStateModification".into());
+        }
+        BugType::AssertionFailure => {
+            map.insert("type".into(), "AssertionFailure".into());
+        }
+        BugType::PanicOverflow => {
+            map.insert("type".into(), "PanicOverflow".into());
+        }
+        BugType::PanicDivisionByZero => {
+            map.insert("type".into(), "PanicDivisionByZero".into());
+        }
+        BugType::PanicIndexOutOfBounds => {
+            map.insert("type".into(), "PanicIndexOutOfBounds".into());
+        }
         BugType::IntegerOverflow => {
             map.insert("type".into(), "IntegerOverflow".into());
         }
+        BugType::CheckedOverflowReverted => {
+            map.insert("type".into(), "CheckedOverflowReverted".into());
+        }
         BugType::IntegerSubUnderflow => {
             map.insert("type".to_string(), "IntegerSubUnderflow".to_string());
         }
@@ -210,6 +652,9 @@ fn hash_map_from_bug_type(bug_type: &BugType) -> StdHashMap<String, String> {
         BugType::RevertOrInvalid => {
             map.insert("type".to_string(), "RevertOrInvalid".to_string());
         }
+        BugType::UncheckedCallReturn => {
+            map.insert("type".to_string(), "UncheckedCallReturn".to_string());
+        }
         BugType::Unclassified => {
             map.insert("type".to_string(), "Unclassified".to_string());
         }
@@ -224,10 +669,15 @@ impl From<Bug> for WrappedBug {
             opcode: bug.opcode,
             position: bug.position,
             address_index: bug.address_index,
+            calldata_taint: bug.calldata_taint,
+            count: bug.count,
+            call_stack: bug.call_stack.iter().map(Into::into).collect(),
+            id: format!("{:#x}", bug.id),
         }
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl WrappedBug {
     /// Get the string representation bug type
@@ -237,136 +687,735 @@ impl WrappedBug {
 }
 
 /// A wrapper around `Log` for use by Python
-/// All fields are hex encoded
+/// All fields are hex encoded, `data` only when accessed
 #[derive(Clone, Debug)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PyLog {
-    #[pyo3(get)]
     pub id: usize,
-    #[pyo3(get)]
     pub depth: usize,
-    #[pyo3(get)]
     pub address: String,
-    #[pyo3(get)]
     pub topics: Vec<String>,
-    #[pyo3(get)]
-    pub data: String,
+    pub data: Bytes,
+    /// Event decoded with the ABIs of `TinyEVM.add_event_abi`
+    pub decoded: Option<DecodedEvent>,
+    /// Emitted in a reverted frame, hence missing from the receipt
+    pub reverted: bool,
+}
+
+/// Python value of an ABI token: addresses and bytes are hex encoded,
+/// arrays are lists and tuples are tuples
+#[cfg(feature = "python")]
+fn token_to_py(py: Python<'_>, token: &Token) -> PyResult<PyObject> {
+    let uint = |v: &ethers::types::U256| {
+        let mut bytes = [0u8; 32];
+        v.to_big_endian(&mut bytes);
+        BigInt::from_bytes_be(num_bigint::Sign::Plus, &bytes)
+    };
+    match token {
+        Token::Address(address) => format!("{:#x}", address).into_py_any(py),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => {
+            format!("0x{}", hex::encode(bytes)).into_py_any(py)
+        }
+        Token::Uint(v) => uint(v).into_py_any(py),
+        Token::Int(v) if v.bit(255) => (uint(v) - (BigInt::from(1) << 256usize)).into_py_any(py),
+        Token::Int(v) => uint(v).into_py_any(py),
+        Token::Bool(b) => b.into_py_any(py),
+        Token::String(s) => s.into_py_any(py),
+        Token::FixedArray(tokens) | Token::Array(tokens) => tokens
+            .iter()
+            .map(|t| token_to_py(py, t))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_py_any(py),
+        Token::Tuple(tokens) => {
+            let tokens = tokens
+                .iter()
+                .map(|t| token_to_py(py, t))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyTuple::new(py, tokens)?.into_py_any(py)
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyLog {
+    #[getter]
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    #[getter]
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    #[getter]
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    #[getter]
+    fn topics(&self) -> Vec<String> {
+        self.topics.clone()
+    }
+
+    #[getter]
+    fn reverted(&self) -> bool {
+        self.reverted
+    }
+
+    /// Hex encoded data
+    #[getter]
+    fn data(&self) -> String {
+        format!("0x{}", self.data.encode_hex::<String>())
+    }
+
+    /// Name of the event, if decoded
+    #[getter]
+    fn event(&self) -> Option<String> {
+        self.decoded.as_ref().map(|event| event.name.clone())
+    }
+
+    /// Parameters of the event by name, if decoded
+    #[getter]
+    fn args<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(event) = &self.decoded else {
+            return Ok(None);
+        };
+        let args = PyDict::new(py);
+        for (name, value) in &event.params {
+            args.set_item(name, token_to_py(py, value)?)?;
+        }
+        Ok(Some(args))
+    }
 }
 
 /// A wrapper around `CallTrace` for use by Python
-/// All fields are hex encoded
+/// All fields are hex encoded, `input` and `return_data` only when accessed
 #[derive(Clone, Debug)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PyCallTrace {
-    #[pyo3(get)]
     pub id: usize,
-    #[pyo3(get)]
     pub caller: String,
-    #[pyo3(get)]
     pub to: String,
-    #[pyo3(get)]
+    /// Indexes of `caller` and `to` in `Response.addresses`, `None` for
+    /// the traces passed to the trace hook
+    pub caller_index: Option<usize>,
+    pub to_index: Option<usize>,
     pub value: BigInt,
-    #[pyo3(get)]
-    pub input: String,
-    #[pyo3(get)]
+    pub input: Bytes,
     pub depth: usize,
-    #[pyo3(get)]
-    pub return_data: String,
-    #[pyo3(get)]
+    /// `None` if the frame did not return
+    pub return_data: Option<Bytes>,
     pub is_static: bool,
-    #[pyo3(get)]
+    /// `call`, `staticcall`, `delegatecall`, `callcode`, or `create` and
+    /// `create2` for contract creations whose `input` is the init code,
+    /// `return_data` the deployed code and `to` the created address
+    pub call_type: String,
+    pub gas: u64,
+    pub gas_used: u64,
     pub status: String,
+    /// Id of the trace of the calling frame
+    pub parent: Option<usize>,
+    /// Ids of the traces of the calls made by the frame
+    pub children: Vec<usize>,
+    /// `(address, slot, old value, new value)` of the storage written by
+    /// the frame, excluding its subcalls
+    pub storage_changes: Vec<(String, BigInt, BigInt, BigInt)>,
 }
 
-impl From<Log> for PyLog {
-    fn from(log: Log) -> Self {
-        Self {
-            id: log.id,
-            depth: log.depth,
-            address: format!("0x{}", log.address.encode_hex::<String>()),
-            topics: log
-                .topics
-                .iter()
-                .map(|x| format!("0x{}", x.encode_hex::<String>()))
-                .collect(),
-            data: format!("0x{}", log.data.encode_hex::<String>()),
-        }
-    }
+/// A call trace with the traces of the calls it made, see `Response.call_tree`
+#[cfg(feature = "python")]
+#[derive(Clone, Debug)]
+#[pyclass(get_all)]
+pub struct PyCallNode {
+    pub trace: PyCallTrace,
+    pub children: Vec<PyCallNode>,
 }
 
-impl From<CallTrace> for PyCallTrace {
-    fn from(trace: CallTrace) -> Self {
-        let input = if trace.input.is_empty() {
+impl PyCallTrace {
+    /// Whether the trace is a contract creation
+    pub fn is_create(&self) -> bool {
+        self.call_type.starts_with("create")
+    }
+
+    /// Hex encoded input, empty if there is no input
+    pub fn input_hex(&self) -> String {
+        if self.input.is_empty() {
             "".into()
         } else {
-            format!("0x{}", trace.input.encode_hex::<String>())
-        };
-        Self {
-            id: trace.id,
-            caller: format!("0x{}", trace.from.encode_hex::<String>()),
-            to: format!("0x{}", trace.to.encode_hex::<String>()),
-            value: ruint_u256_to_bigint(&trace.value),
-            input,
-            depth: trace.depth,
-            return_data: trace
-                .return_data
-                .map(|x| format!("0x{}", x.encode_hex::<String>()))
-                .unwrap_or_default(),
-            is_static: trace.is_static,
-            status: trace.status.map(|x| format!("{:?}", x)).unwrap_or_default(),
+            format!("0x{}", self.input.encode_hex::<String>())
         }
     }
-}
 
-/// Response from EVM executor
-#[pyclass]
-#[derive(Clone, Debug)]
-pub struct Response {
-    /// True if the execution is exitted normally
-    #[pyo3(get)]
-    pub success: bool,
-    /// A ExitReason code
-    #[pyo3(get)]
-    pub exit_reason: String,
-    /// Address for deploy, or return data for contract call
-    #[pyo3(get)]
-    pub data: Vec<u8>,
-    /// Emitted events
-    #[pyo3(get)]
-    pub events: Vec<PyLog>,
-    #[pyo3(get)]
-    pub traces: Vec<PyCallTrace>,
-    /// Bug signal data
-    pub bug_data: BugData,
-    /// Heuristics data
-    pub heuristics: Heuristics,
-    /// Gas usage
-    #[pyo3(get)]
-    pub gas_usage: u64,
-    /// Ignored addresses
-    #[pyo3(get)]
-    pub ignored_addresses: Vec<String>,
-    /// Seen PCs by address
-    pub seen_pcs: HashMap<Address, HashSet<usize>>,
+    /// Hex encoded return data, empty if the frame did not return
+    pub fn return_data_hex(&self) -> String {
+        self.return_data
+            .as_ref()
+            .map(|x| format!("0x{}", x.encode_hex::<String>()))
+            .unwrap_or_default()
+    }
 }
 
-impl From<RevmResult> for Response {
-    fn from(
-        RevmResult {
-            result,
-            bug_data,
-            heuristics,
-            seen_pcs,
-            traces,
-            transient_logs,
-            ignored_addresses,
-        }: RevmResult,
-    ) -> Self {
-        let events = transient_logs
-            .into_iter()
-            .map(|x| x.into())
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyCallTrace {
+    #[getter]
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    #[getter]
+    fn caller(&self) -> String {
+        self.caller.clone()
+    }
+
+    #[getter]
+    fn to(&self) -> String {
+        self.to.clone()
+    }
+
+    #[getter]
+    fn caller_index(&self) -> Option<usize> {
+        self.caller_index
+    }
+
+    #[getter]
+    fn to_index(&self) -> Option<usize> {
+        self.to_index
+    }
+
+    #[getter]
+    fn value(&self) -> BigInt {
+        self.value.clone()
+    }
+
+    #[getter]
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    #[getter]
+    fn is_static(&self) -> bool {
+        self.is_static
+    }
+
+    #[getter]
+    fn call_type(&self) -> String {
+        self.call_type.clone()
+    }
+
+    #[getter]
+    fn gas(&self) -> u64 {
+        self.gas
+    }
+
+    #[getter]
+    fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    #[getter]
+    fn status(&self) -> String {
+        self.status.clone()
+    }
+
+    #[getter]
+    fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    #[getter]
+    fn children(&self) -> Vec<usize> {
+        self.children.clone()
+    }
+
+    #[getter]
+    fn storage_changes(&self) -> Vec<(String, BigInt, BigInt, BigInt)> {
+        self.storage_changes.clone()
+    }
+
+    #[getter]
+    fn input(&self) -> String {
+        self.input_hex()
+    }
+
+    #[getter]
+    fn return_data(&self) -> String {
+        self.return_data_hex()
+    }
+}
+
+#[cfg(feature = "python")]
+impl PyCallNode {
+    /// Build the trees of the `traces` without parent
+    fn from_traces(traces: &[PyCallTrace]) -> Vec<Self> {
+        let by_id: StdHashMap<usize, &PyCallTrace> = traces.iter().map(|t| (t.id, t)).collect();
+        fn node(trace: &PyCallTrace, by_id: &StdHashMap<usize, &PyCallTrace>) -> PyCallNode {
+            PyCallNode {
+                trace: trace.clone(),
+                children: trace
+                    .children
+                    .iter()
+                    .filter_map(|id| by_id.get(id))
+                    .map(|child| node(child, by_id))
+                    .collect(),
+            }
+        }
+        traces
+            .iter()
+            .filter(|t| t.parent.is_none())
+            .map(|t| node(t, &by_id))
+            .collect()
+    }
+}
+
+impl From<Log> for PyLog {
+    fn from(log: Log) -> Self {
+        Self {
+            id: log.id,
+            depth: log.depth,
+            address: format!("0x{}", log.address.encode_hex::<String>()),
+            topics: log
+                .topics
+                .iter()
+                .map(|x| format!("0x{}", x.encode_hex::<String>()))
+                .collect(),
+            data: log.data,
+            decoded: log.event,
+            reverted: log.reverted,
+        }
+    }
+}
+
+impl From<CallTrace> for PyCallTrace {
+    fn from(trace: CallTrace) -> Self {
+        Self {
+            id: trace.id,
+            caller: format!("0x{}", trace.from.encode_hex::<String>()),
+            to: format!("0x{}", trace.to.encode_hex::<String>()),
+            caller_index: None,
+            to_index: None,
+            value: ruint_u256_to_bigint(&trace.value),
+            input: trace.input,
+            depth: trace.depth,
+            return_data: trace.return_data,
+            is_static: trace.is_static,
+            call_type: match trace.create_scheme {
+                Some(CreateScheme::Create) => "create".into(),
+                Some(CreateScheme::Create2 { .. }) => "create2".into(),
+                None => format!("{:?}", trace.scheme).to_lowercase(),
+            },
+            gas: trace.gas_limit,
+            gas_used: trace.gas_used,
+            status: trace.status.map(|x| format!("{:?}", x)).unwrap_or_default(),
+            parent: trace.parent,
+            children: trace.children,
+            storage_changes: trace
+                .storage_changes
+                .iter()
+                .map(|change| {
+                    (
+                        format!("0x{}", change.address.encode_hex::<String>()),
+                        ruint_u256_to_bigint(&change.slot),
+                        ruint_u256_to_bigint(&change.old),
+                        ruint_u256_to_bigint(&change.new),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Error of a call trace in the OpenEthereum format, `None` if the call
+/// succeeded or did not return
+fn parity_error(status: &str) -> Option<String> {
+    match status {
+        "" | "Stop" | "Return" | "SelfDestruct" => None,
+        "Revert" => Some("Reverted".into()),
+        _ if status.contains("OutOfGas") || status.ends_with("OOG") => Some("Out of gas".into()),
+        _ => Some(status.into()),
+    }
+}
+
+/// Convert call traces, in call order, into the OpenEthereum `trace`
+/// array returned by `trace_transaction`
+pub fn parity_traces(traces: &[PyCallTrace]) -> Vec<serde_json::Value> {
+    let mut trace_addresses: StdHashMap<usize, Vec<usize>> = StdHashMap::new();
+    let mut parity_traces = vec![];
+    for trace in traces {
+        let parent = trace.parent.and_then(|parent| {
+            let siblings = &traces.iter().find(|t| t.id == parent)?.children;
+            let index = siblings.iter().position(|id| *id == trace.id)?;
+            Some((trace_addresses.get(&parent)?, index))
+        });
+        let trace_address = match parent {
+            Some((address, index)) => [address.as_slice(), &[index]].concat(),
+            None => vec![],
+        };
+
+        let input = format!("0x{}", trace.input.encode_hex::<String>());
+        let output = format!(
+            "0x{}",
+            trace
+                .return_data
+                .as_ref()
+                .map(|x| x.encode_hex::<String>())
+                .unwrap_or_default()
+        );
+        let is_create = trace.is_create();
+        let (action, result) = if is_create {
+            let action = serde_json::json!({
+                "from": trace.caller,
+                "gas": format!("{:#x}", trace.gas),
+                "init": input,
+                "value": format!("{:#x}", trace.value),
+            });
+            let result = serde_json::json!({
+                "address": trace.to,
+                "code": output,
+                "gasUsed": format!("{:#x}", trace.gas_used),
+            });
+            (action, result)
+        } else {
+            let action = serde_json::json!({
+                "callType": trace.call_type,
+                "from": trace.caller,
+                "to": trace.to,
+                "gas": format!("{:#x}", trace.gas),
+                "input": input,
+                "value": format!("{:#x}", trace.value),
+            });
+            let result = serde_json::json!({
+                "gasUsed": format!("{:#x}", trace.gas_used),
+                "output": output,
+            });
+            (action, result)
+        };
+        let mut parity_trace = serde_json::json!({
+            "action": action,
+            "subtraces": trace.children.len(),
+            "traceAddress": trace_address,
+            "type": if is_create { "create" } else { "call" },
+        });
+        match parity_error(&trace.status) {
+            Some(error) => parity_trace["error"] = error.into(),
+            None => parity_trace["result"] = result,
+        }
+        trace_addresses.insert(trace.id, trace_address);
+        parity_traces.push(parity_trace);
+    }
+    parity_traces
+}
+
+/// Frame of a call trace in the gas profiles, the called address followed
+/// by the function selector if any, or the created address followed by the
+/// creation scheme
+fn profile_frame(trace: &PyCallTrace) -> String {
+    if trace.is_create() {
+        return format!("{}:{}", trace.to, trace.call_type);
+    }
+    match trace.input.get(..4) {
+        Some(selector) => format!("{}:0x{}", trace.to, selector.encode_hex::<String>()),
+        None => trace.to.clone(),
+    }
+}
+
+/// Gas spent by each call trace excluding its subcalls
+fn self_gas(traces: &[PyCallTrace]) -> Vec<u64> {
+    let gas_used: StdHashMap<usize, u64> = traces.iter().map(|t| (t.id, t.gas_used)).collect();
+    traces
+        .iter()
+        .map(|trace| {
+            let children: u64 = trace
+                .children
+                .iter()
+                .filter_map(|id| gas_used.get(id))
+                .sum();
+            trace.gas_used.saturating_sub(children)
+        })
+        .collect()
+}
+
+/// Convert call traces, in call order, into the collapsed stacks read by
+/// the flamegraph tools: one `root;caller;callee gas` line per call, with
+/// the gas spent by the call excluding its subcalls
+pub fn collapsed_stacks(traces: &[PyCallTrace]) -> Vec<String> {
+    let mut stacks: StdHashMap<usize, String> = StdHashMap::new();
+    let mut lines = vec![];
+    for (trace, gas) in traces.iter().zip(self_gas(traces)) {
+        let frame = profile_frame(trace);
+        let stack = match trace.parent.and_then(|parent| stacks.get(&parent)) {
+            Some(parent) => format!("{};{}", parent, frame),
+            None => frame,
+        };
+        lines.push(format!("{} {}", stack, gas));
+        stacks.insert(trace.id, stack);
+    }
+    lines
+}
+
+/// Convert call traces, in call order, into an evented speedscope profile
+/// whose values are the gas spent, each call being opened after the gas
+/// spent by the previous calls of its caller
+pub fn speedscope_profile(traces: &[PyCallTrace], name: &str) -> serde_json::Value {
+    let by_id: StdHashMap<usize, &PyCallTrace> = traces.iter().map(|t| (t.id, t)).collect();
+    let mut frames: Vec<String> = vec![];
+    let mut events = vec![];
+
+    fn visit(
+        trace: &PyCallTrace,
+        at: u64,
+        by_id: &StdHashMap<usize, &PyCallTrace>,
+        frames: &mut Vec<String>,
+        events: &mut Vec<serde_json::Value>,
+    ) -> u64 {
+        let frame = profile_frame(trace);
+        let index = match frames.iter().position(|f| *f == frame) {
+            Some(index) => index,
+            None => {
+                frames.push(frame);
+                frames.len() - 1
+            }
+        };
+        events.push(serde_json::json!({"type": "O", "frame": index, "at": at}));
+        let mut cursor = at;
+        for child in trace.children.iter().filter_map(|id| by_id.get(id)) {
+            cursor = visit(child, cursor, by_id, frames, events);
+        }
+        let end = cursor.max(at + trace.gas_used);
+        events.push(serde_json::json!({"type": "C", "frame": index, "at": end}));
+        end
+    }
+
+    let mut end = 0;
+    for root in traces.iter().filter(|t| t.parent.is_none()) {
+        end = visit(root, end, &by_id, &mut frames, &mut events);
+    }
+    let frames: Vec<_> = frames
+        .into_iter()
+        .map(|name| serde_json::json!({ "name": name }))
+        .collect();
+    serde_json::json!({
+        "$schema": "https://www.speedscope.app/file-format-schema.json",
+        "shared": { "frames": frames },
+        "profiles": [{
+            "type": "evented",
+            "name": name,
+            "unit": "none",
+            "startValue": 0,
+            "endValue": end,
+            "events": events,
+        }],
+        "name": name,
+        "exporter": "tinyevm",
+    })
+}
+
+/// How a transaction ended, the halts specific to EOF or to the
+/// inspector are `OtherHalt`
+#[cfg_attr(feature = "python", pyclass(eq, eq_int))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExitReason {
+    Success,
+    Revert,
+    OutOfGas,
+    OpcodeNotFound,
+    InvalidJump,
+    StackUnderflow,
+    StackOverflow,
+    OutOfOffset,
+    CreateCollision,
+    CreateContractSizeLimit,
+    CreateInitCodeSizeLimit,
+    NonceOverflow,
+    OutOfFunds,
+    CallTooDeep,
+    StateChangeDuringStaticCall,
+    PrecompileError,
+    OtherHalt,
+    /// The transaction was not executed, e.g. the caller cannot pay for it
+    EvmError,
+}
+
+impl From<&HaltReason> for ExitReason {
+    fn from(reason: &HaltReason) -> Self {
+        match reason {
+            HaltReason::OutOfGas(_) => Self::OutOfGas,
+            HaltReason::OpcodeNotFound | HaltReason::InvalidEFOpcode => Self::OpcodeNotFound,
+            HaltReason::InvalidJump => Self::InvalidJump,
+            HaltReason::StackUnderflow => Self::StackUnderflow,
+            HaltReason::StackOverflow => Self::StackOverflow,
+            HaltReason::OutOfOffset => Self::OutOfOffset,
+            HaltReason::CreateCollision => Self::CreateCollision,
+            HaltReason::CreateContractSizeLimit => Self::CreateContractSizeLimit,
+            HaltReason::CreateInitCodeSizeLimit => Self::CreateInitCodeSizeLimit,
+            HaltReason::NonceOverflow => Self::NonceOverflow,
+            HaltReason::OutOfFunds => Self::OutOfFunds,
+            HaltReason::CallTooDeep => Self::CallTooDeep,
+            HaltReason::StateChangeDuringStaticCall | HaltReason::CallNotAllowedInsideStatic => {
+                Self::StateChangeDuringStaticCall
+            }
+            HaltReason::PrecompileError => Self::PrecompileError,
+            _ => Self::OtherHalt,
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ExitReason {
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Response from EVM executor
+#[cfg_attr(feature = "python", pyclass(subclass))]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Response {
+    /// True if the execution is exitted normally
+    pub success: bool,
+    /// How the transaction ended
+    pub exit_reason: ExitReason,
+    /// Description of the exit reason, e.g. the kind of out of gas error,
+    /// followed by the decoded `revert_reason` if any
+    pub exit_message: String,
+    /// Address for deploy, or return data for contract call, or the raw
+    /// revert data
+    pub data: Bytes,
+    /// Runtime code returned by a successful deployment, `None` for a
+    /// contract call
+    pub deployed_code: Option<Vec<u8>>,
+    /// Hex encoded hash of `deployed_code`
+    pub deployed_code_hash: Option<String>,
+    /// Message of a revert with `Error(string)` or description of a revert
+    /// with `Panic(uint256)`
+    pub revert_reason: Option<String>,
+    /// Emitted events in emission order, which is the order of the
+    /// receipt once the `reverted` events are left out. Requires the EVM
+    /// tracing to be enabled
+    pub events: Vec<PyLog>,
+    /// Hex encoded bloom filter of the receipt logs, as the `logsBloom` of
+    /// the transaction receipt
+    pub logs_bloom: String,
+    pub traces: Vec<PyCallTrace>,
+    /// Bug signal data, shared with the executor until its next transaction
+    pub bug_data: Arc<BugData>,
+    /// Heuristics data, shared with the executor until its next transaction
+    pub heuristics: Arc<Heuristics>,
+    /// `heuristics` converted for Python, on first access
+    #[cfg_attr(feature = "with-serde", serde(skip))]
+    #[cfg_attr(not(feature = "python"), allow(dead_code))]
+    wrapped_heuristics: OnceLock<WrappedHeuristics>,
+    /// Addresses referred to by index in the response: the
+    /// `heuristics.seen_addresses` followed by the other addresses of the
+    /// traces and of `seen_pcs`
+    pub addresses: AddressTable,
+    /// Effective gas used by the transaction, i.e. after the refund
+    pub gas_usage: u64,
+    /// Gas refunded at the end of the transaction, e.g. for cleared storage
+    pub gas_refunded: u64,
+    /// Gas charged before the execution: the base cost, the calldata, the
+    /// access list and the initcode
+    pub intrinsic_gas: u64,
+    /// Gas spent by the execution, excluding the intrinsic gas and before
+    /// the refund
+    pub execution_gas: u64,
+    /// EIP-4844 blob gas of the transaction, priced separately
+    pub blob_gas_used: u64,
+    /// Ignored addresses
+    pub ignored_addresses: Vec<String>,
+    /// Seen PCs by address, shared with the executor until it sees a new PC
+    pub seen_pcs: Arc<HashMap<Address, PcSet>>,
+    /// Contracts created by CREATE and CREATE2
+    pub deployments: Vec<Deployment>,
+    /// Steps recorded by the struct logger, see `TinyEVM.set_struct_logging`
+    pub struct_logs: Vec<StructLog>,
+    /// States captured at the breakpoints, see `TinyEVM.add_breakpoint`
+    pub breakpoint_hits: Vec<BreakpointHit>,
+    /// Invariants not holding after the transaction
+    pub invariant_violations: Vec<InvariantViolation>,
+    /// Accounts touched by the transaction with their state before and
+    /// after it, empty if the transaction was not executed
+    pub state_diff: Vec<AccountDiff>,
+}
+
+/// Selector of the Solidity `Error(string)` error
+pub(crate) const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Description of the Solidity panic codes
+fn panic_description(code: U256) -> &'static str {
+    match code.try_into() {
+        Ok(0x00u8) => "generic compiler panic",
+        Ok(0x01u8) => "assertion failed",
+        Ok(0x11u8) => "arithmetic overflow or underflow",
+        Ok(0x12u8) => "division or modulo by zero",
+        Ok(0x21u8) => "invalid enum value",
+        Ok(0x22u8) => "invalid storage byte array encoding",
+        Ok(0x31u8) => "pop on an empty array",
+        Ok(0x32u8) => "array index out of bounds",
+        Ok(0x41u8) => "out of memory",
+        Ok(0x51u8) => "call to a zero-initialized function",
+        _ => "unknown panic code",
+    }
+}
+
+/// Decode revert data of the Solidity `Error(string)` and `Panic(uint256)` errors
+pub fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    if let Some(code) = panic_code(output) {
+        return Some(format!("Panic({:#x}): {}", code, panic_description(code)));
+    }
+    let data = output.strip_prefix(&ERROR_SELECTOR)?;
+    let word = |offset: usize| -> Option<usize> {
+        let word = data.get(offset..offset.checked_add(32)?)?;
+        usize::try_from(U256::from_be_slice(word)).ok()
+    };
+    let offset = word(0)?;
+    let len = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let message = data.get(start..start.checked_add(len)?)?;
+    Some(String::from_utf8_lossy(message).into_owned())
+}
+
+impl From<RevmResult> for Response {
+    fn from(
+        RevmResult {
+            result,
+            bug_data,
+            heuristics,
+            seen_pcs,
+            deployments,
+            struct_logs,
+            breakpoint_hits,
+            traces,
+            transient_logs,
+            ignored_addresses,
+            intrinsic_gas,
+            blob_gas_used,
+            state_diff,
+        }: RevmResult,
+    ) -> Self {
+        let events = transient_logs
+            .into_iter()
+            .map(|x| x.into())
             .collect::<Vec<_>>();
-        let traces = traces.into_iter().map(|x| x.into()).collect();
+        let mut addresses = heuristics.seen_addresses.clone();
+        let traces = traces
+            .into_iter()
+            .map(|trace| PyCallTrace {
+                caller_index: Some(addresses.intern(trace.from)),
+                to_index: Some(addresses.intern(trace.to)),
+                ..trace.into()
+            })
+            .collect();
+        for address in seen_pcs.keys() {
+            addresses.intern(*address);
+        }
         let ignored_addresses = ignored_addresses
             .iter()
             .map(|x| format!("0x{}", x.encode_hex::<String>()))
@@ -374,50 +1423,107 @@ impl From<RevmResult> for Response {
         if result.is_err() {
             return Self {
                 success: false,
-                exit_reason: format!("EVM InfallibleError: {:?}", result.err()),
-                data: Vec::new(),
+                exit_reason: ExitReason::EvmError,
+                exit_message: format!("EVM InfallibleError: {:?}", result.err()),
+                data: Bytes::new(),
+                deployed_code: None,
+                deployed_code_hash: None,
+                revert_reason: None,
                 bug_data,
                 heuristics,
+                wrapped_heuristics: OnceLock::new(),
+                addresses,
+                logs_bloom: format!("0x{}", Bloom::ZERO.encode_hex::<String>()),
                 gas_usage: 0,
+                gas_refunded: 0,
+                intrinsic_gas,
+                execution_gas: 0,
+                blob_gas_used,
                 seen_pcs,
+                deployments,
+                struct_logs,
+                breakpoint_hits,
                 events,
                 traces,
                 ignored_addresses,
+                invariant_violations: vec![],
+                state_diff,
             };
         }
 
         let result = result.unwrap();
         let success = result.is_success();
-
         let gas_usage = result.gas_used();
 
-        let exit_reason = match result {
-            ExecutionResult::Success { .. } => "Success".into(),
-            ExecutionResult::Revert { .. } => "Revert".into(),
-            ExecutionResult::Halt { reason, .. } => format!("{:?}", reason),
-        };
-
-        let data = match result {
-            ExecutionResult::Success { output, .. } => match output {
-                Output::Call(data) => data.to_vec(),
-                Output::Create(_data, Some(address)) => address.to_vec(),
-                _ => Vec::new(), // WARN: assuming no such case that creation succeeds but no address is returned
-            },
-            ExecutionResult::Revert { output, .. } => output.to_vec(),
-            _ => Vec::new(),
+        // Move the output out of the result in a single match
+        let mut logs_bloom = Bloom::ZERO;
+        let mut gas_refunded = 0;
+        let mut revert_reason = None;
+        let mut deployed_code = None;
+        let (exit_reason, exit_message, data) = match result {
+            ExecutionResult::Success {
+                output,
+                logs,
+                gas_refunded: refunded,
+                ..
+            } => {
+                logs_bloom = Bloom::from_iter(&logs);
+                gas_refunded = refunded;
+                let data = match output {
+                    Output::Call(data) => data,
+                    Output::Create(code, Some(address)) => {
+                        deployed_code = Some(Vec::from(code));
+                        Bytes::copy_from_slice(address.as_slice())
+                    }
+                    _ => Bytes::new(), // WARN: assuming no such case that creation succeeds but no address is returned
+                };
+                (ExitReason::Success, "Success".into(), data)
+            }
+            ExecutionResult::Revert { output, .. } => {
+                revert_reason = decode_revert_reason(&output);
+                let exit_message = match &revert_reason {
+                    Some(reason) => format!("Revert: {}", reason),
+                    None => "Revert".into(),
+                };
+                (ExitReason::Revert, exit_message, output)
+            }
+            ExecutionResult::Halt { reason, .. } => {
+                ((&reason).into(), format!("{:?}", reason), Bytes::new())
+            }
         };
+        let logs_bloom = format!("0x{}", logs_bloom.encode_hex::<String>());
+        let execution_gas = (gas_usage + gas_refunded).saturating_sub(intrinsic_gas);
+        let deployed_code_hash = deployed_code
+            .as_ref()
+            .map(|code| format!("0x{}", keccak256(code).encode_hex::<String>()));
 
         Self {
             success,
             exit_reason,
+            exit_message,
             data,
+            deployed_code,
+            deployed_code_hash,
+            revert_reason,
             bug_data,
             heuristics,
+            wrapped_heuristics: OnceLock::new(),
+            addresses,
+            logs_bloom,
             gas_usage,
+            gas_refunded,
+            intrinsic_gas,
+            execution_gas,
+            blob_gas_used,
             seen_pcs,
+            deployments,
+            struct_logs,
+            breakpoint_hits,
             events,
             traces,
             ignored_addresses,
+            invariant_violations: vec![],
+            state_diff,
         }
     }
 }
@@ -427,29 +1533,97 @@ impl Display for Response {
         write!(
             f,
             "success: {}, exit_reason: {}, data: {:?}, gas_usage: {}, bugs: {:?}, heuristics: {:?}, seen_pcs: {:?}",
-            self.success, self.exit_reason, self.data, self.gas_usage, self.bug_data, self.heuristics, self.seen_pcs
+            self.success, self.exit_message, self.data, self.gas_usage, self.bug_data, self.heuristics, self.seen_pcs
         )
     }
 }
 
 /// A map from address as hex strign to a list of PCs visited by the adddress
-#[pyclass]
-pub struct SeenPcsMap(HashMap<String, HashSet<usize>>);
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(not(feature = "python"), allow(dead_code))]
+pub struct SeenPcsMap(HashMap<String, PcSet>);
 
+/// Key of an address in `SeenPcsMap`, lower case with the `0x` prefix
+#[cfg(feature = "python")]
+fn seen_pcs_key(address: &str) -> String {
+    format!("0x{}", trim_prefix(address, "0x").to_lowercase())
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl SeenPcsMap {
+    /// Create an empty map, e.g. to accumulate the coverage of several
+    /// responses with `union_update`
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        PyList::new(py, self.keys())?.as_any().try_iter()
+    }
+
+    fn __contains__(&self, key: &str) -> bool {
+        self.0.contains_key(&seen_pcs_key(key))
+    }
+
+    fn __getitem__(&self, key: &str) -> PyResult<StdHashSet<usize>> {
+        self.get(key)
+            .ok_or_else(|| PyKeyError::new_err(key.to_string()))
+    }
+
     /// Return all keys (addresses) in the map
     fn keys(&self) -> Vec<String> {
         self.0.keys().map(|x| x.to_string()).collect()
     }
+
     /// Return seen PCs for the given address
     fn get(&self, key: &str) -> Option<StdHashSet<usize>> {
-        self.0.get(key).map(|x| x.into_iter().copied().collect())
+        self.0.get(&seen_pcs_key(key)).map(|x| x.iter().collect())
+    }
+
+    /// Return the `(address, PCs)` pairs of the map
+    fn items(&self) -> Vec<(String, StdHashSet<usize>)> {
+        self.0
+            .iter()
+            .map(|(address, pcs)| (address.clone(), pcs.iter().collect()))
+            .collect()
+    }
+
+    /// Number of PCs seen, summed over all the addresses
+    fn total_pcs(&self) -> usize {
+        self.0.values().map(PcSet::len).sum()
+    }
+
+    /// Add the PCs of `other`, return the number of PCs which were not in
+    /// this map
+    fn union_update(&mut self, other: &SeenPcsMap) -> usize {
+        let before = self.total_pcs();
+        for (address, pcs) in &other.0 {
+            self.0.entry(address.clone()).or_default().union_with(pcs);
+        }
+        self.total_pcs() - before
     }
 }
 
-impl From<HashMap<H160, HashSet<usize>>> for SeenPcsMap {
-    fn from(seen_pcs: HashMap<H160, HashSet<usize>>) -> Self {
+impl From<&HashMap<Address, PcSet>> for SeenPcsMap {
+    fn from(seen_pcs: &HashMap<Address, PcSet>) -> Self {
+        Self(
+            seen_pcs
+                .iter()
+                .map(|(addr, pcs)| (format!("0x{}", addr.encode_hex::<String>()), pcs.clone()))
+                .collect(),
+        )
+    }
+}
+
+impl From<HashMap<H160, PcSet>> for SeenPcsMap {
+    fn from(seen_pcs: HashMap<H160, PcSet>) -> Self {
         let mut map = HashMap::new();
         for (addr, pcs) in seen_pcs {
             map.insert(format!("0x{}", addr.encode_hex::<String>()), pcs);
@@ -458,39 +1632,420 @@ impl From<HashMap<H160, HashSet<usize>>> for SeenPcsMap {
     }
 }
 
+/// Read-only byte buffer exposed through the Python buffer protocol, so that
+/// it can be wrapped without copying by `memoryview` or `numpy.frombuffer`
+#[cfg(feature = "python")]
+#[pyclass(frozen)]
+pub struct CoverageBuffer(Vec<u8>);
+
+#[cfg(feature = "python")]
+impl CoverageBuffer {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl CoverageBuffer {
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Coverage buffer is read-only"));
+        }
+        let bytes = &slf.get().0;
+        // The bytes are never mutated and `view.obj` keeps `slf` alive
+        // until the buffer is released
+        if ffi::PyBuffer_FillInfo(
+            view,
+            slf.as_ptr(),
+            bytes.as_ptr() as *mut c_void,
+            bytes.len() as ffi::Py_ssize_t,
+            1,
+            flags,
+        ) != 0
+        {
+            return Err(PyErr::fetch(slf.py()));
+        }
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, _view: *mut ffi::Py_buffer) {}
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl Response {
+    #[getter]
+    fn success(&self) -> bool {
+        self.success
+    }
+
+    #[getter]
+    fn exit_reason(&self) -> ExitReason {
+        self.exit_reason
+    }
+
+    #[getter]
+    fn exit_message(&self) -> String {
+        self.exit_message.clone()
+    }
+
+    #[getter]
+    fn deployed_code(&self) -> Option<Vec<u8>> {
+        self.deployed_code.clone()
+    }
+
+    #[getter]
+    fn deployed_code_hash(&self) -> Option<String> {
+        self.deployed_code_hash.clone()
+    }
+
+    #[getter]
+    fn revert_reason(&self) -> Option<String> {
+        self.revert_reason.clone()
+    }
+
+    #[getter]
+    fn events(&self) -> Vec<PyLog> {
+        self.events.clone()
+    }
+
+    #[getter]
+    fn logs_bloom(&self) -> String {
+        self.logs_bloom.clone()
+    }
+
+    #[getter]
+    fn traces(&self) -> Vec<PyCallTrace> {
+        self.traces.clone()
+    }
+
+    #[getter]
+    fn gas_usage(&self) -> u64 {
+        self.gas_usage
+    }
+
+    #[getter]
+    fn gas_refunded(&self) -> u64 {
+        self.gas_refunded
+    }
+
+    #[getter]
+    fn intrinsic_gas(&self) -> u64 {
+        self.intrinsic_gas
+    }
+
+    #[getter]
+    fn execution_gas(&self) -> u64 {
+        self.execution_gas
+    }
+
+    #[getter]
+    fn blob_gas_used(&self) -> u64 {
+        self.blob_gas_used
+    }
+
+    #[getter]
+    fn ignored_addresses(&self) -> Vec<String> {
+        self.ignored_addresses.clone()
+    }
+
     /// Response to string for Python
     fn __str__(&self) -> String {
         self.to_string()
     }
 
+    /// Output of the call, the address of a deployment or the revert data
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.data)
+    }
+
     /// List of bugs signals
     #[getter]
     fn bug_data(&self) -> Vec<WrappedBug> {
         self.bug_data.iter().map(|b| b.clone().into()).collect()
     }
 
-    /// Heuristics data
+    /// Heuristics data, converted once per response
     #[getter]
     fn heuristics(&self) -> WrappedHeuristics {
-        self.heuristics.clone().into()
+        self.wrapped_heuristics
+            .get_or_init(|| self.heuristics.as_ref().into())
+            .clone()
+    }
+
+    /// Number of missed branches, without converting the heuristics
+    fn missed_branch_count(&self) -> usize {
+        self.heuristics.missed_branches.len()
+    }
+
+    /// Number of jumpi destinations, without converting the heuristics
+    fn coverage_len(&self) -> usize {
+        self.heuristics.coverage.len()
+    }
+
+    /// Call traces nested by calling frame, the root is the trace of the
+    /// transaction. Requires the EVM tracing to be enabled
+    #[getter]
+    fn call_tree(&self) -> Vec<PyCallNode> {
+        PyCallNode::from_traces(&self.traces)
+    }
+
+    /// Call traces as the JSON of the OpenEthereum `trace_transaction`
+    /// output. Requires the EVM tracing to be enabled
+    fn parity_traces(&self) -> Result<String> {
+        Ok(serde_json::to_string(&parity_traces(&self.traces))?)
+    }
+
+    /// Gas spent by the calls as collapsed stacks, one line per call, for
+    /// the flamegraph tools. Requires the EVM tracing to be enabled
+    fn collapsed_stacks(&self) -> String {
+        collapsed_stacks(&self.traces).join("\n")
+    }
+
+    /// Gas spent by the calls as the JSON of a speedscope profile.
+    /// Requires the EVM tracing to be enabled
+    #[pyo3(signature = (name="transaction"))]
+    fn speedscope(&self, name: &str) -> Result<String> {
+        Ok(serde_json::to_string(&speedscope_profile(
+            &self.traces,
+            name,
+        ))?)
+    }
+
+    /// Execution steps as the JSON of the `structLogs` returned by the Geth
+    /// `debug_traceTransaction`. Requires `TinyEVM.set_struct_logging`
+    fn struct_logs(&self) -> Result<String> {
+        let logs: Vec<_> = self
+            .struct_logs
+            .iter()
+            .map(StructLog::to_geth_json)
+            .collect();
+        Ok(serde_json::to_string(&logs)?)
+    }
+
+    /// States captured at the breakpoints, in execution order
+    #[getter]
+    fn breakpoint_hits(&self) -> Vec<WrappedBreakpointHit> {
+        self.breakpoint_hits.iter().map(Into::into).collect()
+    }
+
+    /// Contracts created by CREATE and CREATE2 during the transaction
+    #[getter]
+    fn deployments(&self) -> Vec<WrappedDeployment> {
+        self.deployments.iter().map(Into::into).collect()
+    }
+
+    /// Contracts created by CREATE and CREATE2 which exist after the
+    /// transaction, i.e. the `deployments` which succeeded and were not
+    /// reverted, including the ones of nested factories
+    #[getter]
+    fn created_contracts(&self) -> Vec<WrappedDeployment> {
+        self.deployments
+            .iter()
+            .filter(|deployment| deployment.is_created())
+            .map(Into::into)
+            .collect()
+    }
+
+    /// Accounts touched by the transaction with their balance, nonce,
+    /// code and changed storage before and after it, ordered by address
+    #[getter]
+    fn state_diff(&self) -> Vec<WrappedAccountDiff> {
+        self.state_diff.iter().map(Into::into).collect()
+    }
+
+    /// Invariants not holding after the transaction, see `TinyEVM.add_invariant`
+    #[getter]
+    fn invariant_violations(&self) -> Vec<WrappedInvariantViolation> {
+        self.invariant_violations.iter().map(Into::into).collect()
+    }
+
+    /// Number of executions of each opcode, for the given address or for
+    /// all addresses. Requires `REVMConfig.record_opcode_histogram`
+    #[pyo3(signature = (address=None))]
+    fn opcode_histogram(&self, address: Option<AddressArg>) -> Result<StdHashMap<u8, u64>> {
+        let counts = match address {
+            Some(address) => {
+                let address = address
+                    .address()
+                    .or(Err(PyValueError::new_err("Invalid address format")))?;
+                self.heuristics
+                    .opcode_counts
+                    .get(&address)
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            None => self.heuristics.opcode_histogram(),
+        };
+        Ok(counts
+            .into_iter()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .map(|(opcode, count)| (opcode as u8, count))
+            .collect())
+    }
+
+    /// Gas spent by category: `execution`, `memory`, `sload_cold`,
+    /// `sload_warm`, `sstore_cold`, `sstore_warm` and `calls` for the
+    /// opcodes, with the `intrinsic` gas and the `refunded` gas. Requires
+    /// `REVMConfig.record_gas_breakdown`
+    fn gas_breakdown(&self) -> StdHashMap<&'static str, u64> {
+        let breakdown = &self.heuristics.gas_breakdown;
+        StdHashMap::from([
+            ("intrinsic", self.intrinsic_gas),
+            ("execution", breakdown.execution),
+            ("memory", breakdown.memory),
+            ("sload_cold", breakdown.sload_cold),
+            ("sload_warm", breakdown.sload_warm),
+            ("sstore_cold", breakdown.sstore_cold),
+            ("sstore_warm", breakdown.sstore_warm),
+            ("calls", breakdown.calls),
+            ("refunded", self.gas_refunded),
+        ])
+    }
+
+    /// Hex encoded addresses indexed by the `address_index` of the bugs,
+    /// the missed branches and the comparison operands, and by the
+    /// `caller_index` and `to_index` of the traces
+    #[getter]
+    fn addresses(&self) -> Vec<String> {
+        self.addresses
+            .iter()
+            .map(|address| format!("0x{}", address.encode_hex::<String>()))
+            .collect()
+    }
+
+    /// Index of `address` in `addresses`, `None` if the address does not
+    /// appear in the response
+    fn address_index(&self, address: AddressArg) -> Result<Option<usize>> {
+        let address = address
+            .address()
+            .or(Err(PyValueError::new_err("Invalid address format")))?;
+        Ok(self.addresses.index_of(&address))
+    }
+
+    /// PCs visited by address as of this response
+    #[getter]
+    fn seen_pcs(&self) -> SeenPcsMap {
+        self.seen_pcs.as_ref().into()
+    }
+
+    /// Gas spent by the opcodes executed at each address, without the gas
+    /// of the frames they call. Requires `REVMConfig.record_gas_by_address`
+    fn gas_by_address(&self) -> StdHashMap<String, u64> {
+        self.heuristics
+            .gas_by_address
+            .iter()
+            .map(|(address, gas)| (format!("0x{}", address.encode_hex::<String>()), *gas))
+            .collect()
     }
 
     /// Return a set of unique PCs visited by the address
-    fn pcs_by_address(&self, address: String) -> Result<StdHashSet<usize>> {
+    fn pcs_by_address(&self, address: AddressArg) -> Result<StdHashSet<usize>> {
         let mut pc_set = StdHashSet::new();
-        let address = Address::from_str(trim_prefix(&address, "0x"))
+        let address = address
+            .address()
             .or(Err(PyValueError::new_err("Invalid address format")))?;
         let pcs = self.seen_pcs.get(&address);
 
         if let Some(pcs) = pcs {
-            for pc in pcs {
-                pc_set.insert(*pc);
-            }
+            pc_set.extend(pcs.iter());
             Ok(pc_set)
         } else {
             Ok(pc_set)
         }
     }
+
+    /// Compact CBOR encoding of the response, much cheaper to send to
+    /// another process than the Python objects of its fields
+    #[cfg(feature = "with-serde")]
+    pub fn to_bytes(&self) -> Result<Cow<'static, [u8]>> {
+        Ok(Cow::Owned(serde_cbor::to_vec(self)?))
+    }
+
+    /// Decode a response encoded with `to_bytes`
+    #[cfg(feature = "with-serde")]
+    #[staticmethod]
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(serde_cbor::from_slice(data)?)
+    }
+
+    /// CBOR encoding of the heuristics, see `WrappedHeuristics.from_bytes`
+    #[cfg(feature = "with-serde")]
+    pub fn heuristics_bytes(&self) -> Result<Cow<'static, [u8]>> {
+        Ok(Cow::Owned(serde_cbor::to_vec(&self.heuristics)?))
+    }
+
+    /// Whether a PC visited as of this response was not visited as of the
+    /// `previous` response, at any address
+    pub fn has_new_pcs(&self, previous: &Response) -> bool {
+        self.seen_pcs
+            .iter()
+            .any(|(address, pcs)| match previous.seen_pcs.get(address) {
+                Some(previous) => pcs.has_new(previous),
+                None => !pcs.is_empty(),
+            })
+    }
+}
+
+/// Response of a deployment, with the deployed address and runtime code.
+/// `data` still holds the address bytes
+#[cfg(feature = "python")]
+#[pyclass(extends = Response)]
+pub struct DeployResult {
+    /// Deployed address, zero if the deployment failed
+    pub address: Address,
+}
+
+#[cfg(feature = "python")]
+impl DeployResult {
+    /// Python object of the response of a deployment
+    pub fn new_py(py: Python<'_>, resp: Response) -> PyResult<Py<Self>> {
+        let address = match resp.data.len() {
+            20 if resp.success => Address::from_slice(&resp.data),
+            _ => Address::ZERO,
+        };
+        Py::new(
+            py,
+            PyClassInitializer::from(resp).add_subclass(Self { address }),
+        )
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl DeployResult {
+    /// Checksummed deployed address
+    #[getter]
+    fn address(&self) -> String {
+        self.address.to_checksum(None)
+    }
+
+    /// Deployed address as 20 bytes
+    #[getter]
+    fn address_bytes(&self) -> Cow<'static, [u8]> {
+        Cow::Owned(self.address.to_vec())
+    }
+
+    /// Runtime code of the deployed contract, empty if the deployment failed
+    #[getter]
+    fn runtime_code(slf: PyRef<'_, Self>) -> Cow<'static, [u8]> {
+        Cow::Owned(slf.as_ref().deployed_code.clone().unwrap_or_default())
+    }
+
+    /// Gas used by the deployment
+    #[getter]
+    fn gas_used(slf: PyRef<'_, Self>) -> u64 {
+        slf.as_ref().gas_usage
+    }
 }
@@ -0,0 +1,169 @@
+use eyre::{eyre, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use revm::interpreter::opcode;
+use std::fmt::{Display, Formatter};
+
+/// Position in a source file, line and column start at 1
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// Length in bytes of the source range
+    pub length: usize,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SourceLocation {
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SourceLocation({})", self)
+    }
+}
+
+/// Source range of an instruction `s:l:f`, all fields are -1 when the
+/// instruction is not related to any source file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct SourceRange {
+    offset: isize,
+    length: isize,
+    file: isize,
+}
+
+/// Parse a solc source map, empty fields are inherited from the previous
+/// entry. The jump type and the modifier depth are ignored.
+fn parse_source_map(source_map: &str) -> Result<Vec<SourceRange>> {
+    let mut ranges = vec![];
+    let mut last = SourceRange::default();
+    if source_map.is_empty() {
+        return Ok(ranges);
+    }
+    for (i, entry) in source_map.split(';').enumerate() {
+        let mut fields = entry.split(':');
+        let invalid = || eyre!("Invalid source map entry {}: {:?}", i, entry);
+        if let Some(s) = fields.next().filter(|s| !s.is_empty()) {
+            last.offset = s.parse().map_err(|_| invalid())?;
+        }
+        if let Some(l) = fields.next().filter(|l| !l.is_empty()) {
+            last.length = l.parse().map_err(|_| invalid())?;
+        }
+        if let Some(f) = fields.next().filter(|f| !f.is_empty()) {
+            last.file = f.parse().map_err(|_| invalid())?;
+        }
+        ranges.push(last);
+    }
+    Ok(ranges)
+}
+
+/// A source file with the byte offsets of its lines
+#[derive(Clone, Debug)]
+struct Source {
+    name: String,
+    content: String,
+    line_starts: Vec<usize>,
+}
+
+impl Source {
+    fn new(name: String, content: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            name,
+            content,
+            line_starts,
+        }
+    }
+
+    /// Line and column of a byte offset
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        (line, offset - self.line_starts[line - 1] + 1)
+    }
+}
+
+/// Translation of the PCs of a bytecode into source locations
+#[derive(Clone, Debug)]
+pub struct SourceMap {
+    /// Source range of the instruction starting at each PC
+    ranges: Vec<Option<SourceRange>>,
+    sources: Vec<Source>,
+    /// PCs of the JUMPIs of the code
+    jumpi_pcs: Vec<usize>,
+}
+
+impl SourceMap {
+    /// `source_map` is the solc source map of `code`, one entry per
+    /// instruction. `sources` are `(path, content)` ordered by source id.
+    pub fn new(code: &[u8], source_map: &str, sources: Vec<(String, String)>) -> Result<Self> {
+        let mut entries = parse_source_map(source_map)?.into_iter();
+        let mut ranges = vec![None; code.len()];
+        let mut jumpi_pcs = vec![];
+        let mut pc = 0;
+        while pc < code.len() {
+            ranges[pc] = entries.next();
+            let op = code[pc];
+            if op == opcode::JUMPI {
+                jumpi_pcs.push(pc);
+            }
+            pc += 1;
+            if (opcode::PUSH1..=opcode::PUSH32).contains(&op) {
+                pc += (op - opcode::PUSH1 + 1) as usize;
+            }
+        }
+
+        let sources = sources
+            .into_iter()
+            .map(|(name, content)| Source::new(name, content))
+            .collect();
+        Ok(Self {
+            ranges,
+            sources,
+            jumpi_pcs,
+        })
+    }
+
+    /// Source location of the instruction at `pc`, if it maps to one of
+    /// the sources
+    pub fn location(&self, pc: usize) -> Option<SourceLocation> {
+        let range = self.ranges.get(pc).copied().flatten()?;
+        let source = self.sources.get(usize::try_from(range.file).ok()?)?;
+        let (line, column) = source.line_column(usize::try_from(range.offset).ok()?);
+        Some(SourceLocation {
+            file: source.name.clone(),
+            line,
+            column,
+            length: usize::try_from(range.length).unwrap_or_default(),
+        })
+    }
+
+    /// Source locations of the instructions mapping to one of the sources,
+    /// by PC
+    pub fn locations(&self) -> impl Iterator<Item = (usize, SourceLocation)> + '_ {
+        (0..self.ranges.len()).filter_map(|pc| Some((pc, self.location(pc)?)))
+    }
+
+    /// PCs of the JUMPIs of the code
+    pub fn jumpi_pcs(&self) -> &[usize] {
+        &self.jumpi_pcs
+    }
+
+    /// `(path, content)` of the sources, ordered by source id
+    pub fn sources(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.sources
+            .iter()
+            .map(|source| (source.name.as_str(), source.content.as_str()))
+    }
+}
@@ -0,0 +1,81 @@
+use revm::{
+    primitives::{Address, Bytes, EVMError, ExecutionResult, B256, KECCAK_EMPTY},
+    Database, DatabaseCommit,
+};
+use ruint::aliases::U256;
+
+use crate::TinyEVM;
+
+/// Account touched by a transaction with its state before and after it
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountDiff {
+    pub address: Address,
+    /// `(before, after)` balance
+    pub balance: (U256, U256),
+    /// `(before, after)` nonce
+    pub nonce: (u64, u64),
+    /// `(before, after)` code hash, `KECCAK_EMPTY` without code
+    pub code_hash: (B256, B256),
+    /// Code after the transaction if it changed, e.g. for created contracts
+    pub code: Option<Bytes>,
+    /// `(slot, before, after)` of the slots whose value changed, by slot
+    pub storage: Vec<(U256, U256, U256)>,
+    pub selfdestructed: bool,
+}
+
+type TransactResult<DB> = Result<ExecutionResult, EVMError<<DB as Database>::Error>>;
+
+impl TinyEVM {
+    /// Execute and commit the current transaction, returning the diff of
+    /// the accounts it touched ordered by address
+    pub(crate) fn transact_commit_with_diff(
+        &mut self,
+    ) -> (TransactResult<crate::TinyEvmDb>, Vec<AccountDiff>) {
+        let result_and_state = match self.transact() {
+            Ok(result_and_state) => result_and_state,
+            Err(e) => return (Err(e), vec![]),
+        };
+
+        let db = self.db();
+        let mut diff: Vec<AccountDiff> = result_and_state
+            .state
+            .iter()
+            .filter(|(_, account)| account.is_touched())
+            .map(|(address, account)| {
+                let before = db.accounts.get(address).map(|a| &a.info);
+                let balance = before.map_or(U256::ZERO, |info| info.balance);
+                let nonce = before.map_or(0, |info| info.nonce);
+                let code_hash = before.map_or(KECCAK_EMPTY, |info| info.code_hash);
+                let selfdestructed = account.is_selfdestructed();
+                let after = if selfdestructed {
+                    Default::default()
+                } else {
+                    account.info.clone()
+                };
+                let mut storage: Vec<_> = account
+                    .storage
+                    .iter()
+                    .filter(|(_, slot)| slot.is_changed())
+                    .map(|(slot, value)| (*slot, value.original_value, value.present_value))
+                    .collect();
+                storage.sort_unstable_by_key(|(slot, _, _)| *slot);
+                AccountDiff {
+                    address: *address,
+                    balance: (balance, after.balance),
+                    nonce: (nonce, after.nonce),
+                    code_hash: (code_hash, after.code_hash),
+                    code: (code_hash != after.code_hash)
+                        .then(|| after.code.map(|code| code.original_bytes()))
+                        .flatten(),
+                    storage,
+                    selfdestructed,
+                }
+            })
+            .collect();
+        diff.sort_unstable_by_key(|account| account.address);
+
+        self.db_mut().commit(result_and_state.state);
+        (Ok(result_and_state.result), diff)
+    }
+}
@@ -0,0 +1,219 @@
+use pyo3::{
+    exceptions::PyBaseException,
+    prelude::*,
+    types::{PyDict, PyType},
+};
+
+use crate::errors::PyEvmResult;
+
+/// Generate the `.pyi` type stubs of the module from the signatures and the
+/// docs of its functions and classes, and write them to `path` if provided.
+/// PyO3 does not expose the Rust types at runtime, so the parameters and
+/// the results are typed as `Any`
+#[pyfunction]
+#[pyo3(signature = (path = None))]
+pub fn generate_stubs(py: Python<'_>, path: Option<String>) -> PyEvmResult<String> {
+    let module = py.import("tinyevm")?;
+    let keywords: Vec<String> = py.import("keyword")?.getattr("kwlist")?.extract()?;
+    let mut stubs = String::from(
+        "# Generated by tinyevm.generate_stubs(), do not edit\n\
+         from typing import Any, ClassVar, Iterator\n",
+    );
+    for (name, value) in module.dict().iter() {
+        let name: String = name.extract()?;
+        if name.starts_with("__") {
+            continue;
+        }
+        stubs.push('\n');
+        if let Ok(class) = value.downcast::<PyType>() {
+            class_stub(&mut stubs, &keywords, &name, class)?;
+        } else if value.is_callable() {
+            function_stub(&mut stubs, &keywords, "", &name, &value, false)?;
+        }
+    }
+    if let Some(path) = path {
+        std::fs::write(path, &stubs)?;
+    }
+    Ok(stubs)
+}
+
+fn class_stub(
+    stubs: &mut String,
+    keywords: &[String],
+    name: &str,
+    class: &Bound<'_, PyType>,
+) -> PyResult<()> {
+    if class.is_subclass_of::<PyBaseException>()? {
+        let base: String = class.getattr("__base__")?.getattr("__name__")?.extract()?;
+        stubs.push_str(&format!("class {}({}):\n", name, base));
+        doc_stub(stubs, "    ", class.as_any())?;
+        stubs.push_str("    ...\n");
+        return Ok(());
+    }
+
+    stubs.push_str(&format!("class {}:\n", name));
+    let documented = doc_stub(stubs, "    ", class.as_any())?;
+    let mut members = 0;
+    // `__dict__` of a class is a read-only proxy of its dict
+    let dict = class
+        .getattr("__dict__")?
+        .call_method0("copy")?
+        .downcast_into::<PyDict>()?;
+    for (member, value) in dict.iter() {
+        let member: String = member.extract()?;
+        let kind: String = value.get_type().name()?.extract()?;
+        if member == "__new__" {
+            // Only the classes with a constructor have a text signature
+            if let Ok(signature) = class.getattr("__text_signature__")?.extract::<String>() {
+                let signature = signature.replacen('(', "($self, ", 1).replace(", )", ")");
+                stubs.push_str(&format!(
+                    "    def __init__{} -> None: ...\n",
+                    stub_signature(keywords, &signature)
+                ));
+                members += 1;
+            }
+            continue;
+        }
+        if value.is_instance(class)? {
+            // Variants of an enum
+            stubs.push_str(&format!("    {}: ClassVar[{}]\n", member, name));
+        } else if kind == "getset_descriptor" {
+            stubs.push_str(&format!("    {}: Any\n", member));
+            doc_stub(stubs, "    ", &value)?;
+        } else if kind == "staticmethod" {
+            stubs.push_str("    @staticmethod\n");
+            function_stub(
+                stubs,
+                keywords,
+                "    ",
+                &member,
+                &value.getattr("__func__")?,
+                false,
+            )?;
+        } else if kind == "classmethod_descriptor" {
+            stubs.push_str("    @classmethod\n");
+            function_stub(stubs, keywords, "    ", &member, &value, false)?;
+        } else if kind == "method_descriptor" || kind == "wrapper_descriptor" {
+            function_stub(stubs, keywords, "    ", &member, &value, true)?;
+        } else {
+            continue;
+        }
+        members += 1;
+    }
+    if members == 0 && !documented {
+        stubs.push_str("    ...\n");
+    }
+    Ok(())
+}
+
+fn function_stub(
+    stubs: &mut String,
+    keywords: &[String],
+    indent: &str,
+    name: &str,
+    function: &Bound<'_, PyAny>,
+    method: bool,
+) -> PyResult<()> {
+    let signature = function
+        .getattr("__text_signature__")?
+        .extract::<String>()
+        .unwrap_or_else(|_| {
+            if method {
+                "($self, *args, **kwargs)"
+            } else {
+                "(*args, **kwargs)"
+            }
+            .into()
+        });
+    stubs.push_str(&format!(
+        "{}def {}{} -> {}:",
+        indent,
+        name,
+        stub_signature(keywords, &signature),
+        return_stub(name)
+    ));
+    let mut body = String::new();
+    if doc_stub(&mut body, &format!("{}    ", indent), function)? {
+        stubs.push('\n');
+        stubs.push_str(&body);
+    } else {
+        stubs.push_str(" ...\n");
+    }
+    Ok(())
+}
+
+/// Convert a text signature such as `($self, address, data=None)` to the
+/// signature of a stub. The parameters named after a Python keyword, which
+/// can only be passed by position, are renamed to positional-only `__name`
+fn stub_signature(keywords: &[String], signature: &str) -> String {
+    let signature = signature
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')');
+    let mut params = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in signature.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(&signature[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(&signature[start..]);
+
+    let params: Vec<String> = params
+        .into_iter()
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .map(|param| match param {
+            "$self" => "self".into(),
+            "$type" | "$cls" => "cls".into(),
+            "/" | "*" => param.into(),
+            _ => {
+                let (name, default) = match param.split_once('=') {
+                    Some((name, _)) => (name.trim(), " = ..."),
+                    None => (param, ""),
+                };
+                if keywords.iter().any(|keyword| keyword == name) {
+                    format!("__{}: Any{}", name, default)
+                } else {
+                    format!("{}: Any{}", name, default)
+                }
+            }
+        })
+        .collect();
+    format!("({})", params.join(", "))
+}
+
+/// Result type of the special methods, `Any` for the others
+fn return_stub(name: &str) -> &'static str {
+    match name {
+        "__str__" | "__repr__" => "str",
+        "__len__" | "__int__" | "__hash__" => "int",
+        "__contains__" | "__eq__" | "__ne__" | "__lt__" | "__le__" | "__gt__" | "__ge__"
+        | "__bool__" => "bool",
+        "__iter__" => "Iterator[Any]",
+        "__setstate__" | "__setitem__" | "__delitem__" => "None",
+        _ => "Any",
+    }
+}
+
+/// Write the docstring of an object if any, returns whether it has one
+fn doc_stub(stubs: &mut String, indent: &str, object: &Bound<'_, PyAny>) -> PyResult<bool> {
+    let doc = match object.getattr("__doc__")?.extract::<String>() {
+        Ok(doc) if !doc.trim().is_empty() => doc,
+        _ => return Ok(false),
+    };
+    let doc = doc
+        .trim()
+        .replace('\\', "\\\\")
+        .replace("\"\"\"", "\\\"\\\"\\\"");
+    let doc = doc.replace('\n', &format!("\n{}", indent));
+    stubs.push_str(&format!("{}\"\"\"{}\"\"\"\n", indent, doc));
+    Ok(true)
+}
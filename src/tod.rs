@@ -0,0 +1,93 @@
+use eyre::Result;
+use revm::primitives::{Address, Bytes};
+use ruint::aliases::U256;
+
+use crate::{instrument::StorageFootprint, TinyEVM};
+
+/// Transaction of a transaction-order-dependency analysis
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TodTx {
+    pub caller: Address,
+    pub contract: Address,
+    pub data: Vec<u8>,
+    pub value: U256,
+}
+
+/// Two transactions of a sequence whose outcome depends on their order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TodPair {
+    /// Index of the earlier transaction in the sequence
+    pub first: usize,
+    /// Index of the later transaction in the sequence
+    pub second: usize,
+    /// Slots `(address, slot)` written by one transaction and accessed by the other
+    pub slots: Vec<(Address, U256)>,
+}
+
+/// Success and output of each transaction and final value of the written slots
+type Outcome = (Vec<(bool, Bytes)>, Vec<U256>);
+
+impl TinyEVM {
+    /// Run `txs` in order and report the pairs of transactions accessing the
+    /// same storage, one of them writing it, whose outcome differs when the
+    /// two are executed in the reverse order from the initial state. The
+    /// outcome is the success and output of both transactions and the
+    /// final value of the slots they write. The state is restored once done.
+    pub fn find_tod_pairs(&mut self, txs: &[TodTx]) -> Result<Vec<TodPair>> {
+        let initial = self.db().clone();
+        let record_storage_footprint = self.instrument_config_mut().record_storage_footprint;
+        self.instrument_config_mut().record_storage_footprint = true;
+
+        let footprints: Vec<StorageFootprint> = txs
+            .iter()
+            .map(|tx| self.run_tod_tx(tx).heuristics.storage_footprint.clone())
+            .collect();
+
+        let mut pairs = vec![];
+        for (first, a) in footprints.iter().enumerate() {
+            for (second, b) in footprints.iter().enumerate().skip(first + 1) {
+                let slots = a.conflicts(b);
+                if slots.is_empty() {
+                    continue;
+                }
+                let written: Vec<_> = a.writes.keys().chain(b.writes.keys()).copied().collect();
+                *self.db_mut() = initial.clone();
+                let in_order = self.tod_outcome(&[&txs[first], &txs[second]], &written)?;
+                *self.db_mut() = initial.clone();
+                let (mut results, storage) =
+                    self.tod_outcome(&[&txs[second], &txs[first]], &written)?;
+                results.reverse();
+                if in_order != (results, storage) {
+                    pairs.push(TodPair {
+                        first,
+                        second,
+                        slots,
+                    });
+                }
+            }
+        }
+
+        *self.db_mut() = initial;
+        self.instrument_config_mut().record_storage_footprint = record_storage_footprint;
+        Ok(pairs)
+    }
+
+    fn run_tod_tx(&mut self, tx: &TodTx) -> crate::Response {
+        self.contract_call_helper(tx.contract, tx.caller, tx.data.clone(), tx.value, None)
+    }
+
+    fn tod_outcome(&mut self, txs: &[&TodTx], slots: &[(Address, U256)]) -> Result<Outcome> {
+        let results = txs
+            .iter()
+            .map(|tx| {
+                let resp = self.run_tod_tx(tx);
+                (resp.success, resp.data)
+            })
+            .collect();
+        let storage = slots
+            .iter()
+            .map(|(address, slot)| self.get_storage_by_address(*address, *slot))
+            .collect::<Result<_>>()?;
+        Ok((results, storage))
+    }
+}
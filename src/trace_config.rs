@@ -0,0 +1,156 @@
+use std::{fs::File, sync::Mutex};
+
+use eyre::Result;
+use tracing::{Dispatch, Level};
+use tracing_subscriber::{
+    fmt::{self, writer::BoxMakeWriter},
+    EnvFilter,
+};
+#[cfg(feature = "python")]
+use {
+    pyo3::prelude::*,
+    std::fmt::Write as _,
+    tracing::{
+        field::{Field, Visit},
+        Event, Subscriber,
+    },
+    tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        Layer, Registry,
+    },
+};
+
+/// Where and how the logs of a `TinyEVM` are written
+#[derive(Debug, Clone)]
+pub struct TraceConfig {
+    /// Level of the `tinyevm` and `revm` logs
+    pub level: String,
+    /// Directives in the `RUST_LOG` format, overriding `level`
+    pub filter: Option<String>,
+    /// File the logs are written to, instead of stdout
+    pub path: Option<String>,
+    /// Write the logs as JSON lines
+    pub json: bool,
+    /// Name of the Python logger the logs are sent to, instead of being
+    /// written to `path` or stdout
+    #[cfg(feature = "python")]
+    pub logger: Option<String>,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            level: "trace".into(),
+            filter: None,
+            path: None,
+            json: false,
+            #[cfg(feature = "python")]
+            logger: None,
+        }
+    }
+}
+
+impl TraceConfig {
+    /// Subscriber collecting the logs, to set as the default dispatcher
+    /// while the instance runs
+    pub fn dispatch(&self) -> Result<Dispatch> {
+        let filter = match &self.filter {
+            Some(filter) => EnvFilter::try_new(filter)?,
+            None => EnvFilter::try_new(format!("tinyevm={0},revm={0}", self.level))?,
+        };
+        #[cfg(feature = "python")]
+        if let Some(name) = &self.logger {
+            let layer = PyLoggingLayer::new(name)?;
+            return Ok(Dispatch::new(Registry::default().with(filter).with(layer)));
+        }
+        let writer = match &self.path {
+            Some(path) => BoxMakeWriter::new(Mutex::new(File::create(path)?)),
+            None => BoxMakeWriter::new(std::io::stdout),
+        };
+        let builder = fmt::Subscriber::builder()
+            .with_env_filter(filter)
+            .with_writer(writer)
+            .with_ansi(self.path.is_none());
+        Ok(if self.json {
+            Dispatch::new(builder.json().finish())
+        } else {
+            Dispatch::new(builder.finish())
+        })
+    }
+}
+
+/// Level of the Python `logging` module matching a `tracing` level,
+/// `TRACE` being below `logging.DEBUG`
+pub fn python_log_level(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 40,
+        Level::WARN => 30,
+        Level::INFO => 20,
+        Level::DEBUG => 10,
+        Level::TRACE => 5,
+    }
+}
+
+/// Message of an event followed by its other fields as `name=value`
+#[cfg(feature = "python")]
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+#[cfg(feature = "python")]
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
+
+/// Layer forwarding the events to a logger of the Python `logging` module
+#[cfg(feature = "python")]
+struct PyLoggingLayer {
+    logger: PyObject,
+}
+
+#[cfg(feature = "python")]
+impl PyLoggingLayer {
+    fn new(name: &str) -> Result<Self> {
+        let logger = Python::with_gil(|py| -> PyResult<PyObject> {
+            let logging = py.import("logging")?;
+            Ok(logging.call_method1("getLogger", (name,))?.unbind())
+        })?;
+        Ok(Self { logger })
+    }
+}
+
+#[cfg(feature = "python")]
+impl<S: Subscriber> Layer<S> for PyLoggingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let message = format!(
+            "{}: {}{}",
+            metadata.target(),
+            visitor.message,
+            visitor.fields
+        );
+        let level = python_log_level(metadata.level());
+        Python::with_gil(|py| {
+            // Errors of the handlers are reported by `logging` itself
+            let _ = self.logger.call_method1(py, "log", (level, message));
+        });
+    }
+}
@@ -1,24 +1,53 @@
 /// Test REVM functions
 extern crate lazy_static;
+use alloy_dyn_abi::DynSolValue;
+use ethers::abi::Token;
 use eyre::{ContextCompat, Result};
 use hex::ToHex;
 use lazy_static::lazy_static;
 use num_bigint::BigInt;
 use primitive_types::{H160, H256};
-use revm::interpreter::opcode::{self, CREATE, CREATE2, SELFDESTRUCT};
-use revm::primitives::Address;
+use revm::interpreter::opcode::{self, CREATE, CREATE2, DELEGATECALL, SELFDESTRUCT};
+use revm::interpreter::{CallScheme, InstructionResult};
+use revm::primitives::{
+    alloy_primitives::Bloom, keccak256, Address, Bytecode, SpecId, B256, KECCAK_EMPTY,
+};
 use ruint::aliases::U256;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::env;
 use std::iter::repeat_with;
 use std::ops::Add;
 use std::str::FromStr;
-use tinyevm::instrument::bug::{Bug, BugType, MissedBranch};
+use std::sync::{Arc, Mutex};
+use tinyevm::abi::decode_params;
+use tinyevm::analysis::{analyze, control_flow_graph, BytecodeAnalysis, ControlFlowGraph};
+use tinyevm::args::{AddressArg, BytesArg, PyU256, U256Arg};
+use tinyevm::config::TinyEVMConfig;
+use tinyevm::errors::{parse_address, Failure};
+use tinyevm::instrument::bug::{
+    Bug, BugType, CallFrame, Deployment, GasBreakdown, Heuristics, InstrumentConfig, MissedBranch,
+    Sha3Mapping,
+};
+use tinyevm::instrument::cheatcode_inspector::CHEATCODE_ADDRESS;
+use tinyevm::instrument::log_inspector::{
+    BreakpointHit, CallTrace, LogInspector, StructLog, TraceFilter,
+};
+use tinyevm::instrument::opcode_hook::OpcodeHooks;
+use tinyevm::instrument::trace_hook::TraceEvent;
+use tinyevm::instrument::PcSet;
+use tinyevm::invariant::{Invariant, InvariantViolation};
+use tinyevm::parallel::ParallelTx;
+use tinyevm::response::{
+    collapsed_stacks, decode_revert_reason, parity_traces, speedscope_profile, ExitReason,
+    Response, WrappedHeuristics,
+};
+use tinyevm::source_map::SourceLocation;
+use tinyevm::tod::{TodPair, TodTx};
 use tracing::warn;
 
 use tinyevm::{
-    enable_tracing, fn_sig_to_prefix, ruint_u256_to_bigint, trim_prefix, TinyEVM, TX_GAS_LIMIT,
+    event_topic, fn_sig_to_prefix, keccak256_bytes, trim_prefix, TinyEVM, WarmupTx, TX_GAS_LIMIT,
     UZERO,
 };
 
@@ -99,13 +128,15 @@ fn t_erc20_balance_query(vm: &mut TinyEVM, address: Address, expected_balance: U
 
     println!("resp.data: {:?}", resp.data);
 
-    let balance = U256::from_be_bytes::<32>(resp.data.as_slice().try_into().unwrap());
+    let balance = U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap());
 
     assert_eq!(expected_balance, balance);
 }
 
 fn setup() {
-    let _ = enable_tracing();
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter("tinyevm=trace,revm=trace")
+        .try_init();
 }
 
 /// Convenient function create binary for the solidty function: transfer(address,uint256)
@@ -261,7 +292,7 @@ fn test_revert_or_invalid() {
         (U256::from(10u64), None, false),
         (
             U256::from(200u64),
-            Some((BugType::RevertOrInvalid, 348)),
+            Some((BugType::PanicOverflow, 348)),
             true,
         ),
     ];
@@ -272,6 +303,87 @@ fn test_revert_or_invalid() {
     );
 }
 
+#[test]
+fn test_panic_codes() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Revert with Panic(code) for a code given as calldata, INVALID without calldata:
+    // CALLDATASIZE PUSH1 5 JUMPI INVALID JUMPDEST(pc 5) PUSH4 0x4e487b71 PUSH1 0xe0 SHL
+    // PUSH1 0 MSTORE PUSH1 0 CALLDATALOAD PUSH1 4 MSTORE PUSH1 0x24 PUSH1 0 REVERT(pc 27)
+    let bytecode = hex::decode("36600557fe5b634e487b7160e01b60005260003560045260246000fd").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let mut revert_bugs = |calldata: Vec<u8>| -> Vec<(BugType, usize)> {
+        let resp = vm.contract_call_helper(contract, *OWNER, calldata, UZERO, None);
+        assert!(!resp.success);
+        resp.bug_data
+            .iter()
+            .filter(|b| matches!(b.opcode, opcode::REVERT | opcode::INVALID))
+            .map(|b| (b.bug_type, b.position))
+            .collect()
+    };
+    let panic = |code: u64| U256::from(code).to_be_bytes::<32>().to_vec();
+
+    assert_eq!(vec![(BugType::RevertOrInvalid, 4)], revert_bugs(vec![]));
+    assert_eq!(
+        vec![(BugType::AssertionFailure, 27)],
+        revert_bugs(panic(0x01))
+    );
+    assert_eq!(
+        vec![(BugType::PanicDivisionByZero, 27)],
+        revert_bugs(panic(0x12))
+    );
+    assert_eq!(
+        vec![(BugType::PanicIndexOutOfBounds, 27)],
+        revert_bugs(panic(0x32))
+    );
+    assert_eq!(
+        vec![(BugType::Panic(U256::from(0x41)), 27)],
+        revert_bugs(panic(0x41))
+    );
+    assert_eq!(
+        vec![(BugType::Panic(UZERO), 27)],
+        revert_bugs(panic(0x00)),
+        "Generic compiler panic"
+    );
+}
+
+#[test]
+fn test_revert_reason() {
+    setup();
+    // Error("Not owner")
+    let error = hex::decode(concat!(
+        "08c379a0",
+        "0000000000000000000000000000000000000000000000000000000000000020",
+        "0000000000000000000000000000000000000000000000000000000000000009",
+        "4e6f74206f776e65720000000000000000000000000000000000000000000000"
+    ))
+    .unwrap();
+    assert_eq!(Some("Not owner".into()), decode_revert_reason(&error));
+    assert_eq!(None, decode_revert_reason(&error[..40]), "Truncated data");
+    assert_eq!(None, decode_revert_reason(&[]));
+
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // REVERT with Panic(CALLDATALOAD(0))
+    let bytecode = hex::decode("634e487b7160e01b60005260003560045260246000fd").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    let calldata = U256::from(0x11).to_be_bytes::<32>().to_vec();
+    let resp = vm.contract_call_helper(contract, *OWNER, calldata, UZERO, None);
+    assert!(!resp.success);
+    assert_eq!(
+        Some("Panic(0x11): arithmetic overflow or underflow"),
+        resp.revert_reason.as_deref()
+    );
+    assert_eq!(ExitReason::Revert, resp.exit_reason);
+    assert_eq!(
+        "Revert: Panic(0x11): arithmetic overflow or underflow",
+        resp.exit_message
+    );
+    assert_eq!(36, resp.data.len(), "The raw revert data should be kept");
+}
+
 #[test]
 fn test_timestamp_and_block_number() {
     let fn_args = format!("{:0>64x}", U256::from(32u64));
@@ -312,20 +424,31 @@ fn test_tx_origin() {
 #[test]
 fn test_tx_origin_v2() {
     setup();
+    // require(msg.sender == tx.origin) only rejects contract callers
     let contract_hex = include_str!("../tests/contracts/test_txorigin.hex");
-    let fn_args = "";
-    let fn_sig = "txorigin()";
-    let expected_bug = Some((BugType::TxOriginDependency, 54));
-    let revert = false;
+    let data = hex::decode(fn_sig_to_prefix("txorigin()")).unwrap();
+    let owner = OWNER.to_owned();
 
-    single_bugtype_test_helper(
-        contract_hex,
-        1,
-        fn_sig,
-        fn_args,
-        expected_bug,
-        revert, // this tests require(msg.sender == tx.origin) for one transaction
-    );
+    for (report_all_tx_origin, expected) in [(false, false), (true, true)] {
+        let mut vm = TinyEVM::default();
+        vm.instrument_config_mut().report_all_tx_origin = report_all_tx_origin;
+        let resp = vm
+            .deploy_helper(owner, hex::decode(contract_hex).unwrap(), UZERO, None, None)
+            .unwrap();
+        let address = Address::from_slice(&resp.data);
+
+        let resp = vm.contract_call_helper(address, owner, data.clone(), UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        let found = resp
+            .bug_data
+            .iter()
+            .any(|b| b.bug_type == BugType::TxOriginDependency && b.position == 54);
+        assert_eq!(
+            expected, found,
+            "TxOriginDependency with report_all_tx_origin {}: {:?}",
+            report_all_tx_origin, resp.bug_data
+        );
+    }
 }
 
 #[test]
@@ -413,6 +536,47 @@ fn test_deterministic_deploy_fail() {
     assert!(!c.success, "Deploy invalid deployment binary should fail",);
 }
 
+#[test]
+fn test_deployed_code() {
+    setup();
+    let mut vm = TinyEVM::default();
+    // Runtime code returning 42
+    let runtime = hex::decode("602a60005260206000f3").unwrap();
+    // Copy the runtime code appended at 11 and return it
+    let init_code = [
+        hex::decode("600a80600b6000396000f3").unwrap(),
+        runtime.clone(),
+    ]
+    .concat();
+
+    let resp = vm
+        .deploy_helper(*OWNER, init_code, UZERO, None, None)
+        .unwrap();
+    assert!(resp.success);
+    assert_eq!(Some(runtime.clone()), resp.deployed_code);
+    let hash = format!("0x{}", hex::encode(keccak256(&runtime)));
+    assert_eq!(Some(hash), resp.deployed_code_hash);
+
+    let address = Address::from_slice(&resp.data);
+    let resp = vm.contract_call_helper(address, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    assert_eq!(None, resp.deployed_code);
+    assert_eq!(None, resp.deployed_code_hash);
+
+    // The constructor reverts
+    let resp = vm
+        .deploy_helper(
+            *OWNER,
+            hex::decode("60006000fd").unwrap(),
+            UZERO,
+            None,
+            None,
+        )
+        .unwrap();
+    assert!(!resp.success);
+    assert_eq!(None, resp.deployed_code);
+}
+
 #[test]
 fn test_deterministic_deploy_overwrite() -> Result<()> {
     setup();
@@ -506,10 +670,11 @@ fn test_heuristics_inner(
 
     let heuristics = resp.heuristics;
 
-    let missed_branches: Vec<_> = heuristics.missed_branches.into_iter().skip(4).collect();
+    let missed_branches: Vec<_> = heuristics.missed_branches.iter().copied().skip(4).collect();
     let coverage: Vec<usize> = heuristics
         .coverage
-        .into_iter()
+        .iter()
+        .copied()
         .skip(4) // skip 4 from function selector operations
         .collect();
 
@@ -526,6 +691,37 @@ fn test_heuristics_inner(
     );
 }
 
+#[test]
+fn test_disable_inspector() {
+    setup();
+    deploy_hex!("../tests/contracts/heuristics.hex", exe, address);
+    let address = Address::new(address.0);
+    let tx_data = hex::decode(format!(
+        "{}{:0>64x}",
+        fn_sig_to_prefix("coverage(uint256)"),
+        U256::from(200)
+    ))
+    .unwrap();
+
+    let resp = exe.contract_call_helper(address, *OWNER, tx_data.clone(), UZERO, None);
+    assert!(resp.success);
+    assert!(!resp.heuristics.coverage.is_empty());
+
+    exe.set_inspector_enabled(false);
+    let fast = exe.contract_call_helper(address, *OWNER, tx_data.clone(), UZERO, None);
+    assert!(fast.success);
+    assert_eq!(resp.data, fast.data);
+    assert!(
+        fast.heuristics.coverage.is_empty(),
+        "Nothing is recorded without the inspectors"
+    );
+
+    exe.set_inspector_enabled(true);
+    let resp = exe.contract_call_helper(address, *OWNER, tx_data, UZERO, None);
+    assert!(resp.success);
+    assert!(!resp.heuristics.coverage.is_empty());
+}
+
 #[test]
 fn test_heuristics() {
     setup();
@@ -593,13 +789,14 @@ fn test_heuristics_signed_int() {
 
     assert!(resp.success, "Transaction should succeed.");
 
-    let r = U256::from_be_bytes::<32>(resp.data.try_into().unwrap());
+    let r = U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap());
     println!("Result: {r}");
 
     let missed_branches: Vec<_> = resp
         .heuristics
         .missed_branches
-        .into_iter()
+        .iter()
+        .copied()
         .skip(4)
         .collect();
 
@@ -692,10 +889,10 @@ fn test_deploy_with_args_and_value() {
         assert!(
             resp.success,
             "Read public value with {} error {:?}.",
-            fn_sig, resp.exit_reason
+            fn_sig, resp.exit_message
         );
 
-        let v = U256::from_be_bytes::<32>(resp.data.as_slice().try_into().unwrap());
+        let v = U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap());
         assert_eq!(expected_value, v, "Incorrect value read from {}", fn_sig);
     };
 
@@ -749,7 +946,7 @@ fn test_gas_usage() {
     let bin = hex::decode(bin).unwrap();
     let resp = vm.contract_call_helper(address, owner, bin, UZERO, None);
 
-    let value = U256::from_be_bytes::<32>(resp.data.as_slice().try_into().unwrap());
+    let value = U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap());
     assert_eq!(
         value,
         U256::from(9998),
@@ -763,6 +960,25 @@ fn test_gas_usage() {
     );
 }
 
+#[test]
+fn test_set_local_storage() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let address = Address::new(H160::random().0);
+    // Return SLOAD(7)
+    let code = hex::decode("60075460005260206000f3").unwrap();
+    vm.set_code_by_address(address, code).unwrap();
+    vm.set_local_storage_by_address(address, U256::from(7), U256::from(42));
+    assert_eq!(
+        U256::from(42),
+        vm.get_storage_by_address(address, U256::from(7)).unwrap()
+    );
+
+    let resp = vm.contract_call_helper(address, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(U256::from(42), U256::from_be_slice(&resp.data));
+}
+
 #[test]
 fn test_set_get_storage() {
     let owner = *OWNER;
@@ -773,16 +989,21 @@ fn test_set_get_storage() {
     let index = format!("{:064x}", index);
     let value = format!("{:064x}", target_value);
 
-    let r = exe.set_storage(address.clone(), index.clone(), value);
+    let r = exe.set_storage(
+        address.clone().into(),
+        index.clone().into(),
+        value.into(),
+        false,
+    );
     assert!(
         r.is_ok(),
         "Set storage by address and index should succeed."
     );
-    let val = exe.get_storage(address, index);
+    let val = exe.get_storage(address.into(), index.into());
     assert!(val.is_ok(), "Get storage should return some data");
     assert_eq!(
-        ruint_u256_to_bigint(&target_value),
-        val.unwrap(),
+        target_value,
+        val.unwrap().0,
         "Storage should be updated to the target value"
     );
 
@@ -797,7 +1018,7 @@ fn test_set_get_storage() {
         resp
     );
 
-    let result = U256::from_be_bytes::<32>(resp.data.as_slice().try_into().unwrap());
+    let result = U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap());
     assert_eq!(
         target_value, result,
         "Set storage should modify the corresponding value in contract in {:?}",
@@ -850,7 +1071,7 @@ fn test_exp_overflow() {
     let resp = vm.contract_call_helper(Address::new(address.0), owner, bin, UZERO, None);
 
     assert!(
-        !resp.bug_data.into_iter().any(|b| b.opcode == opcode::EXP),
+        !resp.bug_data.iter().any(|b| b.opcode == opcode::EXP),
         "Not expecting exp overflow"
     );
 
@@ -941,14 +1162,14 @@ fn test_blockhash() {
             let resp = vm.contract_call_helper(addr, owner, bin, UZERO, None);
             format!(
                 "{:x}",
-                U256::from_be_bytes::<32>(resp.data.try_into().unwrap())
+                U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap())
             )
         };
 
         let current_block = {
             let bin = hex::decode(fn_sig_to_prefix("bn()")).unwrap();
             let resp = vm.contract_call_helper(addr, owner, bin, UZERO, None);
-            U256::from_be_bytes::<32>(resp.data.try_into().unwrap())
+            U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap())
         };
 
         assert_eq!(
@@ -1009,7 +1230,7 @@ fn test_tod() {
 
     assert!(
         resp.bug_data
-            .into_iter()
+            .iter()
             .any(|b| b.bug_type == expected_sstore.bug_type
                 && b.opcode == expected_sstore.opcode
                 && b.position == expected_sstore.position),
@@ -1017,6 +1238,90 @@ fn test_tod() {
     );
 }
 
+#[test]
+fn test_find_tod_pairs() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // With calldata SSTORE(0, CALLDATALOAD(0)), otherwise return SLOAD(0):
+    // CALLDATASIZE PUSH1 15 JUMPI PUSH1 0 SLOAD PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+    // JUMPDEST(pc 15) PUSH1 0 CALLDATALOAD PUSH1 0 SSTORE STOP
+    let bytecode = hex::decode("36600f5760005460005260206000f35b600035600055").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let tx = |data: Vec<u8>| TodTx {
+        caller: *OWNER,
+        contract,
+        data,
+        value: UZERO,
+    };
+    let write = U256::from(5).to_be_bytes::<32>().to_vec();
+    let txs = [tx(vec![]), tx(write.clone()), tx(write)];
+    let pairs = vm.find_tod_pairs(&txs).unwrap();
+
+    let slots = vec![(contract, UZERO)];
+    assert_eq!(
+        vec![
+            TodPair {
+                first: 0,
+                second: 1,
+                slots: slots.clone()
+            },
+            TodPair {
+                first: 0,
+                second: 2,
+                slots
+            },
+        ],
+        pairs,
+        "Writing the same value twice does not depend on the order"
+    );
+    assert_eq!(UZERO, vm.get_storage_by_address(contract, UZERO).unwrap());
+    assert!(!vm.instrument_config_mut().record_storage_footprint);
+}
+
+#[test]
+fn test_invariants() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // With calldata SSTORE(0, CALLDATALOAD(0)), otherwise return SLOAD(0)
+    let bytecode = hex::decode("36600f5760005460005260206000f35b600035600055").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    let invariant = Invariant {
+        contract,
+        data: vec![],
+        expected: vec![0; 32],
+    };
+    vm.push_invariant(invariant.clone());
+
+    let write = |vm: &mut TinyEVM, value: u64| {
+        let calldata = U256::from(value).to_be_bytes::<32>().to_vec();
+        let resp = vm.contract_call_helper(contract, *OWNER, calldata, UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        resp
+    };
+
+    assert!(write(&mut vm, 0).invariant_violations.is_empty());
+    let resp = write(&mut vm, 5);
+    assert_eq!(
+        vec![InvariantViolation {
+            index: 0,
+            invariant,
+            success: true,
+            output: U256::from(5).to_be_bytes::<32>().to_vec(),
+        }],
+        resp.invariant_violations
+    );
+    assert!(
+        !vm.pcs_by_address()[&contract].contains(6),
+        "The view calls should not be instrumented"
+    );
+
+    vm.clear_invariants();
+    assert!(write(&mut vm, 6).invariant_violations.is_empty());
+}
+
 #[test]
 fn test_get_set_balance() {
     // Test balance set get
@@ -1053,7 +1358,7 @@ fn test_get_set_balance() {
     assert!(resp.success, "Call error {:?}", resp);
     assert_eq!(
         target_balance,
-        U256::from_be_bytes::<32>(resp.data.try_into().unwrap()),
+        U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap()),
         "Should be able to change the balance of a contract"
     );
 
@@ -1068,7 +1373,7 @@ fn test_get_set_balance() {
     assert!(resp.success, "Call error {:?}", resp);
     assert_eq!(
         target_balance,
-        U256::from_be_bytes::<32>(resp.data.try_into().unwrap()),
+        U256::from_be_bytes::<32>(resp.data[..].try_into().unwrap()),
         "Should be able to get the changed balance of others from inside a contract"
     );
 }
@@ -1084,11 +1389,12 @@ fn test_selfdestruct_and_create() {
 
     let bugs = resp.bug_data;
     assert!(
-        &bugs
-            .iter()
-            .clone()
-            .any(|b| matches!(b.bug_type, BugType::Unclassified) && b.opcode == SELFDESTRUCT),
-        "Selfdestruct should be detected"
+        &bugs.iter().clone().any(|b| b.bug_type
+            == BugType::UnprotectedSelfdestruct(
+                H160::from_str("0x4675C7e5BaAFBFFbca748158bEcBA61ef3b0a263").unwrap()
+            )
+            && b.opcode == SELFDESTRUCT),
+        "Unprotected selfdestruct should be detected"
     );
 
     assert!(
@@ -1102,100 +1408,2546 @@ fn test_selfdestruct_and_create() {
 }
 
 #[test]
-fn test_seen_pcs() {
-    // Deploy contract B
-    deploy_hex!("../tests/contracts/contract_creation_B.hex", vm, address);
+fn test_unchecked_call_return() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+
+    // CALL(gas, 0xdead, 0, 0, 0, 0, 0) at pc 14, followed by:
+    let cases = [
+        // POP STOP
+        ("5000", true),
+        // STOP, status left on the stack
+        ("00", true),
+        // ISZERO PUSH1 0x14 JUMPI STOP JUMPDEST STOP
+        ("15601457005b00", false),
+        // DUP1 ISZERO PUSH1 0x15 JUMPI STOP JUMPDEST POP STOP
+        ("8015601557005b5000", false),
+    ];
 
-    // Give owner some ether
-    let owner: Address = *OWNER;
-    vm.set_account_balance(
-        owner,
-        U256::from_str_radix("9999999999999999999999", 16).unwrap(),
-    )
-    .unwrap();
+    for (after_call, unchecked) in cases {
+        let bytecode = format!("6000600060006000600061dead5af1{}", after_call);
+        vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+            .unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
 
-    // Call b.add() with some ether
-    let bin = hex::decode(fn_sig_to_prefix("add()")).unwrap();
-    let resp = vm.contract_call_helper(
-        Address::new(address.0),
-        *OWNER,
-        bin,
-        U256::from_str_radix("999999", 16).unwrap(),
-        None,
-    );
-    assert!(resp.success, "Call error {:?}", resp);
+        let found = resp
+            .bug_data
+            .iter()
+            .any(|b| b.bug_type == BugType::UncheckedCallReturn && b.position == 14);
+        assert_eq!(
+            unchecked, found,
+            "Unchecked call return detection for {}: {:?}",
+            after_call, resp.bug_data
+        );
+    }
+}
 
-    let seen_pcs = &vm.pcs_by_address().get(&Address::new(address.0));
-    assert!(
-        seen_pcs.is_some(),
-        "Seen PCs should be found for the target contract "
-    );
-    let seen_pcs = seen_pcs.unwrap();
+#[test]
+fn test_checked_overflow_reverted() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+
+    // PUSH32 MAX PUSH1 1 ADD(pc 35) POP, then revert with
+    // `Panic(code)`: PUSH4 0x4e487b71 PUSH1 0xe0 SHL PUSH1 0 MSTORE
+    // PUSH1 code PUSH1 4 MSTORE PUSH1 0x24 PUSH1 0 REVERT
+    let cases = [("11", true), ("01", false)];
+
+    for (panic_code, checked) in cases {
+        let bytecode = format!(
+            "7f{}60010150634e487b7160e01b60005260{}60045260246000fd",
+            "ff".repeat(32),
+            panic_code
+        );
+        vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+            .unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        assert!(!resp.success, "Call should revert {:?}", resp);
 
-    println!("Seen PCs: {:?}", seen_pcs);
-    assert!(!seen_pcs.is_empty(), "Seen PCs should have some values");
+        let bug_types: Vec<_> = resp
+            .bug_data
+            .iter()
+            .filter(|b| b.position == 35)
+            .map(|b| b.bug_type)
+            .collect();
+        let expected = if checked {
+            BugType::CheckedOverflowReverted
+        } else {
+            BugType::IntegerOverflow
+        };
+        assert_eq!(
+            vec![expected],
+            bug_types,
+            "Overflow reverted with Panic(0x{})",
+            panic_code
+        );
+    }
 }
 
 #[test]
-fn test_runtime_configuration() {
+fn test_selfdestruct_guarded_by_caller() {
     setup();
-    deploy_hex!("../tests/contracts/contract_creation_B.hex", vm, address);
-    let address = Address::new(address.0);
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
 
-    vm.instrument_config_mut().pcs_by_address = false;
+    // CALLER PUSH20 OWNER EQ PUSH1 0x1b JUMPI STOP JUMPDEST PUSH2 0xdead SELFDESTRUCT(pc 31)
+    let bytecode = format!("3373{}14601b57005b61deadff", OWNER.encode_hex::<String>());
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
 
-    vm.set_account_balance(
-        *OWNER,
-        U256::from_str_radix("9999999999999999999999", 16).unwrap(),
-    )
-    .unwrap();
+    let selfdestruct = resp.bug_data.iter().find(|b| b.opcode == SELFDESTRUCT);
+    assert_eq!(
+        Some(BugType::Unclassified),
+        selfdestruct.map(|b| b.bug_type),
+        "Selfdestruct guarded by a caller check should not be reported as unprotected"
+    );
+}
 
-    // Call b.add() with some ether
-    let bin = hex::decode(fn_sig_to_prefix("add()")).unwrap();
-    let resp = vm.contract_call_helper(
-        address,
-        *OWNER,
-        bin,
-        U256::from_str_radix("999999", 16).unwrap(),
-        None,
+#[test]
+fn test_delegatecall_to_untrusted() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let target = H160::from_low_u64_be(0xdead);
+    let calldata = hex::decode(format!("{:0>64}", target.encode_hex::<String>())).unwrap();
+    let is_reported = |resp: &Response| {
+        resp.bug_data.iter().any(|b| {
+            b.bug_type == BugType::DelegatecallToUntrusted(target) && b.opcode == DELEGATECALL
+        })
+    };
+
+    // DELEGATECALL(gas, CALLDATALOAD(0), 0, 0, 0, 0)
+    let contract = Address::new(H160::random().0);
+    let bytecode = hex::decode("60006000600060006000355af400").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    let resp = vm.contract_call_helper(contract, *OWNER, calldata.clone(), UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(
+        is_reported(&resp),
+        "Delegatecall to calldata should be detected"
+    );
+
+    // DELEGATECALL(gas, 0xdead, 0, 0, 0, 0)
+    let bytecode = hex::decode("600060006000600061dead5af400").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    let resp = vm.contract_call_helper(contract, *OWNER, calldata.clone(), UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(
+        !is_reported(&resp),
+        "Delegatecall to a constant should not be reported"
     );
+
+    // With calldata: SSTORE(0, CALLDATALOAD(0)), otherwise
+    // DELEGATECALL(gas, SLOAD(0), 0, 0, 0, 0)
+    let bytecode = hex::decode("3660125760006000600060006000545af4005b600035600055").unwrap();
+    let contract = Address::new(H160::random().0);
+    vm.set_code_by_address(contract, bytecode.clone()).unwrap();
+    let resp = vm.contract_call_helper(contract, *OWNER, calldata, UZERO, None);
     assert!(resp.success, "Call error {:?}", resp);
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(
+        is_reported(&resp),
+        "Delegatecall to a slot written from calldata should be detected"
+    );
 
-    let seen_pcs = &vm.pcs_by_address().get(&address);
+    let contract = Address::new(H160::random().0);
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    vm.set_storage_by_address(contract, UZERO, U256::from(0xdead))
+        .unwrap();
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
     assert!(
-        seen_pcs.is_none() || seen_pcs.unwrap().is_empty(),
-        "No PCs by address should be recorded"
+        !is_reported(&resp),
+        "Delegatecall to a slot not written from calldata should not be reported"
     );
 }
 
 #[test]
-fn test_library_method_with_large_string() {
-    deploy_hex!("../tests/contracts/VeLogo.hex", vm, address);
-    let fn_sig = "tokenURI(uint256,uint256,uint256,uint256)";
-    let fn_args_hex: String = repeat_with(H256::random).take(4).map(hex::encode).collect();
-    let address = Address::new(address.0);
+fn test_arbitrary_send() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    let receiver = H160::from_low_u64_be(0xdead);
+    let calldata = hex::decode(format!("{:0>64}", receiver.encode_hex::<String>())).unwrap();
+    vm.set_account_balance(contract, U256::from(10)).unwrap();
+
+    // CALL(gas, CALLDATALOAD(0), value, 0, 0, 0, 0) at pc 14
+    for (value, expected) in [(1, true), (0, false)] {
+        let bytecode = format!("600060006000600060{:02x}6000355af100", value);
+        vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+            .unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, calldata.clone(), UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
 
-    let add_hex = format!("{}{}", fn_sig_to_prefix(fn_sig), fn_args_hex);
-    let data = hex::decode(add_hex).unwrap();
-    let r = vm.contract_call_helper(address, *OWNER, data, UZERO, None);
-    assert!(r.success);
-    r.seen_pcs
-        .into_iter()
-        .for_each(|e| println!("seen_pcs len: {} {}", e.0, e.1.len()));
+        let found = resp.bug_data.iter().any(|b| {
+            b.bug_type == BugType::ArbitrarySend(U256::from(value), receiver) && b.position == 14
+        });
+        assert_eq!(
+            expected, found,
+            "Sending {} wei to a calldata address: {:?}",
+            value, resp.bug_data
+        );
+    }
 }
 
 #[test]
-fn test_reset_storage() {
-    deploy_hex!("../tests/contracts/storage.hex", vm, addr);
-    let index = U256::ZERO;
+fn test_bug_calldata_taint() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    let calldata = vec![1u8; 4 + 64];
+    let max = "ff".repeat(32);
+
+    let cases = [
+        // ADD(CALLDATALOAD(4), MAX) at pc 36
+        (format!("7f{}60043501", max), 36, (4, 36)),
+        // CALLDATACOPY(0, 4, 64) then ADD(MLOAD(16), MAX) at pc 43
+        (format!("604060046000377f{}60105101", max), 43, (20, 52)),
+    ];
 
-    let target_value = U256::from(99u64);
+    for (bytecode, pc, expected) in cases {
+        vm.set_code_by_address(contract, hex::decode(&bytecode).unwrap())
+            .unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, calldata.clone(), UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
 
-    println!(
-        "Setting storage for address: {:?} index: {:?} to value: {:?}",
-        addr, index, target_value
-    );
-    let addr = Address::new(addr.0);
+        let overflow = resp
+            .bug_data
+            .iter()
+            .find(|b| b.bug_type == BugType::IntegerOverflow && b.position == pc);
+        assert_eq!(
+            Some(Some(expected)),
+            overflow.map(|b| b.calldata_taint),
+            "Calldata bytes influencing the overflow in {}: {:?}",
+            bytecode,
+            resp.bug_data
+        );
+    }
+
+    // The taint is not tracked when no detector needs it
+    let config = vm.instrument_config_mut();
+    config.calldata_taint = false;
+    config.detect_dependency = false;
+    let resp = vm.contract_call_helper(contract, *OWNER, calldata, UZERO, None);
+    let overflow = resp
+        .bug_data
+        .iter()
+        .find(|b| b.bug_type == BugType::IntegerOverflow && b.position == 43);
+    assert_eq!(Some(None), overflow.map(|b| b.calldata_taint));
+}
+
+#[test]
+fn test_cmp_operands() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // PUSH1 7 PUSH1 9 EQ(pc 4) PUSH1 3 LT(pc 7) STOP
+    let bytecode = hex::decode("600760091460031000").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(
+        resp.heuristics.cmp_operands.is_empty(),
+        "Comparison operands should not be recorded by default"
+    );
+
+    vm.instrument_config_mut().record_cmp_operands = true;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    let operands: Vec<_> = resp
+        .heuristics
+        .cmp_operands
+        .iter()
+        .map(|c| (c.pc, c.opcode, c.a, c.b))
+        .collect();
+    assert_eq!(
+        vec![
+            (4, opcode::EQ, U256::from(9), U256::from(7)),
+            (7, opcode::LT, U256::from(3), U256::ZERO),
+        ],
+        operands
+    );
+}
+
+#[test]
+fn test_bug_call_stack() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    let callee = Address::new(H160::random().0);
+    // TIMESTAMP STOP
+    vm.set_code_by_address(callee, hex::decode("4200").unwrap())
+        .unwrap();
+    // MSTORE(0, 0xdeadbeef << 224), CALL(gas, callee, 0, 0, 4, 0, 0), TIMESTAMP(pc 44)
+    let bytecode = format!(
+        "63deadbeef60e01b6000526000600060046000600073{}5af14200",
+        callee.encode_hex::<String>()
+    );
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![0x12, 0x34, 0x56, 0x78], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+
+    let outer = CallFrame {
+        caller: *OWNER,
+        callee: contract,
+        selector: Some([0x12, 0x34, 0x56, 0x78]),
+    };
+    let inner = CallFrame {
+        caller: contract,
+        callee,
+        selector: Some([0xde, 0xad, 0xbe, 0xef]),
+    };
+    let call_stacks: Vec<_> = resp
+        .bug_data
+        .iter()
+        .filter(|b| b.bug_type == BugType::TimestampDependency)
+        .map(|b| (b.position, b.call_stack.to_vec()))
+        .collect();
+    assert_eq!(
+        vec![(0, vec![outer, inner]), (44, vec![outer])],
+        call_stacks
+    );
+}
+
+#[test]
+fn test_branch_distance_of_interleaved_comparisons() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    let callee = Address::new(H160::random().0);
+    // EQ(1, 100) STOP
+    vm.set_code_by_address(callee, hex::decode("6001606414").unwrap())
+        .unwrap();
+    // LT(10, 3), POP(EQ(1, 1)), POP(CALL(gas, callee, 0, 0, 0, 0, 0)),
+    // JUMPI(49, LT result) at pc 47, STOP, JUMPDEST STOP
+    let bytecode = format!(
+        "6003600a106001600114506000600060006000600073{}5af150603157005b00",
+        callee.encode_hex::<String>()
+    );
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    let distances: Vec<_> = resp
+        .heuristics
+        .missed_branches
+        .iter()
+        .filter(|b| b.prev_pc == 47)
+        .map(|b| b.distance)
+        .collect();
+    assert_eq!(
+        vec![U256::from(8)],
+        distances,
+        "The distance should be the one of LT(10, 3)"
+    );
+}
+
+#[test]
+fn test_stable_bug_ids() {
+    setup();
+    // SLOAD(slot) TIMESTAMP(pc 3) STOP
+    let code = |slot: &str| hex::decode(format!("60{}544200", slot)).unwrap();
+    let ids = |code: Vec<u8>| -> Vec<(BugType, B256)> {
+        let mut vm = TinyEVM::default();
+        let contract = Address::new(H160::random().0);
+        vm.set_code_by_address(contract, code).unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        resp.bug_data.iter().map(|b| (b.bug_type, b.id)).collect()
+    };
+
+    let first = ids(code("00"));
+    assert_eq!(first, ids(code("00")), "Same code at another address");
+    assert!(first.iter().all(|(_, id)| *id != B256::ZERO));
+    assert!(first.contains(&(
+        BugType::TimestampDependency,
+        Bug::stable_id(&BugType::TimestampDependency, keccak256(code("00")), 3)
+    )));
+    let other = ids(code("01"));
+    assert!(first.iter().zip(&other).all(|((_, a), (_, b))| a != b));
+}
+
+#[test]
+fn test_detector_categories() {
+    setup();
+    // TIMESTAMP POP SSTORE(0, 1) SUB(0, 1) POP STOP
+    let code = hex::decode("4250600160005560016000035000").unwrap();
+    let contract = Address::new(H160::random().0);
+    let bug_types = |config: fn(&mut InstrumentConfig)| -> Vec<BugType> {
+        let mut vm = TinyEVM::default();
+        config(vm.instrument_config_mut());
+        vm.set_code_by_address(contract, code.clone()).unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        resp.bug_data.iter().map(|b| b.bug_type).collect()
+    };
+
+    let all = bug_types(|_| {});
+    assert!(all.contains(&BugType::TimestampDependency));
+    assert!(all.contains(&BugType::Sstore(U256::ZERO, U256::from(1))));
+    assert!(all.contains(&BugType::IntegerSubUnderflow));
+
+    let bugs = bug_types(|c| c.detect_arithmetic = false);
+    assert!(!bugs.contains(&BugType::IntegerSubUnderflow));
+    assert!(bugs.contains(&BugType::TimestampDependency));
+
+    let bugs = bug_types(|c| c.detect_dependency = false);
+    assert!(!bugs.contains(&BugType::TimestampDependency));
+    assert!(bugs.contains(&BugType::IntegerSubUnderflow));
+
+    let bugs = bug_types(|c| c.detect_storage = false);
+    assert!(!bugs.iter().any(|b| matches!(b, BugType::Sstore(..))));
+    assert!(bugs.contains(&BugType::IntegerSubUnderflow));
+
+    // The detectors can be changed between the transactions of an instance
+    let mut vm = TinyEVM::default();
+    vm.set_code_by_address(contract, code).unwrap();
+    for enabled in [true, false, true] {
+        vm.instrument_config_mut().detect_arithmetic = enabled;
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        let found = resp
+            .bug_data
+            .iter()
+            .any(|b| b.bug_type == BugType::IntegerSubUnderflow);
+        assert_eq!(enabled, found);
+    }
+}
+
+#[test]
+fn test_disabled_detectors_record_nothing() {
+    setup();
+    // SSTORE(0, 1) SUB(0, 1) POP KECCAK256(0, 32) POP STOP
+    let code = hex::decode("600160005560016000035060206000205000").unwrap();
+    let contract = Address::new(H160::random().0);
+    let mut vm = TinyEVM::default();
+    vm.set_code_by_address(contract, code).unwrap();
+    let mut call = |enabled: bool| {
+        let config = vm.instrument_config_mut();
+        config.detect_arithmetic = enabled;
+        config.detect_storage = enabled;
+        config.record_storage_footprint = enabled;
+        config.record_sha3_mapping = enabled;
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        let bug_types: Vec<_> = resp.bug_data.iter().map(|b| b.bug_type).collect();
+        (bug_types, resp.heuristics)
+    };
+
+    let (bugs, heuristics) = call(true);
+    assert!(bugs.contains(&BugType::IntegerSubUnderflow));
+    assert!(bugs.contains(&BugType::Sstore(U256::ZERO, U256::from(1))));
+    assert!(!heuristics.storage_footprint.writes.is_empty());
+    assert_eq!(1, heuristics.sha3_mapping.len());
+
+    let (disabled_bugs, disabled_heuristics) = call(false);
+    assert!(disabled_bugs.iter().all(|b| !matches!(
+        b,
+        BugType::IntegerSubUnderflow | BugType::Sstore(..) | BugType::Sload(..)
+    )));
+    assert!(disabled_heuristics.storage_footprint.writes.is_empty());
+    assert!(disabled_heuristics.sha3_mapping.is_empty());
+
+    let (enabled_bugs, enabled_heuristics) = call(true);
+    assert_eq!(bugs, enabled_bugs);
+    assert_eq!(
+        heuristics.storage_footprint.writes,
+        enabled_heuristics.storage_footprint.writes
+    );
+    assert_eq!(1, enabled_heuristics.sha3_mapping.len());
+}
+
+#[test]
+fn test_coverage_only() {
+    setup();
+    // SUB(0, 1) POP JUMPI(11, 1) JUMPDEST STOP
+    let code = hex::decode("6001600003506001600b575b00").unwrap();
+    let contract = Address::new(H160::random().0);
+    let mut vm = TinyEVM::default();
+    vm.set_code_by_address(contract, code).unwrap();
+    let config = vm.instrument_config_mut();
+    config.detect_arithmetic = false;
+    config.detect_dependency = false;
+    config.detect_storage = false;
+    config.record_sha3_mapping = false;
+    config.calldata_taint = false;
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(resp
+        .bug_data
+        .iter()
+        .all(|b| b.bug_type != BugType::IntegerSubUnderflow));
+    assert!(resp.heuristics.coverage.contains(&11));
+    assert!(!resp.heuristics.missed_branches.is_empty());
+    assert!(vm.pcs_by_address()[&contract].contains(11));
+}
+
+#[test]
+fn test_cheatcodes() {
+    setup();
+    let mut vm = TinyEVM::default();
+    vm.set_cheatcodes_enabled(true);
+    let cheat = |vm: &mut TinyEVM, signature: &str, args: &[B256]| {
+        let mut data = keccak256(signature)[..4].to_vec();
+        for arg in args {
+            data.extend_from_slice(arg.as_slice());
+        }
+        vm.contract_call_helper(CHEATCODE_ADDRESS, *OWNER, data, UZERO, None)
+    };
+    let word = |x: u64| B256::from(U256::from(x));
+    let contract = Address::new(H160::random().0);
+
+    // TIMESTAMP NUMBER ADD PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+    vm.set_code_by_address(contract, hex::decode("42430160005260206000f3").unwrap())
+        .unwrap();
+    assert!(cheat(&mut vm, "warp(uint256)", &[word(1000)]).success);
+    assert!(cheat(&mut vm, "roll(uint256)", &[word(7)]).success);
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert_eq!(word(1007).as_slice(), resp.data.as_ref());
+
+    let resp = cheat(
+        &mut vm,
+        "deal(address,uint256)",
+        &[contract.into_word(), word(5)],
+    );
+    assert!(resp.success);
+    assert_eq!(U256::from(5), vm.get_eth_balance(contract).unwrap());
+
+    let args = [contract.into_word(), word(1), word(9)];
+    assert!(cheat(&mut vm, "store(address,bytes32,bytes32)", &args).success);
+    assert_eq!(
+        U256::from(9),
+        vm.get_storage_by_address(contract, U256::from(1)).unwrap()
+    );
+    let resp = cheat(&mut vm, "load(address,bytes32)", &args[..2]);
+    assert_eq!(word(9).as_slice(), resp.data.as_ref());
+
+    let resp = cheat(&mut vm, "ffi(string[])", &[]);
+    assert!(!resp.success);
+    assert!(resp
+        .revert_reason
+        .is_some_and(|r| r.starts_with("Unsupported cheatcode")));
+
+    // Contract calling a cheatcode with one argument, then calling
+    // `callee` and returning the status and the first word returned
+    let mstore = |offset: u8, word: B256| format!("7f{}60{:02x}52", hex::encode(word), offset);
+    let call = |to: Address, args_len: u8, ret_len: u8| {
+        format!(
+            "60{:02x}600060{:02x}6000600073{}5af1",
+            ret_len,
+            args_len,
+            hex::encode(to)
+        )
+    };
+    let caller = |signature: &str, arg: B256, callee: Address| {
+        let mut selector = B256::ZERO;
+        selector[..4].copy_from_slice(&keccak256(signature)[..4]);
+        // Status at 0 and the returned word at 32
+        format!(
+            "{}{}{}50{}600051602052600052{}",
+            mstore(0, selector),
+            mstore(4, arg),
+            call(CHEATCODE_ADDRESS, 36, 0),
+            call(callee, 0, 32),
+            "60406000f3",
+        )
+    };
+    let contract = Address::new(H160::random().0);
+    let callee = Address::new(H160::random().0);
+    let prankster = Address::new(H160::random().0);
+    let run = |vm: &mut TinyEVM, code: String, callee_code: &str| {
+        vm.set_code_by_address(contract, hex::decode(code).unwrap())
+            .unwrap();
+        vm.set_code_by_address(callee, hex::decode(callee_code).unwrap())
+            .unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        (
+            U256::from_be_slice(&resp.data[..32]),
+            B256::from_slice(&resp.data[32..]),
+        )
+    };
+
+    // CALLER PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+    let returns_caller = "3360005260206000f3";
+    let prank = caller("prank(address)", prankster.into_word(), callee);
+    let (status, sender) = run(&mut vm, prank.clone(), returns_caller);
+    assert_eq!(U256::from(1), status);
+    assert_eq!(prankster.into_word(), sender);
+
+    // PUSH1 0 PUSH1 0 REVERT
+    let expect_revert = caller("expectRevert()", B256::ZERO, callee);
+    let (status, _) = run(&mut vm, expect_revert.clone(), "60006000fd");
+    assert_eq!(U256::from(1), status, "The revert was expected");
+    let (status, _) = run(&mut vm, expect_revert, "00");
+    assert_eq!(UZERO, status, "The call did not revert");
+
+    vm.set_cheatcodes_enabled(false);
+    let (_, sender) = run(&mut vm, prank, returns_caller);
+    assert_eq!(contract.into_word(), sender);
+}
+
+#[test]
+fn test_excluded_addresses() {
+    setup();
+    let contract = Address::new(H160::random().0);
+    let dependency = Address::new(H160::random().0);
+    // CALL(gas, dependency, 0, 0, 0, 0, 0), TIMESTAMP(pc 33)
+    let bytecode = format!(
+        "6000600060006000600073{}5af14200",
+        dependency.encode_hex::<String>()
+    );
+
+    for excluded in [false, true] {
+        let mut vm = TinyEVM::default();
+        // TIMESTAMP STOP
+        vm.set_code_by_address(dependency, hex::decode("4200").unwrap())
+            .unwrap();
+        vm.set_code_by_address(contract, hex::decode(&bytecode).unwrap())
+            .unwrap();
+        if excluded {
+            vm.instrument_config_mut()
+                .excluded_addresses
+                .insert(dependency);
+        }
+
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        let timestamp_bugs: Vec<_> = resp
+            .bug_data
+            .iter()
+            .filter(|b| b.bug_type == BugType::TimestampDependency)
+            .map(|b| b.position)
+            .collect();
+        let expected = if excluded { vec![33] } else { vec![0, 33] };
+        assert_eq!(expected, timestamp_bugs);
+        assert_eq!(!excluded, resp.seen_pcs.contains_key(&dependency));
+        assert_eq!(
+            !excluded,
+            resp.heuristics.seen_addresses.contains(&dependency)
+        );
+    }
+}
+
+#[test]
+fn test_storage_collision() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let proxy = Address::new(H160::random().0);
+    let implementation = Address::new(H160::random().0);
+    let eip1967_slot =
+        U256::from_be_bytes(keccak256("eip1967.proxy.implementation").0) - U256::from(1);
+    let eip1967_slot = eip1967_slot.to_be_bytes::<32>().encode_hex::<String>();
+
+    // SSTORE(0, 1), SLOAD(eip1967 slot), DELEGATECALL(gas, implementation, 0, 0, 0, 0)
+    let bytecode = format!(
+        "60016000557f{}5450600060006000600073{}5af400",
+        eip1967_slot,
+        implementation.encode_hex::<String>()
+    );
+    vm.set_code_by_address(proxy, hex::decode(bytecode).unwrap())
+        .unwrap();
+    // SLOAD(0) at pc 2, SLOAD(eip1967 slot)
+    let bytecode = format!("600054507f{}545000", eip1967_slot);
+    vm.set_code_by_address(implementation, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let collisions = |resp: &Response| -> Vec<(BugType, usize)> {
+        resp.bug_data
+            .iter()
+            .filter(|b| matches!(b.bug_type, BugType::StorageCollision(_)))
+            .map(|b| (b.bug_type, b.position))
+            .collect()
+    };
+
+    let resp = vm.contract_call_helper(proxy, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(collisions(&resp).is_empty());
+
+    vm.instrument_config_mut().storage_collision = Some((proxy, implementation));
+    let resp = vm.contract_call_helper(proxy, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(
+        vec![(BugType::StorageCollision(U256::ZERO), 2)],
+        collisions(&resp)
+    );
+
+    // Collisions are reported once
+    let resp = vm.contract_call_helper(proxy, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(collisions(&resp).is_empty());
+}
+
+#[test]
+fn test_subcall_out_of_gas() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    let callee = Address::new(H160::random().0);
+    // Infinite loop: JUMPDEST PUSH1 0 JUMP
+    vm.set_code_by_address(callee, hex::decode("5b600056").unwrap())
+        .unwrap();
+
+    // CALL(1000, callee, 0, 0, 0, 0, 0) at pc 34, then STOP or REVERT(0, 0)
+    for (end, expected) in [("00", true), ("60006000fd", false)] {
+        let bytecode = format!(
+            "6000600060006000600073{}6103e8f1{}",
+            callee.encode_hex::<String>(),
+            end
+        );
+        vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+            .unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+        assert_eq!(expected, resp.success, "Call error {:?}", resp);
+
+        let found = resp.bug_data.iter().any(|b| {
+            b.bug_type == BugType::SubcallOutOfGas(H160::from(callee.0 .0), 1000)
+                && b.position == 34
+                && b.opcode == opcode::CALL
+        });
+        assert_eq!(
+            expected, found,
+            "Subcall out of gas with caller ending in {}: {:?}",
+            end, resp.bug_data
+        );
+    }
+}
+
+#[test]
+fn test_opcode_histogram() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Loop 3 times over SLOAD:
+    // PUSH1 3 JUMPDEST PUSH1 0 SLOAD POP PUSH1 1 SWAP1 SUB DUP1 PUSH1 2 JUMPI STOP
+    let bytecode = hex::decode("60035b60005450600190038060025700").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(resp.heuristics.opcode_counts.is_empty());
+
+    vm.instrument_config_mut().record_opcode_histogram = true;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    let histogram = resp.heuristics.opcode_histogram();
+    assert_eq!(3, histogram[opcode::SLOAD as usize]);
+    assert_eq!(3, histogram[opcode::JUMPI as usize]);
+    assert_eq!(1, histogram[opcode::STOP as usize]);
+    assert_eq!(1 + 3 * 10 + 1, histogram.iter().sum::<u64>());
+    assert_eq!(
+        Some(&histogram),
+        resp.heuristics.opcode_counts.get(&contract)
+    );
+}
+
+#[test]
+fn test_execution_pressure_heuristics() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    let callee = Address::new(H160::random().0);
+    // MSTORE(0x40, 1) STOP
+    vm.set_code_by_address(callee, hex::decode("600160405200").unwrap())
+        .unwrap();
+    // MSTORE(0, 1), CALL(gas, callee, 0, 0, 0, 0, 0) with 7 items on the stack
+    let bytecode = format!(
+        "60016000526000600060006000600073{}5af100",
+        callee.encode_hex::<String>()
+    );
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(2, resp.heuristics.max_call_depth);
+    assert_eq!(7, resp.heuristics.max_stack_depth);
+    assert_eq!(0x60, resp.heuristics.max_memory_size);
+}
+
+#[test]
+fn test_keccak_equality_distance() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // MSTORE(0, "abc"), MSTORE(0x20, "abd"),
+    // JUMPI(29, EQ(KECCAK256(0, 0x20), KECCAK256(0x20, 0x20))) at pc 27, STOP, JUMPDEST STOP
+    let bytecode =
+        hex::decode("62616263600052626162646020526020602020602060002014601d57005b00").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    let distances: Vec<_> = resp
+        .heuristics
+        .missed_branches
+        .iter()
+        .map(|b| (b.prev_pc, b.distance))
+        .collect();
+    assert_eq!(
+        vec![(27, U256::from(1))],
+        distances,
+        "The hashed inputs differ by one byte"
+    );
+}
+
+#[test]
+fn test_opcode_hooks() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // SSTORE(1, 2) at pc 4, SLOAD(1) at pc 7
+    let bytecode = hex::decode("6002600155600154").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let calls = Arc::new(Mutex::new(vec![]));
+    let hook = {
+        let calls = calls.clone();
+        move |address: Address, pc: usize, opcode: u8, gas: u64, stack: &[U256]| -> Result<()> {
+            calls
+                .lock()
+                .unwrap()
+                .push((address, pc, opcode, gas, stack.to_vec()));
+            Ok(())
+        }
+    };
+    vm.set_opcode_hooks(Some(OpcodeHooks::new(Box::new(hook), &[opcode::SSTORE])));
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(
+        vec![(
+            contract,
+            4,
+            opcode::SSTORE,
+            // The intrinsic gas and two PUSH1 were charged
+            TX_GAS_LIMIT - 21000 - 6,
+            vec![U256::from(1), U256::from(2)]
+        )],
+        *calls.lock().unwrap()
+    );
+
+    vm.set_opcode_hooks(None);
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(1, calls.lock().unwrap().len());
+}
+
+#[test]
+fn test_state_bytes() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // SSTORE(0, 1)
+    vm.set_code_by_address(contract, hex::decode("600160005500")?)?;
+    vm.set_account_balance(*OWNER, U256::from(1000))?;
+    vm.instrument_config_mut().record_gas_by_address = true;
+    vm.exe_mut().block_mut().number = U256::from(42);
+    vm.take_snapshot(format!("{:#x}", contract).into())?;
+
+    let mut restored = TinyEVM::from_bytes(&vm.to_bytes()?)?;
+    assert_eq!(
+        vm.state().db.accounts.len(),
+        restored.state().db.accounts.len()
+    );
+    assert_eq!(
+        hex::decode("600160005500")?,
+        restored.get_code_by_address(contract)?
+    );
+    assert_eq!(
+        vm.get_balance(format!("{:#x}", *OWNER).into())?,
+        restored.get_balance(format!("{:#x}", *OWNER).into())?
+    );
+    assert_eq!(vm.state().tx_gas_limit, restored.state().tx_gas_limit);
+    assert!(restored.instrument_config_mut().record_gas_by_address);
+    assert_eq!(U256::from(42), restored.exe_mut().block().number);
+    assert!(restored.snapshots.contains_key(&contract));
+
+    let resp = restored.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let expected = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert_eq!(expected.gas_usage, resp.gas_usage);
+    assert_eq!(
+        expected.heuristics.gas_by_address,
+        resp.heuristics.gas_by_address
+    );
+    Ok(())
+}
+
+#[test]
+fn test_state_bytes_spec() -> Result<()> {
+    setup();
+    let config = TinyEVMConfig {
+        spec: "shanghai".into(),
+        ..Default::default()
+    };
+    let vm = TinyEVM::with_config(&config)?;
+
+    let mut restored = TinyEVM::from_bytes(&vm.to_bytes()?)?;
+    assert_eq!(SpecId::SHANGHAI, restored.exe_mut().spec_id());
+    Ok(())
+}
+
+#[test]
+fn test_scopes() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    let address = Address::new(H160::random().0);
+    let balance = |vm: &mut TinyEVM| vm.get_balance(format!("{:#x}", address).into()).unwrap().0;
+
+    vm.set_account_balance(address, U256::from(1))?;
+    vm.enter_scope()?;
+    vm.set_account_balance(address, U256::from(2))?;
+    vm.enter_scope()?;
+    vm.set_account_balance(address, U256::from(3))?;
+    assert_eq!(U256::from(3), balance(&mut vm));
+    vm.exit_scope()?;
+    assert_eq!(U256::from(2), balance(&mut vm));
+    vm.exit_scope()?;
+    assert_eq!(U256::from(1), balance(&mut vm));
+
+    assert!(vm.exit_scope().is_err(), "No scope left to exit");
+    Ok(())
+}
+
+#[test]
+fn test_account_iteration() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // SSTORE(0, 1) SSTORE(2, 3)
+    vm.set_code_by_address(contract, hex::decode("6001600055600360025500")?)?;
+    vm.set_account_balance(*OWNER, U256::from(1000))?;
+
+    let accounts = vm.account_infos();
+    assert!(accounts.windows(2).all(|w| w[0].0 < w[1].0));
+    let (_, owner) = accounts
+        .iter()
+        .find(|(address, _)| *address == *OWNER)
+        .context("Owner not found")?;
+    assert_eq!(U256::from(1000), owner.balance);
+    assert!(accounts.iter().any(|(address, _)| *address == contract));
+
+    assert!(vm.storage_of(contract).is_empty());
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    assert_eq!(
+        vec![
+            (U256::from(0), U256::from(1)),
+            (U256::from(2), U256::from(3))
+        ],
+        vm.storage_of(contract)
+    );
+    assert!(vm.storage_of(Address::new(H160::random().0)).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_clone_instance() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    let address = Address::new(H160::random().0);
+    vm.set_account_balance(address, U256::from(1))?;
+    vm.owner = address;
+    vm.exe_mut().block_mut().number = U256::from(42);
+
+    let mut copy = vm.clone_instance()?;
+    assert_eq!(U256::from(42), copy.exe_mut().block().number);
+    assert_eq!(address, copy.owner);
+    assert_eq!(
+        U256::from(1),
+        copy.get_balance(format!("{:#x}", address).into())?.0
+    );
+
+    copy.set_account_balance(address, U256::from(2))?;
+    assert_eq!(
+        U256::from(1),
+        vm.get_balance(format!("{:#x}", address).into())?.0,
+        "The copy should not change the original"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_instance_in_other_thread() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // SSTORE(0, CALLDATALOAD(0))
+    vm.set_code_by_address(contract, hex::decode("600035600055").unwrap())?;
+    vm.set_evm_tracing(true);
+
+    // The instance, its executor and its inspectors are moved to the thread
+    let mut vm = std::thread::spawn(move || {
+        let data = U256::from(7).to_be_bytes::<32>().to_vec();
+        let resp = vm.contract_call_helper(contract, *OWNER, data, UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        vm
+    })
+    .join()
+    .unwrap();
+    assert_eq!(U256::from(7), vm.get_storage_by_address(contract, UZERO)?);
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    assert_eq!(UZERO, vm.get_storage_by_address(contract, UZERO)?);
+    Ok(())
+}
+
+#[test]
+fn test_run_parallel() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // With calldata SSTORE(0, CALLDATALOAD(0)), otherwise return SLOAD(0)
+    let bytecode = hex::decode("36600f5760005460005260206000f35b600035600055").unwrap();
+    vm.set_code_by_address(contract, bytecode)?;
+    let value = U256::from(7).to_be_bytes::<32>().to_vec();
+    vm.contract_call_helper(contract, *OWNER, value, UZERO, None);
+
+    let tx = |data: Vec<u8>| ParallelTx {
+        caller: *OWNER,
+        contract,
+        data,
+        value: UZERO,
+    };
+    let write = U256::from(5).to_be_bytes::<32>().to_vec();
+    let txs: Vec<_> = (0..16)
+        .map(|i| tx(if i % 2 == 0 { write.clone() } else { vec![] }))
+        .collect();
+    let resps = vm.run_parallel(&txs)?;
+
+    assert_eq!(txs.len(), resps.len());
+    for (i, resp) in resps.iter().enumerate() {
+        assert!(resp.success);
+        if i % 2 == 1 {
+            assert_eq!(
+                U256::from(7),
+                U256::from_be_slice(&resp.data),
+                "Every call should start from the current state"
+            );
+        }
+    }
+    assert_eq!(U256::from(7), vm.get_storage_by_address(contract, UZERO)?);
+    Ok(())
+}
+
+#[test]
+fn test_with_config() -> Result<()> {
+    setup();
+    let config = TinyEVMConfig {
+        chain_id: 10,
+        spec: "shanghai".into(),
+        default_balance: U256::from(100),
+        ..Default::default()
+    };
+    let mut vm = TinyEVM::with_config(&config)?;
+    assert_eq!(10, vm.exe_mut().cfg().chain_id);
+    assert_eq!(SpecId::SHANGHAI, vm.exe_mut().spec_id());
+    assert_eq!(U256::from(100), vm.get_eth_balance(vm.owner)?);
+
+    let config = TinyEVMConfig {
+        spec: "unknown".into(),
+        ..Default::default()
+    };
+    assert!(TinyEVM::with_config(&config).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_hash_utilities() -> Result<()> {
+    assert_eq!("a9059cbb", fn_sig_to_prefix("transfer(address,uint256)"));
+    assert_eq!(
+        "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+        event_topic("Transfer(address,address,uint256)")
+    );
+    assert_eq!(
+        KECCAK_EMPTY.as_slice(),
+        keccak256_bytes(BytesArg::from("0x"))?.as_ref()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_decode_params() -> Result<()> {
+    let data = hex::decode(format!("{:0>64}{:0>64x}", "01", 42))?;
+    let values = decode_params(&["bool".into(), "uint256".into()], &data)?;
+    assert_eq!(
+        vec![
+            DynSolValue::Bool(true),
+            DynSolValue::Uint(U256::from(42), 256)
+        ],
+        values
+    );
+    assert!(decode_params(&["uint256".into()], &data[..16]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_python_args() -> Result<()> {
+    let address = Address::new(H160::random().0);
+    assert_eq!(
+        address,
+        AddressArg::from(format!("{:#x}", address)).address()?
+    );
+    assert_eq!(address, AddressArg::Bytes(address.to_vec()).address()?);
+    let err = AddressArg::Bytes(vec![0x12, 0x34]).address().unwrap_err();
+    assert_eq!(
+        Some(&Failure::InvalidAddress("0x1234".into())),
+        err.downcast_ref::<Failure>()
+    );
+
+    assert_eq!(vec![0x12, 0x34], BytesArg::from("0x1234").into_bytes()?);
+    assert_eq!(vec![0x12, 0x34], BytesArg::from("1234").into_bytes()?);
+    assert_eq!(
+        vec![0x12, 0x34],
+        BytesArg::Bytes(vec![0x12, 0x34]).into_bytes()?
+    );
+
+    assert_eq!(U256::from(255), U256Arg::from("0xff").value()?);
+    assert_eq!(U256::from(255), U256Arg::from(BigInt::from(255)).value()?);
+    assert_eq!(U256::MAX, U256Arg::from(U256::MAX).value()?);
+    assert_eq!(PyU256(U256::MAX), PyU256::from(&U256::MAX));
+    assert!(U256Arg::from(BigInt::from(-1)).value().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_failures() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    let address = Address::new(H160::random().0);
+
+    assert_eq!(address, parse_address(&format!("{:#x}", address))?);
+    assert_eq!(address, parse_address(&address.encode_hex::<String>())?);
+    let err = parse_address("0x1234").unwrap_err();
+    assert_eq!(
+        Some(&Failure::InvalidAddress("0x1234".into())),
+        err.downcast_ref::<Failure>()
+    );
+
+    let err = vm
+        .take_snapshot(format!("{:#x}", address).into())
+        .unwrap_err();
+    assert_eq!(Some(&Failure::AccountNotFound(address)), err.failure());
+
+    let err = vm
+        .restore_snapshot(format!("{:#x}", address).into())
+        .unwrap_err();
+    assert_eq!(
+        Some(&Failure::SnapshotNotFound(format!("{:#x}", address))),
+        err.failure()
+    );
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let err = vm.restore_global_snapshot(id.clone(), true).unwrap_err();
+    assert_eq!(Some(&Failure::SnapshotNotFound(id)), err.failure());
+
+    // Other errors carry no failure
+    let err = vm
+        .set_code(format!("{:#x}", address).into(), "0xzz".into())
+        .unwrap_err();
+    assert_eq!(None, err.failure());
+    Ok(())
+}
+
+#[test]
+fn test_analyze_bytecode() {
+    setup();
+    // JUMPI(8, 1) at pc 4, STOP, JUMPDEST(pc 6) STOP, JUMPDEST(pc 8) JUMPI(6, 0) at pc 13, STOP
+    let bytecode = hex::decode("6001600857005b005b600060065700").unwrap();
+    let analysis = analyze(&bytecode);
+    assert_eq!(
+        BytecodeAnalysis {
+            jumpdests: vec![6, 8],
+            jumpis: vec![(4, Some(8)), (13, Some(6))],
+            jumps: vec![],
+            basic_blocks: vec![(0, 4), (5, 5), (6, 7), (8, 13), (14, 14)],
+        },
+        analysis
+    );
+
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    assert_eq!(
+        vec![(4, 8), (4, 5), (13, 6), (13, 14)],
+        vm.uncovered_branches_by_address(contract).unwrap()
+    );
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(
+        vec![(4, 5), (13, 6)],
+        vm.uncovered_branches_by_address(contract).unwrap()
+    );
+}
+
+#[test]
+fn test_control_flow_graph() {
+    setup();
+    // JUMP(CALLDATALOAD(0)) at pc 3, JUMPDEST(pc 4) STOP,
+    // JUMPDEST(pc 6) JUMP(10) at pc 9, JUMPDEST(pc 10) STOP
+    let bytecode = hex::decode("600035565b005b600a565b00").unwrap();
+    let basic_blocks = vec![(0, 3), (4, 5), (6, 9), (10, 11)];
+    assert_eq!(
+        ControlFlowGraph {
+            basic_blocks: basic_blocks.clone(),
+            edges: vec![(6, 10)],
+        },
+        control_flow_graph(&bytecode, &Default::default())
+    );
+
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    let calldata = U256::from(6).to_be_bytes::<32>().to_vec();
+    let resp = vm.contract_call_helper(contract, *OWNER, calldata, UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(
+        ControlFlowGraph {
+            basic_blocks,
+            edges: vec![(0, 6), (6, 10)],
+        },
+        vm.control_flow_graph_by_address(contract).unwrap(),
+        "The dynamic jump should be added"
+    );
+}
+
+#[test]
+fn test_bug_buffer_size_and_dedup() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Loop 3 times over SLOAD(pc 5):
+    // PUSH1 3 JUMPDEST PUSH1 0 SLOAD POP PUSH1 1 SWAP1 SUB DUP1 PUSH1 2 JUMPI STOP
+    let bytecode = hex::decode("60035b600054506001900380600257").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let sload_counts = |resp: &Response| -> Vec<usize> {
+        resp.bug_data
+            .iter()
+            .filter(|b| matches!(b.bug_type, BugType::Sload(_)))
+            .map(|b| {
+                assert_eq!(5, b.position);
+                b.count
+            })
+            .collect()
+    };
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(vec![1, 1, 1], sload_counts(&resp));
+
+    vm.instrument_config_mut().bug_buffer_size = 2;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(2, resp.bug_data.len());
+    assert_eq!(vec![1, 1], sload_counts(&resp));
+
+    vm.instrument_config_mut().dedup_bugs = true;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(vec![3], sload_counts(&resp));
+}
+
+#[test]
+fn test_coverage_bitmap() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // PUSH1 1 PUSH1 10 JUMPI(pc 4) STOP x5 JUMPDEST(pc 10) STOP
+    let bytecode = hex::decode("6001600a5700000000005b00").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    assert_eq!(vec![0, 0], vm.coverage_bitmap(contract).unwrap());
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(
+        vec![0b0001_0101, 0b0000_1100],
+        vm.coverage_bitmap(contract).unwrap(),
+        "PCs 0, 2, 4, 10 and 11 should be set"
+    );
+}
+
+#[test]
+fn test_selector_coverage() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Jump to pc 16 when the selector is 0xaabbccdd:
+    // PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR PUSH4 0xaabbccdd EQ PUSH1 16 JUMPI(pc 14)
+    // STOP JUMPDEST(pc 16) STOP
+    let bytecode = hex::decode("60003560e01c63aabbccdd14601057005b00").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let resp = vm.contract_call_helper(
+        contract,
+        *OWNER,
+        hex::decode("aabbccdd").unwrap(),
+        UZERO,
+        None,
+    );
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(resp.heuristics.selector_coverage.is_empty());
+
+    vm.instrument_config_mut().coverage_by_selector = true;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert!(
+        resp.heuristics.selector_coverage.is_empty(),
+        "Calls without a selector are not recorded"
+    );
+
+    // (selector, pc executed, pc skipped, branch condition)
+    for (selector, seen, skipped, cond) in [(0xaabbccdd, 16, 15, true), (0x11223344, 15, 16, false)]
+    {
+        let calldata = u32::to_be_bytes(selector).to_vec();
+        let resp = vm.contract_call_helper(contract, *OWNER, calldata, UZERO, None);
+        assert!(resp.success, "Call error {:?}", resp);
+        let coverage = &resp.heuristics.selector_coverage[&contract];
+        assert_eq!(1, coverage.len());
+        let coverage = &coverage[&selector];
+        assert!(coverage.pcs.contains(&seen) && !coverage.pcs.contains(&skipped));
+        assert!(!coverage.missed_branches.is_empty());
+        assert!(coverage
+            .missed_branches
+            .iter()
+            .all(|b| b.dest_pc == 16 && b.cond == cond));
+    }
+}
+
+#[test]
+fn test_source_map() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    let source = "contract C {\n  function f() {\n    block.timestamp;\n  }\n}\n";
+    let sources = vec![("C.sol".to_string(), source.to_string())];
+    assert!(vm
+        .set_source_map_by_address(contract, "0:60:0", sources.clone())
+        .is_err());
+
+    // PUSH1 1 TIMESTAMP(pc 2) STOP
+    vm.set_code_by_address(contract, hex::decode("60014200").unwrap())
+        .unwrap();
+    assert!(vm
+        .set_source_map_by_address(contract, "0:x:0", sources.clone())
+        .is_err());
+    vm.set_source_map_by_address(contract, "0:60:0;34:15;-1:0:-1", sources)
+        .unwrap();
+
+    let location = |line, column, length| {
+        Some(SourceLocation {
+            file: "C.sol".into(),
+            line,
+            column,
+            length,
+        })
+    };
+    assert_eq!(
+        location(1, 1, 60),
+        vm.source_location_by_address(contract, 0)
+    );
+    assert_eq!(
+        None,
+        vm.source_location_by_address(contract, 1),
+        "PUSH data"
+    );
+    assert_eq!(
+        location(3, 5, 15),
+        vm.source_location_by_address(contract, 2)
+    );
+    assert_eq!(
+        None,
+        vm.source_location_by_address(contract, 3),
+        "No source file"
+    );
+    assert_eq!(
+        "C.sol:3:5",
+        vm.source_location_by_address(contract, 2)
+            .unwrap()
+            .to_string()
+    );
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    let locations: Vec<_> = resp
+        .bug_data
+        .iter()
+        .zip(vm.bug_source_locations(&resp))
+        .filter(|(b, _)| b.bug_type == BugType::TimestampDependency)
+        .map(|(_, location)| location)
+        .collect();
+    assert_eq!(vec![location(3, 5, 15)], locations);
+}
+
+#[test]
+fn test_coverage_report() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    let source = "contract C {\n  function f() {\n    if (msg.data.length > 0) {\n      block.timestamp;\n    }\n    block.number;\n  }\n}\n";
+    // CALLDATASIZE PUSH1 6 JUMPI NUMBER STOP JUMPDEST(pc 6) TIMESTAMP STOP
+    vm.set_code_by_address(contract, hex::decode("3660065743005b4200").unwrap())
+        .unwrap();
+    let offset = |s: &str| source.find(s).unwrap();
+    let source_map = format!(
+        "{}:25:0;;;{}:12:0;-1:0:-1;{}:15:0;;-1:0:-1",
+        offset("msg.data"),
+        offset("block.number"),
+        offset("block.timestamp")
+    );
+    let sources = vec![("C.sol".to_string(), source.to_string())];
+    vm.set_source_map_by_address(contract, &source_map, sources)
+        .unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(
+        "TN:\nSF:C.sol\nBRDA:3,0,0,0\nBRDA:3,0,1,1\nBRF:2\nBRH:1\n\
+         DA:3,3\nDA:4,0\nDA:6,1\nLF:3\nLH:2\nend_of_record\n",
+        vm.coverage_report().to_lcov()
+    );
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![1], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    let report = vm.coverage_report();
+    let file = &report.files["C.sol"];
+    assert_eq!((3, 3), file.line_counts());
+    assert_eq!(Some(&2), file.lines.get(&4));
+    assert!(file.branches[0].jumped && file.branches[0].fell_through);
+
+    let html = report.to_html();
+    assert!(html.contains("C.sol</a>: 3/3 lines"));
+    assert!(html.contains("<td>    if (msg.data.length &gt; 0) {</td>"));
+}
+
+#[test]
+fn test_seen_pcs() {
+    // Deploy contract B
+    deploy_hex!("../tests/contracts/contract_creation_B.hex", vm, address);
+
+    // Give owner some ether
+    let owner: Address = *OWNER;
+    vm.set_account_balance(
+        owner,
+        U256::from_str_radix("9999999999999999999999", 16).unwrap(),
+    )
+    .unwrap();
+
+    // Call b.add() with some ether
+    let bin = hex::decode(fn_sig_to_prefix("add()")).unwrap();
+    let resp = vm.contract_call_helper(
+        Address::new(address.0),
+        *OWNER,
+        bin,
+        U256::from_str_radix("999999", 16).unwrap(),
+        None,
+    );
+    assert!(resp.success, "Call error {:?}", resp);
+
+    let seen_pcs = &vm.pcs_by_address().get(&Address::new(address.0));
+    assert!(
+        seen_pcs.is_some(),
+        "Seen PCs should be found for the target contract "
+    );
+    let seen_pcs = seen_pcs.unwrap();
+
+    println!("Seen PCs: {:?}", seen_pcs);
+    assert!(!seen_pcs.is_empty(), "Seen PCs should have some values");
+}
+
+#[test]
+fn test_runtime_configuration() {
+    setup();
+    deploy_hex!("../tests/contracts/contract_creation_B.hex", vm, address);
+    let address = Address::new(address.0);
+
+    vm.instrument_config_mut().pcs_by_address = false;
+
+    vm.set_account_balance(
+        *OWNER,
+        U256::from_str_radix("9999999999999999999999", 16).unwrap(),
+    )
+    .unwrap();
+
+    // Call b.add() with some ether
+    let bin = hex::decode(fn_sig_to_prefix("add()")).unwrap();
+    let resp = vm.contract_call_helper(
+        address,
+        *OWNER,
+        bin,
+        U256::from_str_radix("999999", 16).unwrap(),
+        None,
+    );
+    assert!(resp.success, "Call error {:?}", resp);
+
+    let seen_pcs = &vm.pcs_by_address().get(&address);
+    assert!(
+        seen_pcs.is_none() || seen_pcs.unwrap().is_empty(),
+        "No PCs by address should be recorded"
+    );
+}
+
+#[test]
+fn test_library_method_with_large_string() {
+    deploy_hex!("../tests/contracts/VeLogo.hex", vm, address);
+    let fn_sig = "tokenURI(uint256,uint256,uint256,uint256)";
+    let fn_args_hex: String = repeat_with(H256::random).take(4).map(hex::encode).collect();
+    let address = Address::new(address.0);
+
+    let add_hex = format!("{}{}", fn_sig_to_prefix(fn_sig), fn_args_hex);
+    let data = hex::decode(add_hex).unwrap();
+    let r = vm.contract_call_helper(address, *OWNER, data, UZERO, None);
+    assert!(r.success);
+    r.seen_pcs
+        .iter()
+        .for_each(|e| println!("seen_pcs len: {} {}", e.0, e.1.len()));
+}
+
+#[test]
+fn test_pc_set() {
+    let mut pcs: PcSet = [3, 64, 200].into_iter().collect();
+    assert!(!pcs.insert(64));
+    assert!(pcs.insert(0));
+    assert_eq!(4, pcs.len());
+    assert!(pcs.contains(200) && !pcs.contains(199) && !pcs.contains(1000));
+    assert_eq!(Some(200), pcs.max());
+    assert_eq!(vec![0, 3, 64, 200], pcs.iter().collect::<Vec<_>>());
+
+    let previous: PcSet = [0, 3, 64, 200, 300].into_iter().collect();
+    assert!(!pcs.has_new(&previous));
+    assert!(previous.has_new(&pcs));
+    pcs.union_with(&previous);
+    assert_eq!(previous, pcs);
+    assert!(!PcSet::new().has_new(&PcSet::new()));
+}
+
+#[test]
+fn test_has_new_pcs() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // With calldata SSTORE(0, CALLDATALOAD(0)), otherwise return SLOAD(0)
+    let bytecode = hex::decode("36600f5760005460005260206000f35b600035600055").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let read = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(read.success);
+    assert!(read.seen_pcs[&contract].contains(6));
+    assert!(!read.seen_pcs[&contract].contains(15));
+
+    let read_again = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(
+        !read_again.has_new_pcs(&read),
+        "No new PCs on the same path"
+    );
+
+    let write = vm.contract_call_helper(contract, *OWNER, vec![1; 32], UZERO, None);
+    assert!(write.success);
+    assert!(write.has_new_pcs(&read_again), "The write branch is new");
+    assert!(!read_again.has_new_pcs(&write));
+}
+
+#[test]
+fn test_deployments() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let factory = Address::new(H160::random().0);
+    // Deploy the init code STOP, stored at memory 0, with CREATE and with
+    // CREATE2 and salt 42:
+    // PUSH1 1 PUSH1 0 PUSH1 0 CREATE POP
+    // PUSH1 42 PUSH1 1 PUSH1 0 PUSH1 0 CREATE2 POP STOP
+    let bytecode = hex::decode("600160006000f050602a600160006000f55000").unwrap();
+    vm.set_code_by_address(factory, bytecode).unwrap();
+
+    let resp = vm.contract_call_helper(factory, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+
+    let init_code_hash = keccak256([0x00]);
+    let salt = U256::from(42);
+    let created = factory.create2(salt.to_be_bytes::<32>(), init_code_hash);
+    assert_eq!(2, resp.deployments.len());
+    assert_eq!(
+        Deployment {
+            creator: factory,
+            salt: Some(salt),
+            init_code_hash,
+            address: Some(created),
+            code_hash: Some(KECCAK_EMPTY),
+            reverted: false,
+        },
+        resp.deployments[1]
+    );
+    let create = resp.deployments[0];
+    assert_eq!((factory, None), (create.creator, create.salt));
+    assert!(create.address.is_some_and(|a| a != created));
+    assert_eq!(
+        &vec![create.address.unwrap(), created],
+        vm.created_addresses()
+    );
+}
+
+#[test]
+fn test_created_contracts() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let factory = Address::new(H160::random().0);
+    // Init code deploying the init code STOP, then the same init code
+    // reverting: PUSH1 1 PUSH1 0 PUSH1 0 CREATE POP (STOP | PUSH1 0 PUSH1 0 REVERT)
+    let nested = "600160006000f05000";
+    let reverting = "600160006000f05060006000fd";
+    // Copy each init code, appended to the factory code at 31 and 40, and
+    // deploy it with CREATE then STOP
+    let bytecode = format!(
+        "6009601f600039600960006000f050600d6028600039600d60006000f05000{nested}{reverting}"
+    );
+    vm.set_code_by_address(factory, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let resp = vm.contract_call_helper(factory, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+
+    // In completion order, the nested deployments first
+    let [nested_child, nested, reverted_child, reverting] = resp.deployments.as_slice() else {
+        panic!("Expecting four deployments: {:?}", resp.deployments);
+    };
+    assert_eq!(factory, nested.creator);
+    assert_eq!(nested.address, Some(nested_child.creator));
+    assert_eq!(keccak256([0x00]), nested_child.init_code_hash);
+    assert!(nested.is_created() && nested_child.is_created());
+    assert_eq!(Some(KECCAK_EMPTY), nested_child.code_hash);
+
+    assert_eq!(None, reverting.code_hash);
+    assert!(!reverting.is_created());
+    assert!(reverted_child.reverted && reverted_child.code_hash.is_some());
+    assert!(!reverted_child.is_created());
+}
+
+#[test]
+fn test_snapshot_shares_accounts() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    let address = Address::new([1; 20]);
+    let slot = U256::from(1);
+    vm.set_storage_by_address(address, slot, U256::from(2))?;
+
+    let snapshot = vm.exe.as_ref().unwrap().db().clone();
+    let shared = |vm: &TinyEVM| {
+        let accounts = &vm.exe.as_ref().unwrap().db().accounts;
+        Arc::ptr_eq(&accounts[&address], &snapshot.accounts[&address])
+    };
+    assert!(shared(&vm), "The snapshot does not copy the accounts");
+
+    vm.set_storage_by_address(address, slot, U256::from(3))?;
+    assert!(!shared(&vm), "The modified account is copied");
+    assert_eq!(U256::from(2), snapshot.accounts[&address].storage[&slot]);
+    assert_eq!(U256::from(3), vm.get_storage_by_address(address, slot)?);
+
+    // The storage of the copied account is shared until it is written
+    let snapshot = vm.exe.as_ref().unwrap().db().clone();
+    vm.set_account_balance(address, U256::from(1))?;
+    let account = &vm.exe.as_ref().unwrap().db().accounts[&address];
+    assert!(!Arc::ptr_eq(account, &snapshot.accounts[&address]));
+    assert!(account.storage.ptr_eq(&snapshot.accounts[&address].storage));
+    Ok(())
+}
+
+#[test]
+fn test_analyzed_code() -> Result<()> {
+    setup();
+    let mut vm = TinyEVM::default();
+    // Init code returning the runtime code PUSH1 42 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+    let runtime_code = hex::decode("602a60005260206000f3")?;
+    let init_code = hex::decode("600a600c600039600a6000f3602a60005260206000f3")?;
+    let resp = vm.deploy_helper(*OWNER, init_code, UZERO, None, None)?;
+    assert!(resp.success, "Deployment error {:?}", resp);
+    let address = Address::from_slice(&resp.data);
+
+    let db = vm.exe.as_ref().unwrap().db();
+    let code = db.accounts[&address].info.code.clone();
+    assert!(matches!(code, Some(Bytecode::LegacyAnalyzed(_))));
+    let code_hash = db.accounts[&address].info.code_hash;
+    assert!(matches!(
+        db.contracts.get(&code_hash),
+        Some(Bytecode::LegacyAnalyzed(_))
+    ));
+    assert_eq!(runtime_code, vm.get_code_by_address(address)?);
+
+    let resp = vm.contract_call_helper(address, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    assert_eq!(U256::from(42), U256::from_be_slice(&resp.data));
+    Ok(())
+}
+
+#[test]
+fn test_trace_ids_per_instance() {
+    setup();
+    let init_code = hex::decode("600a600c600039600a6000f3602a60005260206000f3").unwrap();
+    let deploy = |vm: &mut TinyEVM| {
+        let resp = vm
+            .deploy_helper(*OWNER, init_code.clone(), UZERO, None, None)
+            .unwrap();
+        assert!(resp.success, "Deployment error {:?}", resp);
+        resp.traces.iter().map(|t| t.id).collect::<Vec<_>>()
+    };
+
+    let mut vm = TinyEVM::default();
+    vm.set_evm_tracing(true);
+    assert_eq!(vec![0], deploy(&mut vm));
+    assert_eq!(vec![1], deploy(&mut vm));
+
+    // Ids are not shared with the other instances of the thread
+    let mut other = TinyEVM::default();
+    other.set_evm_tracing(true);
+    assert_eq!(vec![0], deploy(&mut other));
+}
+
+#[test]
+fn test_create_traces() {
+    setup();
+    let mut vm = TinyEVM::default();
+    vm.set_evm_tracing(true);
+    // Factory deploying the init code STOP with CREATE and with CREATE2,
+    // see `test_deployments`
+    let factory_code = "600160006000f050602a600160006000f55000";
+    // Init code copying the factory code after it to memory and returning it:
+    // PUSH1 19 DUP1 PUSH1 11 PUSH1 0 CODECOPY PUSH1 0 RETURN
+    let init_code = format!("601380600b6000396000f3{}", factory_code);
+    let resp = vm
+        .deploy_helper(*OWNER, hex::decode(init_code).unwrap(), UZERO, None, None)
+        .unwrap();
+    assert!(resp.success, "Deployment error {:?}", resp);
+    let factory = Address::from_slice(&resp.data);
+
+    let [deployment] = resp.traces.as_slice() else {
+        panic!("Expecting one trace: {:?}", resp.traces);
+    };
+    assert_eq!("create", deployment.call_type);
+    assert_eq!(format!("{:#x}", factory), deployment.to);
+    assert_eq!(format!("0x{}", factory_code), deployment.return_data_hex());
+
+    let resp = vm.contract_call_helper(factory, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let [root, create, create2] = resp.traces.as_slice() else {
+        panic!("Expecting three traces: {:?}", resp.traces);
+    };
+    assert_eq!(vec![create.id, create2.id], root.children);
+    for (trace, call_type, deployment) in [
+        (create, "create", &resp.deployments[0]),
+        (create2, "create2", &resp.deployments[1]),
+    ] {
+        assert_eq!(call_type, trace.call_type);
+        assert_eq!(Some(root.id), trace.parent);
+        assert_eq!((1, "0x00"), (trace.depth, trace.input_hex().as_str()));
+        assert_eq!(format!("{:#x}", deployment.address.unwrap()), trace.to);
+    }
+
+    let traces = parity_traces(&resp.traces);
+    assert_eq!("create", traces[1]["type"]);
+    assert_eq!("0x00", traces[1]["action"]["init"]);
+    assert_eq!(create.to, traces[1]["result"]["address"]);
+}
+
+#[test]
+fn test_response_addresses() {
+    setup();
+    let mut vm = TinyEVM::default();
+    vm.set_evm_tracing(true);
+    // Factory deploying the init code STOP with CREATE and with CREATE2,
+    // see `test_create_traces`
+    let factory = Address::new(H160::random().0);
+    let factory_code = hex::decode("600160006000f050602a600160006000f55000").unwrap();
+    vm.set_code_by_address(factory, factory_code).unwrap();
+
+    let resp = vm.contract_call_helper(factory, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(
+        resp.heuristics.seen_addresses[..],
+        resp.addresses[..resp.heuristics.seen_addresses.len()]
+    );
+    for trace in &resp.traces {
+        let caller = resp.addresses[trace.caller_index.unwrap()];
+        let to = resp.addresses[trace.to_index.unwrap()];
+        assert_eq!(
+            (format!("{:#x}", caller), format!("{:#x}", to)),
+            (trace.caller.clone(), trace.to.clone())
+        );
+    }
+    assert!(resp.addresses.index_of(&factory).is_some());
+    for deployment in &resp.deployments {
+        assert!(resp
+            .addresses
+            .index_of(&deployment.address.unwrap())
+            .is_some());
+    }
+}
+
+#[test]
+fn test_response_bytes() {
+    setup();
+    let mut vm = TinyEVM::default();
+    vm.set_evm_tracing(true);
+    let contract = Address::new(H160::random().0);
+    // Without calldata, STATICCALL itself with the calldata 0x01, otherwise
+    // SSTORE(0, 1), see `test_static_state_modification`
+    let bytecode = "3660165760016000536000600060016000305afa50005b600160005500";
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(!resp.bug_data.is_empty() && !resp.traces.is_empty());
+    let bytes = resp.to_bytes().unwrap();
+    let decoded = Response::from_bytes(&bytes).unwrap();
+    assert_eq!(resp.bug_data, decoded.bug_data);
+    assert_eq!(resp.seen_pcs, decoded.seen_pcs);
+    assert_eq!(
+        format!("{:?}", resp.traces),
+        format!("{:?}", decoded.traces)
+    );
+    assert_eq!(
+        (resp.success, resp.gas_usage, &resp.data),
+        (decoded.success, decoded.gas_usage, &decoded.data)
+    );
+    assert!(Response::from_bytes(&bytes[1..]).is_err());
+
+    let heuristics = resp.heuristics_bytes().unwrap();
+    let decoded = WrappedHeuristics::from_bytes(&heuristics).unwrap();
+    let expected = WrappedHeuristics::from((*resp.heuristics).clone());
+    assert!(!expected.coverage.is_empty());
+    assert_eq!(
+        (expected.coverage, expected.seen_addresses),
+        (decoded.coverage, decoded.seen_addresses)
+    );
+}
+
+#[test]
+fn test_call_tree() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Without calldata, call itself with the calldata 0x01 then 0x02,
+    // otherwise return the first calldata word:
+    // CALLDATASIZE PUSH1 43 JUMPI
+    // PUSH1 1 PUSH1 0 MSTORE8 CALL(GAS, ADDRESS, 0, 0, 1, 0, 0) POP
+    // PUSH1 2 PUSH1 0 MSTORE8 CALL(GAS, ADDRESS, 0, 0, 1, 0, 0) POP STOP
+    // JUMPDEST(pc 43) PUSH1 0 CALLDATALOAD PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+    let call = "60006000600160006000305af150";
+    let bytecode = format!(
+        "36602b576001600053{}6002600053{}005b60003560005260206000f3",
+        call, call
+    );
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+    vm.set_evm_tracing(true);
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let [root, first, second] = resp.traces.as_slice() else {
+        panic!("Expecting three call traces: {:?}", resp.traces);
+    };
+    assert_eq!(None, root.parent);
+    assert_eq!(vec![first.id, second.id], root.children);
+    for (trace, byte) in [(first, "01"), (second, "02")] {
+        assert_eq!(Some(root.id), trace.parent);
+        assert!(trace.children.is_empty());
+        assert_eq!(format!("0x{:0<64}", byte), trace.return_data_hex());
+    }
+}
+
+#[test]
+fn test_trace_data_limit() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Return the first calldata word, see `test_call_tree`
+    vm.set_code_by_address(contract, hex::decode("60003560005260206000f3").unwrap())
+        .unwrap();
+    vm.set_evm_tracing(true);
+    vm.set_trace_data_limit(Some(2));
+
+    let data = hex::decode(format!("{:0<64}", "abcdef")).unwrap();
+    let resp = vm.contract_call_helper(contract, *OWNER, data.clone(), UZERO, None);
+    assert!(resp.success);
+    assert_eq!(
+        data,
+        resp.data.to_vec(),
+        "The output itself is not truncated"
+    );
+    let [trace] = resp.traces.as_slice() else {
+        panic!("Expecting one call trace: {:?}", resp.traces);
+    };
+    assert_eq!("0xabcd", trace.input_hex());
+    assert_eq!("0xabcd", trace.return_data_hex());
+
+    vm.set_trace_data_limit(None);
+    let resp = vm.contract_call_helper(contract, *OWNER, data.clone(), UZERO, None);
+    assert_eq!(
+        format!("0x{}", hex::encode(&data)),
+        resp.traces[0].input_hex()
+    );
+}
+
+#[test]
+fn test_reentrant_call_traces() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Call itself twice with the calldata word `n - 1` unless `n` is 0:
+    // PUSH1 0 CALLDATALOAD DUP1 ISZERO PUSH1 43 JUMPI
+    // PUSH1 1 SWAP1 SUB PUSH1 0 MSTORE
+    // CALL(GAS, ADDRESS, 0, 0, 32, 0, 0) POP, twice
+    // JUMPDEST(pc 43) STOP
+    let call = "60006000602060006000305af150";
+    let bytecode = format!("6000358015602b5760019003600052{}{}5b00", call, call);
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+    vm.set_evm_tracing(true);
+
+    let n = U256::from(2);
+    let data = n.to_be_bytes::<32>().to_vec();
+    let resp = vm.contract_call_helper(contract, *OWNER, data, UZERO, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    assert_eq!(7, resp.traces.len());
+
+    let by_id: HashMap<usize, _> = resp.traces.iter().map(|t| (t.id, t)).collect();
+    let word = |input: &str| U256::from_be_slice(&hex::decode(&input[2..]).unwrap());
+    for trace in &resp.traces {
+        assert_eq!("Stop", trace.status, "Trace not returned: {:?}", trace);
+        let n = word(&trace.input_hex());
+        let children: Vec<_> = trace.children.iter().map(|id| by_id[id]).collect();
+        assert_eq!(if n.is_zero() { 0 } else { 2 }, children.len());
+        for child in children {
+            assert_eq!(Some(trace.id), child.parent);
+            assert_eq!(trace.depth + 1, child.depth);
+            assert_eq!(n - U256::from(1), word(&child.input_hex()));
+        }
+    }
+}
+
+#[test]
+fn test_trace_hook() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Without calldata, call itself with the calldata 0x01 then 0x02,
+    // otherwise return the first calldata word, see `test_call_tree`
+    let call = "60006000600160006000305af150";
+    let bytecode = format!(
+        "36602b576001600053{}6002600053{}005b60003560005260206000f3",
+        call, call
+    );
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+    vm.set_evm_tracing(true);
+
+    // Abort once `limit` calls returned
+    let returned = Arc::new(Mutex::new(vec![]));
+    let hook = |limit: usize| {
+        let returned = returned.clone();
+        move |event: TraceEvent<'_>| -> Result<bool> {
+            let TraceEvent::Call(trace) = event else {
+                return Ok(true);
+            };
+            let mut returned = returned.lock().unwrap();
+            returned.push((trace.depth, trace.input.to_vec()));
+            Ok(returned.len() < limit)
+        }
+    };
+    vm.set_boxed_trace_hook(Some(Box::new(hook(usize::MAX))));
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    assert_eq!(
+        vec![(1, vec![1]), (1, vec![2]), (0, vec![])],
+        *returned.lock().unwrap()
+    );
+
+    returned.lock().unwrap().clear();
+    vm.set_boxed_trace_hook(Some(Box::new(hook(1))));
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(!resp.success, "The transaction should be aborted");
+    assert_eq!(vec![(1, vec![1])], *returned.lock().unwrap());
+
+    let failing = |_: TraceEvent<'_>| -> Result<bool> { Err(eyre::eyre!("Stop")) };
+    vm.set_boxed_trace_hook(Some(Box::new(failing)));
+    let err = vm
+        .contract_call(format!("{:#x}", contract).into(), None, None, None)
+        .unwrap_err();
+    assert_eq!("Stop", err.to_string());
+
+    vm.set_boxed_trace_hook(None);
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+}
+
+#[test]
+fn test_gas_accounting() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Store the first calldata word in the slot 0:
+    // PUSH1 0 CALLDATALOAD PUSH1 0 SSTORE STOP
+    vm.set_code_by_address(contract, hex::decode("60003560005500").unwrap())
+        .unwrap();
+
+    let mut word = vec![0u8; 32];
+    word[31] = 1;
+    let resp = vm.contract_call_helper(contract, *OWNER, word, UZERO, None);
+    assert!(resp.success);
+    // Base cost and 31 zero bytes and a non-zero byte of calldata
+    assert_eq!(21000 + 31 * 4 + 16, resp.intrinsic_gas);
+    assert_eq!(0, resp.gas_refunded);
+    // Three PUSH1 and CALLDATALOAD, and a cold SSTORE of a new value
+    assert_eq!(3 * 3 + 22100, resp.execution_gas);
+    assert_eq!(resp.intrinsic_gas + resp.execution_gas, resp.gas_usage);
+    assert_eq!(0, resp.blob_gas_used);
+
+    // Clearing the slot is refunded
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![0u8; 32], UZERO, None);
+    assert!(resp.success);
+    assert_eq!(21000 + 32 * 4, resp.intrinsic_gas);
+    assert_eq!(4800, resp.gas_refunded);
+    assert_eq!(3 * 3 + 5000, resp.execution_gas);
+    assert_eq!(
+        resp.intrinsic_gas + resp.execution_gas - resp.gas_refunded,
+        resp.gas_usage
+    );
+}
+
+#[test]
+fn test_gas_breakdown() {
+    setup();
+    let mut vm = TinyEVM::default();
+    vm.instrument_config_mut().record_gas_breakdown = true;
+    let contract = Address::new(H160::random().0);
+    // PUSH1 0 SLOAD POP PUSH1 0 SLOAD PUSH1 0 MSTORE PUSH1 1 PUSH1 0 SSTORE
+    // STATICCALL(GAS, 0xff, 0, 0, 0, 0) POP STOP
+    let bytecode = "600054506000546000526001600055600060006000600060ff5afa5000";
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let breakdown = &resp.heuristics.gas_breakdown;
+    // 10 PUSH1, 2 POP, MSTORE and GAS
+    assert_eq!(10 * 3 + 2 * 2 + 3 + 2, breakdown.execution);
+    assert_eq!(3, breakdown.memory);
+    assert_eq!(2100, breakdown.sload_cold);
+    assert_eq!(100, breakdown.sload_warm);
+    assert_eq!(0, breakdown.sstore_cold);
+    assert_eq!(20000, breakdown.sstore_warm);
+    // Cold account access, the forwarded gas is returned by the empty account
+    assert_eq!(2600, breakdown.calls);
+    assert_eq!(resp.execution_gas, breakdown.total());
+
+    // Not recorded by default
+    vm.instrument_config_mut().record_gas_breakdown = false;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert_eq!(GasBreakdown::default(), resp.heuristics.gas_breakdown);
+}
+
+#[test]
+fn test_gas_by_address() {
+    setup();
+    let mut vm = TinyEVM::default();
+    vm.instrument_config_mut().record_gas_by_address = true;
+    let (caller, callee) = (
+        Address::new(H160::random().0),
+        Address::new(H160::random().0),
+    );
+    // SSTORE(0, 1)
+    vm.set_code_by_address(callee, hex::decode("600160005500").unwrap())
+        .unwrap();
+    // CALL(GAS, callee, 0, 0, 0, 0, 0) POP STOP
+    let bytecode = format!("6000600060006000600073{}5af15000", hex::encode(callee));
+    vm.set_code_by_address(caller, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let resp = vm.contract_call_helper(caller, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let gas = &resp.heuristics.gas_by_address;
+    // Two PUSH1 and a cold SSTORE of a new value
+    assert_eq!(Some(&(2 * 3 + 22100)), gas.get(&callee));
+    // Six PUSH, GAS, the cold account access of CALL and POP
+    assert_eq!(Some(&(6 * 3 + 2 + 2600 + 2)), gas.get(&caller));
+    assert_eq!(resp.execution_gas, gas.values().sum::<u64>());
+}
+
+#[test]
+fn test_static_state_modification() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Without calldata, STATICCALL itself with the calldata 0x01, otherwise
+    // SSTORE(0, 1) at pc 27: CALLDATASIZE PUSH1 22 JUMPI
+    // PUSH1 1 PUSH1 0 MSTORE8 STATICCALL(GAS, ADDRESS, 0, 1, 0, 0) POP STOP
+    // JUMPDEST(pc 22) PUSH1 1 PUSH1 0 SSTORE STOP
+    let bytecode = "3660165760016000536000600060016000305afa50005b600160005500";
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let is_static_modification = |b: &Bug| {
+        b.bug_type == BugType::StaticStateModification
+            && b.position == 27
+            && b.opcode == opcode::SSTORE
+    };
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success, "The failed STATICCALL is ignored");
+    assert!(
+        resp.bug_data.iter().any(is_static_modification),
+        "SSTORE in a static frame: {:?}",
+        resp.bug_data
+    );
+    assert_eq!(UZERO, vm.get_storage_by_address(contract, UZERO).unwrap());
+
+    // The same SSTORE outside of a static frame
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![1], UZERO, None);
+    assert!(resp.success);
+    assert!(!resp.bug_data.iter().any(is_static_modification));
+}
+
+#[test]
+fn test_state_diff() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Store the first calldata word in the slot 0, see `test_gas_accounting`
+    vm.set_code_by_address(contract, hex::decode("60003560005500").unwrap())
+        .unwrap();
+    let owner_balance = U256::from(1000);
+    vm.set_account_balance(*OWNER, owner_balance).unwrap();
+    let balance = vm.get_eth_balance(contract).unwrap();
+
+    let value = U256::from(5);
+    let data = U256::from(1).to_be_bytes::<32>().to_vec();
+    let resp = vm.contract_call_helper(contract, *OWNER, data, value, None);
+    assert!(resp.success, "Call error {:?}", resp);
+    let addresses: Vec<_> = resp.state_diff.iter().map(|d| d.address).collect();
+    assert!(addresses.is_sorted(), "Diff not ordered {:?}", addresses);
+
+    let find = |address| resp.state_diff.iter().find(|d| d.address == address);
+    let diff = find(contract).expect("Contract not in the diff");
+    assert_eq!((balance, balance + value), diff.balance);
+    assert_eq!(vec![(UZERO, UZERO, U256::from(1))], diff.storage);
+    assert_eq!(diff.code_hash.0, diff.code_hash.1);
+    assert_eq!(None, diff.code);
+    let diff = find(*OWNER).expect("Caller not in the diff");
+    assert_eq!((owner_balance, owner_balance - value), diff.balance);
+    assert_eq!(diff.nonce.0 + 1, diff.nonce.1);
+    assert!(diff.storage.is_empty());
+
+    // Writing the same value changes nothing in the storage
+    let data = U256::from(1).to_be_bytes::<32>().to_vec();
+    let resp = vm.contract_call_helper(contract, *OWNER, data, UZERO, None);
+    let diff = resp
+        .state_diff
+        .iter()
+        .find(|d| d.address == contract)
+        .unwrap();
+    assert!(diff.storage.is_empty());
+    assert_eq!((balance + value, balance + value), diff.balance);
+}
+
+#[test]
+fn test_logs_bloom() {
+    setup();
+    let mut vm = TinyEVM::default();
+    vm.set_evm_tracing(true);
+    let contract = Address::new(H160::random().0);
+    // Without calldata, LOG1(0xaa), call itself with the calldata 0x01 then
+    // LOG1(0xcc), otherwise LOG1(0xbb) and revert:
+    // CALLDATASIZE PUSH1 38 JUMPI LOG1(0, 0, 0xaa) MSTORE8(0, 1)
+    // CALL(GAS, ADDRESS, 0, 0, 1, 0, 0) POP LOG1(0, 0, 0xcc) STOP
+    // JUMPDEST(pc 38) LOG1(0, 0, 0xbb) REVERT(0, 0)
+    let bytecode = "3660265760aa60006000a1600160005360006000600160006000305af15060cc60006000a1005b60bb60006000a160006000fd";
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let topic = |byte: u8| B256::from(U256::from(byte));
+    let events: Vec<_> = resp
+        .events
+        .iter()
+        .map(|e| (e.topics[0].clone(), e.reverted))
+        .collect();
+    let expected: Vec<_> = [(0xaa, false), (0xbb, true), (0xcc, false)]
+        .into_iter()
+        .map(|(byte, reverted)| (format!("0x{}", hex::encode(topic(byte))), reverted))
+        .collect();
+    assert_eq!(expected, events);
+
+    let mut bloom = Bloom::ZERO;
+    bloom.accrue_raw_log(contract, &[topic(0xaa)]);
+    bloom.accrue_raw_log(contract, &[topic(0xcc)]);
+    assert_eq!(format!("0x{}", hex::encode(bloom)), resp.logs_bloom);
+
+    // Reverted transactions have no receipt logs
+    vm.set_code_by_address(contract, hex::decode("60aa60006000a160006000fd").unwrap())
+        .unwrap();
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(!resp.success);
+    assert!(resp.events.iter().all(|e| e.reverted));
+    assert_eq!(format!("0x{}", hex::encode(Bloom::ZERO)), resp.logs_bloom);
+}
+
+#[test]
+fn test_exit_reasons() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    for (bytecode, expected, message) in [
+        ("00", ExitReason::Success, "Success"),
+        ("60006000fd", ExitReason::Revert, "Revert"),
+        // JUMP without operand, JUMP to a non JUMPDEST, INVALID
+        ("56", ExitReason::StackUnderflow, "StackUnderflow"),
+        ("600056", ExitReason::InvalidJump, "InvalidJump"),
+        ("fe", ExitReason::OpcodeNotFound, "InvalidEFOpcode"),
+        // Infinite loop
+        ("5b600056", ExitReason::OutOfGas, "OutOfGas(Basic)"),
+    ] {
+        vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+            .unwrap();
+        let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, Some(100_000));
+        assert_eq!(
+            (expected, message),
+            (resp.exit_reason, resp.exit_message.as_str()),
+            "Exit of {}",
+            bytecode
+        );
+        assert_eq!(expected == ExitReason::Success, resp.success);
+    }
+}
+
+#[test]
+fn test_parity_traces() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Without calldata, STATICCALL itself with the calldata 0x01, otherwise
+    // revert: CALLDATASIZE PUSH1 22 JUMPI
+    // PUSH1 1 PUSH1 0 MSTORE8 STATICCALL(GAS, ADDRESS, 0, 1, 0, 0) POP STOP
+    // JUMPDEST(pc 22) PUSH1 0 PUSH1 0 REVERT
+    let bytecode = "3660165760016000536000600060016000305afa50005b60006000fd";
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+    vm.set_evm_tracing(true);
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let traces = parity_traces(&resp.traces);
+    assert_eq!(2, traces.len());
+    let address = format!("{:#x}", contract);
+
+    let root = &traces[0];
+    assert_eq!("call", root["action"]["callType"]);
+    assert_eq!(format!("{:#x}", *OWNER), root["action"]["from"]);
+    assert_eq!(address, root["action"]["to"]);
+    assert_eq!("0x", root["action"]["input"]);
+    assert_eq!(1, root["subtraces"]);
+    assert_eq!(serde_json::json!([]), root["traceAddress"]);
+    assert_eq!("0x", root["result"]["output"]);
+    assert!(root.get("error").is_none());
+
+    let subcall = &traces[1];
+    assert_eq!("staticcall", subcall["action"]["callType"]);
+    assert_eq!(address, subcall["action"]["from"]);
+    assert_eq!("0x01", subcall["action"]["input"]);
+    assert_eq!(serde_json::json!([0]), subcall["traceAddress"]);
+    assert_eq!("Reverted", subcall["error"]);
+    assert!(subcall.get("result").is_none());
+}
+
+#[test]
+fn test_gas_profiles() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Without calldata, call itself with the calldata 0x01 then 0x02,
+    // otherwise return the first calldata word, see `test_call_tree`
+    let call = "60006000600160006000305af150";
+    let bytecode = format!(
+        "36602b576001600053{}6002600053{}005b60003560005260206000f3",
+        call, call
+    );
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+    vm.set_evm_tracing(true);
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let address = format!("{:#x}", contract);
+    let (root, child) = (&resp.traces[0], &resp.traces[1]);
+    assert_eq!(0, root.depth);
+    assert!(root.gas_used > 2 * child.gas_used);
+
+    let stacks = collapsed_stacks(&resp.traces);
+    let expected_child = format!("{};{} {}", address, address, child.gas_used);
+    assert_eq!(
+        vec![
+            format!("{} {}", address, root.gas_used - 2 * child.gas_used),
+            expected_child.clone(),
+            expected_child,
+        ],
+        stacks
+    );
+
+    let profile = speedscope_profile(&resp.traces, "test");
+    assert_eq!(
+        serde_json::json!([{ "name": address }]),
+        profile["shared"]["frames"]
+    );
+    let profile = &profile["profiles"][0];
+    assert_eq!(root.gas_used, profile["endValue"]);
+    let events: Vec<_> = profile["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["type"].as_str().unwrap(), e["at"].as_u64().unwrap()))
+        .collect();
+    let g = child.gas_used;
+    assert_eq!(
+        vec![
+            ("O", 0),
+            ("O", 0),
+            ("C", g),
+            ("O", g),
+            ("C", 2 * g),
+            ("C", root.gas_used)
+        ],
+        events
+    );
+}
+
+#[test]
+fn test_storage_changes_in_traces() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // Without calldata, SSTORE(0, 1) then call itself with the calldata
+    // 0x01, otherwise SSTORE(0, 2) and SSTORE(1, 0):
+    // CALLDATASIZE PUSH1 29 JUMPI PUSH1 1 PUSH1 0 SSTORE
+    // PUSH1 1 PUSH1 0 MSTORE8 CALL(GAS, ADDRESS, 0, 0, 1, 0, 0) POP STOP
+    // JUMPDEST(pc 29) PUSH1 2 PUSH1 0 SSTORE PUSH1 0 PUSH1 1 SSTORE STOP
+    let bytecode = "36601d576001600055600160005360006000600160006000305af150005b600260005560006001\
+                    5500";
+    vm.set_code_by_address(contract, hex::decode(bytecode).unwrap())
+        .unwrap();
+    vm.set_evm_tracing(true);
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let [root, subcall] = resp.traces.as_slice() else {
+        panic!("Expecting two call traces: {:?}", resp.traces);
+    };
+    let change = |slot: u64, old: u64, new: u64| {
+        (
+            format!("{:#x}", contract),
+            BigInt::from(slot),
+            BigInt::from(old),
+            BigInt::from(new),
+        )
+    };
+    assert_eq!(vec![change(0, 0, 1)], root.storage_changes);
+    assert_eq!(
+        vec![change(0, 1, 2), change(1, 0, 0)],
+        subcall.storage_changes
+    );
+}
+
+#[test]
+fn test_breakpoints() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // PUSH1 7 PUSH1 0 SSTORE PUSH1 0xaa PUSH1 0 MSTORE8 PUSH1 1 PUSH1 2 STOP
+    let bytecode = hex::decode("600760005560aa600053600160020000").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    let address = format!("{:#x}", contract);
+    vm.add_breakpoint(address.clone().into(), 4, vec![])
+        .unwrap();
+    vm.add_breakpoint(address.into(), 14, vec![(0, 2), (40, 2)])
+        .unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    assert_eq!(
+        vec![
+            BreakpointHit {
+                address: contract,
+                pc: 4,
+                depth: 1,
+                stack: vec![U256::from(7), UZERO],
+                memory: vec![],
+                storage: vec![],
+            },
+            BreakpointHit {
+                address: contract,
+                pc: 14,
+                depth: 1,
+                stack: vec![U256::from(1), U256::from(2)],
+                memory: vec![(0, vec![0xaa, 0]), (40, vec![0, 0])],
+                storage: vec![(UZERO, U256::from(7))],
+            }
+        ],
+        resp.breakpoint_hits
+    );
+
+    vm.clear_breakpoints();
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.breakpoint_hits.is_empty());
+}
+
+#[test]
+fn test_trace_filter() {
+    let contract = Address::new(H160::random().0);
+    let trace = |id, depth, parent, children, value, status| CallTrace {
+        from: *OWNER,
+        to: contract,
+        value: U256::from(value),
+        input: Default::default(),
+        depth,
+        return_data: None,
+        is_static: false,
+        scheme: CallScheme::Call,
+        create_scheme: None,
+        gas_limit: 0,
+        gas_used: 0,
+        status: Some(status),
+        id,
+        parent,
+        children,
+        storage_changes: vec![],
+    };
+    // 0 -> 1 -> 2 (reverted), 0 -> 3 (with value)
+    let mut inspector = LogInspector::default();
+    inspector.traces = vec![
+        trace(0, 0, None, vec![1, 3], 0, InstructionResult::Stop),
+        trace(1, 1, Some(0), vec![2], 0, InstructionResult::Return),
+        trace(2, 2, Some(1), vec![], 0, InstructionResult::Revert),
+        trace(3, 1, Some(0), vec![], 1, InstructionResult::Stop),
+    ];
+    let ids = |inspector: &LogInspector| {
+        inspector
+            .filtered_traces()
+            .iter()
+            .map(|t| (t.id, t.parent, t.children.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(4, ids(&inspector).len());
+
+    inspector.trace_filter = TraceFilter {
+        max_depth: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![
+            (0, None, vec![1, 3]),
+            (1, Some(0), vec![]),
+            (3, Some(0), vec![])
+        ],
+        ids(&inspector)
+    );
+
+    inspector.trace_filter.value_transfers_only = true;
+    assert_eq!(vec![(3, None, vec![])], ids(&inspector));
+
+    inspector.trace_filter = TraceFilter {
+        failed_only: true,
+        ..Default::default()
+    };
+    assert_eq!(vec![(2, None, vec![])], ids(&inspector));
+
+    inspector.trace_filter = TraceFilter {
+        addresses: [Address::new(H160::random().0)].into_iter().collect(),
+        ..Default::default()
+    };
+    assert!(ids(&inspector).is_empty());
+
+    // Descendants of a dropped trace are attached to its closest ancestor kept
+    inspector.traces[1].to = Address::new(H160::random().0);
+    inspector.traces[1].from = inspector.traces[1].to;
+    inspector.trace_filter = TraceFilter {
+        addresses: [contract].into_iter().collect(),
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![
+            (0, None, vec![2, 3]),
+            (2, Some(0), vec![]),
+            (3, Some(0), vec![])
+        ],
+        ids(&inspector)
+    );
+}
+
+#[test]
+fn test_struct_logs() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // PUSH1 2 PUSH1 3 ADD PUSH1 0 MSTORE STOP
+    let bytecode = hex::decode("600260030160005200").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.struct_logs.is_empty(), "The struct logger is opt-in");
+
+    vm.set_struct_logging(true, true, true);
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    let steps: Vec<_> = resp
+        .struct_logs
+        .iter()
+        .map(|log| (log.pc, log.op, log.gas_cost, log.depth))
+        .collect();
+    assert_eq!(
+        vec![
+            (0, opcode::PUSH1, 3, 1),
+            (2, opcode::PUSH1, 3, 1),
+            (4, opcode::ADD, 3, 1),
+            (5, opcode::PUSH1, 3, 1),
+            (7, opcode::MSTORE, 6, 1),
+            (8, opcode::STOP, 0, 1),
+        ],
+        steps
+    );
+    let add = &resp.struct_logs[2];
+    assert_eq!(Some(vec![U256::from(2), U256::from(3)]), add.stack);
+    assert_eq!(resp.struct_logs[0].gas, add.gas + 6);
+    let stop: &StructLog = &resp.struct_logs[5];
+    assert_eq!(
+        Some(U256::from(5).to_be_bytes::<32>().to_vec()),
+        stop.memory
+    );
+
+    let json = add.to_geth_json();
+    assert_eq!("ADD", json["op"]);
+    assert_eq!(serde_json::json!(["0x2", "0x3"]), json["stack"]);
+    assert_eq!(serde_json::json!([]), json["memory"]);
+}
+
+#[test]
+fn test_reset_storage() {
+    deploy_hex!("../tests/contracts/storage.hex", vm, addr);
+    let index = U256::ZERO;
+
+    let target_value = U256::from(99u64);
+
+    println!(
+        "Setting storage for address: {:?} index: {:?} to value: {:?}",
+        addr, index, target_value
+    );
+    let addr = Address::new(addr.0);
 
     let r = vm.set_storage_by_address(addr, index, target_value);
 
@@ -1237,7 +3989,7 @@ fn test_sha3_mapping() {
 
     let resp = vm.contract_call_helper(addr, *OWNER, bin, UZERO, None);
     assert!(resp.success, "Call error {:?}", resp);
-    let actual_mapping = resp.heuristics.sha3_mapping;
+    let actual_mapping = &resp.heuristics.sha3_mapping;
     println!("sha3_mappings: {:?}", actual_mapping);
     let expected_hash =
         H256::from_str("0x036b6384b5eca791c62761152d0c79bb0604c104a5fb6f4eb0703f3154bb3db0")
@@ -1254,6 +4006,139 @@ fn test_sha3_mapping() {
     );
 }
 
+#[test]
+fn test_sha3_mapping_capacity() {
+    let hash = |i: u8| H256::repeat_byte(i);
+    let mut mapping = Sha3Mapping::default();
+    for i in 0..3 {
+        mapping.insert(hash(i), &[i], 3);
+    }
+    // Hashing 0 again makes 1 the least recently used
+    mapping.insert(hash(0), &[0], 3);
+    let copy = mapping.clone();
+    mapping.insert(hash(3), &[3], 3);
+
+    assert_eq!(3, mapping.len());
+    assert_eq!(None, mapping.get(&hash(1)));
+    assert_eq!(Some(&vec![0]), mapping.get(&hash(0)));
+    assert_eq!(Some(&vec![3]), mapping.get(&hash(3)));
+    assert_eq!(
+        Some(&vec![1]),
+        copy.get(&hash(1)),
+        "The copy is not modified"
+    );
+
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // KECCAK256 of the 32 bytes word 0 to 9:
+    // PUSH1 0 JUMPDEST(pc 2) DUP1 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 SHA3 POP
+    // PUSH1 1 ADD DUP1 PUSH1 10 GT PUSH1 2 JUMPI STOP
+    let bytecode = hex::decode("60005b8060005260206000205060010180600a11600257").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+    vm.instrument_config_mut().sha3_mapping_capacity = 4;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert!(resp.success);
+    assert_eq!(4, resp.heuristics.sha3_mapping.len());
+    let last = H256::from_slice(keccak256(U256::from(9).to_be_bytes::<32>()).as_slice());
+    assert!(resp.heuristics.sha3_mapping.get(&last).is_some());
+}
+
+#[test]
+fn test_long_sha3_preimages() {
+    setup();
+    let mut vm = TinyEVM::default();
+    let contract = Address::new(H160::random().0);
+    // KECCAK256 of the 64 bytes `key . slot` with key 7 and slot 3:
+    // PUSH1 7 PUSH1 0 MSTORE PUSH1 3 PUSH1 32 MSTORE PUSH1 64 PUSH1 0 SHA3 STOP
+    let bytecode = hex::decode("60076000526003602052604060002000").unwrap();
+    vm.set_code_by_address(contract, bytecode).unwrap();
+
+    let preimage = [
+        U256::from(7).to_be_bytes::<32>(),
+        U256::from(3).to_be_bytes::<32>(),
+    ]
+    .concat();
+    let hash = H256::from_slice(keccak256(&preimage).as_slice());
+
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert_eq!(
+        Some(&preimage[32..].to_vec()),
+        resp.heuristics.sha3_mapping.get(&hash)
+    );
+
+    vm.instrument_config_mut().sha3_preimage_max_len = 64;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert_eq!(Some(&preimage), resp.heuristics.sha3_mapping.get(&hash));
+
+    vm.instrument_config_mut().sha3_preimage_max_len = 40;
+    let resp = vm.contract_call_helper(contract, *OWNER, vec![], UZERO, None);
+    assert_eq!(
+        Some(&preimage[24..].to_vec()),
+        resp.heuristics.sha3_mapping.get(&hash)
+    );
+}
+
+#[test]
+fn test_heuristics_reset_in_place() {
+    let mut heuristics = Heuristics::new();
+    for pc in 0..100 {
+        heuristics.record_missed_branch(pc, pc + 10, true, U256::from(1), 0);
+        heuristics.coverage.push_back(pc);
+    }
+    heuristics.record_seen_address(Address::with_last_byte(1));
+    heuristics.record_sha3_mapping(&[1], H256::random(), 16);
+    let capacity = heuristics.missed_branches.capacity();
+
+    heuristics.reset_in_place();
+    let default = Heuristics::default();
+    assert_eq!(
+        (
+            default.coverage,
+            default.missed_branches,
+            default.seen_addresses
+        ),
+        (
+            heuristics.coverage.clone(),
+            heuristics.missed_branches.clone(),
+            heuristics.seen_addresses.clone()
+        )
+    );
+    assert!(heuristics.sha3_mapping.is_empty());
+    assert_eq!(capacity, heuristics.missed_branches.capacity());
+
+    // The missed branches recorded before the reset are not duplicates
+    heuristics.record_missed_branch(1, 11, true, U256::from(1), 0);
+    assert_eq!(1, heuristics.missed_branches.len());
+}
+
+#[test]
+fn test_heuristics_dedup() {
+    let mut heuristics = Heuristics::new();
+    let a = Address::with_last_byte(1);
+    let b = Address::with_last_byte(2);
+    assert_eq!(0, heuristics.record_seen_address(a));
+    assert_eq!(1, heuristics.record_seen_address(b));
+    assert_eq!(0, heuristics.record_seen_address(a));
+    assert_eq!([a, b], heuristics.seen_addresses[..]);
+
+    heuristics.record_missed_branch(1, 10, true, U256::from(3), 0);
+    heuristics.record_missed_branch(1, 10, false, U256::from(3), 1);
+    heuristics.record_missed_branch(1, 10, true, U256::from(4), 0);
+    assert_eq!(2, heuristics.missed_branches.len());
+
+    // Branches pushed directly to the list are also deduplicated
+    heuristics
+        .missed_branches
+        .push(MissedBranch::new(2, 20, true, U256::from(1), 0));
+    heuristics.record_missed_branch(2, 20, true, U256::from(1), 0);
+    assert_eq!(3, heuristics.missed_branches.len());
+
+    heuristics.reset();
+    heuristics.record_missed_branch(1, 10, true, U256::from(3), 0);
+    assert_eq!(1, heuristics.missed_branches.len());
+}
+
 #[test]
 fn test_seen_addresses() {
     setup();
@@ -1305,7 +4190,7 @@ fn test_seen_addresses() {
     println!("resp: {:?}", resp);
     assert!(resp.success, "Call error {:?}", resp);
 
-    let seen = resp.heuristics.seen_addresses;
+    let seen = &resp.heuristics.seen_addresses;
     println!("seen_addresses: {:?}", seen);
 
     assert!(seen.contains(&addr_a), "Contract A should be seen");
@@ -1350,7 +4235,7 @@ fn test_distance_signed() {
     let missed_branches_distance = resp
         .heuristics
         .missed_branches
-        .into_iter()
+        .iter()
         .map(|b| b.distance)
         .collect::<Vec<_>>();
 
@@ -1424,7 +4309,7 @@ fn test_fork() -> Result<()> {
     let fork_url = Some("https://eth.llamarpc.com".into());
     let block_id = Some(17869485);
 
-    let mut evm = TinyEVM::new(fork_url, block_id)?;
+    let mut evm = TinyEVM::new(fork_url, block_id, None)?;
 
     let sender = Some("0xC6CDE7C39eB2f0F0095F41570af89eFC2C1Ea828".into());
     let contract = "dAC17F958D2ee523a2206206994597C13D831ec7".into();
@@ -1438,7 +4323,7 @@ fn test_fork() -> Result<()> {
 
     println!("result: {:?}", result);
 
-    let balance: [u8; 32] = result.data.as_slice().try_into()?;
+    let balance: [u8; 32] = result.data[..].try_into()?;
     let balance = U256::from_be_bytes(balance);
 
     assert_eq!(U256::from_str_radix("2691791472364000", 10)?, balance,);
@@ -1458,9 +4343,15 @@ fn test_call_forked_contract_from_local_contract() -> Result<()> {
     let fork_url = Some("https://bscrpc.com".into());
     let block_id = Some(0x1e08bd6);
 
-    let mut evm = TinyEVM::new(fork_url, block_id)?;
+    let mut evm = TinyEVM::new(fork_url, block_id, None)?;
 
-    let resp = evm.deploy(bin.into(), None)?;
+    let resp = evm.deploy_helper(
+        evm.owner,
+        BytesArg::from(bin).into_bytes()?,
+        UZERO,
+        None,
+        None,
+    )?;
 
     assert!(resp.success, "Deploy error {:?}", resp);
 
@@ -1474,13 +4365,19 @@ fn test_call_forked_contract_from_local_contract() -> Result<()> {
 
     let init_balance = BigInt::from_str("998888888888888888888").unwrap();
 
-    evm.set_balance(sender.clone(), init_balance).unwrap();
+    evm.set_balance(sender.clone().into(), init_balance.into())
+        .unwrap();
 
     let value = BigInt::from_str("18888888888888888888").unwrap();
 
     println!("Sender sending ether to WBNB");
 
-    let resp = evm.contract_call(wbnb_address, Some(sender), None, Some(value))?;
+    let resp = evm.contract_call(
+        wbnb_address.into(),
+        Some(sender.into()),
+        None,
+        Some(value.into()),
+    )?;
 
     assert!(resp.success, "Call error {:?}", resp);
 
@@ -1510,14 +4407,12 @@ fn test_call_forked_contract_from_local_contract() -> Result<()> {
         evm.get_forked_slots("0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c".into())?;
 
     assert_eq!(
-        ruint_u256_to_bigint(
-            &U256::from_str_radix(
-                "5aca9f8e8ddd72ad4b96de957d6bd49b602eab95954cc54154e3c000532f36a2",
-                16
-            )
-            .unwrap()
-        ),
-        *remote_storage_indices.first().unwrap()
+        U256::from_str_radix(
+            "5aca9f8e8ddd72ad4b96de957d6bd49b602eab95954cc54154e3c000532f36a2",
+            16
+        )
+        .unwrap(),
+        remote_storage_indices.first().unwrap().0
     );
 
     Ok(())
@@ -1535,9 +4430,15 @@ fn test_sturdy_hack() -> Result<()> {
     let fork_url = Some("https://eth.llamarpc.com".into());
     let block_id = Some(17_460_609);
 
-    let mut evm = TinyEVM::new(fork_url, block_id)?;
+    let mut evm = TinyEVM::new(fork_url, block_id, None)?;
 
-    let resp = evm.deploy(bin.into(), None)?;
+    let resp = evm.deploy_helper(
+        evm.owner,
+        BytesArg::from(bin).into_bytes()?,
+        UZERO,
+        None,
+        None,
+    )?;
 
     assert!(resp.success, "Deploy error {:?}", resp);
 
@@ -1553,23 +4454,34 @@ fn test_sturdy_hack() -> Result<()> {
         .contract_call(
             weth_address.into(),
             None,
-            Some(balance_of_query_data.clone()),
+            Some(balance_of_query_data.clone().into()),
             None,
         )
         .map(|resp| {
-            let balance: [u8; 32] = resp.data.as_slice().try_into().unwrap();
+            let balance: [u8; 32] = resp.data[..].try_into().unwrap();
             U256::from_be_bytes(balance)
         })?;
 
-    evm.set_balance(sender.clone(), init_balance).unwrap();
+    evm.set_balance(sender.clone().into(), init_balance.into())
+        .unwrap();
 
-    let data = "ca1ba028".into(); // testExploit()
-    let _resp = evm.contract_call(attacker, Some(sender), Some(data), None)?;
+    let data: String = "ca1ba028".into(); // testExploit()
+    let _resp = evm.contract_call(
+        attacker.into(),
+        Some(sender.into()),
+        Some(data.into()),
+        None,
+    )?;
 
     let sender_end_weth_balance = evm
-        .contract_call(weth_address.into(), None, Some(balance_of_query_data), None)
+        .contract_call(
+            weth_address.into(),
+            None,
+            Some(balance_of_query_data.into()),
+            None,
+        )
         .map(|resp| {
-            let balance: [u8; 32] = resp.data.as_slice().try_into().unwrap();
+            let balance: [u8; 32] = resp.data[..].try_into().unwrap();
             U256::from_be_bytes(balance)
         })?;
 
@@ -1585,7 +4497,13 @@ fn test_sturdy_hack() -> Result<()> {
 fn test_events() -> Result<()> {
     let bin = include_str!("../tests/contracts/TestEvents.hex");
     let mut vm = TinyEVM::default();
-    let resp = vm.deploy(bin.into(), None)?;
+    let resp = vm.deploy_helper(
+        vm.owner,
+        BytesArg::from(bin).into_bytes()?,
+        UZERO,
+        None,
+        None,
+    )?;
     assert!(resp.success, "Deploy error {:?}", resp);
     let contract = format!("0x{:0>40}", hex::encode(&resp.data));
     println!("Contract address: {}", contract);
@@ -1594,13 +4512,18 @@ fn test_events() -> Result<()> {
         "1401d2b5", // makeEvent(3232)
         U256::from(3232)
     );
-    let resp = vm.contract_call(contract.clone(), None, Some(data.clone()), None)?;
+    let resp = vm.contract_call(
+        contract.clone().into(),
+        None,
+        Some(data.clone().into()),
+        None,
+    )?;
     assert!(resp.success, "Call error {:?}", resp);
     assert!(resp.events.is_empty(), "Expecting no events");
     assert!(resp.traces.is_empty(), "Expecting no call traces");
 
     vm.set_evm_tracing(true);
-    let resp = vm.contract_call(contract.clone(), None, Some(data), None)?;
+    let resp = vm.contract_call(contract.clone().into(), None, Some(data.into()), None)?;
 
     assert!(resp.success, "Call error {:?}", resp);
     assert!(resp.events.len() == 1, "Expecting one event");
@@ -1614,3 +4537,251 @@ fn test_events() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_event_abi() -> Result<()> {
+    let bin = include_str!("../tests/contracts/TestEvents.hex");
+    let mut vm = TinyEVM::default();
+    let resp = vm.deploy_helper(
+        vm.owner,
+        BytesArg::from(bin).into_bytes()?,
+        UZERO,
+        None,
+        None,
+    )?;
+    assert!(resp.success, "Deploy error {:?}", resp);
+    let contract = format!("0x{:0>40}", hex::encode(&resp.data));
+    let abi = r#"[{"anonymous": false, "name": "Transfer", "type": "event", "inputs": [
+        {"indexed": true, "name": "src", "type": "address"},
+        {"indexed": true, "name": "dst", "type": "address"},
+        {"indexed": false, "name": "wad", "type": "uint256"}]}]"#;
+    vm.set_evm_tracing(true);
+    let data = format!("{}{:064x}", "1401d2b5", U256::from(3232)); // makeEvent(3232)
+
+    let resp = vm.contract_call(
+        contract.clone().into(),
+        None,
+        Some(data.clone().into()),
+        None,
+    )?;
+    assert_eq!(None, resp.events[0].decoded, "No ABI registered");
+
+    vm.add_event_abi(abi.into(), Some(format!("0x{:040x}", 1).into()))?;
+    let resp = vm.contract_call(
+        contract.clone().into(),
+        None,
+        Some(data.clone().into()),
+        None,
+    )?;
+    assert_eq!(None, resp.events[0].decoded, "ABI of another address");
+
+    vm.add_event_abi(
+        format!(r#"{{"abi": {}}}"#, abi),
+        Some(contract.clone().into()),
+    )?;
+    let resp = vm.contract_call(contract.clone().into(), None, Some(data.into()), None)?;
+    let event = resp.events[0]
+        .decoded
+        .clone()
+        .context("Event should be decoded")?;
+    assert_eq!("Transfer", event.name);
+    let dst = H160::from_str("F58764c35eD1528Ec78DF18BebB24Fa20f6A626F")?;
+    assert_eq!(
+        vec![
+            ("src".into(), Token::Address(H160::from_str(&contract)?)),
+            ("dst".into(), Token::Address(dst)),
+            ("wad".into(), Token::Uint(3232.into())),
+        ],
+        event.params
+    );
+    Ok(())
+}
+
+#[test]
+fn test_tiered_cache_write_back() -> Result<()> {
+    use tinyevm::cache::{request_hash, MemoryProviderCache, ProviderCache, TieredCache};
+
+    let key = request_hash(&[b"abcd"]);
+    let fast = MemoryProviderCache::default();
+    let slow = MemoryProviderCache::default();
+    slow.store("eth", 1, "eth_getBalance", &key, &[0xff])?;
+
+    let cache = TieredCache::new(fast.clone(), slow.clone());
+    assert!(fast.is_empty(), "Fast layer should start empty");
+    assert_eq!(vec![0xff], cache.get("eth", 1, "eth_getBalance", &key)?);
+    assert_eq!(
+        vec![0xff],
+        fast.get("eth", 1, "eth_getBalance", &key)?,
+        "Entry should be written back to the fast layer on hit"
+    );
+
+    cache.store("eth", 1, "eth_getCode", &key, &[0x60, 0x80])?;
+    assert_eq!(vec![0x60, 0x80], slow.get("eth", 1, "eth_getCode", &key)?);
+    assert!(cache.get("eth", 2, "eth_getCode", &key).is_err());
+    Ok(())
+}
+
+#[cfg(feature = "redis")]
+#[test]
+fn test_tiered_cache_redis_miss() -> Result<()> {
+    use tinyevm::cache::{
+        redis_cache::RedisCacheConfig, request_hash, MemoryProviderCache, ProviderCache,
+        RedisProviderCache, TieredCache,
+    };
+
+    // Keys of a new namespace, missing from redis
+    let config = RedisCacheConfig {
+        key_prefix: format!("tinyevm-test-{}", uuid::Uuid::new_v4()),
+        ..RedisCacheConfig::from_env()?
+    };
+    let fast = RedisProviderCache::with_config(&config)?;
+    let slow = MemoryProviderCache::default();
+    let key = request_hash(&[b"abcd"]);
+    assert!(
+        fast.get("eth", 1, "eth_getBalance", &key).is_err(),
+        "A missing key should be a cache miss"
+    );
+
+    slow.store("eth", 1, "eth_getBalance", &key, &[0xff])?;
+    let cache = TieredCache::new(fast.clone(), slow);
+    assert_eq!(vec![0xff], cache.get("eth", 1, "eth_getBalance", &key)?);
+    assert_eq!(vec![0xff], fast.get("eth", 1, "eth_getBalance", &key)?);
+    Ok(())
+}
+
+#[test]
+fn test_memory_cache_eviction() -> Result<()> {
+    use tinyevm::cache::{request_hash, MemoryProviderCache, ProviderCache};
+
+    let keys: Vec<_> = (0u8..3).map(|i| request_hash(&[&[i]])).collect();
+    let cache = MemoryProviderCache::with_capacity(64);
+    cache.store("eth", 1, "eth_getCode", &keys[0], &[0; 32])?;
+    cache.store("eth", 1, "eth_getCode", &keys[1], &[1; 32])?;
+    assert_eq!(64, cache.size());
+
+    // The first entry is used last, the second one is evicted
+    cache.get("eth", 1, "eth_getCode", &keys[0])?;
+    cache.store("eth", 1, "eth_getCode", &keys[2], &[2; 32])?;
+    assert_eq!(2, cache.len());
+    assert_eq!(vec![0; 32], cache.get("eth", 1, "eth_getCode", &keys[0])?);
+    assert!(cache.get("eth", 1, "eth_getCode", &keys[1]).is_err());
+    assert_eq!(vec![2; 32], cache.get("eth", 1, "eth_getCode", &keys[2])?);
+
+    // Larger than the capacity, not cached
+    cache.store("eth", 1, "eth_getCode", &keys[1], &[1; 65])?;
+    assert!(cache.get("eth", 1, "eth_getCode", &keys[1]).is_err());
+    assert_eq!(64, cache.size());
+    Ok(())
+}
+
+/// Memory cache whose writes wait for a message of `gate`, if any
+#[derive(Clone, Default)]
+struct GatedCache {
+    inner: tinyevm::cache::MemoryProviderCache,
+    gate: Option<Arc<Mutex<std::sync::mpsc::Receiver<()>>>>,
+}
+
+impl tinyevm::cache::ProviderCache for GatedCache {
+    fn store(
+        &self,
+        chain: &str,
+        block: u64,
+        api: &str,
+        request_hash: &B256,
+        response: &[u8],
+    ) -> Result<()> {
+        if let Some(gate) = &self.gate {
+            gate.lock().unwrap().recv()?;
+        }
+        self.inner.store(chain, block, api, request_hash, response)
+    }
+
+    fn get(&self, chain: &str, block: u64, api: &str, request_hash: &B256) -> Result<Vec<u8>> {
+        self.inner.get(chain, block, api, request_hash)
+    }
+}
+
+#[test]
+fn test_async_cache_pending_rewrite() -> Result<()> {
+    use tinyevm::cache::{request_hash, AsyncProviderCache, ProviderCache};
+
+    let (open, gate) = std::sync::mpsc::channel();
+    let inner = GatedCache {
+        gate: Some(Arc::new(Mutex::new(gate))),
+        ..Default::default()
+    };
+    let cache = AsyncProviderCache::new(inner.clone());
+    // Dropped before `cache`, whose writer would otherwise wait forever on
+    // a failed assertion
+    let open = open;
+    let key = request_hash(&[b"balance"]);
+    cache.store("eth", 1, "eth_getBalance", &key, &[1])?;
+    cache.store("eth", 1, "eth_getBalance", &key, &[2])?;
+
+    // Only the first value is written, the second one is still queued
+    open.send(())?;
+    while inner.get("eth", 1, "eth_getBalance", &key).is_err() {
+        std::thread::yield_now();
+    }
+    assert_eq!(vec![1], inner.get("eth", 1, "eth_getBalance", &key)?);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert_eq!(vec![2], cache.get("eth", 1, "eth_getBalance", &key)?);
+
+    open.send(())?;
+    cache.flush()?;
+    assert_eq!(vec![2], inner.get("eth", 1, "eth_getBalance", &key)?);
+    assert_eq!(vec![2], cache.get("eth", 1, "eth_getBalance", &key)?);
+    Ok(())
+}
+
+#[test]
+fn test_cache_entry_integrity() -> Result<()> {
+    use tinyevm::cache::{decode_entry, encode_entry};
+
+    let entry = encode_entry(&[0xff, b':']);
+    assert_eq!(&[0xff, b':'], decode_entry(&entry)?);
+
+    let tampered = [entry.as_slice(), &[0]].concat();
+    assert!(
+        decode_entry(&tampered).is_err(),
+        "Corrupted entry should fail the checksum"
+    );
+    assert!(
+        decode_entry(&[0xff]).is_err(),
+        "Entry without header should be rejected"
+    );
+    let old_version = [b"tinyevm:1:".as_slice(), &entry[b"tinyevm:2:".len()..]].concat();
+    assert!(
+        decode_entry(&old_version).is_err(),
+        "Entry with another format version should be rejected"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_warm_cache() -> Result<()> {
+    setup();
+    if env::var("TINYEVM_CI_TESTS").is_ok() {
+        warn!("Skipping tests on CI");
+        return Ok(());
+    }
+
+    let fork_url = Some("https://eth.llamarpc.com".into());
+    let block_id = Some(17869485);
+    let mut evm = TinyEVM::new(fork_url, block_id, None)?;
+
+    // balanceOf("0xf977814e90da44bfa03b6295a0616a897441acec") on USDT
+    let call = WarmupTx::Call(
+        "0xdAC17F958D2ee523a2206206994597C13D831ec7".into(),
+        Some("70a08231000000000000000000000000f977814e90da44bfa03b6295a0616a897441acec".into()),
+    );
+    let responses = evm.warm_cache(vec![call])?;
+    assert_eq!(1, responses.len());
+    assert!(responses[0].success, "Call error {:?}", responses[0]);
+    assert!(
+        evm.get_forked_addresses()?.is_empty(),
+        "EVM state should be restored after warming up the cache"
+    );
+
+    Ok(())
+}
@@ -22,3 +22,43 @@ class TestTinyEVM(unittest.TestCase):
         tevm = tinyevm.TinyEVM(fork_url, 17890805)
 
         assert 1378414300424348501 == tevm.get_balance('0x8ee335785a9c08219CEf04d46f1f01865F102Bf4')
+
+    def test_python_cache_backend(self):
+        class DictCache:
+            def __init__(self):
+                self.entries = {}
+
+            def store(self, chain, block, api, request_hash, response):
+                self.entries[(chain, block, api, request_hash)] = response
+
+            def get(self, chain, block, api, request_hash):
+                return self.entries.get((chain, block, api, request_hash))
+
+        fork_url = "https://eth.llamarpc.com"
+        cache = DictCache()
+        tevm = tinyevm.TinyEVM(fork_url, 17890805)
+        tevm.set_cache_backend(cache)
+
+        address = '0x8ee335785a9c08219CEf04d46f1f01865F102Bf4'
+        assert 1378414300424348501 == tevm.get_balance(address)
+        assert any(api == 'eth_getBalance' for (_, _, api, _) in cache.entries)
+        assert all(isinstance(response, bytes) for response in cache.entries.values())
+        assert all(isinstance(request_hash, bytes) and len(request_hash) == 32
+                   for (_, _, _, request_hash) in cache.entries)
+
+        # Served from the Python backend
+        tevm = tinyevm.TinyEVM(fork_url, 17890805)
+        tevm.set_cache_backend(cache)
+        assert 1378414300424348501 == tevm.get_balance(address)
+
+    def test_warm_cache(self):
+        fork_url = "https://eth.llamarpc.com"
+        tevm = tinyevm.TinyEVM(fork_url, 17869485)
+
+        usdt = '0xdAC17F958D2ee523a2206206994597C13D831ec7'
+        data = fn_sig('balanceOf(address)') + '000000000000000000000000f977814e90da44bfa03b6295a0616a897441acec'
+        responses = tevm.warm_cache([(usdt, data)])
+
+        assert len(responses) == 1
+        assert responses[0].success
+        assert tevm.get_forked_addresses() == [], 'state should be restored after warming up the cache'
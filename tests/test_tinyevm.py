@@ -1,5 +1,16 @@
+import ast
+import asyncio
+import json
+import logging
+import os
+import pickle
+import sys
+import sysconfig
+import tempfile
+import threading
 import tinyevm
 import unittest
+import uuid
 from Crypto.Hash import keccak
 
 salt = '0x01'
@@ -14,10 +25,6 @@ def tprint(*args):
     print(*args, flush=True)
 
 class TestTinyEVM(unittest.TestCase):
-    @classmethod
-    def setUpClass(cls):
-        tinyevm.enable_tracing()
-
     def test_get_set_balance(self):
         tevm = tinyevm.TinyEVM()
         assert tevm.get_balance('0x388C818CA8B9251b393131C08a736A67ccB19297') == 0
@@ -77,6 +84,983 @@ class TestTinyEVM(unittest.TestCase):
 
         assert config.target_address == '0x388C818CA8B9251b393131C08a736A67ccB19297'
 
+    def test_cmp_operands(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # PUSH1 7 PUSH1 9 EQ STOP
+        tevm.set_code(address, '6007600914' + '00')
+
+        config = tevm.get_instrument_config()
+        config.record_cmp_operands = True
+        tevm.configure(config)
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        operands = resp.heuristics.cmp_operands
+        assert [(c.pc, c.a, c.b) for c in operands] == [(4, 9, 7)]
+
+    def test_cheatcodes(self):
+        tevm = tinyevm.TinyEVM()
+        cheatcodes = '0x7109709ECfa91a80626fF3989D68f67F5b1DD12D'
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # TIMESTAMP PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        tevm.set_code(address, '4260005260206000f3')
+        tevm.toggle_cheatcodes(True)
+
+        resp = tevm.contract_call(cheatcodes, None, tinyevm.encode_call('warp(uint256)', [1234]), None)
+        assert resp.success
+        resp = tevm.contract_call(address, None, None, None)
+        assert int.from_bytes(bytes(resp.data), 'big') == 1234
+
+        store = tinyevm.encode_call('store(address,bytes32,bytes32)', [address, bytes(31) + b'\x01', bytes(31) + b'\x02'])
+        assert tevm.contract_call(cheatcodes, None, store, None).success
+        assert tevm.get_storage(address, 1) == 2
+
+    def test_coverage_buffer(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # PUSH1 1 PUSH1 10 JUMPI STOP x5 JUMPDEST STOP
+        tevm.set_code(address, '6001600a570000000000' + '5b00')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+
+        buffer = memoryview(tevm.coverage_buffer(address))
+        assert buffer.readonly
+        assert bytes(buffer) == bytes([0b00010101, 0b00001100])
+
+    def test_has_new_pcs(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # CALLDATASIZE PUSH1 5 JUMPI STOP JUMPDEST STOP
+        tevm.set_code(address, '3660055700' + '5b00')
+
+        first = tevm.contract_call(address, None, None, None)
+        second = tevm.contract_call(address, None, None, None)
+        assert not second.has_new_pcs(first)
+
+        third = tevm.contract_call(address, None, '01', None)
+        assert third.has_new_pcs(second)
+
+    def test_seen_pcs_map(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Jump to pc 6 without calldata: CALLDATASIZE ISZERO PUSH1 6 JUMPI STOP JUMPDEST STOP
+        tevm.set_code(address, '3615600657005b00')
+
+        first = tevm.contract_call(address, None, '01', None).seen_pcs
+        assert len(first) == 1 and list(first) == [address.lower()]
+        assert address in first and address.lower() in first
+        assert first[address] == {0, 1, 2, 4, 5}
+        assert first.items() == [(address.lower(), {0, 1, 2, 4, 5})]
+        assert first.total_pcs() == 5
+
+        coverage = tinyevm.SeenPcsMap()
+        assert coverage.union_update(first) == 5
+        second = tevm.contract_call(address, None, None, None).seen_pcs
+        assert coverage.union_update(second) == 2
+        assert coverage.union_update(second) == 0
+        assert coverage.get(address) == {0, 1, 2, 4, 5, 6, 7}
+        with self.assertRaises(KeyError):
+            coverage['0x' + '00' * 20]
+
+    def test_deployments(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # CREATE and CREATE2 with salt 42 of the init code STOP
+        tevm.set_code(address, '600160006000f050602a600160006000f55000')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        create, create2 = resp.deployments
+        assert create.creator == create2.creator == address.lower()
+        assert create.salt is None and create2.salt == 42
+        assert create2.init_code_hash == '0x' + keccak.new(data=b'\x00', digest_bits=256).hexdigest()
+        assert create.address is not None and create2.address is not None
+        assert create.code_hash == create2.code_hash == '0x' + keccak.new(data=b'', digest_bits=256).hexdigest()
+        assert [c.address for c in resp.created_contracts] == [create.address, create2.address]
+
+    def test_deploy_result(self):
+        tevm = tinyevm.TinyEVM()
+        resp = tevm.deploy(open('tests/contracts/TestEvents.hex').read())
+        assert resp.success
+        assert isinstance(resp, tinyevm.DeployResult) and isinstance(resp, tinyevm.Response)
+        assert isinstance(resp.data, bytes) and resp.address_bytes == resp.data
+        assert resp.address.lower() == '0x' + resp.address_bytes.hex()
+        assert resp.runtime_code
+        # The code in the state is padded
+        assert tevm.get_code(resp.address).startswith(resp.runtime_code.hex())
+        assert resp.gas_used == resp.gas_usage > 0
+
+        resp = tevm.deploy('fe')
+        assert not resp.success
+        assert resp.address_bytes == bytes(20)
+        assert resp.runtime_code == b''
+
+    def test_call_tree(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, call itself with the calldata 0x01 then 0x02,
+        # otherwise return the first calldata word
+        call = '60006000600160006000305af150'
+        tevm.set_code(address, '36602b576001600053' + call + '6002600053' + call
+                      + '005b60003560005260206000f3')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        [root] = resp.call_tree
+        assert root.trace.parent is None
+        assert [c.trace.return_data for c in root.children] == [
+            '0x01' + '00' * 31, '0x02' + '00' * 31]
+        assert all(c.trace.parent == root.trace.id and not c.children for c in root.children)
+
+    def test_gas_profiles(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, call itself with the calldata 0x01 then 0x02,
+        # otherwise return the first calldata word
+        call = '60006000600160006000305af150'
+        tevm.set_code(address, '36602b576001600053' + call + '6002600053' + call
+                      + '005b60003560005260206000f3')
+
+        resp = tevm.contract_call(address, None, None, None)
+        root = resp.traces[0]
+        stacks = [line.rsplit(' ', 1) for line in resp.collapsed_stacks().splitlines()]
+        assert [stack for stack, _ in stacks] == [
+            address.lower(), address.lower() + ';' + address.lower(),
+            address.lower() + ';' + address.lower()]
+        assert sum(int(gas) for _, gas in stacks) == root.gas_used
+
+        profile = json.loads(resp.speedscope())
+        assert profile['profiles'][0]['endValue'] == root.gas_used
+        assert [e['type'] for e in profile['profiles'][0]['events']] == list('OOCOCC')
+
+    def test_create_traces(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Deploy the init code STOP with CREATE and with CREATE2
+        tevm.set_code(address, '600160006000f050602a600160006000f55000')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        [root] = resp.call_tree
+        assert [c.trace.call_type for c in root.children] == ['create', 'create2']
+        assert [c.trace.to for c in root.children] == [d.address for d in resp.deployments]
+        for c in root.children:
+            assert resp.addresses[c.trace.to_index] == c.trace.to
+            assert resp.address_index(c.trace.to) == c.trace.to_index
+        assert resp.addresses[root.trace.caller_index] == root.trace.caller
+
+    def test_trace_hook(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, call itself with the calldata 0x01 then 0x02,
+        # otherwise return the first calldata word
+        call = '60006000600160006000305af150'
+        tevm.set_code(address, '36602b576001600053' + call + '6002600053' + call
+                      + '005b60003560005260206000f3')
+
+        returned = []
+        tevm.set_trace_hook(lambda trace: returned.append(trace.input))
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        assert returned == ['0x01', '0x02', '']
+
+        # Abort after the first subcall
+        returned.clear()
+        tevm.set_trace_hook(lambda trace: returned.append(trace.input) or False)
+        resp = tevm.contract_call(address, None, None, None)
+        assert not resp.success
+        assert returned == ['0x01']
+
+        tevm.set_trace_hook(None)
+        assert tevm.contract_call(address, None, None, None).success
+
+    def test_parity_traces(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, STATICCALL itself with the calldata 0x01, otherwise revert
+        tevm.set_code(address, '3660165760016000536000600060016000305afa50005b60006000fd')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        root, subcall = json.loads(resp.parity_traces())
+        assert root['subtraces'] == 1 and root['traceAddress'] == []
+        assert root['action']['to'] == address.lower()
+        assert subcall['action']['callType'] == 'staticcall'
+        assert subcall['traceAddress'] == [0]
+        assert subcall['error'] == 'Reverted'
+
+    def test_storage_changes_in_traces(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, SSTORE(0, 1) then call itself with the calldata
+        # 0x01, otherwise SSTORE(0, 2)
+        tevm.set_code(address, '36601d576001600055600160005360006000600160006000305af15000'
+                      + '5b60026000550000')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        root, subcall = resp.traces
+        assert root.storage_changes == [(address.lower(), 0, 0, 1)]
+        assert subcall.storage_changes == [(address.lower(), 0, 1, 2)]
+
+    def test_breakpoints(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # PUSH1 7 PUSH1 0 SSTORE PUSH1 0xaa PUSH1 0 MSTORE8 PUSH1 1 PUSH1 2 STOP
+        tevm.set_code(address, '600760005560aa600053600160020000')
+        tevm.add_breakpoint(address, 14, [(0, 2)])
+
+        resp = tevm.contract_call(address, None, None, None)
+        [hit] = resp.breakpoint_hits
+        assert hit.pc == 14 and hit.address == address.lower()
+        assert hit.stack == [1, 2]
+        assert hit.memory == [(0, 'aa00')]
+        assert hit.storage == [(0, 7)]
+
+    def test_trace_filter(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, STATICCALL itself with the calldata 0x01, otherwise revert
+        tevm.set_code(address, '3660165760016000536000600060016000305afa50005b60006000fd')
+
+        tevm.set_trace_filter(failed_only=True)
+        resp = tevm.contract_call(address, None, None, None)
+        [trace] = resp.traces
+        assert trace.status == 'Revert' and trace.parent is None
+
+        tevm.set_trace_filter(max_depth=0)
+        resp = tevm.contract_call(address, None, None, None)
+        [trace] = resp.traces
+        assert trace.depth == 0 and trace.children == []
+
+    def test_struct_logs(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_struct_logging(True, memory=True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # PUSH1 2 PUSH1 3 ADD PUSH1 0 MSTORE STOP
+        tevm.set_code(address, '600260030160005200')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        logs = json.loads(resp.struct_logs())
+        assert [log['op'] for log in logs] == ['PUSH1', 'PUSH1', 'ADD', 'PUSH1', 'MSTORE', 'STOP']
+        assert logs[2]['stack'] == ['0x2', '0x3'] and logs[2]['gasCost'] == 3
+        assert logs[5]['memory'] == ['%064x' % 5]
+
+    def test_deployed_code(self):
+        tevm = tinyevm.TinyEVM()
+        resp = tevm.deploy(open('tests/contracts/TestEvents.hex').read())
+        assert resp.success
+        address = '0x' + bytes(resp.data).hex()
+        code = bytes(resp.deployed_code).hex()
+        assert tevm.get_code(address).startswith(code)
+        assert resp.deployed_code_hash == '0x' + keccak.new(data=bytes(resp.deployed_code), digest_bits=256).hexdigest()
+
+    def test_event_abi(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        resp = tevm.deploy(open('tests/contracts/TestEvents.hex').read())
+        assert resp.success
+        address = '0x' + bytes(resp.data).hex()
+        abi = json.dumps([{'anonymous': False, 'name': 'Transfer', 'type': 'event', 'inputs': [
+            {'indexed': True, 'name': 'src', 'type': 'address'},
+            {'indexed': True, 'name': 'dst', 'type': 'address'},
+            {'indexed': False, 'name': 'wad', 'type': 'uint256'}]}])
+        tevm.add_event_abi(abi)
+
+        # makeEvent(3232)
+        resp = tevm.contract_call(address, None, '1401d2b5' + '%064x' % 3232, None)
+        assert resp.success
+        [event] = resp.events
+        assert event.event == 'Transfer'
+        assert event.args == {'src': address, 'dst': '0xf58764c35ed1528ec78df18bebb24fa20f6a626f',
+                              'wad': 3232}
+
+    def test_gas_accounting(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Store the first calldata word in the slot 0
+        tevm.set_code(address, '60003560005500')
+
+        resp = tevm.contract_call(address, None, '00' * 31 + '01', None)
+        assert resp.success and resp.gas_refunded == 0
+        assert resp.gas_usage == resp.intrinsic_gas + resp.execution_gas
+
+        # Clearing the slot is refunded
+        resp = tevm.contract_call(address, None, '00' * 32, None)
+        assert resp.success and resp.gas_refunded > 0
+        assert resp.intrinsic_gas == 21000 + 32 * 4
+        assert resp.gas_usage == resp.intrinsic_gas + resp.execution_gas - resp.gas_refunded
+
+    def test_static_state_modification(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, STATICCALL itself with the calldata 0x01,
+        # otherwise SSTORE(0, 1) at pc 27
+        tevm.set_code(address, '3660165760016000536000600060016000305afa50005b600160005500')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        assert [b.position for b in resp.bug_data
+                if b.bug_type['type'] == 'StaticStateModification'] == [27]
+
+    def test_response_bytes(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, STATICCALL itself with the calldata 0x01,
+        # otherwise SSTORE(0, 1)
+        tevm.set_code(address, '3660165760016000536000600060016000305afa50005b600160005500')
+
+        resp = tevm.contract_call(address, None, None, None)
+        decoded = tinyevm.Response.from_bytes(resp.to_bytes())
+        assert decoded.success == resp.success and decoded.gas_usage == resp.gas_usage
+        assert [t.input for t in decoded.traces] == [t.input for t in resp.traces]
+        assert [b.bug_type for b in decoded.bug_data] == [b.bug_type for b in resp.bug_data]
+
+        heuristics = tinyevm.WrappedHeuristics.from_bytes(resp.heuristics_bytes())
+        assert heuristics.coverage == resp.heuristics.coverage
+
+    def test_state_diff(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Store the first calldata word in the slot 0
+        tevm.set_code(address, '60003560005500')
+
+        resp = tevm.contract_call(address, None, '00' * 31 + '01', None)
+        [diff] = [d for d in resp.state_diff if d.address == address.lower()]
+        assert diff.storage == [(0, 0, 1)]
+        assert diff.balance[0] == diff.balance[1] and diff.code is None
+
+        # Created contracts come with their code
+        resp = tevm.deploy(open('tests/contracts/TestEvents.hex').read())
+        created = '0x' + bytes(resp.data).hex()
+        [diff] = [d for d in resp.state_diff if d.address == created]
+        assert diff.nonce == (0, 1) and tevm.get_code(created).startswith(diff.code[2:])
+
+    def test_logs_bloom(self):
+        tevm = tinyevm.TinyEVM()
+        tevm.set_evm_tracing(True)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Without calldata, LOG1(0xaa), call itself with the calldata 0x01
+        # then LOG1(0xcc), otherwise LOG1(0xbb) and revert
+        tevm.set_code(address, '3660265760aa60006000a1600160005360006000600160006000305af150'
+                      + '60cc60006000a1005b60bb60006000a160006000fd')
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        assert [(e.topics[0][-2:], e.reverted) for e in resp.events] == [
+            ('aa', False), ('bb', True), ('cc', False)]
+        assert len(resp.logs_bloom) == 2 + 512 and int(resp.logs_bloom, 16) != 0
+
+    def test_exit_reason(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        tevm.set_code(address, '60006000fd')
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.exit_reason == tinyevm.ExitReason.Revert
+        assert str(resp.exit_reason) == resp.exit_message == 'Revert'
+
+        # Infinite loop
+        tevm.set_code(address, '5b600056')
+        resp = tevm.contract_call(address, None, None, 100000)
+        assert resp.exit_reason == tinyevm.ExitReason.OutOfGas
+        assert resp.exit_message == 'OutOfGas(Basic)'
+
+    def test_opcode_histogram(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # PUSH1 0 SLOAD PUSH1 0 SLOAD STOP
+        tevm.set_code(address, '6000546000' + '5400')
+
+        config = tevm.get_instrument_config()
+        config.record_opcode_histogram = True
+        tevm.configure(config)
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        assert resp.opcode_histogram() == {0x60: 2, 0x54: 2, 0x00: 1}
+        assert resp.opcode_histogram(address) == resp.opcode_histogram()
+        assert resp.opcode_histogram('0x' + '00' * 20) == {}
+
+    def test_gas_breakdown(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # PUSH1 0 SLOAD PUSH1 0 SLOAD STOP
+        tevm.set_code(address, '6000546000' + '5400')
+
+        config = tevm.get_instrument_config()
+        config.record_gas_breakdown = True
+        tevm.configure(config)
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        breakdown = resp.gas_breakdown()
+        assert breakdown['intrinsic'] == 21000
+        assert breakdown['execution'] == 2 * 3
+        assert breakdown['sload_cold'] == 2100
+        assert breakdown['sload_warm'] == 100
+        assert breakdown['memory'] == breakdown['calls'] == breakdown['refunded'] == 0
+        refunded = breakdown.pop('refunded')
+        assert sum(breakdown.values()) - refunded == resp.gas_usage
+
+    def test_gas_by_address(self):
+        tevm = tinyevm.TinyEVM()
+        callee = '0x' + '22' * 20
+        caller = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # SSTORE(0, 1)
+        tevm.set_code(callee, '600160005500')
+        # CALL(GAS, callee, 0, 0, 0, 0, 0) POP STOP
+        tevm.set_code(caller, '6000600060006000600073' + callee[2:] + '5af15000')
+
+        config = tevm.get_instrument_config()
+        config.record_gas_by_address = True
+        tevm.configure(config)
+
+        resp = tevm.contract_call(caller, None, None, None)
+        assert resp.success
+        gas = resp.gas_by_address()
+        assert gas[callee] == 2 * 3 + 22100
+        assert gas[caller.lower()] == 6 * 3 + 2 + 2600 + 2
+        assert sum(gas.values()) == resp.execution_gas
+
+    def test_selector_coverage(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Jump to pc 16 when the selector is 0xaabbccdd
+        tevm.set_code(address, '60003560e01c63aabbccdd14601057005b00')
+
+        config = tevm.get_instrument_config()
+        config.coverage_by_selector = True
+        tevm.configure(config)
+
+        resp = tevm.contract_call(address, None, 'aabbccdd', None)
+        assert resp.success
+        coverage = resp.heuristics.selector_coverage[address.lower()]
+        assert list(coverage) == ['0xaabbccdd']
+        assert 16 in coverage['0xaabbccdd'].pcs
+        assert 15 not in coverage['0xaabbccdd'].pcs
+
+    def test_source_map(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # PUSH1 1 TIMESTAMP(pc 2) STOP
+        tevm.set_code(address, '60014200')
+        source = 'contract C {\n  function f() {\n    block.timestamp;\n  }\n}\n'
+        tevm.set_source_map(address, '0:60:0;34:15;-1:0:-1', [('C.sol', source)])
+
+        assert str(tevm.source_location(address, 2)) == 'C.sol:3:5'
+        assert tevm.source_location(address, 3) is None
+        lines = [loc and loc.line for loc in tevm.source_locations(address, [0, 1, 2])]
+        assert lines == [1, None, 3]
+
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        locations = tevm.bug_locations(resp)
+        assert len(locations) == len(resp.bug_data)
+        assert any(str(loc) == 'C.sol:3:5' for loc in locations)
+
+    def test_find_tod(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        sender = '0x' + 'f0' * 20
+        # With calldata SSTORE(0, CALLDATALOAD(0)), otherwise return SLOAD(0)
+        tevm.set_code(address, '36600f5760005460005260206000f35b600035600055')
+
+        read = (sender, address, '', 0)
+        write = (sender, address, '%064x' % 5, 0)
+        pairs = tevm.find_tod([read, write, write])
+        assert [(p.first, p.second) for p in pairs] == [(0, 1), (0, 2)]
+        assert pairs[0].slots == [(address.lower(), 0)]
+        assert tevm.get_storage(address, '0x0') == 0
+
+    def test_execute_parallel(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        sender = '0x' + 'f0' * 20
+        # With calldata SSTORE(0, CALLDATALOAD(0)), otherwise return SLOAD(0)
+        tevm.set_code(address, '36600f5760005460005260206000f35b600035600055')
+
+        read = (sender, address, '', 0)
+        write = (sender, address, '%064x' % 5, 0)
+        resps = tevm.execute_parallel([write, read] * 8)
+        assert len(resps) == 16
+        assert all(resp.success for resp in resps)
+        assert all(int.from_bytes(bytes(resp.data), 'big') == 0 for resp in resps[1::2])
+        assert tevm.get_storage(address, '0x0') == 0
+
+    def test_extract_cfg(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # JUMP(CALLDATALOAD(0)) at pc 3, JUMPDEST(pc 6) JUMP(10), JUMPDEST(pc 10) STOP
+        tevm.set_code(address, '600035565b005b600a565b00')
+        assert tevm.extract_cfg(address).edges == [(6, 10)]
+
+        resp = tevm.contract_call(address, None, '%064x' % 6, None)
+        assert resp.success
+        cfg = tevm.extract_cfg(address)
+        assert cfg.basic_blocks == [(0, 3), (4, 5), (6, 9), (10, 11)]
+        assert cfg.edges == [(0, 6), (6, 10)]
+
+    def test_invariants(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # With calldata SSTORE(0, CALLDATALOAD(0)), otherwise return SLOAD(0)
+        tevm.set_code(address, '36600f5760005460005260206000f35b600035600055')
+        tevm.add_invariant(address, '', '%064x' % 0)
+
+        resp = tevm.contract_call(address, None, '%064x' % 0, None)
+        assert resp.success
+        assert resp.invariant_violations == []
+
+        resp = tevm.contract_call(address, None, '%064x' % 5, None)
+        assert resp.success
+        [violation] = resp.invariant_violations
+        assert violation.index == 0
+        assert violation.success
+        assert violation.output == '%064x' % 5
+
+        tevm.clear_invariants()
+        resp = tevm.contract_call(address, None, '%064x' % 6, None)
+        assert resp.invariant_violations == []
+
+    def test_opcode_hook(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # SSTORE(1, 2) at pc 4, SLOAD(1) at pc 7
+        tevm.set_code(address, '6002600155' + '600154')
+
+        calls = []
+        tevm.set_opcode_hook(lambda *args: calls.append(args), [0x55])
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        assert calls == [(address.lower(), 4, 0x55, [1, 2])]
+
+        def oracle(address, pc, opcode, stack):
+            raise ValueError('oracle failed')
+        tevm.set_opcode_hook(oracle, [0x54])
+        with self.assertRaises(Exception):
+            tevm.contract_call(address, None, None, None)
+
+        tevm.set_opcode_hook(None)
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+
+    def test_step_hook(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # SSTORE(1, 2) at pc 4, SLOAD(1) at pc 7
+        tevm.set_code(address, '6002600155' + '600154')
+
+        steps = []
+        tevm.set_step_hook(lambda *args: steps.append(args), [0x55, 0x54])
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        assert [step[:3] for step in steps] == [(address.lower(), 4, 0x55), (address.lower(), 7, 0x54)]
+        # SSTORE of a new slot and PUSH1 were charged between the steps
+        assert steps[0][3] - steps[1][3] == 22100 + 3
+
+        with self.assertRaises(RuntimeError):
+            tevm.set_step_hook(lambda *args: None)
+
+        tevm.set_step_hook(None)
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        assert len(steps) == 2
+
+    def test_analyze_bytecode(self):
+        # JUMPI(8, 1) at pc 4, STOP, JUMPDEST STOP, JUMPDEST JUMPI(6, 0) at pc 13, STOP
+        code = '6001600857005b00' + '5b600060065700'
+        analysis = tinyevm.analyze_bytecode(code)
+        assert analysis.jumpdests == [6, 8]
+        assert analysis.jumpis == [(4, 8), (13, 6)]
+        assert analysis.basic_blocks == [(0, 4), (5, 5), (6, 7), (8, 13), (14, 14)]
+
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        tevm.set_code(address, code)
+        resp = tevm.contract_call(address, None, None, None)
+        assert resp.success
+        assert tevm.uncovered_branches(address) == [(4, 5), (13, 6)]
+
+    def test_exceptions(self):
+        for name in ['InvalidHexError', 'InvalidAddressError', 'AccountNotFoundError',
+                     'SnapshotNotFoundError', 'ProviderError', 'AddressCollisionError']:
+            assert issubclass(getattr(tinyevm, name), tinyevm.TinyEvmError)
+        assert issubclass(tinyevm.TinyEvmError, RuntimeError)
+
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        with self.assertRaises(tinyevm.InvalidAddressError):
+            tevm.get_balance('0x1234')
+        with self.assertRaises(tinyevm.InvalidHexError):
+            tevm.set_code(address, '0xzz')
+        with self.assertRaises(tinyevm.InvalidHexError):
+            tinyevm.analyze_bytecode('zz')
+        with self.assertRaises(tinyevm.AccountNotFoundError):
+            tevm.take_snapshot(address)
+        with self.assertRaises(tinyevm.SnapshotNotFoundError):
+            tevm.restore_snapshot(address)
+        with self.assertRaises(tinyevm.SnapshotNotFoundError):
+            tevm.restore_global_snapshot(str(uuid.uuid4()), True)
+        # Still a RuntimeError for the existing callers
+        with self.assertRaises(RuntimeError):
+            tevm.get_balance('0x1234')
+
+    def test_pickle(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # SSTORE(0, 1)
+        tevm.set_code(address, '600160005500')
+        tevm.set_balance(address, 1234)
+        tevm.tx_gas_limit = 100000
+
+        restored = pickle.loads(pickle.dumps(tevm))
+        assert restored.get_code(address) == tevm.get_code(address)
+        assert restored.get_balance(address) == 1234
+        assert restored.tx_gas_limit == 100000
+        resp = restored.contract_call(address, None, None, None)
+        assert resp.success
+        # The instances do not share their state
+        assert restored.get_storage(address, '0x0') == 1
+        assert tevm.get_storage(address, '0x0') == 0
+
+        restored = tinyevm.TinyEVM.from_bytes(tevm.to_bytes())
+        assert restored.get_code(address) == tevm.get_code(address)
+
+    def test_generate_stubs(self):
+        stubs = tinyevm.generate_stubs()
+        tree = ast.parse(stubs)
+        classes = {node.name: node for node in tree.body if isinstance(node, ast.ClassDef)}
+        for name in ['TinyEVM', 'Response', 'WrappedBug', 'REVMConfig', 'ExitReason', 'TinyEvmError']:
+            assert name in classes, name
+        methods = {node.name: node for node in classes['TinyEVM'].body if isinstance(node, ast.FunctionDef)}
+        assert [arg.arg for arg in methods['contract_call'].args.args][:3] == ['self', 'contract', 'sender']
+        # Keywords are only passed by position
+        assert [arg.arg for arg in methods['copy_snapshot'].args.args] == ['self', '__from', 'to']
+        assert 'class InvalidAddressError(TinyEvmError):' in stubs
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, 'tinyevm.pyi')
+            assert tinyevm.generate_stubs(path) == stubs
+            assert open(path).read() == stubs
+
+    def test_scoped_snapshot(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        tevm.set_balance(address, 1)
+
+        with tevm as vm:
+            assert vm is tevm
+            tevm.set_balance(address, 2)
+            with tevm:
+                tevm.set_balance(address, 3)
+            assert tevm.get_balance(address) == 2
+        assert tevm.get_balance(address) == 1
+
+        with self.assertRaises(ValueError):
+            with tevm:
+                tevm.set_balance(address, 4)
+                raise ValueError()
+        assert tevm.get_balance(address) == 1
+
+    def test_bytes_and_int_args(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        raw_address = bytes.fromhex(address[2:])
+        # SSTORE(0, CALLDATALOAD(0))
+        tevm.set_code(raw_address, bytes.fromhex('600035600055'))
+        assert tevm.get_code(address) == '600035600055'
+
+        tevm.set_balance(raw_address, 1234)
+        assert tevm.get_balance(address) == 1234
+        tevm.set_balance(address, '0x10')
+        assert tevm.get_balance(raw_address) == 16
+
+        resp = tevm.contract_call(raw_address, raw_address, (7).to_bytes(32, 'big'), 0)
+        assert resp.success
+        assert tevm.get_storage(raw_address, 0) == 7
+        tevm.set_storage(raw_address, 1, 2)
+        assert tevm.get_storage(address, '0x1') == 2
+        tevm.set_storage(raw_address, 3, 4, local_only=True)
+        assert tevm.get_storage(address, 3) == 4
+        assert resp.pcs_by_address(raw_address) == resp.pcs_by_address(address)
+
+        with self.assertRaises(tinyevm.InvalidAddressError):
+            tevm.get_balance(b'1234')
+        with self.assertRaises(TypeError):
+            tevm.get_balance(1234)
+
+    def test_accounts_and_storage_items(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388c818ca8b9251b393131c08a736a67ccb19297'
+        tevm.set_balance(address, 1234)
+        tevm.set_storage(address, 2, 3)
+        tevm.set_storage(address, 0, 1)
+
+        accounts = {account.address: account for account in tevm.accounts()}
+        assert accounts[address].balance == 1234
+        assert accounts[address].nonce == 0
+        assert accounts[address].code_hash.startswith('0x')
+        assert list(accounts) == sorted(accounts)
+
+        assert list(tevm.storage_items(address)) == [(0, 1), (2, 3)]
+        assert list(tevm.storage_items('0x' + '00' * 19 + '01')) == []
+
+    def test_u256_ints(self):
+        tevm = tinyevm.TinyEVM()
+        address = '0x388c818ca8b9251b393131c08a736a67ccb19297'
+        for value in (0, 1, 2 ** 64, 2 ** 128 - 1, 2 ** 128, 2 ** 255 + 1, 2 ** 256 - 1):
+            tevm.set_storage(address, value, value)
+            assert tevm.get_storage(address, value) == value
+            tevm.set_balance(address, value)
+            assert tevm.get_balance(address) == value
+            assert type(tevm.get_balance(address)) is int
+
+        with self.assertRaises(tinyevm.TinyEvmError):
+            tevm.set_balance(address, -1)
+        with self.assertRaises(tinyevm.TinyEvmError):
+            tevm.set_balance(address, 2 ** 256)
+
+    def test_hash_utilities(self):
+        assert tinyevm.fn_sig_to_prefix('transfer(address,uint256)') == fn_sig('transfer(address,uint256)')
+        assert tinyevm.keccak256(b'') == keccak.new(data=b'', digest_bits=256).digest()
+        assert tinyevm.keccak256('0x00') == keccak.new(data=b'\x00', digest_bits=256).digest()
+        topic = tinyevm.event_topic('Transfer(address,address,uint256)')
+        assert topic == '0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef'
+
+    def test_abi_helpers(self):
+        to = '0x388c818ca8b9251b393131c08a736a67ccb19297'
+        data = tinyevm.encode_call('transfer(address, uint)', [to, 1000])
+        assert data.hex() == fn_sig('transfer(address,uint256)') + to[2:].rjust(64, '0') + hex(1000)[2:].rjust(64, '0')
+        assert tinyevm.encode_call('warp(uint)', [1])[:4].hex() == fn_sig('warp(uint256)')
+        assert tinyevm.encode_call('f((uint)[1])', [[(1,)]])[:4].hex() == fn_sig('f((uint256)[1])')
+
+        values = tinyevm.decode_output(['address', 'uint256', 'int8', 'bool'],
+                                       tinyevm.encode_call('f(address,uint256,int8,bool)', [to, 7, -2, True])[4:])
+        assert values == [to, 7, -2, True]
+        assert tinyevm.decode_output(['uint256[]', '(string,bytes2)'],
+                                     tinyevm.encode_call('g(uint256[],(string,bytes2))', [[1, 2], ('a', b'\x01\x02')])[4:]) == [[1, 2], ('a', '0x0102')]
+
+        with self.assertRaises(tinyevm.TinyEvmError):
+            tinyevm.encode_call('transfer(address,uint256)', [to])
+
+    def test_config(self):
+        config = tinyevm.TinyEVMConfig(tx_gas_limit=1000000, chain_id=10, spec='SHANGHAI')
+        config.default_balance = 100
+        tevm = tinyevm.TinyEVM(config=config)
+        assert tevm.tx_gas_limit == 1000000
+        assert int(tevm.get_env_value_by_field('chain_id'), 16) == 10
+        assert tevm.get_balance(tevm.get_owner()) == 100
+
+        with self.assertRaises(tinyevm.TinyEvmError):
+            tinyevm.TinyEVMConfig(spec='unknown')
+
+    def test_config_pickle(self):
+        # PUSH0 is invalid before SHANGHAI
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        tevm = tinyevm.TinyEVM(config=tinyevm.TinyEVMConfig(spec='LONDON'))
+        tevm.set_code(address, '5f00')
+        assert not tevm.contract_call(address, None, None, None).success
+
+        restored = pickle.loads(pickle.dumps(tevm))
+        assert not restored.contract_call(address, None, None, None).success
+        latest = tinyevm.TinyEVM()
+        latest.set_code(address, '5f00')
+        assert latest.contract_call(address, None, None, None).success
+
+    def test_tracing(self):
+        tevm = tinyevm.TinyEVM()
+        bytecode = open('tests/contracts/TestEvents.hex').read()
+        with tempfile.TemporaryDirectory() as dir:
+            path = os.path.join(dir, 'trace.log')
+            tevm.enable_tracing(level='debug', path=path, json=True)
+            tevm.deploy(bytecode)
+            with open(path) as f:
+                lines = f.readlines()
+            assert lines
+            assert all(json.loads(line)['level'] in ('DEBUG', 'INFO', 'WARN', 'ERROR') for line in lines)
+
+            tevm.disable_tracing()
+            tevm.deploy(bytecode)
+            with open(path) as f:
+                assert f.readlines() == lines
+
+            with self.assertRaises(tinyevm.TinyEvmError):
+                tevm.enable_tracing(filter='tinyevm=nolevel')
+
+    def test_tracing_to_logging(self):
+        tevm = tinyevm.TinyEVM()
+        bytecode = open('tests/contracts/TestEvents.hex').read()
+        logger = logging.getLogger('tinyevm.test')
+        logger.setLevel(5)
+        tevm.enable_tracing(level='trace', logger='tinyevm.test')
+        with self.assertLogs(logger, level=5) as logs:
+            tevm.deploy(bytecode)
+        assert logs.records
+        assert all(r.levelno in (5, logging.DEBUG, logging.INFO, logging.WARNING, logging.ERROR)
+                   for r in logs.records)
+        assert any(r.levelno == 5 for r in logs.records)
+
+    def test_tracing_outside_transactions(self):
+        tevm = tinyevm.TinyEVM()
+        logger = logging.getLogger('tinyevm.test')
+        logger.setLevel(5)
+        tevm.enable_tracing(level='trace', logger='tinyevm.test')
+        with self.assertLogs(logger, level=5) as logs:
+            tevm.set_storage('0x' + '11' * 20, 1, 2)
+        assert any('insert_account_storage' in r.getMessage() for r in logs.records)
+
+    def test_instances_in_threads(self):
+        bytecode = open('tests/contracts/TestEvents.hex').read()
+        errors = []
+
+        def run():
+            try:
+                tevm = tinyevm.TinyEVM()
+                tevm.set_evm_tracing(True)
+                for _ in range(10):
+                    resp = tevm.deploy(bytecode)
+                    assert resp.success
+            except Exception as e:
+                errors.append(e)
+
+        threads = [threading.Thread(target=run) for _ in range(4)]
+        for t in threads:
+            t.start()
+        for t in threads:
+            t.join()
+        assert not errors
+
+    def test_free_threading(self):
+        pool = tinyevm.TinyEVMPool(4)
+        bytecode = open('tests/contracts/C.hex').read()
+        owner = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        barrier = threading.Barrier(4)
+        addresses = {}
+        errors = []
+
+        def run(i):
+            try:
+                vm = pool.checkout()
+                vm.set_balance(owner, 0xffff0000000000000000000000000000000000000000000000000000000000ff)
+                barrier.wait()
+                for _ in range(20):
+                    resp = vm.deploy(bytecode, owner=owner)
+                    assert resp.success
+                addresses[i] = resp.address
+                pool.checkin(vm)
+            except Exception as e:
+                errors.append(e)
+
+        threads = [threading.Thread(target=run, args=(i,)) for i in range(4)]
+        for t in threads:
+            t.start()
+        for t in threads:
+            t.join()
+        assert not errors
+        # Each executor deploys from the same state, at the same addresses
+        assert len(set(addresses.values())) == 1
+        if sysconfig.get_config_var('Py_GIL_DISABLED'):
+            # Importing the module did not enable the GIL again
+            assert not sys._is_gil_enabled()
+
+    def test_pool(self):
+        pool = tinyevm.TinyEVMPool(2)
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        assert pool.size == 2
+
+        vm1 = pool.checkout()
+        vm2 = pool.checkout()
+        assert vm1 is not vm2
+        assert pool.available == 0
+        with self.assertRaises(tinyevm.TinyEvmError):
+            pool.checkout()
+
+        vm1.set_balance(address, 1234)
+        assert vm2.get_balance(address) == 0
+        pool.checkin(vm1)
+        assert pool.available == 1
+        with self.assertRaises(tinyevm.TinyEvmError):
+            pool.checkin(vm1)
+
+        vm = pool.checkout()
+        assert vm is vm1
+        assert vm.get_balance(address) == 0
+
+    def test_pool_in_threads(self):
+        pool = tinyevm.TinyEVMPool(4)
+        bytecode = open('tests/contracts/TestEvents.hex').read()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        errors = []
+
+        def run(balance):
+            try:
+                for _ in range(5):
+                    vm = pool.checkout()
+                    assert vm.get_balance(address) == 0
+                    vm.set_balance(address, balance)
+                    resp = vm.deploy(bytecode)
+                    assert resp.success
+                    assert vm.get_balance(address) == balance
+                    pool.checkin(vm)
+            except Exception as e:
+                errors.append(e)
+
+        threads = [threading.Thread(target=run, args=(i + 1,)) for i in range(4)]
+        for t in threads:
+            t.start()
+        for t in threads:
+            t.join()
+        assert not errors
+        assert pool.available == 4
+
+    def test_async_calls(self):
+        pool = tinyevm.TinyEVMPool(2)
+        vm1, vm2 = pool.checkout(), pool.checkout()
+        address = '0x388C818CA8B9251b393131C08a736A67ccB19297'
+        # Return the first calldata word
+        vm1.set_code(address, '60003560005260206000f3')
+        vm2.set_code(address, '60003560005260206000f3')
+        bytecode = open('tests/contracts/TestEvents.hex').read()
+
+        async def run():
+            resp = await vm1.deploy_async(bytecode)
+            assert isinstance(resp, tinyevm.DeployResult) and resp.success
+            assert vm1.get_code(resp.address)
+            # Calls of different instances run concurrently
+            resps = await asyncio.gather(
+                vm1.contract_call_async(address, None, (1).to_bytes(32, 'big')),
+                vm2.contract_call_async(address, None, (2).to_bytes(32, 'big')),
+            )
+            assert [r.data for r in resps] == [(1).to_bytes(32, 'big'), (2).to_bytes(32, 'big')]
+            # The arguments are checked before awaiting
+            with self.assertRaises(tinyevm.TinyEvmError):
+                vm1.contract_call_async('0x1234')
+
+            # Deploy the init code STOP with a value, then with an initial balance
+            owner = '0x' + '11' * 20
+            vm2.set_balance(owner, 10**18)
+            resp = await vm2.deploy_async('00', owner, value=1234)
+            assert resp.success
+            assert vm2.get_balance(resp.address) == 1234
+            assert vm2.get_balance(owner) == 10**18 - 1234
+            resp = await vm2.deploy_async('00', owner, None, 1, init_value=42,
+                                          deploy_to_address=address)
+            assert resp.success and resp.address.lower() == address.lower()
+            assert vm2.get_balance(address) == 42
+
+        asyncio.run(run())
+
     def test_deployment(self):
         tevm = tinyevm.TinyEVM()
 
@@ -111,3 +1095,6 @@ class TestTinyEVM(unittest.TestCase):
 
         heuristics = resp.heuristics
         tprint('Heuristics: {}'.format(heuristics))
+        assert resp.coverage_len() == len(heuristics.coverage)
+        assert resp.missed_branch_count() == len(heuristics.missed_branches)
+        assert resp.heuristics.coverage == heuristics.coverage